@@ -36,4 +36,5 @@ export class GbConfig {
 	signingFormat?: string | undefined;
 	gpgProgram?: string | undefined;
 	gpgSshProgram?: string | undefined;
+	changeIdTrailer?: boolean | undefined;
 }
@@ -16,6 +16,20 @@ export type LocalKey = {
 
 export type Key = Exclude<KeyType, 'local'> | LocalKey;
 
+export type DefaultTrailer = {
+	key: string;
+	value:
+		| { type: 'fixed'; subject: string }
+		| { type: 'author' }
+		| { type: 'committer' }
+		| { type: 'changeId' };
+};
+
+export type CommitIssueKey = {
+	pattern: string;
+	format: string;
+};
+
 export class Project {
 	id!: string;
 	title!: string;
@@ -28,6 +42,9 @@ export class Project {
 	use_diff_context: boolean | undefined;
 	snapshot_lines_threshold!: number | undefined;
 	use_experimental_locking!: boolean;
+	max_commit_file_size: number | undefined;
+	default_commit_trailers!: DefaultTrailer[];
+	commit_issue_key: CommitIssueKey | undefined;
 	// Produced just for the frontend to determine if the project is open in any window.
 	is_open!: boolean;
 
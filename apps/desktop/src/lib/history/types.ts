@@ -18,11 +18,14 @@ export type Operation =
 	| 'ApplyBranch'
 	| 'DiscardHunk'
 	| 'DiscardFile'
+	| 'DiscardAllChanges'
+	| 'CheckoutFileFromCommit'
 	| 'AmendCommit'
 	| 'UndoCommit'
 	| 'UnapplyBranch'
 	| 'CherryPick'
 	| 'SquashCommit'
+	| 'UnsquashCommit'
 	| 'UpdateCommitMessage'
 	| 'MoveCommit'
 	| 'RestoreFromSnapshot'
@@ -30,7 +33,9 @@ export type Operation =
 	| 'InsertBlankCommit'
 	| 'MoveCommitFile'
 	| 'FileChanges'
-	| 'EnterEditMode';
+	| 'EnterEditMode'
+	| 'ResetWorkspace'
+	| 'DedupeIntegratedCommits';
 
 export class Trailer {
 	key!: string;
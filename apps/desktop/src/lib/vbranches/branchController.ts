@@ -9,6 +9,26 @@ import type { VirtualBranchService } from './virtualBranch';
 
 export type CommitIdOrChangeId = { CommitId: string } | { ChangeId: string };
 
+export type UpstreamIntegrationOutcome =
+	| { type: 'upToDate' }
+	| { type: 'fastForwarded' }
+	| { type: 'rebased'; subject: { new_head: string } }
+	| { type: 'conflicted'; subject: { files: ConflictedFile[] } };
+
+export type ConflictHunk = {
+	old_start: number;
+	old_lines: number;
+	new_start: number;
+	new_lines: number;
+	diff: string;
+};
+
+export type ConflictedFile = {
+	path: string;
+	oursHunks: ConflictHunk[];
+	theirsHunks: ConflictHunk[];
+};
+
 export class BranchController {
 	constructor(
 		readonly projectId: string,
@@ -79,7 +99,7 @@ export class BranchController {
 
 	async mergeUpstream(branch: string) {
 		try {
-			await invoke<void>('integrate_upstream_commits', {
+			return await invoke<UpstreamIntegrationOutcome>('integrate_upstream_commits', {
 				projectId: this.projectId,
 				branch
 			});
@@ -90,7 +110,7 @@ export class BranchController {
 
 	async mergeUpstreamForSeries(branch: string, seriesName: string) {
 		try {
-			await invoke<void>('integrate_upstream_commits', {
+			return await invoke<UpstreamIntegrationOutcome>('integrate_upstream_commits', {
 				projectId: this.projectId,
 				branch,
 				seriesName
@@ -22,6 +22,8 @@ export class Hunk {
 	})
 	modifiedAt!: Date;
 	filePath!: string;
+	/// The path the file had before if it was renamed.
+	renamedFrom?: string;
 	hash?: string;
 	locked!: boolean;
 	@Type(() => HunkLock)
@@ -102,6 +104,13 @@ export class VirtualBranches {
 	skippedFiles!: SkippedFile[];
 }
 
+export type UpstreamStatus =
+	| { type: 'gone' }
+	| { type: 'upToDate' }
+	| { type: 'ahead'; subject: number }
+	| { type: 'behind'; subject: number }
+	| { type: 'diverged'; subject: { ahead: number; behind: number } };
+
 export class VirtualBranch {
 	id!: string;
 	name!: string;
@@ -118,9 +127,16 @@ export class VirtualBranch {
 	upstream?: Branch;
 	upstreamData?: BranchData;
 	upstreamName?: string;
+	// How the branch relates to its upstream, if it has one
+	upstreamStatus?: UpstreamStatus;
 	conflicted!: boolean;
 	// TODO: to be removed from the API
 	baseCurrent!: boolean;
+	// The branch has commits that need rebasing onto the current target before further work
+	needsIntegration!: boolean;
+	// The number of commits not on the target branch, and of commits on the target branch not in the branch
+	ahead!: number;
+	behind!: number;
 	ownership!: string;
 	// This should actually be named "canBeCleanlyApplied" - if it's false, applying this branch will generate conflict markers,
 	// but it's totatlly okay for a user to apply it.
@@ -135,6 +151,9 @@ export class VirtualBranch {
 	mergeBase!: string;
 	/// The fork point between the target branch and the virtual branch
 	forkPoint!: string;
+	/// The commit the virtual branch is stacked on, as persisted with the branch
+	baseOid?: string;
+	commitAuthor?: { name: string; email: string };
 	allowRebasing!: boolean;
 	pr?: PullRequest;
 	refname!: string;
@@ -204,6 +223,7 @@ export class DetailedCommit {
 	createdAt!: Date;
 	isRemote!: boolean;
 	isIntegrated!: boolean;
+	integrationUnknown!: boolean;
 	parentIds!: string[];
 	branchId!: string;
 	changeId!: string;
@@ -443,6 +463,11 @@ export class PatchSeries {
 	 * This would occur when the branch has been merged at the remote and the workspace has been updated with that change.
 	 */
 	archived!: boolean;
+	/**
+	 * If files are listed against the base, the changes of this series since the head of the series below it.
+	 */
+	@Type(() => LocalFile)
+	files!: LocalFile[];
 
 	get localCommits() {
 		return this.patches.filter((c) => c.status === 'local');
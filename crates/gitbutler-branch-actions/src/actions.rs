@@ -1,7 +1,12 @@
 use super::r#virtual as vbranch;
 use crate::branch_upstream_integration;
 use crate::move_commits;
+use crate::ownership::{self, OwnershipConflict};
+use crate::r#virtual::{
+    BehindUpstream, BranchSummary, CommitOptions, ConflictingFile, WorkspaceDiffStats,
+};
 use crate::reorder::{self, StackOrder};
+use crate::status_delta::{self, StatusDelta};
 use crate::upstream_integration::{
     self, BaseBranchResolution, BaseBranchResolutionApproach, BranchStatuses, Resolution,
     UpstreamIntegrationContext,
@@ -10,26 +15,31 @@ use crate::{
     base,
     base::BaseBranch,
     branch_manager::BranchManagerExt,
-    file::RemoteBranchFile,
+    conflicts::{self, ConflictSidePaths},
+    file::{RemoteBranchFile, VirtualBranchFile},
+    hunk::HunkContent,
+    integration::{self, ForceResetOutcome},
     remote,
     remote::{RemoteBranch, RemoteBranchData, RemoteCommit},
     VirtualBranchesExt,
 };
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use gitbutler_branch::{BranchCreateRequest, BranchUpdateRequest};
 use gitbutler_command_context::CommandContext;
 use gitbutler_diff::DiffByPathMap;
+use gitbutler_error::error::Code;
 use gitbutler_operating_modes::assure_open_workspace_mode;
 use gitbutler_oplog::{
-    entry::{OperationKind, SnapshotDetails},
+    entry::{OperationKind, SnapshotDetails, Trailer},
     OplogExt, SnapshotExt,
 };
-use gitbutler_project::{FetchResult, Project};
+use gitbutler_project::{access::WorktreeWritePermission, FetchResult, Project};
 use gitbutler_reference::{ReferenceName, Refname, RemoteRefname};
-use gitbutler_repo::RepositoryExt;
+use gitbutler_repo::{credentials::Interactivity, RepositoryExt};
 use gitbutler_repo_actions::RepoActionsExt;
-use gitbutler_stack::{BranchOwnershipClaims, StackId};
-use std::path::PathBuf;
+use gitbutler_stack::{BranchOwnershipClaims, OwnershipClaim, StackId};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use tracing::instrument;
 
 pub fn create_commit(
@@ -38,13 +48,35 @@ pub fn create_commit(
     message: &str,
     ownership: Option<&BranchOwnershipClaims>,
     run_hooks: bool,
+) -> Result<git2::Oid> {
+    create_commit_with_options(
+        project,
+        branch_id,
+        message,
+        ownership,
+        &CommitOptions {
+            run_hooks,
+            ..Default::default()
+        },
+    )
+}
+
+pub fn create_commit_with_options(
+    project: &Project,
+    branch_id: StackId,
+    message: &str,
+    ownership: Option<&BranchOwnershipClaims>,
+    options: &CommitOptions,
 ) -> Result<git2::Oid> {
     let ctx = open_with_verify(project)?;
     assure_open_workspace_mode(&ctx).context("Creating a commit requires open workspace mode")?;
     let mut guard = project.exclusive_worktree_access();
     let snapshot_tree = ctx.project().prepare_snapshot(guard.read_permission());
     let result =
-        vbranch::commit(&ctx, branch_id, message, ownership, run_hooks).map_err(Into::into);
+        integrate_upstream_before_commit(&ctx, branch_id, options, guard.write_permission())
+            .and_then(|()| {
+                vbranch::commit_with_options(&ctx, branch_id, message, ownership, options)
+            });
     let _ = snapshot_tree.and_then(|snapshot_tree| {
         ctx.project().snapshot_commit_creation(
             snapshot_tree,
@@ -57,6 +89,54 @@ pub fn create_commit(
     result
 }
 
+/// Commit the hunks claimed by each ownership in `commits` with its message to its branch,
+/// under a single snapshot, running the commit hooks for each if `run_hooks` is set.
+/// Either all commits are created or none.
+pub fn commit_split(
+    project: &Project,
+    commits: &[(StackId, BranchOwnershipClaims, String)],
+    run_hooks: bool,
+) -> Result<Vec<git2::Oid>> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx).context("Creating a commit requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    let snapshot_tree = ctx.project().prepare_snapshot(guard.read_permission());
+    let result = vbranch::commit_split(&ctx, commits, run_hooks);
+    let _ = snapshot_tree.and_then(|snapshot_tree| {
+        ctx.project().snapshot_commit_creation(
+            snapshot_tree,
+            result.as_ref().err(),
+            commits
+                .iter()
+                .map(|(_, _, message)| message.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            None,
+            guard.write_permission(),
+        )
+    });
+    result
+}
+
+fn integrate_upstream_before_commit(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    options: &CommitOptions,
+    perm: &mut WorktreeWritePermission,
+) -> Result<()> {
+    if options.behind_upstream != BehindUpstream::Integrate {
+        return Ok(());
+    }
+    let branch = ctx
+        .project()
+        .virtual_branches()
+        .get_branch_in_workspace(branch_id)?;
+    if branch_upstream_integration::is_behind_upstream(ctx, &branch)? {
+        branch_upstream_integration::integrate_upstream_commits(ctx, branch_id, perm, false)?;
+    }
+    Ok(())
+}
+
 pub fn can_apply_remote_branch(project: &Project, branch_name: &RemoteRefname) -> Result<bool> {
     let ctx = CommandContext::open(project)?;
     assure_open_workspace_mode(&ctx)
@@ -76,6 +156,52 @@ pub fn list_virtual_branches(
         .map_err(Into::into)
 }
 
+/// Like [`list_virtual_branches()`], but if `files_against_base` is set, the `files` of each branch are all its
+/// changes relative to its base instead of only its uncommitted changes, for a stacked-diff view.
+/// `diff_options` control what the hunks of these files are annotated with.
+pub fn list_virtual_branches_with_options(
+    project: &Project,
+    files_against_base: bool,
+    diff_options: gitbutler_diff::DiffOptions,
+) -> Result<(Vec<vbranch::VirtualBranch>, Vec<gitbutler_diff::FileDiff>)> {
+    let ctx = open_with_verify(project)?;
+
+    assure_open_workspace_mode(&ctx)
+        .context("Listing virtual branches requires open workspace mode")?;
+
+    let mut guard = project.exclusive_worktree_access();
+    vbranch::list_virtual_branches_with_options(
+        &ctx,
+        guard.write_permission(),
+        None,
+        files_against_base,
+        diff_options,
+    )
+}
+
+/// Like [`list_virtual_branches()`], but without writing to the state of the project or taking
+/// exclusive access to the worktree, so it can be polled frequently.
+pub fn list_virtual_branches_read_only(
+    project: &Project,
+) -> Result<(Vec<vbranch::VirtualBranch>, Vec<gitbutler_diff::FileDiff>)> {
+    let ctx = CommandContext::open(project)?;
+    let guard = project.shared_worktree_access();
+    vbranch::list_virtual_branches_read_only(&ctx, None, guard.read_permission())
+}
+
+/// Like [`get_applied_status()`](crate::get_applied_status()), but only report the hunks that changed since
+/// the state described by `token` as returned by a previous call, or all hunks if there is no `token`
+/// or it is stale.
+pub fn get_status_since(project: &Project, token: Option<&str>) -> Result<StatusDelta> {
+    let ctx = open_with_verify(project)?;
+
+    assure_open_workspace_mode(&ctx)
+        .context("Getting the workspace status requires open workspace mode")?;
+
+    let guard = project.shared_worktree_access();
+    status_delta::get_applied_status_since(&ctx, token, guard.read_permission())
+}
+
 pub fn list_virtual_branches_cached(
     project: &Project,
     worktree_changes: Option<DiffByPathMap>,
@@ -104,6 +230,24 @@ pub fn create_virtual_branch(project: &Project, create: &BranchCreateRequest) ->
     Ok(branch_id)
 }
 
+/// Create a new virtual branch with copies of the commits of `branch_id`, leaving the original untouched.
+/// Uncommitted changes remain owned by the original branch.
+pub fn duplicate_branch(
+    project: &Project,
+    branch_id: StackId,
+    create: BranchCreateRequest,
+) -> Result<StackId> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Duplicating a branch requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    let branch_manager = ctx.branch_manager();
+    let branch_id = branch_manager
+        .duplicate_branch(branch_id, create, guard.write_permission())?
+        .id;
+    Ok(branch_id)
+}
+
 /// Deletes a local branch reference and it's associated virtual branch.
 /// If there is a virtual branch and it is applied, this function will return an error.
 /// If there is no such local reference, this function will return an error.
@@ -150,14 +294,152 @@ pub fn list_commit_files(
     crate::file::list_commit_files(ctx.repository(), commit_oid).map_err(Into::into)
 }
 
+/// Lists the files that changed in the worktree of the oplog snapshot `snapshot_oid`,
+/// compared to the snapshot that came before it.
+pub fn snapshot_files(project: &Project, snapshot_oid: git2::Oid) -> Result<Vec<RemoteBranchFile>> {
+    let diff = project.snapshot_diff(snapshot_oid)?;
+    Ok(diff.into_values().map(Into::into).collect())
+}
+
+/// Restores the worktree, virtual branches and conflict state captured in the oplog snapshot `snapshot_oid`.
+/// Returns the id of the snapshot recording the state right before the restore.
+pub fn restore_snapshot(project: &Project, snapshot_oid: git2::Oid) -> Result<git2::Oid> {
+    project.restore_snapshot(snapshot_oid)
+}
+
+/// Undo the most recent operation recorded in the oplog by restoring the snapshot taken right before it,
+/// and return the kind of the operation that was undone.
+///
+/// Operations that didn't change anything, i.e. whose snapshot is identical to the current state, are skipped.
+/// Restores, including those of previous undos, are skipped along with the operations they undid, so undoing
+/// repeatedly walks further back in the history.
+pub fn undo_last(project: &Project) -> Result<OperationKind> {
+    let ctx = CommandContext::open(project)?;
+    let mut guard = project.exclusive_worktree_access();
+    let current_tree = project.prepare_snapshot(guard.read_permission())?;
+    let mut undone = HashSet::new();
+    for snapshot in project.list_snapshots(100, None)? {
+        if undone.contains(&snapshot.commit_id) {
+            continue;
+        }
+        if let Some(details) = snapshot
+            .details
+            .as_ref()
+            .filter(|details| details.operation == OperationKind::RestoreFromSnapshot)
+        {
+            undone.extend(
+                details
+                    .trailers
+                    .iter()
+                    .filter(|trailer| trailer.key == "restored_from")
+                    .filter_map(|trailer| trailer.value.parse::<git2::Oid>().ok()),
+            );
+            continue;
+        }
+        if ctx.repository().find_commit(snapshot.commit_id)?.tree_id() == current_tree {
+            continue;
+        }
+        project.restore_snapshot_with_permission(snapshot.commit_id, guard.write_permission())?;
+        return Ok(snapshot
+            .details
+            .map_or(OperationKind::Unknown, |details| details.operation));
+    }
+    Err(anyhow!("there is no operation to undo").context(Code::Validation))
+}
+
+/// Lists the changes a pull request of `branch_id` against the target branch would show,
+/// i.e. the three-dot diff between the branch and its merge-base with the target.
+pub fn pr_diff(project: &Project, branch_id: StackId) -> Result<Vec<RemoteBranchFile>> {
+    let ctx = CommandContext::open(project)?;
+    vbranch::pr_diff(&ctx, branch_id)
+}
+
+/// Lists the changes between the commits `from` and `to` of `branch_id`, with `from` being an ancestor of `to`.
+pub fn range_diff(
+    project: &Project,
+    branch_id: StackId,
+    from: git2::Oid,
+    to: git2::Oid,
+) -> Result<Vec<RemoteBranchFile>> {
+    let ctx = CommandContext::open(project)?;
+    vbranch::range_diff(&ctx, branch_id, from, to)
+}
+
+/// Lists the combined changes of all applied branches and the uncommitted changes against the target.
+pub fn workspace_diff(project: &Project) -> Result<Vec<RemoteBranchFile>> {
+    let ctx = CommandContext::open(project)?;
+    let guard = project.shared_worktree_access();
+    vbranch::workspace_diff(&ctx, guard.read_permission())
+}
+
+/// Returns the id of the tree of the target commit.
+pub fn target_tree(project: &Project) -> Result<git2::Oid> {
+    let ctx = CommandContext::open(project)?;
+    vbranch::target_tree(&ctx)
+}
+
+/// Returns the id of the tree of the workspace commit, which merges all applied branches into the target.
+pub fn workspace_tree(project: &Project) -> Result<git2::Oid> {
+    let ctx = CommandContext::open(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Getting the workspace tree requires open workspace mode")?;
+    vbranch::workspace_tree(&ctx)
+}
+
+/// Summarizes the size of the changes [`workspace_diff()`] lists.
+pub fn workspace_diff_stats(project: &Project) -> Result<WorkspaceDiffStats> {
+    let ctx = CommandContext::open(project)?;
+    let guard = project.shared_worktree_access();
+    vbranch::workspace_diff_stats(&ctx, guard.read_permission())
+}
+
+/// Returns a commit message buffer to edit, with the changes to be committed to `branch_id` below a scissors line.
+pub fn commit_message_template(project: &Project, branch_id: StackId) -> Result<String> {
+    let ctx = CommandContext::open(project)?;
+    let guard = project.shared_worktree_access();
+    vbranch::commit_message_template(&ctx, branch_id, guard.read_permission())
+}
+
+/// Returns a local branch reference name derived from the name of `branch_id` that doesn't exist yet,
+/// so it can be shown before the branch is turned into a real branch.
+pub fn suggest_branch_ref_name(project: &Project, branch_id: StackId) -> Result<ReferenceName> {
+    let ctx = CommandContext::open(project)?;
+    vbranch::suggest_branch_ref_name(&ctx, branch_id)
+}
+
 pub fn set_base_branch(project: &Project, target_branch: &RemoteRefname) -> Result<BaseBranch> {
+    set_base_branch_with_local_branch(project, target_branch, None)
+}
+
+/// Like [`set_base_branch()`], but also keep the local branch named `local_branch_name` in sync with
+/// `target_branch`, even if it is named differently.
+pub fn set_base_branch_with_local_branch(
+    project: &Project,
+    target_branch: &RemoteRefname,
+    local_branch_name: Option<&str>,
+) -> Result<BaseBranch> {
     let ctx = CommandContext::open(project)?;
     let mut guard = project.exclusive_worktree_access();
     let _ = ctx.project().create_snapshot(
         SnapshotDetails::new(OperationKind::SetBaseBranch),
         guard.write_permission(),
     );
-    base::set_base_branch(&ctx, target_branch)
+    base::set_base_branch(&ctx, target_branch, local_branch_name)
+}
+
+/// Rebuild the workspace commit from scratch as a last resort if the workspace fails verification.
+/// Nothing happens unless `confirmed` is set, see [`ForceResetOutcome`] for what would be affected.
+pub fn force_reset_integration(project: &Project, confirmed: bool) -> Result<ForceResetOutcome> {
+    // Not verified, as that is what is expected to fail.
+    let ctx = CommandContext::open(project)?;
+    let mut guard = project.exclusive_worktree_access();
+    if confirmed {
+        let _ = ctx.project().create_snapshot(
+            SnapshotDetails::new(OperationKind::ResetWorkspace),
+            guard.write_permission(),
+        );
+    }
+    integration::force_reset_integration(&ctx, confirmed, guard.write_permission())
 }
 
 pub fn set_target_push_remote(project: &Project, push_remote: &str) -> Result<()> {
@@ -170,17 +452,54 @@ pub fn push_base_branch(project: &Project, with_force: bool) -> Result<()> {
     base::push(&ctx, with_force)
 }
 
+/// Drop the commits of the branch with `branch_id` whose changes were already integrated into the
+/// remote target branch by other commits, and return their ids.
+pub fn dedupe_integrated_commits(project: &Project, branch_id: StackId) -> Result<Vec<git2::Oid>> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Deduplicating commits requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    let _ = ctx.project().create_snapshot(
+        SnapshotDetails::new(OperationKind::DedupeIntegratedCommits),
+        guard.write_permission(),
+    );
+    vbranch::dedupe_integrated_commits(&ctx, branch_id, guard.write_permission())
+}
+
+/// Keep the last known upstream commit of the branch with `branch_id` reachable from a reference
+/// in `refs/gitbutler/upstream-backup/`, and return its name.
+pub fn snapshot_upstream(project: &Project, branch_id: StackId) -> Result<ReferenceName> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Snapshotting the upstream requires open workspace mode")?;
+    vbranch::snapshot_upstream(&ctx, branch_id)
+}
+
 pub fn integrate_upstream_commits(
     project: &Project,
     branch_id: StackId,
     series_name: Option<String>,
-) -> Result<()> {
+) -> Result<branch_upstream_integration::UpstreamIntegrationOutcome> {
+    integrate_upstream_commits_with_options(project, branch_id, series_name, false)
+}
+
+/// Like [`integrate_upstream_commits()`], but with `keep_empty` commits that become empty when
+/// rebased onto the upstream commits are kept instead of dropped.
+pub fn integrate_upstream_commits_with_options(
+    project: &Project,
+    branch_id: StackId,
+    series_name: Option<String>,
+    keep_empty: bool,
+) -> Result<branch_upstream_integration::UpstreamIntegrationOutcome> {
     let ctx = open_with_verify(project)?;
     assure_open_workspace_mode(&ctx)
         .context("Integrating upstream commits requires open workspace mode")?;
     let mut guard = project.exclusive_worktree_access();
     let _ = ctx.project().create_snapshot(
-        SnapshotDetails::new(OperationKind::MergeUpstream),
+        SnapshotDetails::new(OperationKind::MergeUpstream).with_trailers(vec![Trailer {
+            key: branch_upstream_integration::BRANCH_ID_TRAILER.to_string(),
+            value: branch_id.to_string(),
+        }]),
         guard.write_permission(),
     );
     if let Some(series_name) = series_name {
@@ -189,17 +508,97 @@ pub fn integrate_upstream_commits(
             branch_id,
             guard.write_permission(),
             series_name,
+            keep_empty,
         )
     } else {
         branch_upstream_integration::integrate_upstream_commits(
             &ctx,
             branch_id,
             guard.write_permission(),
+            keep_empty,
         )
     }
     .map_err(Into::into)
 }
 
+/// Abort the integration of upstream commits that left the branch with `branch_id` conflicted, like
+/// `git rebase --abort`, by restoring the snapshot taken when the integration started.
+pub fn abort_integration(project: &Project, branch_id: StackId) -> Result<()> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Aborting an integration requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    let snapshot_id = branch_upstream_integration::integration_snapshot(&ctx, branch_id)?;
+    project.restore_snapshot_with_permission(snapshot_id, guard.write_permission())?;
+    Ok(())
+}
+
+/// Integrate the upstream commits of all applied branches under a single snapshot,
+/// returning what happened to each branch that has an upstream.
+pub fn integrate_upstream_all(
+    project: &Project,
+) -> Result<Vec<(StackId, branch_upstream_integration::IntegrationOutcome)>> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Integrating upstream commits requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    let _ = ctx.project().create_snapshot(
+        SnapshotDetails::new(OperationKind::MergeUpstream),
+        guard.write_permission(),
+    );
+    branch_upstream_integration::integrate_upstream_commits_for_all(&ctx, guard.write_permission())
+}
+
+/// Rename the file at `from` to `to` in the worktree, with both sides of the rename owned by `branch_id`
+/// so that they are committed together.
+pub fn stage_rename(project: &Project, branch_id: StackId, from: &Path, to: &Path) -> Result<()> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx).context("Renaming a file requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    let _ = ctx.project().create_snapshot(
+        SnapshotDetails::new(OperationKind::FileChanges),
+        guard.write_permission(),
+    );
+    vbranch::stage_rename(&ctx, branch_id, from, to, guard.write_permission())
+}
+
+/// Apply the changes of the remote branch `remote_ref` as uncommitted changes owned by `branch_id`,
+/// instead of importing its commits like [`create_virtual_branch_from_branch()`] does.
+pub fn apply_remote_as_changes(
+    project: &Project,
+    remote_ref: &RemoteRefname,
+    branch_id: StackId,
+) -> Result<()> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Applying a remote branch as changes requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    let _ = ctx.project().create_snapshot(
+        SnapshotDetails::new(OperationKind::FileChanges),
+        guard.write_permission(),
+    );
+    vbranch::apply_remote_as_changes(&ctx, remote_ref, branch_id, guard.write_permission())
+}
+
+/// Applies the hunks of `claim` as `commit_oid` introduced them to the worktree, as uncommitted changes
+/// owned by `branch_id`, without changing the commit.
+pub fn extract_hunk_from_commit(
+    project: &Project,
+    commit_oid: git2::Oid,
+    claim: &OwnershipClaim,
+    branch_id: StackId,
+) -> Result<()> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Extracting hunks from a commit requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    let _ = ctx.project().create_snapshot(
+        SnapshotDetails::new(OperationKind::FileChanges),
+        guard.write_permission(),
+    );
+    vbranch::extract_hunk_from_commit(&ctx, commit_oid, claim, branch_id, guard.write_permission())
+}
+
 pub fn update_virtual_branch(project: &Project, branch_update: BranchUpdateRequest) -> Result<()> {
     let ctx = open_with_verify(project)?;
     assure_open_workspace_mode(&ctx).context("Updating a branch requires open workspace mode")?;
@@ -223,6 +622,27 @@ pub fn update_virtual_branch(project: &Project, branch_update: BranchUpdateReque
     Ok(())
 }
 
+/// Make the branch with `branch_id` the only one selected for changes, so it receives the hunks
+/// no branch owns yet.
+pub fn set_default_branch(project: &Project, branch_id: StackId) -> Result<()> {
+    update_virtual_branch(
+        project,
+        BranchUpdateRequest {
+            id: branch_id,
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+    )
+}
+
+/// Return the id of the branch that is selected for changes, if there is one.
+pub fn get_default_branch(project: &Project) -> Result<Option<StackId>> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Getting the default branch requires open workspace mode")?;
+    vbranch::default_branch(&ctx.project().virtual_branches())
+}
+
 pub fn update_branch_order(
     project: &Project,
     branch_updates: Vec<BranchUpdateRequest>,
@@ -268,6 +688,25 @@ pub fn unapply_ownership(project: &Project, ownership: &BranchOwnershipClaims) -
     vbranch::unapply_ownership(&ctx, ownership, guard.write_permission()).map_err(Into::into)
 }
 
+/// Report all hunks that overlap with hunks claimed by other branches in the workspace.
+pub fn validate_ownership(project: &Project) -> Result<Vec<OwnershipConflict>> {
+    let ctx = CommandContext::open(project)?;
+    ownership::validate_ownership(&ctx)
+}
+
+/// Assign the lines claimed by more than one branch to a single branch.
+/// Returns the conflicts that were resolved.
+pub fn repair_ownership(project: &Project) -> Result<Vec<OwnershipConflict>> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx).context("Repairing ownership requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    let _ = ctx.project().create_snapshot(
+        SnapshotDetails::new(OperationKind::MoveHunk),
+        guard.write_permission(),
+    );
+    ownership::repair_ownership(&ctx, guard.write_permission())
+}
+
 pub fn reset_files(project: &Project, branch_id: StackId, files: &[PathBuf]) -> Result<()> {
     let ctx = open_with_verify(project)?;
     assure_open_workspace_mode(&ctx).context("Resetting a file requires open workspace mode")?;
@@ -279,11 +718,132 @@ pub fn reset_files(project: &Project, branch_id: StackId, files: &[PathBuf]) ->
     vbranch::reset_files(&ctx, branch_id, files, guard.write_permission()).map_err(Into::into)
 }
 
+/// Discard all uncommitted changes of all applied branches after taking a snapshot, so it can be undone.
+/// Returns the number of files that were reset.
+pub fn discard_all_changes(project: &Project) -> Result<usize> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Discarding all changes requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    // Without the snapshot the changes would be lost for good, so nothing is discarded if it fails.
+    ctx.project()
+        .create_snapshot(
+            SnapshotDetails::new(OperationKind::DiscardAllChanges),
+            guard.write_permission(),
+        )
+        .context("failed to snapshot the changes before discarding them")?;
+    vbranch::discard_all_changes(&ctx, guard.write_permission())
+}
+
+/// Returns the changes [`reset_files()`] would discard, without touching the worktree.
+pub fn preview_reset_files(
+    project: &Project,
+    branch_id: StackId,
+    files: &[PathBuf],
+) -> Result<Vec<VirtualBranchFile>> {
+    let ctx = CommandContext::open(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Previewing a file reset requires open workspace mode")?;
+    let guard = project.shared_worktree_access();
+    vbranch::preview_reset_files(&ctx, branch_id, files, guard.read_permission())
+}
+
+/// Restore the file or directory at `path` in the worktree to its state in `commit_oid`, deleting it if it
+/// didn't exist there. The changes are owned by the branch containing `commit_oid`.
+pub fn checkout_file_from_commit(
+    project: &Project,
+    commit_oid: git2::Oid,
+    path: &Path,
+) -> Result<()> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Checking out a file from a commit requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    let _ = ctx.project().create_snapshot(
+        SnapshotDetails::new(OperationKind::CheckoutFileFromCommit),
+        guard.write_permission(),
+    );
+    vbranch::checkout_file_from_commit(&ctx, commit_oid, path, guard.write_permission())
+}
+
+/// Write the base, ours and theirs versions of the conflicting `path` to files for an external merge tool,
+/// with ours taken from the branch `branch_id`, and return their paths.
+pub fn write_conflict_sides(
+    project: &Project,
+    branch_id: StackId,
+    path: &Path,
+) -> Result<ConflictSidePaths> {
+    let ctx = CommandContext::open(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Writing conflict sides requires open workspace mode")?;
+    conflicts::write_conflict_sides(&ctx, branch_id, path)
+}
+
+/// Write the merge tool's result at `merged_path` to the conflicting `path` and mark it as resolved.
+pub fn finalize_merge_tool_result(
+    project: &Project,
+    path: &Path,
+    merged_path: &Path,
+) -> Result<()> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Finalizing a merge tool result requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    let _ = ctx.project().create_snapshot(
+        SnapshotDetails::new(OperationKind::FileChanges),
+        guard.write_permission(),
+    );
+    conflicts::finalize_merge_tool_result(&ctx, path, merged_path, guard.write_permission())
+}
+
+/// Returns the files and hunks that would conflict if the branches `a` and `b` were applied together,
+/// or `None` if they don't conflict.
+pub fn branches_conflict(
+    project: &Project,
+    a: StackId,
+    b: StackId,
+) -> Result<Option<Vec<ConflictingFile>>> {
+    let ctx = CommandContext::open(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Checking branches for conflicts requires open workspace mode")?;
+    let guard = project.shared_worktree_access();
+    vbranch::branches_conflict(&ctx, a, b, guard.read_permission())
+}
+
+/// Returns the number of uncommitted files, hunks and lines, and the number of commits of `branch_id`.
+pub fn branch_summary(project: &Project, branch_id: StackId) -> Result<BranchSummary> {
+    let ctx = CommandContext::open(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Summarizing a branch requires open workspace mode")?;
+    vbranch::branch_summary(&ctx, branch_id)
+}
+
+/// Returns the content before and after the change of the uncommitted hunk that `claim` refers to.
+pub fn hunk_content(project: &Project, claim: &OwnershipClaim) -> Result<HunkContent> {
+    let ctx = CommandContext::open(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Reading hunk content requires open workspace mode")?;
+    let guard = project.shared_worktree_access();
+    vbranch::hunk_content(&ctx, claim, guard.read_permission())
+}
+
 pub fn amend(
     project: &Project,
     branch_id: StackId,
     commit_oid: git2::Oid,
     ownership: &BranchOwnershipClaims,
+) -> Result<git2::Oid> {
+    amend_with_options(project, branch_id, commit_oid, ownership, false)
+}
+
+/// Like [`amend()`], but with `reset_author` the current user becomes the author of the amended commit,
+/// as of now, like `git commit --amend --reset-author` does. Otherwise the original author is kept.
+pub fn amend_with_options(
+    project: &Project,
+    branch_id: StackId,
+    commit_oid: git2::Oid,
+    ownership: &BranchOwnershipClaims,
+    reset_author: bool,
 ) -> Result<git2::Oid> {
     let ctx = open_with_verify(project)?;
     assure_open_workspace_mode(&ctx).context("Amending a commit requires open workspace mode")?;
@@ -297,10 +857,31 @@ pub fn amend(
         branch_id,
         commit_oid,
         ownership,
+        reset_author,
         guard.write_permission(),
     )
 }
 
+/// Preview what [`amend()`] would do, without changing the branch or creating a snapshot.
+pub fn amend_dry_run(
+    project: &Project,
+    branch_id: StackId,
+    commit_oid: git2::Oid,
+    ownership: &BranchOwnershipClaims,
+) -> Result<vbranch::AmendPreview> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Previewing an amended commit requires open workspace mode")?;
+    let guard = project.shared_worktree_access();
+    vbranch::amend_dry_run(
+        &ctx,
+        branch_id,
+        commit_oid,
+        ownership,
+        guard.read_permission(),
+    )
+}
+
 pub fn move_commit_file(
     project: &Project,
     branch_id: StackId,
@@ -311,6 +892,7 @@ pub fn move_commit_file(
     let ctx = open_with_verify(project)?;
     assure_open_workspace_mode(&ctx).context("Amending a commit requires open workspace mode")?;
     let mut guard = project.exclusive_worktree_access();
+    vbranch::ensure_commits_on_branch(&ctx, branch_id, &[from_commit_oid, to_commit_oid])?;
     let _ = ctx.project().create_snapshot(
         SnapshotDetails::new(OperationKind::MoveCommitFile),
         guard.write_permission(),
@@ -355,7 +937,67 @@ pub fn insert_blank_commit(
     vbranch::insert_blank_commit(&ctx, branch_id, commit_oid, offset).map_err(Into::into)
 }
 
+/// Pin `commit_oid` in `branch_id` so it can't be reordered or squashed, to keep it where it is.
+pub fn pin_commit(project: &Project, branch_id: StackId, commit_oid: git2::Oid) -> Result<()> {
+    set_commit_pinned(project, branch_id, commit_oid, true)
+}
+
+/// Undo [`pin_commit()`].
+pub fn unpin_commit(project: &Project, branch_id: StackId, commit_oid: git2::Oid) -> Result<()> {
+    set_commit_pinned(project, branch_id, commit_oid, false)
+}
+
+fn set_commit_pinned(
+    project: &Project,
+    branch_id: StackId,
+    commit_oid: git2::Oid,
+    pinned: bool,
+) -> Result<()> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx).context("Pinning a commit requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    let _ = ctx.project().create_snapshot(
+        SnapshotDetails::new(OperationKind::GenericBranchUpdate),
+        guard.write_permission(),
+    );
+    vbranch::set_commit_pinned(&ctx, branch_id, commit_oid, pinned)
+}
+
 pub fn reorder_stack(project: &Project, stack_id: StackId, stack_order: StackOrder) -> Result<()> {
+    reorder_stack_with_options(project, stack_id, stack_order, false)
+}
+
+/// Like [`reorder_stack()`], but with `keep_empty` commits that become empty by being moved
+/// are kept instead of dropped.
+pub fn reorder_stack_with_options(
+    project: &Project,
+    stack_id: StackId,
+    stack_order: StackOrder,
+    keep_empty: bool,
+) -> Result<()> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx).context("Reordering a commit requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    let _ = ctx.project().create_snapshot(
+        SnapshotDetails::new(OperationKind::ReorderCommit),
+        guard.write_permission(),
+    );
+    reorder::reorder_stack(
+        &ctx,
+        stack_id,
+        stack_order,
+        keep_empty,
+        guard.write_permission(),
+    )
+}
+
+/// Move `commit_oid` by `offset` positions within the stack with `branch_id`, see [`reorder::reorder_commit()`].
+pub fn reorder_commit(
+    project: &Project,
+    branch_id: StackId,
+    commit_oid: git2::Oid,
+    offset: i32,
+) -> Result<()> {
     let ctx = open_with_verify(project)?;
     assure_open_workspace_mode(&ctx).context("Reordering a commit requires open workspace mode")?;
     let mut guard = project.exclusive_worktree_access();
@@ -363,7 +1005,13 @@ pub fn reorder_stack(project: &Project, stack_id: StackId, stack_order: StackOrd
         SnapshotDetails::new(OperationKind::ReorderCommit),
         guard.write_permission(),
     );
-    reorder::reorder_stack(&ctx, stack_id, stack_order, guard.write_permission())
+    reorder::reorder_commit(
+        &ctx,
+        branch_id,
+        commit_oid,
+        offset,
+        guard.write_permission(),
+    )
 }
 
 pub fn reset_virtual_branch(
@@ -381,6 +1029,17 @@ pub fn reset_virtual_branch(
     vbranch::reset_branch(&ctx, branch_id, target_commit_oid).map_err(Into::into)
 }
 
+/// List the files whose worktree state would change if the branch with `branch_id` was unapplied,
+/// without unapplying it.
+pub fn preview_unapply(project: &Project, branch_id: StackId) -> Result<Vec<RemoteBranchFile>> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Previewing the unapplying of a branch requires open workspace mode")?;
+    let guard = project.shared_worktree_access();
+    ctx.branch_manager()
+        .preview_unapply(branch_id, guard.read_permission())
+}
+
 pub fn save_and_unapply_virutal_branch(
     project: &Project,
     branch_id: StackId,
@@ -404,15 +1063,68 @@ pub fn save_and_unapply_virutal_branch(
     result
 }
 
+/// Returns the commits that [`push_virtual_branch()`] would send for `branch_id`, newest first.
+pub fn unpushed_commits(project: &Project, branch_id: StackId) -> Result<Vec<git2::Oid>> {
+    let ctx = CommandContext::open(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Listing unpushed commits requires open workspace mode")?;
+    vbranch::unpushed_commits(&ctx, branch_id)
+}
+
+/// Return the commits of the upstream of `branch_id` that aren't in the branch yet, newest first.
+/// Remote changes are only visible after a fetch.
+pub fn incoming_commits(project: &Project, branch_id: StackId) -> Result<Vec<RemoteCommit>> {
+    let ctx = CommandContext::open(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Listing incoming commits requires open workspace mode")?;
+    vbranch::incoming_commits(&ctx, branch_id)
+}
+
+/// Return whether each commit of `branch_id` is reachable from the remote target branch, and thus shared,
+/// or purely local. Remote changes are only visible after a fetch.
+pub fn commit_sharedness(
+    project: &Project,
+    branch_id: StackId,
+) -> Result<HashMap<git2::Oid, bool>> {
+    let ctx = CommandContext::open(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Determining shared commits requires open workspace mode")?;
+    vbranch::commit_sharedness(&ctx, branch_id)
+}
+
 pub fn push_virtual_branch(
     project: &Project,
     branch_id: StackId,
     with_force: bool,
     askpass: Option<Option<StackId>>,
+) -> Result<vbranch::PushResult> {
+    push_virtual_branch_with_options(
+        project,
+        branch_id,
+        with_force.into(),
+        askpass,
+        &[],
+        Interactivity::Interactive,
+    )
+}
+
+/// Like [`push_virtual_branch()`], but with `force` to control if and how commits on the remote branch
+/// may be overwritten, and also sends each of `push_options` to the remote as server-side
+/// push option, e.g. `merge_request.create` to have GitLab open a merge request for the branch.
+///
+/// Fails if the remote doesn't support push options. With [`Interactivity::NonInteractive`], it also fails
+/// instead of prompting for credentials.
+pub fn push_virtual_branch_with_options(
+    project: &Project,
+    branch_id: StackId,
+    force: vbranch::ForceMode,
+    askpass: Option<Option<StackId>>,
+    push_options: &[String],
+    interactivity: Interactivity,
 ) -> Result<vbranch::PushResult> {
     let ctx = open_with_verify(project)?;
     assure_open_workspace_mode(&ctx).context("Pushing a branch requires open workspace mode")?;
-    vbranch::push(&ctx, branch_id, with_force, askpass)
+    vbranch::push(&ctx, branch_id, force, askpass, push_options, interactivity)
 }
 
 pub fn list_local_branches(project: Project) -> Result<Vec<RemoteBranch>> {
@@ -436,11 +1148,52 @@ pub fn squash(project: &Project, branch_id: StackId, commit_oid: git2::Oid) -> R
     vbranch::squash(&ctx, branch_id, commit_oid).map_err(Into::into)
 }
 
+/// Squash all commits from `from_commit_oid` to `to_commit_oid` (inclusive) into the oldest of them.
+pub fn squash_range(
+    project: &Project,
+    branch_id: StackId,
+    from_commit_oid: git2::Oid,
+    to_commit_oid: git2::Oid,
+) -> Result<()> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx).context("Squashing commits requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    let _ = ctx.project().create_snapshot(
+        SnapshotDetails::new(OperationKind::SquashCommit),
+        guard.write_permission(),
+    );
+    vbranch::squash_range(&ctx, branch_id, from_commit_oid, to_commit_oid).map_err(Into::into)
+}
+
+pub fn unsquash(project: &Project, branch_id: StackId, commit_oid: git2::Oid) -> Result<()> {
+    let ctx = open_with_verify(project)?;
+    assure_open_workspace_mode(&ctx)
+        .context("Unsquashing a commit requires open workspace mode")?;
+    let mut guard = project.exclusive_worktree_access();
+    let _ = ctx.project().create_snapshot(
+        SnapshotDetails::new(OperationKind::UnsquashCommit),
+        guard.write_permission(),
+    );
+    vbranch::unsquash(&ctx, branch_id, commit_oid).map_err(Into::into)
+}
+
 pub fn update_commit_message(
     project: &Project,
     branch_id: StackId,
     commit_oid: git2::Oid,
     message: &str,
+) -> Result<()> {
+    update_commit_message_with_options(project, branch_id, commit_oid, message, false)
+}
+
+/// Like [`update_commit_message()`], but optionally canonicalize the trailer block of `message`
+/// like [`CommitOptions::normalize_trailers`] does.
+pub fn update_commit_message_with_options(
+    project: &Project,
+    branch_id: StackId,
+    commit_oid: git2::Oid,
+    message: &str,
+    normalize_trailers: bool,
 ) -> Result<()> {
     let ctx = open_with_verify(project)?;
     assure_open_workspace_mode(&ctx)
@@ -450,7 +1203,8 @@ pub fn update_commit_message(
         SnapshotDetails::new(OperationKind::UpdateCommitMessage),
         guard.write_permission(),
     );
-    vbranch::update_commit_message(&ctx, branch_id, commit_oid, message).map_err(Into::into)
+    vbranch::update_commit_message(&ctx, branch_id, commit_oid, message, normalize_trailers)
+        .map_err(Into::into)
 }
 
 pub fn find_commit(project: &Project, commit_oid: git2::Oid) -> Result<Option<RemoteCommit>> {
@@ -458,14 +1212,31 @@ pub fn find_commit(project: &Project, commit_oid: git2::Oid) -> Result<Option<Re
     remote::get_commit_data(&ctx, commit_oid)
 }
 
-pub fn fetch_from_remotes(project: &Project, askpass: Option<String>) -> Result<FetchResult> {
+/// Fetch all remotes. If `depth` is set, only that many commits of each branch are fetched,
+/// or if the repository is already shallow, its history is deepened by that many commits.
+pub fn fetch_from_remotes(
+    project: &Project,
+    askpass: Option<String>,
+    depth: Option<u32>,
+) -> Result<FetchResult> {
+    fetch_from_remotes_with_interactivity(project, askpass, depth, Interactivity::Interactive)
+}
+
+/// Like [`fetch_from_remotes()`], but with [`Interactivity::NonInteractive`], fetching a remote fails
+/// instead of prompting for credentials.
+pub fn fetch_from_remotes_with_interactivity(
+    project: &Project,
+    askpass: Option<String>,
+    depth: Option<u32>,
+    interactivity: Interactivity,
+) -> Result<FetchResult> {
     let ctx = CommandContext::open(project)?;
 
     let remotes = ctx.repository().remotes_as_string()?;
     let fetch_errors: Vec<_> = remotes
         .iter()
         .filter_map(|remote| {
-            ctx.fetch(remote, askpass.clone())
+            ctx.fetch_with_depth(remote, askpass.clone(), depth, interactivity, None)
                 .err()
                 .map(|err| err.to_string())
         })
@@ -487,6 +1258,17 @@ pub fn fetch_from_remotes(project: &Project, askpass: Option<String>) -> Result<
     Ok(project_data_last_fetched)
 }
 
+/// Fetch all remotes like [`fetch_from_remotes()`], failing if any fetch fails, and return the
+/// remote-tracking branches that were removed as their branch was deleted on the remote.
+pub fn prune_remotes(project: &Project, askpass: Option<String>) -> Result<Vec<RemoteRefname>> {
+    let ctx = CommandContext::open(project)?;
+    let pruned = remote::prune_remotes(&ctx, askpass)?;
+    ctx.project()
+        .virtual_branches()
+        .garbage_collect(ctx.repository())?;
+    Ok(pruned)
+}
+
 pub fn move_commit(
     project: &Project,
     target_branch_id: StackId,
@@ -597,5 +1379,6 @@ pub(crate) fn open_with_verify(project: &Project) -> Result<CommandContext> {
     let mut guard = project.exclusive_worktree_access();
 
     crate::integration::verify_branch(&ctx, guard.write_permission())?;
+    crate::integration::backfill_base_oids(&ctx, guard.write_permission())?;
     Ok(ctx)
 }
@@ -13,7 +13,7 @@ use gitbutler_command_context::CommandContext;
 use gitbutler_error::error::Marker;
 use gitbutler_oxidize::{git2_to_gix_object_id, gix_to_git2_oid};
 use gitbutler_project::FetchResult;
-use gitbutler_reference::{Refname, RemoteRefname};
+use gitbutler_reference::{LocalRefname, Refname, RemoteRefname};
 use gitbutler_repo::{GixRepositoryExt, LogUntil, RepositoryExt};
 use gitbutler_repo_actions::RepoActionsExt;
 use gitbutler_stack::{BranchOwnershipClaims, Stack, Target, VirtualBranchesHandle};
@@ -109,12 +109,27 @@ fn go_back_to_integration(ctx: &CommandContext, default_target: &Target) -> Resu
 pub(crate) fn set_base_branch(
     ctx: &CommandContext,
     target_branch_ref: &RemoteRefname,
+    local_branch_name: Option<&str>,
 ) -> Result<BaseBranch> {
     let repo = ctx.repository();
 
+    if let Some(name) = local_branch_name {
+        if !git2::Branch::name_is_valid(name)?
+            || format!("refs/heads/{name}") == GITBUTLER_WORKSPACE_REFERENCE.to_string()
+        {
+            bail!("'{name}' can't be used as local branch for the target branch");
+        }
+    }
+
     // if target exists, and it is the same as the requested branch, we should go back
-    if let Ok(target) = default_target(&ctx.project().gb_dir()) {
+    if let Ok(mut target) = default_target(&ctx.project().gb_dir()) {
         if target.branch.eq(target_branch_ref) {
+            if target.local_branch_name.as_deref() != local_branch_name {
+                target.local_branch_name = local_branch_name.map(ToOwned::to_owned);
+                ctx.project()
+                    .virtual_branches()
+                    .set_default_target(target.clone())?;
+            }
             return go_back_to_integration(ctx, &target);
         }
     }
@@ -162,6 +177,7 @@ pub(crate) fn set_base_branch(
         remote_url: remote_url.to_string(),
         sha: target_commit_oid,
         push_remote_name: None,
+        local_branch_name: local_branch_name.map(ToOwned::to_owned),
     };
 
     let vb_state = ctx.project().virtual_branches();
@@ -201,7 +217,14 @@ pub(crate) fn set_base_branch(
             );
 
             let (upstream, upstream_head) = if let Refname::Local(head_name) = &head_name {
-                let upstream_name = target_branch_ref.with_branch(head_name.branch());
+                // Prefer the upstream configured for the branch, as with forks it's on the remote
+                // we push to, not on the one the target branch is fetched from.
+                let upstream_name = repo
+                    .find_branch(head_name.branch(), git2::BranchType::Local)
+                    .ok()
+                    .and_then(|branch| LocalRefname::try_from(&branch).ok())
+                    .and_then(|branch| branch.remote().cloned())
+                    .unwrap_or_else(|| target_branch_ref.with_branch(head_name.branch()));
                 if upstream_name.eq(target_branch_ref) {
                     (None, None)
                 } else {
@@ -275,6 +298,48 @@ pub(crate) fn set_target_push_remote(ctx: &CommandContext, push_remote_name: &st
     Ok(())
 }
 
+/// Fast-forward the local branch that mirrors the target branch, if there is one, to the head of
+/// the target branch, or create it if it doesn't exist yet.
+/// A local branch with commits that aren't in the target branch is left alone.
+pub(crate) fn update_local_target_branch(repo: &git2::Repository, target: &Target) -> Result<()> {
+    let Some(local_branch_name) = &target.local_branch_name else {
+        return Ok(());
+    };
+    let target_head = repo
+        .refname_to_id(&target.branch.to_string())
+        .with_context(|| format!("failed to find target branch {}", target.branch))?;
+    match repo.find_branch(local_branch_name, git2::BranchType::Local) {
+        Ok(mut branch) => {
+            let local_head = branch.get().peel_to_commit()?.id();
+            if local_head == target_head {
+                return Ok(());
+            }
+            if !repo.graph_descendant_of(target_head, local_head)? {
+                tracing::warn!(
+                    "not updating {local_branch_name} as it diverged from {}",
+                    target.branch
+                );
+                return Ok(());
+            }
+            branch.get_mut().set_target(
+                target_head,
+                &format!("GitButler: fast-forward to {}", target.branch),
+            )?;
+        }
+        Err(err) if err.code() == git2::ErrorCode::NotFound => {
+            let mut branch =
+                repo.branch(local_branch_name, &repo.find_commit(target_head)?, false)?;
+            branch.set_upstream(Some(&format!(
+                "{}/{}",
+                target.branch.remote(),
+                target.branch.branch()
+            )))?;
+        }
+        Err(err) => return Err(err.into()),
+    }
+    Ok(())
+}
+
 fn set_exclude_decoration(ctx: &CommandContext) -> Result<()> {
     let repo = ctx.repository();
     let mut config = repo.config()?;
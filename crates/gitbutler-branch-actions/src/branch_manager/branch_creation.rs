@@ -1,9 +1,14 @@
+use std::collections::HashMap;
+
 use crate::{branch_trees::checkout_branch_trees, r#virtual as vbranch};
 use anyhow::{anyhow, bail, Context, Result};
 use gitbutler_branch::BranchCreateRequest;
 use gitbutler_branch::{self, dedup};
 use gitbutler_cherry_pick::RepositoryExt as _;
-use gitbutler_commit::{commit_ext::CommitExt, commit_headers::HasCommitHeaders};
+use gitbutler_commit::{
+    commit_ext::CommitExt,
+    commit_headers::{CommitHeadersV2, HasCommitHeaders},
+};
 use gitbutler_error::error::Marker;
 use gitbutler_oplog::SnapshotExt;
 use gitbutler_project::access::WorktreeWritePermission;
@@ -34,15 +39,29 @@ impl BranchManager<'_> {
         let vb_state = self.ctx.project().virtual_branches();
         let default_target = vb_state.get_default_target()?;
 
+        let base_sha = match create.base_sha {
+            Some(base_sha) if base_sha != default_target.sha => {
+                let repo = self.ctx.repository();
+                repo.find_commit(base_sha)
+                    .with_context(|| format!("base commit {base_sha} doesn't exist"))?;
+                if !repo.graph_descendant_of(default_target.sha, base_sha)? {
+                    bail!(
+                        "base commit {base_sha} isn't reachable from the target {}",
+                        default_target.branch
+                    );
+                }
+                base_sha
+            }
+            _ => default_target.sha,
+        };
+
         let commit = self
             .ctx
             .repository()
-            .find_commit(default_target.sha)
-            .context("failed to find default target commit")?;
+            .find_commit(base_sha)
+            .context("failed to find base commit")?;
 
-        let tree = commit
-            .tree()
-            .context("failed to find default target commit tree")?;
+        let tree = commit.tree().context("failed to find base commit tree")?;
 
         let mut all_virtual_branches = vb_state
             .list_branches_in_workspace()
@@ -102,12 +121,13 @@ impl BranchManager<'_> {
             None,
             None,
             tree.id(),
-            default_target.sha,
+            base_sha,
             order,
             selected_for_changes,
             self.ctx.project().ok_with_force_push.into(),
             false, // disallow duplicate branch names on creation
         );
+        branch.local_only = create.local_only.unwrap_or_default();
 
         if let Some(ownership) = &create.ownership {
             vbranch::set_ownership(&vb_state, &mut branch, ownership)
@@ -116,6 +136,87 @@ impl BranchManager<'_> {
 
         vb_state.set_branch(branch.clone())?;
         self.ctx.add_branch_reference(&branch)?;
+        if base_sha != default_target.sha {
+            update_workspace_commit(&vb_state, self.ctx)
+                .context("failed to update gitbutler workspace")?;
+        }
+
+        Ok(branch)
+    }
+
+    /// Create a new virtual branch carrying copies of all commits of the applied branch `source_id`,
+    /// so both branches can diverge independently.
+    ///
+    /// The copies are new commit objects with their own change-ids. Uncommitted changes stay with the source
+    /// branch, which is why `create.ownership` is ignored.
+    #[instrument(level = tracing::Level::DEBUG, skip(self, perm), err(Debug))]
+    pub fn duplicate_branch(
+        &self,
+        source_id: StackId,
+        create: BranchCreateRequest,
+        perm: &mut WorktreeWritePermission,
+    ) -> Result<Stack> {
+        let vb_state = self.ctx.project().virtual_branches();
+        let default_target = vb_state.get_default_target()?;
+        let source = vb_state.get_branch_in_workspace(source_id)?;
+
+        let repo = self.ctx.repository();
+        let source_commits = repo.list_commits(source.head(), default_target.sha)?;
+
+        // Copy the commits first, so failing to sign them doesn't leave an empty branch behind.
+        let mut new_head = None;
+        if !source_commits.is_empty() {
+            let (_, committer) = repo.signatures().context("failed to get signatures")?;
+            // Maps the commits of the source branch to their copies, so parents within the copied range
+            // point to the copies, while all other parents, like the merged side of a merge, are kept.
+            let mut copies: HashMap<git2::Oid, git2::Oid> = HashMap::new();
+            for commit in source_commits.iter().rev() {
+                let parents = commit
+                    .parent_ids()
+                    .map(|parent_id| {
+                        repo.find_commit(copies.get(&parent_id).copied().unwrap_or(parent_id))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                // A fresh change-id keeps the copy from being mistaken for the original.
+                let headers = CommitHeadersV2 {
+                    conflicted: commit
+                        .gitbutler_headers()
+                        .and_then(|headers| headers.conflicted),
+                    ..Default::default()
+                };
+                // This signs the copy if `gitbutler.signCommits` is set, as the signature of the original
+                // doesn't cover the new parents and headers.
+                let copy_id = repo
+                    .commit_with_signature(
+                        None,
+                        &commit.author(),
+                        &committer,
+                        commit.message().unwrap_or_default(),
+                        &commit.tree()?,
+                        &parents.iter().collect::<Vec<_>>(),
+                        Some(headers),
+                    )
+                    .context("failed to copy commit")?;
+                copies.insert(commit.id(), copy_id);
+                new_head = Some(copy_id);
+            }
+        }
+
+        let create = BranchCreateRequest {
+            name: create
+                .name
+                .or_else(|| Some(format!("{} copy", source.name))),
+            ownership: None,
+            ..create
+        };
+        let mut branch = self.create_virtual_branch(&create, perm)?;
+        let Some(new_head) = new_head else {
+            return Ok(branch);
+        };
+
+        let tree = repo.find_commit(new_head)?.tree_id();
+        branch.set_stack_head(self.ctx, new_head, Some(tree))?;
+        update_workspace_commit(&vb_state, self.ctx)?;
 
         Ok(branch)
     }
@@ -221,9 +322,13 @@ impl BranchManager<'_> {
             },
         );
 
-        let mut branch = if let Ok(Some(mut branch)) =
-            vb_state.find_by_source_refname_where_not_in_workspace(target)
-        {
+        let existing_branch = vb_state
+            .find_by_source_refname_where_not_in_workspace(target)
+            .ok()
+            .flatten()
+            .or_else(|| self.recorded_stack(target));
+        let mut branch = if let Some(mut branch) = existing_branch {
+            branch.source_refname = Some(target.clone());
             branch.upstream_head = upstream_branch.is_some().then_some(head_commit.id());
             branch.upstream = upstream_branch;
             branch.ownership = ownership;
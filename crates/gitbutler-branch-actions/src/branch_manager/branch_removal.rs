@@ -1,14 +1,14 @@
 use std::path::PathBuf;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use git2::Commit;
 use gitbutler_branch::BranchExt;
 use gitbutler_commit::commit_headers::CommitHeadersV2;
 use gitbutler_oplog::SnapshotExt;
 use gitbutler_oxidize::git2_to_gix_object_id;
 use gitbutler_oxidize::gix_to_git2_oid;
-use gitbutler_project::access::WorktreeWritePermission;
-use gitbutler_reference::{normalize_branch_name, ReferenceName, Refname};
+use gitbutler_project::access::{WorktreeReadPermission, WorktreeWritePermission};
+use gitbutler_reference::{ReferenceName, Refname};
 use gitbutler_repo::GixRepositoryExt;
 use gitbutler_repo::RepositoryExt;
 use gitbutler_repo::SignaturePurpose;
@@ -20,8 +20,10 @@ use super::BranchManager;
 use crate::r#virtual as vbranch;
 use crate::{
     conflicts::{self},
+    file::{RemoteBranchFile, VirtualBranchFile},
     get_applied_status,
     hunk::VirtualBranchHunk,
+    status::get_applied_status_read_only,
     VirtualBranchesExt,
 };
 
@@ -82,11 +84,6 @@ impl BranchManager<'_> {
 
         let repo = self.ctx.repository();
 
-        let base_tree_id = target_commit
-            .tree()
-            .context("failed to get target tree")?
-            .id();
-
         let applied_statuses = get_applied_status(self.ctx, None)
             .context("failed to get status by branch")?
             .branches;
@@ -98,41 +95,7 @@ impl BranchManager<'_> {
 
         // go through the other applied branches and merge them into the final tree
         // then check that out into the working directory
-        let final_tree = {
-            let _span = tracing::debug_span!(
-                "new tree without deleted branch",
-                num_branches = applied_statuses.len() - 1
-            )
-            .entered();
-            let gix_repo = self.ctx.gix_repository()?;
-            let merge_options = gix_repo.tree_merge_options()?;
-            let final_tree_id = applied_statuses
-                .into_iter()
-                .filter(|(branch, _)| branch.id != branch_id)
-                .try_fold(
-                    git2_to_gix_object_id(target_commit.tree_id()),
-                    |final_tree_id, status| -> Result<_> {
-                        let branch = status.0;
-                        let files = status
-                            .1
-                            .into_iter()
-                            .map(|file| (file.path, file.hunks))
-                            .collect::<Vec<(PathBuf, Vec<VirtualBranchHunk>)>>();
-                        let tree_oid =
-                            gitbutler_diff::write::hunks_onto_oid(self.ctx, branch.head(), files)?;
-                        let mut merge = gix_repo.merge_trees(
-                            git2_to_gix_object_id(base_tree_id),
-                            final_tree_id,
-                            git2_to_gix_object_id(tree_oid),
-                            gix_repo.default_merge_labels(),
-                            merge_options.clone(),
-                        )?;
-                        let final_tree_id = merge.tree.write()?.detach();
-                        Ok(final_tree_id)
-                    },
-                )?;
-            repo.find_tree(gix_to_git2_oid(final_tree_id))?
-        };
+        let final_tree = self.tree_without_branch(branch_id, applied_statuses, target_commit)?;
 
         let _span = tracing::debug_span!("checkout final tree").entered();
         // checkout final_tree into the working directory
@@ -158,17 +121,108 @@ impl BranchManager<'_> {
 
         Ok(())
     }
+
+    /// List the worktree changes that unapplying the branch with `branch_id` would cause,
+    /// without touching the worktree or the virtual branch state, and with all trees built in memory.
+    pub(crate) fn preview_unapply(
+        &self,
+        branch_id: StackId,
+        perm: &WorktreeReadPermission,
+    ) -> Result<Vec<RemoteBranchFile>> {
+        let vb_state = self.ctx.project().virtual_branches();
+        vb_state.get_branch_in_workspace(branch_id)?;
+
+        let applied_statuses = get_applied_status_read_only(self.ctx, None, perm)
+            .context("failed to get status by branch")?
+            .branches;
+        let repo = self.ctx.repository().in_memory_repo()?;
+        let target_tree = repo
+            .find_commit(vb_state.get_default_target()?.sha)?
+            .tree()
+            .context("failed to get target tree")?;
+
+        // The branches that stay applied merge cleanly, as they do in the workspace already.
+        let mut final_tree = target_tree.clone();
+        for (branch, _) in applied_statuses
+            .iter()
+            .filter(|(branch, _)| branch.id != branch_id)
+        {
+            let branch_tree = repo.find_tree(branch.tree)?;
+            let mut merge_index =
+                repo.merge_trees(&target_tree, &final_tree, &branch_tree, None)?;
+            if merge_index.has_conflicts() {
+                bail!(
+                    "the changes of branch {} conflict with those of the other applied branches",
+                    branch.name
+                );
+            }
+            final_tree = repo.find_tree(merge_index.write_tree_to(&repo)?)?;
+        }
+        let worktree = repo.create_wd_tree()?;
+        let diff = gitbutler_diff::trees(&repo, &worktree, &final_tree, true)?;
+        Ok(diff.into_values().map(Into::into).collect())
+    }
 }
 
 impl BranchManager<'_> {
+    /// Merge the uncommitted state of all `applied_statuses` except `branch_id` onto the tree of
+    /// `target_commit`, which is what the worktree looks like once `branch_id` is unapplied.
+    fn tree_without_branch(
+        &self,
+        branch_id: StackId,
+        applied_statuses: Vec<(Stack, Vec<VirtualBranchFile>)>,
+        target_commit: &Commit,
+    ) -> Result<git2::Tree<'_>> {
+        let repo = self.ctx.repository();
+        let base_tree_id = target_commit
+            .tree()
+            .context("failed to get target tree")?
+            .id();
+        let _span = tracing::debug_span!(
+            "new tree without deleted branch",
+            num_branches = applied_statuses.len().saturating_sub(1)
+        )
+        .entered();
+        let gix_repo = self.ctx.gix_repository()?;
+        let merge_options = gix_repo.tree_merge_options()?;
+        let final_tree_id = applied_statuses
+            .into_iter()
+            .filter(|(branch, _)| branch.id != branch_id)
+            .try_fold(
+                git2_to_gix_object_id(target_commit.tree_id()),
+                |final_tree_id, status| -> Result<_> {
+                    let branch = status.0;
+                    let files = status
+                        .1
+                        .into_iter()
+                        .map(|file| (file.path, file.hunks))
+                        .collect::<Vec<(PathBuf, Vec<VirtualBranchHunk>)>>();
+                    let tree_oid =
+                        gitbutler_diff::write::hunks_onto_oid(self.ctx, branch.head(), files)?;
+                    let mut merge = gix_repo.merge_trees(
+                        git2_to_gix_object_id(base_tree_id),
+                        final_tree_id,
+                        git2_to_gix_object_id(tree_oid),
+                        gix_repo.default_merge_labels(),
+                        merge_options.clone(),
+                    )?;
+                    let final_tree_id = merge.tree.write()?.detach();
+                    Ok(final_tree_id)
+                },
+            )?;
+        Ok(repo.find_tree(gix_to_git2_oid(final_tree_id))?)
+    }
+
     #[instrument(level = tracing::Level::DEBUG, skip(self, stack), err(Debug))]
     fn build_real_branch(&self, stack: &mut Stack) -> Result<git2::Branch<'_>> {
         let repo = self.ctx.repository();
         let target_commit = repo.find_commit(stack.head())?;
-        let branch_name = normalize_branch_name(&stack.id.to_string())?;
+        let branch_name = super::branch_name_for_stack(repo, stack)?;
 
         let vb_state = self.ctx.project().virtual_branches();
+        // Only a branch this stack was saved as before is overwritten.
         let branch = repo.branch(&branch_name, &target_commit, true)?;
+        super::record_stack_id(repo, &branch_name, stack.id)?;
         stack.source_refname = Some(Refname::try_from(&branch)?);
         vb_state.set_branch(stack.clone())?;
 
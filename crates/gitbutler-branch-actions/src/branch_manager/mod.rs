@@ -1,4 +1,9 @@
+use std::path::Path;
+
+use anyhow::Result;
 use gitbutler_command_context::CommandContext;
+use gitbutler_reference::{normalize_branch_name, Refname};
+use gitbutler_stack::{Stack, StackId};
 
 mod branch_creation;
 mod branch_removal;
@@ -16,3 +21,90 @@ impl BranchManagerExt for CommandContext {
         BranchManager { ctx: self }
     }
 }
+
+/// When a stack is saved as a real branch, its id is stored in the local repository configuration as
+/// `branch.<name>.gitbutlerStackId`. As `git branch -m` moves this section along with the branch,
+/// the stack can be recognized even after the branch was renamed.
+fn stack_id_config_key(branch_name: &str) -> String {
+    format!("branch.{branch_name}.gitbutlerStackId")
+}
+
+fn record_stack_id(repo: &git2::Repository, branch_name: &str, stack_id: StackId) -> Result<()> {
+    repo.config()?
+        .open_level(git2::ConfigLevel::Local)?
+        .set_str(&stack_id_config_key(branch_name), &stack_id.to_string())?;
+    Ok(())
+}
+
+/// Return the name of the local branch `stack` is saved as, which is its normalized name, or the first of
+/// `<name>-2`, `<name>-3`, … that doesn't collide with an existing local branch.
+/// A branch that `stack` was saved as before can be reused.
+///
+/// As `refs/heads/a/b` can't be created while `refs/heads/a` exists, the `/` of the name are replaced
+/// with `-` if one of its parents is an existing branch.
+pub(crate) fn branch_name_for_stack(repo: &git2::Repository, stack: &Stack) -> Result<String> {
+    let name = normalize_branch_name(&stack.name)?;
+    let parent_exists = Path::new(&name)
+        .ancestors()
+        .skip(1)
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .any(|parent| {
+            repo.find_reference(&format!("refs/heads/{}", parent.display()))
+                .is_ok()
+        });
+    let name = if parent_exists {
+        name.replace('/', "-")
+    } else {
+        name
+    };
+
+    let is_available = |name: &str| -> Result<bool> {
+        let refname = format!("refs/heads/{name}");
+        if repo.find_reference(&refname).is_ok() {
+            let owner = repo
+                .config()?
+                .get_string(&stack_id_config_key(name))
+                .ok()
+                .and_then(|id| id.parse::<StackId>().ok());
+            return Ok(owner == Some(stack.id));
+        }
+        // `refs/heads/a` can't be created if `refs/heads/a/b` exists.
+        Ok(repo
+            .references_glob(&format!("{refname}/*"))?
+            .next()
+            .is_none())
+    };
+
+    let mut candidate = name.clone();
+    let mut suffix = 1;
+    while !is_available(&candidate)? {
+        suffix += 1;
+        candidate = format!("{name}-{suffix}");
+    }
+    Ok(candidate)
+}
+
+impl BranchManager<'_> {
+    /// Return the unapplied stack that the local branch `target` was saved from, if it is still known.
+    fn recorded_stack(&self, target: &Refname) -> Option<Stack> {
+        let Refname::Local(local) = target else {
+            return None;
+        };
+        let stack_id: StackId = self
+            .ctx
+            .repository()
+            .config()
+            .ok()?
+            .get_string(&stack_id_config_key(local.branch()))
+            .ok()?
+            .parse()
+            .ok()?;
+        self.ctx
+            .project()
+            .virtual_branches()
+            .try_branch(stack_id)
+            .ok()
+            .flatten()
+            .filter(|stack| !stack.in_workspace)
+    }
+}
@@ -1,26 +1,150 @@
+use std::path::{Path, PathBuf};
+
 use anyhow::{anyhow, bail, Result};
+use gitbutler_cherry_pick::{ConflictedTreeKey, RepositoryExt as _};
 use gitbutler_command_context::CommandContext;
+use gitbutler_commit::commit_ext::CommitExt as _;
+use gitbutler_diff::GitHunk;
+use gitbutler_error::error::Code;
+use gitbutler_oplog::{entry::OperationKind, OplogExt as _};
 use gitbutler_project::access::WorktreeWritePermission;
 use gitbutler_repo::{
-    rebase::{cherry_rebase_group, gitbutler_merge_commits},
+    rebase::{
+        cherry_rebase_group_with_options, gitbutler_merge_commits_with_options, RebaseOptions,
+    },
     LogUntil, RepositoryExt as _,
 };
 use gitbutler_stack::commit_by_oid_or_change_id;
-use gitbutler_stack::StackId;
+use gitbutler_stack::{Stack, StackId};
+use serde::Serialize;
 
 use crate::{
     branch_trees::{
         checkout_branch_trees, compute_updated_branch_head_for_commits, BranchHeadAndTree,
     },
+    commit::conflict_entries,
     conflicts, VirtualBranchesExt as _,
 };
 
+/// What happened when integrating the upstream commits of a branch with [`integrate_upstream_commits()`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", content = "subject", rename_all = "camelCase")]
+pub enum UpstreamIntegrationOutcome {
+    /// The branch already contained all upstream commits, so nothing changed.
+    UpToDate,
+    /// The branch had no commits of its own and now points to the upstream head.
+    FastForwarded,
+    /// The upstream commits were integrated cleanly by rebasing or merging, resulting in `new_head`.
+    Rebased {
+        #[serde(with = "gitbutler_serde::oid")]
+        new_head: git2::Oid,
+    },
+    /// The upstream commits were integrated, but `files` are conflicted in some of the resulting commits.
+    Conflicted { files: Vec<ConflictedFile> },
+}
+
+/// A file that is conflicted in a commit, along with what each side of the conflict changed
+/// so the conflicting regions can be found without parsing conflict markers.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictedFile {
+    pub path: PathBuf,
+    /// The hunks turning the merge base of the file into the version of the side being rebased onto.
+    pub ours_hunks: Vec<GitHunk>,
+    /// The hunks turning the merge base of the file into the version of the commit being rebased.
+    pub theirs_hunks: Vec<GitHunk>,
+}
+
+impl ConflictedFile {
+    /// Describe the conflicts recorded in the conflicted `commit`.
+    fn from_commit(repo: &git2::Repository, commit: &git2::Commit) -> Result<Vec<Self>> {
+        let paths = conflict_entries(repo, commit)?.paths();
+        if paths.is_empty() {
+            return Ok(vec![]);
+        }
+        let base = repo.find_real_tree(commit, ConflictedTreeKey::Base)?;
+        let ours = repo.find_real_tree(commit, ConflictedTreeKey::Ours)?;
+        let theirs = repo.find_real_tree(commit, ConflictedTreeKey::Theirs)?;
+        paths
+            .into_iter()
+            .map(|path| {
+                Ok(ConflictedFile {
+                    ours_hunks: hunks_at_path(repo, &base, &ours, &path)?,
+                    theirs_hunks: hunks_at_path(repo, &base, &theirs, &path)?,
+                    path,
+                })
+            })
+            .collect()
+    }
+}
+
+fn hunks_at_path(
+    repo: &git2::Repository,
+    old_tree: &git2::Tree,
+    new_tree: &git2::Tree,
+    path: &Path,
+) -> Result<Vec<GitHunk>> {
+    let mut opts = git2::DiffOptions::new();
+    opts.pathspec(path)
+        .disable_pathspec_match(true)
+        .context_lines(0);
+    let diff = repo.diff_tree_to_tree(Some(old_tree), Some(new_tree), Some(&mut opts))?;
+    Ok(gitbutler_diff::hunks_by_filepath(None, &diff)?
+        .remove(path)
+        .map(|file| file.hunks)
+        .unwrap_or_default())
+}
+
+impl UpstreamIntegrationOutcome {
+    /// Determine the outcome of moving a branch from `old_head` to `new_head` to integrate `upstream_head`.
+    /// Conflicts are looked for in the commits between `target_sha` and `new_stack_head` that aren't
+    /// reachable from `old_stack_head`.
+    fn new(
+        repo: &git2::Repository,
+        target_sha: git2::Oid,
+        (old_head, new_head): (git2::Oid, git2::Oid),
+        upstream_head: git2::Oid,
+        (old_stack_head, new_stack_head): (git2::Oid, git2::Oid),
+    ) -> Result<Self> {
+        if old_head == new_head {
+            return Ok(UpstreamIntegrationOutcome::UpToDate);
+        }
+
+        let old_commits = repo.l(old_stack_head, LogUntil::Commit(target_sha), false)?;
+        let mut files = Vec::<ConflictedFile>::new();
+        // Walk from the oldest commit as its conflicts are the ones to resolve first.
+        for commit in repo
+            .log(new_stack_head, LogUntil::Commit(target_sha), false)?
+            .iter()
+            .rev()
+        {
+            if !old_commits.contains(&commit.id()) {
+                for file in ConflictedFile::from_commit(repo, commit)? {
+                    if !files.iter().any(|known| known.path == file.path) {
+                        files.push(file);
+                    }
+                }
+            }
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(if !files.is_empty() {
+            UpstreamIntegrationOutcome::Conflicted { files }
+        } else if new_head == upstream_head {
+            UpstreamIntegrationOutcome::FastForwarded
+        } else {
+            UpstreamIntegrationOutcome::Rebased { new_head }
+        })
+    }
+}
+
 pub fn integrate_upstream_commits_for_series(
     ctx: &CommandContext,
     branch_id: StackId,
     perm: &mut WorktreeWritePermission,
     series_name: String,
-) -> Result<()> {
+    keep_empty: bool,
+) -> Result<UpstreamIntegrationOutcome> {
     conflicts::is_conflicting(ctx, None)?;
 
     let repo = ctx.repository();
@@ -60,6 +184,7 @@ pub fn integrate_upstream_commits_for_series(
         remote_head: remote_head.id(),
         remote_branch_name: &subject_series.head.remote_reference(&remote)?,
         prefers_merge: !do_rebease,
+        keep_empty,
     };
 
     let (BranchHeadAndTree { head, tree }, new_series_head) =
@@ -70,7 +195,13 @@ pub fn integrate_upstream_commits_for_series(
     checkout_branch_trees(ctx, perm)?;
     branch.replace_head(ctx, &series_head, &repo.find_commit(new_series_head)?)?;
     crate::integration::update_workspace_commit(&vb_state, ctx)?;
-    Ok(())
+    UpstreamIntegrationOutcome::new(
+        repo,
+        default_target.sha,
+        (series_head.id(), new_series_head),
+        remote_head.id(),
+        (integrate_upstream_context.branch_head, head),
+    )
 }
 
 /// Integrates upstream work from a remote branch.
@@ -79,11 +210,14 @@ pub fn integrate_upstream_commits_for_series(
 /// of the branch. Any other upstream commits are placed above the local
 /// commits.
 ///
+/// Commits that become empty when rebased are dropped unless `keep_empty` is set.
+/// The changes to the worktree are the same for each returned outcome.
 pub fn integrate_upstream_commits(
     ctx: &CommandContext,
     branch_id: StackId,
     perm: &mut WorktreeWritePermission,
-) -> Result<()> {
+    keep_empty: bool,
+) -> Result<UpstreamIntegrationOutcome> {
     conflicts::is_conflicting(ctx, None)?;
 
     let repository = ctx.repository();
@@ -102,7 +236,7 @@ pub fn integrate_upstream_commits(
     // If the upstream branch head is the same as the local, then the branch is
     // up to date.
     if upstream_branch_head == branch.head() {
-        return Ok(());
+        return Ok(UpstreamIntegrationOutcome::UpToDate);
     }
 
     let default_target = vb_state.get_default_target()?;
@@ -118,6 +252,7 @@ pub fn integrate_upstream_commits(
         remote_head: upstream_branch_head,
         remote_branch_name: upstream_branch.name()?.unwrap_or("Unknown"),
         prefers_merge: !branch.allow_rebasing,
+        keep_empty,
     };
 
     let BranchHeadAndTree { head, tree } =
@@ -131,7 +266,180 @@ pub fn integrate_upstream_commits(
 
     crate::integration::update_workspace_commit(&vb_state, ctx)?;
 
-    Ok(())
+    UpstreamIntegrationOutcome::new(
+        repository,
+        default_target.sha,
+        (integrate_upstream_context.branch_head, head),
+        upstream_branch_head,
+        (integrate_upstream_context.branch_head, head),
+    )
+}
+
+/// The key of the snapshot trailer holding the id of the branch upstream commits are integrated into.
+pub const BRANCH_ID_TRAILER: &str = "branch_id";
+
+/// Return the id of the snapshot taken right before the upstream commits were integrated into the branch with
+/// `branch_id`, which has to be left with conflicts by it. Restoring it aborts the integration.
+///
+/// Only file changes may have been snapshotted since, as restoring it would undo any other operation too.
+pub fn integration_snapshot(ctx: &CommandContext, branch_id: StackId) -> Result<git2::Oid> {
+    let repository = ctx.repository();
+    let vb_state = ctx.project().virtual_branches();
+    let branch = vb_state.get_branch_in_workspace(branch_id)?;
+    let default_target = vb_state.get_default_target()?;
+
+    // conflicts of other branches don't make this one abortable
+    let mut is_conflicted = conflicts::conflicting_files(ctx)?.iter().any(|path| {
+        branch
+            .ownership
+            .claims
+            .iter()
+            .any(|claim| claim.file_path == *path)
+    });
+    for commit_id in repository.l(branch.head(), LogUntil::Commit(default_target.sha), false)? {
+        is_conflicted |= repository.find_commit(commit_id)?.is_conflicted();
+    }
+    if !is_conflicted {
+        return Err(anyhow!(
+            "branch {} has no conflicted integration to abort",
+            branch.name
+        )
+        .context(Code::Validation));
+    }
+
+    let branch_id = branch_id.to_string();
+    for snapshot in ctx.project().list_snapshots(100, None)? {
+        let Some(details) = snapshot.details else {
+            break;
+        };
+        match details.operation {
+            OperationKind::MergeUpstream
+                if details.trailers.iter().any(|trailer| {
+                    trailer.key == BRANCH_ID_TRAILER && trailer.value == branch_id
+                }) =>
+            {
+                return Ok(snapshot.commit_id)
+            }
+            OperationKind::FileChanges => continue,
+            _ => break,
+        }
+    }
+    Err(anyhow!(
+        "the integration into branch {} can't be aborted as other operations happened since",
+        branch.name
+    )
+    .context(Code::Validation))
+}
+
+/// Returns `true` if the upstream of `branch` has commits that aren't part of the branch yet,
+/// so committing to it would make it diverge from its upstream.
+/// Branches without an upstream, or whose upstream wasn't fetched, are never behind.
+pub fn is_behind_upstream(ctx: &CommandContext, branch: &Stack) -> Result<bool> {
+    let Some(upstream_refname) = branch.upstream.clone() else {
+        return Ok(false);
+    };
+    let repository = ctx.repository();
+    let Some(upstream_branch) =
+        repository.maybe_find_branch_by_refname(&upstream_refname.into())?
+    else {
+        return Ok(false);
+    };
+    let upstream_head = upstream_branch.get().peel_to_commit()?.id();
+    Ok(upstream_head != branch.head()
+        && !repository.graph_descendant_of(branch.head(), upstream_head)?)
+}
+
+/// What happened to a single branch in [`integrate_upstream_commits_for_all()`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "subject", rename_all = "camelCase")]
+pub enum IntegrationOutcome {
+    /// The upstream commits were integrated cleanly.
+    Integrated,
+    /// The upstream commits were integrated, but some of the resulting commits are conflicted.
+    Conflicted,
+    /// The branch already contained all upstream commits.
+    UpToDate,
+    /// Integrating the upstream commits failed with `error`.
+    Failed { error: String },
+}
+
+/// Integrate the upstream commits of all applied branches, in workspace order, like
+/// [`integrate_upstream_commits()`] does for a single branch.
+/// Branches without an upstream are skipped, as are those whose remote-tracking branch is gone,
+/// like after it was merged and pruned. A branch that fails to integrate doesn't stop the others
+/// from being integrated, and is reported as [`IntegrationOutcome::Failed`].
+pub fn integrate_upstream_commits_for_all(
+    ctx: &CommandContext,
+    perm: &mut WorktreeWritePermission,
+) -> Result<Vec<(StackId, IntegrationOutcome)>> {
+    let vb_state = ctx.project().virtual_branches();
+    let default_target = vb_state.get_default_target()?;
+
+    let mut branches = vb_state.list_branches_in_workspace()?;
+    branches.sort_by_key(|branch| branch.order);
+
+    let mut outcomes = Vec::new();
+    for branch in branches {
+        match integrate_branch_upstream(ctx, &branch, default_target.sha, perm) {
+            Ok(Some(outcome)) => outcomes.push((branch.id, outcome)),
+            Ok(None) => {}
+            Err(err) => {
+                tracing::warn!(
+                    "failed to integrate the upstream commits of branch {}: {err:#}",
+                    branch.name
+                );
+                outcomes.push((
+                    branch.id,
+                    IntegrationOutcome::Failed {
+                        error: format!("{err:#}"),
+                    },
+                ));
+            }
+        }
+    }
+    Ok(outcomes)
+}
+
+/// Integrate the upstream commits of `branch` for [`integrate_upstream_commits_for_all()`], or return `None`
+/// if it has no upstream to integrate.
+fn integrate_branch_upstream(
+    ctx: &CommandContext,
+    branch: &Stack,
+    target_sha: git2::Oid,
+    perm: &mut WorktreeWritePermission,
+) -> Result<Option<IntegrationOutcome>> {
+    let repository = ctx.repository();
+    let Some(upstream_refname) = branch.upstream.clone() else {
+        return Ok(None);
+    };
+    let Some(upstream_branch) =
+        repository.maybe_find_branch_by_refname(&upstream_refname.into())?
+    else {
+        return Ok(None);
+    };
+    let upstream_head = upstream_branch.get().peel_to_commit()?.id();
+
+    let old_commits = repository.l(branch.head(), LogUntil::Commit(target_sha), false)?;
+    if upstream_head == branch.head() || old_commits.contains(&upstream_head) {
+        return Ok(Some(IntegrationOutcome::UpToDate));
+    }
+
+    integrate_upstream_commits(ctx, branch.id, perm, false)?;
+
+    let new_head = ctx
+        .project()
+        .virtual_branches()
+        .get_branch_in_workspace(branch.id)?
+        .head();
+    let mut new_commits = repository
+        .log(new_head, LogUntil::Commit(target_sha), false)?
+        .into_iter()
+        .filter(|commit| !old_commits.contains(&commit.id()));
+    Ok(Some(if new_commits.any(|commit| commit.is_conflicted()) {
+        IntegrationOutcome::Conflicted
+    } else {
+        IntegrationOutcome::Integrated
+    }))
 }
 
 struct IntegrateUpstreamContext<'a, 'b> {
@@ -153,9 +461,19 @@ struct IntegrateUpstreamContext<'a, 'b> {
 
     /// Whether to merge or rebase
     prefers_merge: bool,
+    /// Whether to keep commits that become empty when rebased
+    keep_empty: bool,
 }
 
 impl IntegrateUpstreamContext<'_, '_> {
+    /// Integrating keeps our version of files whose merge attribute asks for it.
+    fn rebase_options(&self) -> RebaseOptions {
+        RebaseOptions {
+            keep_empty: self.keep_empty,
+            keep_current_by_merge_attribute: true,
+        }
+    }
+
     /// Unlike the `inner_integrate_upstream_commits` method, this will do the rebase in two steps.
     /// First it will rebase the series head and it's remote commits, then it will rebase any remaining on the stack.
     fn inner_integrate_upstream_commits_for_series(
@@ -166,12 +484,13 @@ impl IntegrateUpstreamContext<'_, '_> {
             // If rebase is not allowed AND this is the latest series - create a merge commit on top
             let series_head_commit = self.repository.find_commit(series_head)?;
             let remote_head_commit = self.repository.find_commit(self.remote_head)?;
-            let merge_commit = gitbutler_merge_commits(
+            let merge_commit = gitbutler_merge_commits_with_options(
                 self.repository,
                 series_head_commit,
                 remote_head_commit,
                 self.branch_name,        // for error messages only
                 self.remote_branch_name, // for error messages only
+                self.rebase_options(),
             )?;
             // the are the same
             let new_stack_head = merge_commit.id();
@@ -189,15 +508,24 @@ impl IntegrateUpstreamContext<'_, '_> {
                 self.remote_head,
             )?;
             // First rebase the series with it's remote commits
-            let new_series_head =
-                cherry_rebase_group(self.repository, merge_base, &ordered_commits)?;
+            let new_series_head = cherry_rebase_group_with_options(
+                self.repository,
+                merge_base,
+                &ordered_commits,
+                self.rebase_options(),
+            )?;
             // Get the commits that come after the series head, until the stack head
             let remaining_ids_to_rebase =
                 self.repository
                     .l(self.branch_head, LogUntil::Commit(series_head), false)?;
             // Rebase the remaining commits on top of the new series head in order to get the new stack head
             (
-                cherry_rebase_group(self.repository, new_series_head, &remaining_ids_to_rebase)?,
+                cherry_rebase_group_with_options(
+                    self.repository,
+                    new_series_head,
+                    &remaining_ids_to_rebase,
+                    self.rebase_options(),
+                )?,
                 new_series_head,
             )
         };
@@ -218,12 +546,13 @@ impl IntegrateUpstreamContext<'_, '_> {
         let new_head = if self.prefers_merge {
             let branch_head_commit = self.repository.find_commit(self.branch_head)?;
             let remote_head_commit = self.repository.find_commit(self.remote_head)?;
-            gitbutler_merge_commits(
+            gitbutler_merge_commits_with_options(
                 self.repository,
                 branch_head_commit,
                 remote_head_commit,
                 self.branch_name,
                 self.remote_branch_name,
+                self.rebase_options(),
             )?
             .id()
         } else {
@@ -237,7 +566,12 @@ impl IntegrateUpstreamContext<'_, '_> {
                 self.remote_head,
             )?;
 
-            cherry_rebase_group(self.repository, merge_base, &ordered_commits)?
+            cherry_rebase_group_with_options(
+                self.repository,
+                merge_base,
+                &ordered_commits,
+                self.rebase_options(),
+            )?
         };
 
         // Find what the new head and branch tree should be
@@ -337,6 +671,7 @@ mod test {
                 remote_head: remote_y.id(),
                 remote_branch_name: "test",
                 prefers_merge: false,
+                keep_empty: false,
             };
 
             let BranchHeadAndTree { head, tree: _tree } =
@@ -363,6 +698,66 @@ mod test {
             );
         }
 
+        /// Local:  Base -> A
+        /// Remote: Base -> X, where X makes the same change as A
+        /// Trunk:  Base
+        /// Result: Base -> A, or Base -> A -> X' if empty commits are kept
+        #[test]
+        fn remote_commit_becoming_empty() {
+            let test_repository = TestingRepository::open();
+
+            let base_commit = test_repository.commit_tree(None, &[("foo.txt", "foo")]);
+            let local_a = test_repository.commit_tree_with_message(
+                Some(&base_commit),
+                "A",
+                &[("foo.txt", "foo1")],
+            );
+            let remote_x = test_repository.commit_tree_with_message(
+                Some(&base_commit),
+                "X",
+                &[("foo.txt", "foo1")],
+            );
+
+            let mut ctx = IntegrateUpstreamContext {
+                repository: &test_repository.repository,
+                target_branch_head: base_commit.id(),
+                branch_head: local_a.id(),
+                branch_tree: local_a.tree_id(),
+                branch_name: "test",
+                remote_head: remote_x.id(),
+                remote_branch_name: "test",
+                prefers_merge: false,
+                keep_empty: false,
+            };
+
+            let BranchHeadAndTree { head, tree: _tree } =
+                ctx.inner_integrate_upstream_commits().unwrap();
+            assert_eq!(
+                test_repository
+                    .repository
+                    .l(head, LogUntil::Commit(base_commit.id()), false)
+                    .unwrap(),
+                vec![local_a.id()],
+                "the empty commit is dropped by default"
+            );
+
+            ctx.keep_empty = true;
+            let BranchHeadAndTree { head, tree: _tree } =
+                ctx.inner_integrate_upstream_commits().unwrap();
+            let commits = test_repository
+                .repository
+                .log(head, LogUntil::Commit(base_commit.id()), false)
+                .unwrap();
+            assert_eq!(commits.len(), 2);
+            assert_eq!(commits[0].message(), remote_x.message());
+            assert_eq!(
+                commits[0].tree_id(),
+                local_a.tree_id(),
+                "the kept commit is empty"
+            );
+            assert_eq!(commits[1].id(), local_a.id());
+        }
+
         /// Full Stack: Base -> A -> B -> C -> D
         /// Series One:         A -> B
         /// Series Two:                   C -> D
@@ -392,6 +787,7 @@ mod test {
                 remote_head: remote_y.id(),
                 remote_branch_name: "test",
                 prefers_merge: false,
+                keep_empty: false,
             };
 
             let (BranchHeadAndTree { head, tree: _tree }, new_series_head) = ctx
@@ -461,6 +857,7 @@ mod test {
                 remote_head: remote_y.id(),
                 remote_branch_name: "test",
                 prefers_merge: false,
+                keep_empty: false,
             };
 
             let BranchHeadAndTree { head, tree: _tree } =
@@ -577,6 +974,7 @@ mod test {
                 remote_head: remote_y.id(),
                 remote_branch_name: "test",
                 prefers_merge: false,
+                keep_empty: false,
             };
 
             let BranchHeadAndTree { head, tree: _tree } =
@@ -718,6 +1116,7 @@ mod test {
                 remote_head: remote_y.id(),
                 remote_branch_name: "test",
                 prefers_merge: false,
+                keep_empty: false,
             };
 
             let BranchHeadAndTree { head, tree: _tree } =
@@ -896,4 +1295,163 @@ mod test {
             );
         }
     }
+
+    mod upstream_integration_outcome {
+        use std::path::PathBuf;
+
+        use crate::branch_trees::BranchHeadAndTree;
+        use crate::branch_upstream_integration::UpstreamIntegrationOutcome;
+
+        use super::*;
+
+        /// Local:  Base -> A
+        /// Remote: Base -> A -> X
+        #[test]
+        fn fast_forward_and_up_to_date() {
+            let test_repository = TestingRepository::open();
+            let repo = &test_repository.repository;
+
+            let base_commit = test_repository.commit_tree(None, &[("foo.txt", "foo")]);
+            let local_a = test_repository.commit_tree(Some(&base_commit), &[("foo.txt", "foo1")]);
+            let remote_x = test_repository.commit_tree(Some(&local_a), &[("foo.txt", "foo2")]);
+
+            let outcome = |old_head: git2::Oid, new_head: git2::Oid| {
+                UpstreamIntegrationOutcome::new(
+                    repo,
+                    base_commit.id(),
+                    (old_head, new_head),
+                    remote_x.id(),
+                    (old_head, new_head),
+                )
+                .unwrap()
+            };
+            assert_eq!(
+                outcome(local_a.id(), remote_x.id()),
+                UpstreamIntegrationOutcome::FastForwarded
+            );
+            assert_eq!(
+                outcome(remote_x.id(), remote_x.id()),
+                UpstreamIntegrationOutcome::UpToDate
+            );
+        }
+
+        /// Local:  Base -> A -> B
+        /// Remote: Base -> A -> B' (will conflict when rebased on top of B)
+        /// Trunk:  Base
+        /// Result: Base -> A -> B -> B'' (Cft)
+        #[test]
+        fn conflicted() {
+            let test_repository = TestingRepository::open();
+            let repo = &test_repository.repository;
+
+            let base_commit = test_repository.commit_tree(None, &[]);
+            let local_a = test_repository.commit_tree_with_message(
+                Some(&base_commit),
+                "A",
+                &[("foo.txt", "foo")],
+            );
+            let local_b = test_repository.commit_tree_with_message(
+                Some(&local_a),
+                "B",
+                &[("foo.txt", "foo1"), ("bar.txt", "bar")],
+            );
+            let remote_b = test_repository.commit_tree_with_message(
+                Some(&local_a),
+                "B'",
+                &[("foo.txt", "foo2")],
+            );
+
+            let ctx = IntegrateUpstreamContext {
+                repository: repo,
+                target_branch_head: base_commit.id(),
+                branch_head: local_b.id(),
+                branch_tree: local_b.tree_id(),
+                branch_name: "test",
+                remote_head: remote_b.id(),
+                remote_branch_name: "test",
+                prefers_merge: false,
+                keep_empty: false,
+            };
+            let BranchHeadAndTree { head, tree: _tree } =
+                ctx.inner_integrate_upstream_commits().unwrap();
+
+            let outcome = UpstreamIntegrationOutcome::new(
+                repo,
+                base_commit.id(),
+                (local_b.id(), head),
+                remote_b.id(),
+                (local_b.id(), head),
+            )
+            .unwrap();
+            let UpstreamIntegrationOutcome::Conflicted { files } = outcome else {
+                panic!("expected a conflict, got {outcome:?}");
+            };
+            assert_eq!(
+                files.iter().map(|file| &file.path).collect::<Vec<_>>(),
+                [&PathBuf::from("foo.txt")]
+            );
+        }
+
+        /// Local:  Base -> A
+        /// Remote: Base -> A' (changes the same line as A)
+        /// Trunk:  Base
+        /// Result: Base -> A' -> A'' (Cft)
+        #[test]
+        fn conflicted_files_have_hunks_of_both_sides() {
+            let test_repository = TestingRepository::open();
+            let repo = &test_repository.repository;
+
+            let base_commit =
+                test_repository.commit_tree(None, &[("test.txt", "line1\nline2\nline3\n")]);
+            let local_a = test_repository.commit_tree_with_message(
+                Some(&base_commit),
+                "A",
+                &[("test.txt", "line1\nlocal\nline3\n")],
+            );
+            let remote_a = test_repository.commit_tree_with_message(
+                Some(&base_commit),
+                "A'",
+                &[("test.txt", "line1\nupstream\nline3\n")],
+            );
+
+            let ctx = IntegrateUpstreamContext {
+                repository: repo,
+                target_branch_head: base_commit.id(),
+                branch_head: local_a.id(),
+                branch_tree: local_a.tree_id(),
+                branch_name: "test",
+                remote_head: remote_a.id(),
+                remote_branch_name: "test",
+                prefers_merge: false,
+                keep_empty: false,
+            };
+            let BranchHeadAndTree { head, tree: _tree } =
+                ctx.inner_integrate_upstream_commits().unwrap();
+
+            let outcome = UpstreamIntegrationOutcome::new(
+                repo,
+                base_commit.id(),
+                (local_a.id(), head),
+                remote_a.id(),
+                (local_a.id(), head),
+            )
+            .unwrap();
+            let UpstreamIntegrationOutcome::Conflicted { files } = outcome else {
+                panic!("expected a conflict, got {outcome:?}");
+            };
+            assert_eq!(files.len(), 1);
+            let file = &files[0];
+            assert_eq!(file.path, PathBuf::from("test.txt"));
+
+            assert_eq!(file.ours_hunks.len(), 1);
+            let ours = &file.ours_hunks[0];
+            assert_eq!((ours.new_start, ours.new_lines), (2, 1));
+            assert_eq!(ours.diff_lines, "@@ -2 +2 @@\n-line2\n+upstream\n");
+
+            assert_eq!(file.theirs_hunks.len(), 1);
+            let theirs = &file.theirs_hunks[0];
+            assert_eq!((theirs.new_start, theirs.new_lines), (2, 1));
+            assert_eq!(theirs.diff_lines, "@@ -2 +2 @@\n-line2\n+local\n");
+        }
+    }
 }
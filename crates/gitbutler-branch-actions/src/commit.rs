@@ -28,6 +28,9 @@ pub struct VirtualBranchCommit {
     /// Dont use, favor `remote_commit_id` instead
     pub is_remote: bool,
     pub is_integrated: bool,
+    /// `true` if the repository is shallow and its history doesn't reach far enough to tell
+    /// if this commit is integrated. `is_integrated` is `false` then.
+    pub integration_unknown: bool,
     #[serde(with = "gitbutler_serde::oid_vec")]
     pub parent_ids: Vec<git2::Oid>,
     pub branch_id: StackId,
@@ -74,20 +77,7 @@ pub(crate) fn commit_to_vbranch_commit(
 
     let repository = ctx.repository();
 
-    let conflicted_files = if commit.is_conflicted() {
-        let conflict_files_string = commit.tree()?;
-        let conflict_files_string = conflict_files_string
-            .get_name(&ConflictedTreeKey::ConflictFiles)
-            .ok_or_else(|| anyhow!("conflict files not found"))?;
-        let conflict_files_string = repository
-            .find_blob(conflict_files_string.id())?
-            .content()
-            .to_str_lossy()
-            .to_string();
-        toml::from_str::<ConflictEntries>(&conflict_files_string).unwrap_or_default()
-    } else {
-        Default::default()
-    };
+    let conflicted_files = conflict_entries(repository, commit)?;
 
     let commit = VirtualBranchCommit {
         id: commit.id(),
@@ -96,6 +86,7 @@ pub(crate) fn commit_to_vbranch_commit(
         description: message.into(),
         is_remote,
         is_integrated,
+        integration_unknown: false,
         parent_ids,
         branch_id: branch.id,
         change_id: commit.change_id(),
@@ -108,3 +99,23 @@ pub(crate) fn commit_to_vbranch_commit(
 
     Ok(commit)
 }
+
+/// Returns the files that are conflicted in `commit`, which are none if the commit isn't conflicted.
+pub(crate) fn conflict_entries(
+    repository: &git2::Repository,
+    commit: &git2::Commit,
+) -> Result<ConflictEntries> {
+    if !commit.is_conflicted() {
+        return Ok(Default::default());
+    }
+    let conflict_files_string = commit.tree()?;
+    let conflict_files_string = conflict_files_string
+        .get_name(&ConflictedTreeKey::ConflictFiles)
+        .ok_or_else(|| anyhow!("conflict files not found"))?;
+    let conflict_files_string = repository
+        .find_blob(conflict_files_string.id())?
+        .content()
+        .to_str_lossy()
+        .to_string();
+    Ok(toml::from_str::<ConflictEntries>(&conflict_files_string).unwrap_or_default())
+}
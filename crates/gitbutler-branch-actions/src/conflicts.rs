@@ -14,6 +14,11 @@ use anyhow::{anyhow, bail, Context, Result};
 use bstr::ByteSlice;
 use gitbutler_command_context::CommandContext;
 use gitbutler_error::error::Marker;
+use gitbutler_project::access::WorktreeWritePermission;
+use gitbutler_stack::StackId;
+use serde::Serialize;
+
+use crate::{status::get_applied_status, VirtualBranchesExt as _};
 
 pub(crate) fn mark<P: AsRef<Path>, A: AsRef<[P]>>(
     ctx: &CommandContext,
@@ -126,6 +131,91 @@ pub(crate) fn is_conflicting(repository: &CommandContext, path: Option<&Path>) -
     Ok(is_in_conflicts_file_or_has_conflicts)
 }
 
+/// The three versions of a conflicting file, each written to its own file for an external merge tool.
+/// A side in which the file doesn't exist is written as an empty file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictSidePaths {
+    /// The file as it was in the merge base of both sides.
+    pub base: PathBuf,
+    /// The file as it is on the branch.
+    pub ours: PathBuf,
+    /// The file as it is in the merged-in commit.
+    pub theirs: PathBuf,
+}
+
+fn merge_tool_dir(ctx: &CommandContext) -> PathBuf {
+    ctx.project().gb_dir().join("merge-tool")
+}
+
+/// Write the base, ours and theirs versions of the conflicting `path` to disk, with ours
+/// taken from the head of the applied branch `branch_id`.
+pub(crate) fn write_conflict_sides(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    path: &Path,
+) -> Result<ConflictSidePaths> {
+    if !is_conflicting(ctx, Some(path))? {
+        bail!("{} is not conflicting", path.display());
+    }
+    let merge_parent = merge_parent(ctx)?.context("no merge is in progress")?;
+    let branch = ctx
+        .project()
+        .virtual_branches()
+        .get_branch_in_workspace(branch_id)?;
+
+    let repo = ctx.repository();
+    let base = repo
+        .merge_base(branch.head(), merge_parent)
+        .context("failed to find merge base")?;
+    let dir = merge_tool_dir(ctx);
+    let write_side = |side: &str, commit_oid: git2::Oid| -> Result<PathBuf> {
+        let content = match repo.find_commit(commit_oid)?.tree()?.get_path(path) {
+            Ok(entry) => entry
+                .to_object(repo)?
+                .into_blob()
+                .map_err(|_| anyhow!("{} is not a file in commit {commit_oid}", path.display()))?
+                .content()
+                .to_owned(),
+            Err(err) if err.code() == git2::ErrorCode::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let side_path = dir.join(side).join(path);
+        gitbutler_fs::create_dirs_then_write(&side_path, content)?;
+        Ok(side_path)
+    };
+    Ok(ConflictSidePaths {
+        base: write_side("base", base)?,
+        ours: write_side("ours", branch.head())?,
+        theirs: write_side("theirs", merge_parent)?,
+    })
+}
+
+/// Write the content of `merged_path`, as produced by an external merge tool, to the conflicting `path`
+/// in the worktree, mark `path` as resolved and remove the files written for it by [`write_conflict_sides()`].
+pub(crate) fn finalize_merge_tool_result(
+    ctx: &CommandContext,
+    path: &Path,
+    merged_path: &Path,
+    perm: &mut WorktreeWritePermission,
+) -> Result<()> {
+    if !is_conflicting(ctx, Some(path))? {
+        bail!("{} is not conflicting", path.display());
+    }
+    let merged = std::fs::read(merged_path)
+        .with_context(|| format!("failed to read merge result at {}", merged_path.display()))?;
+    gitbutler_fs::write(ctx.project().worktree_path().join(path), merged)?;
+    resolve(ctx, path)?;
+
+    let dir = merge_tool_dir(ctx);
+    for side in ["base", "ours", "theirs"] {
+        remove_file_ignore_missing(dir.join(side).join(path))?;
+    }
+
+    get_applied_status(ctx, Some(perm))?;
+    Ok(())
+}
+
 // is this project still in a resolving conflict state?
 // - could be that there are no more conflicts, but the state is not committed
 pub(crate) fn is_resolving(ctx: &CommandContext) -> bool {
@@ -0,0 +1,170 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+/// The properties of the `.editorconfig` files that apply to a file which affect how its whitespace is cleaned.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct WhitespaceProperties {
+    /// `trim_trailing_whitespace`, or `None` if it isn't set.
+    pub trim_trailing_whitespace: Option<bool>,
+    /// `insert_final_newline`, or `None` if it isn't set.
+    pub insert_final_newline: Option<bool>,
+}
+
+impl WhitespaceProperties {
+    /// Read the properties of the file at `path`, relative to `worktree_dir`, from the `.editorconfig` files
+    /// in its directory and all of its parents up to the worktree root. Closer files take precedence,
+    /// and a file with `root = true` hides the ones further up.
+    ///
+    /// Numeric ranges like `{1..3}` in section globs aren't supported, and never match.
+    pub(crate) fn for_path(worktree_dir: &Path, path: &Path) -> Result<Self> {
+        let mut dirs: Vec<_> = path.ancestors().skip(1).collect();
+        dirs.reverse();
+        let mut properties = Self::default();
+        for dir in dirs {
+            let config_path = worktree_dir.join(dir).join(".editorconfig");
+            let config = match std::fs::read_to_string(&config_path) {
+                Ok(config) => config,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => {
+                    return Err(err)
+                        .with_context(|| format!("failed to read {}", config_path.display()))
+                }
+            };
+            let relative_path = path.strip_prefix(dir).unwrap_or(path);
+            properties.apply(&config, &relative_path.to_string_lossy().replace('\\', "/"));
+        }
+        Ok(properties)
+    }
+
+    /// Apply the properties of the sections of `config` matching `relative_path`, which is relative to the
+    /// directory of `config` and uses `/` as separator.
+    fn apply(&mut self, config: &str, relative_path: &str) {
+        let mut section: Option<bool> = None;
+        for line in config.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(glob) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                section = Some(
+                    section_pattern(glob).is_some_and(|pattern| pattern.is_match(relative_path)),
+                );
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let (key, value) = (key.trim().to_lowercase(), value.trim().to_lowercase());
+            let value = match value.as_str() {
+                "true" => Some(true),
+                "false" => Some(false),
+                "unset" => None,
+                _ => continue,
+            };
+            match (section, key.as_str()) {
+                (None, "root") if value == Some(true) => *self = Self::default(),
+                (Some(true), "trim_trailing_whitespace") => self.trim_trailing_whitespace = value,
+                (Some(true), "insert_final_newline") => self.insert_final_newline = value,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Translate the `glob` of a section into a regex matching the paths relative to the directory of its
+/// `.editorconfig`, or `None` if it's invalid. Like in `.gitignore`, a glob without `/` matches the file name
+/// at any depth.
+fn section_pattern(glob: &str) -> Option<regex::Regex> {
+    let (anchored, glob) = match glob.strip_prefix('/') {
+        Some(glob) => (true, glob),
+        None => (glob.contains('/'), glob),
+    };
+    let mut pattern = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let mut chars = glob.chars().peekable();
+    let mut open_braces = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                pattern.push_str(".*");
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            '[' => {
+                let mut class = String::new();
+                loop {
+                    match chars.next()? {
+                        ']' => break,
+                        c @ ('\\' | '[' | '&' | '~' | '^') => {
+                            class.push('\\');
+                            class.push(c);
+                        }
+                        c => class.push(c),
+                    }
+                }
+                match class.strip_prefix('!') {
+                    Some(class) => pattern.push_str(&format!("[^{class}]")),
+                    None => pattern.push_str(&format!("[{class}]")),
+                }
+            }
+            '{' => {
+                open_braces += 1;
+                pattern.push_str("(?:");
+            }
+            '}' if open_braces > 0 => {
+                open_braces -= 1;
+                pattern.push(')');
+            }
+            ',' if open_braces > 0 => pattern.push('|'),
+            '\\' => pattern.push_str(&regex::escape(&chars.next()?.to_string())),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    regex::Regex::new(&pattern).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_match_like_editorconfig_globs() {
+        let matches = |glob: &str, path: &str| section_pattern(glob).unwrap().is_match(path);
+        assert!(matches("*", "a/b.txt"));
+        assert!(matches("*.md", "docs/readme.md"));
+        assert!(!matches("*.md", "readme.mdx"));
+        assert!(matches("*.{js,ts}", "src/index.ts"));
+        assert!(matches("/src/*.rs", "src/lib.rs"));
+        assert!(!matches("/src/*.rs", "src/nested/lib.rs"));
+        assert!(matches("src/**.rs", "src/nested/lib.rs"));
+        assert!(matches("[!a]?.txt", "bc.txt"));
+        assert!(!matches("[!a]?.txt", "ac.txt"));
+        assert!(section_pattern("{unclosed").is_none());
+    }
+
+    #[test]
+    fn later_sections_and_closer_files_take_precedence() {
+        let mut properties = WhitespaceProperties::default();
+        properties.apply(
+            "root = true\n[*]\ntrim_trailing_whitespace = true\ninsert_final_newline = true\n\
+             [*.md]\ntrim_trailing_whitespace = false\n",
+            "docs/readme.md",
+        );
+        assert_eq!(
+            properties,
+            WhitespaceProperties {
+                trim_trailing_whitespace: Some(false),
+                insert_final_newline: Some(true),
+            }
+        );
+
+        properties.apply("[*]\ninsert_final_newline = unset\n", "readme.md");
+        assert_eq!(properties.insert_final_newline, None);
+        properties.apply("root = true\n", "readme.md");
+        assert_eq!(properties, WhitespaceProperties::default());
+    }
+}
@@ -94,6 +94,11 @@ pub struct VirtualBranchFile {
     pub conflicted: bool,
     pub binary: bool,
     pub large: bool,
+    /// The mode of the file changed while its content stayed the same, e.g. after `chmod +x`.
+    pub mode_change: bool,
+    /// Whether the file is new (like an untracked file), deleted, or a modification of a tracked file.
+    /// This is only a label and has no bearing on which branch owns the file.
+    pub change_type: gitbutler_diff::ChangeType,
 }
 
 pub trait Get<T> {
@@ -126,6 +131,25 @@ pub(crate) fn list_virtual_commit_files(
     Ok(virtual_hunks_into_virtual_files(ctx, hunks_by_filepath))
 }
 
+/// List the changes from the tree of the `base` commit to `tree`, like all changes a branch made since it forked off.
+pub(crate) fn list_virtual_tree_files(
+    ctx: &CommandContext,
+    base: git2::Oid,
+    tree: git2::Oid,
+    diff_options: gitbutler_diff::DiffOptions,
+) -> Result<Vec<VirtualBranchFile>> {
+    let repository = ctx.repository();
+    let base_tree = repository
+        .find_commit(base)
+        .and_then(|commit| commit.tree())
+        .context("failed to get base tree")?;
+    let tree = repository.find_tree(tree).context("failed to get tree")?;
+    let diff =
+        gitbutler_diff::trees_with_options(repository, &base_tree, &tree, true, diff_options)?;
+    let hunks_by_filepath = virtual_hunks_by_file_diffs(&ctx.project().path, diff);
+    Ok(virtual_hunks_into_virtual_files(ctx, hunks_by_filepath))
+}
+
 fn virtual_hunks_by_file_diffs<'a>(
     project_path: &'a Path,
     diff: impl IntoIterator<Item = (PathBuf, FileDiff)> + 'a,
@@ -150,6 +174,14 @@ pub(crate) fn virtual_hunks_into_virtual_files(
             let conflicted = conflicts::is_conflicting(ctx, Some(&path)).unwrap_or(false);
             let binary = hunks.iter().any(|h| h.binary);
             let modified_at = hunks.iter().map(|h| h.modified_at).max().unwrap_or(0);
+            let mode_change = matches!(hunks.as_slice(), [hunk] if !hunk.binary
+                && hunk.diff.is_empty()
+                && hunk.change_type == gitbutler_diff::ChangeType::Modified);
+            let change_type = hunks
+                .first()
+                .map_or(gitbutler_diff::ChangeType::Modified, |hunk| {
+                    hunk.change_type
+                });
             debug_assert!(hunks.iter().all(|hunk| hunk.file_path == path));
             VirtualBranchFile {
                 id,
@@ -159,6 +191,8 @@ pub(crate) fn virtual_hunks_into_virtual_files(
                 large: false,
                 modified_at,
                 conflicted,
+                mode_change,
+                change_type,
             }
         })
         .collect::<Vec<_>>()
@@ -1,11 +1,13 @@
 use std::{
     collections::HashMap,
+    ops::RangeInclusive,
     path::{Path, PathBuf},
     time,
     time::SystemTime,
 };
 
-use gitbutler_diff::{GitHunk, Hunk, HunkHash};
+use bstr::{BStr, ByteSlice};
+use gitbutler_diff::{DiffLine, GitHunk, Hunk, HunkHash, LineFilter, MovedLine};
 use gitbutler_hunk_dependency::locks::HunkLock;
 use gitbutler_serde::BStringForFrontend;
 use itertools::Itertools;
@@ -35,11 +37,28 @@ pub struct VirtualBranchHunk {
     #[serde(skip)]
     pub old_lines: u32,
     pub binary: bool,
+    /// The hex hash of the blob before the change if the file changed from text to binary content or back.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_blob_id: Option<String>,
+    /// The path the file had before if it was renamed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub renamed_from: Option<PathBuf>,
     pub locked: bool,
     pub locked_to: Option<Box<[HunkLock]>>,
     pub change_type: gitbutler_diff::ChangeType,
     /// Indicates that the hunk depends on multiple branches. In this case the hunk cant be moved or comitted.
     pub poisoned: bool,
+    /// Indicates that the hunk adds lines that look like conflict markers, which is likely a mistake
+    /// unless a merge conflict is currently being resolved.
+    pub conflict_markers: bool,
+    /// Lines of this hunk that were moved within the same file, if this was requested with
+    /// [`DiffOptions::detect_moved_lines`](gitbutler_diff::DiffOptions::detect_moved_lines).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub moved_lines: Vec<MovedLine>,
+    /// The lines of [`diff`](Self::diff) along with their line numbers, if this was requested with
+    /// [`DiffOptions::structured_lines`](gitbutler_diff::DiffOptions::structured_lines).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lines: Vec<DiffLine>,
 }
 
 /// Lifecycle
@@ -55,6 +74,7 @@ impl VirtualBranchHunk {
         locks: &HashMap<Digest, Vec<HunkLock>>,
     ) -> Self {
         let hash = Hunk::hash_diff(&hunk.diff_lines);
+        let conflict_markers = !hunk.binary && adds_conflict_markers(hunk.diff_lines.as_ref());
 
         let binding = Vec::new();
         let locked_to = locks.get(&hash).unwrap_or(&binding);
@@ -72,13 +92,91 @@ impl VirtualBranchHunk {
             start: hunk.new_start,
             end: hunk.new_start + hunk.new_lines,
             binary: hunk.binary,
+            old_blob_id: hunk.old_blob_id,
+            renamed_from: hunk.renamed_from,
             hash,
             locked: !locked_to.is_empty(),
             locked_to: Some(locked_to.clone().into_boxed_slice()),
             change_type: hunk.change_type,
             poisoned: branch_deps_count > 1,
+            conflict_markers,
+            moved_lines: hunk.moved_lines,
+            lines: hunk.lines,
         }
     }
+
+    /// Restrict this hunk to its added or removed lines, see [`GitHunk::filter_lines()`].
+    pub(crate) fn filter_lines(self, filter: LineFilter) -> Option<Self> {
+        let hunk = GitHunk::from(self.clone()).filter_lines(filter)?;
+        Some(self.with_git_hunk(hunk))
+    }
+
+    /// Restrict this hunk to the changes of the given ranges of `lines`, see [`GitHunk::select_lines()`].
+    pub(crate) fn select_lines(self, lines: &[RangeInclusive<u32>]) -> Option<Self> {
+        let hunk = GitHunk::from(self.clone()).select_lines(lines)?;
+        Some(self.with_git_hunk(hunk))
+    }
+
+    fn with_git_hunk(self, hunk: GitHunk) -> Self {
+        VirtualBranchHunk {
+            lines: if self.lines.is_empty() {
+                Vec::new()
+            } else {
+                hunk.structured_lines()
+            },
+            hash: Hunk::hash_diff(&hunk.diff_lines),
+            conflict_markers: adds_conflict_markers(hunk.diff_lines.as_ref()),
+            diff: hunk.diff_lines,
+            old_lines: hunk.old_lines,
+            end: hunk.new_start + hunk.new_lines,
+            ..self
+        }
+    }
+}
+
+/// The content of a single hunk before and after the change, without the line separators.
+/// Both include the unchanged context lines of the hunk.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HunkContent {
+    /// The line number at which [`before`](Self::before) starts in the original file.
+    pub old_start: u32,
+    /// The line number at which [`after`](Self::after) starts in the changed file.
+    pub new_start: u32,
+    pub before: Vec<BStringForFrontend>,
+    pub after: Vec<BStringForFrontend>,
+}
+
+impl HunkContent {
+    /// Split the `-`, `+` and ` ` prefixed lines of `hunk` into its content before and after the change.
+    pub(crate) fn from_hunk(hunk: &VirtualBranchHunk) -> Self {
+        let mut before = Vec::new();
+        let mut after = Vec::new();
+        for line in hunk.diff.lines() {
+            match line.split_first() {
+                Some((b'-', line)) => before.push(line.as_bstr().into()),
+                Some((b'+', line)) => after.push(line.as_bstr().into()),
+                Some((b' ', line)) => {
+                    before.push(line.as_bstr().into());
+                    after.push(line.as_bstr().into());
+                }
+                // The `@@` header and `\ No newline at end of file`.
+                _ => {}
+            }
+        }
+        HunkContent {
+            old_start: hunk.old_start,
+            new_start: hunk.start,
+            before,
+            after,
+        }
+    }
+}
+
+/// Returns `true` if `diff` adds lines starting with `<<<<<<<` or `>>>>>>>`, as written by a merge.
+fn adds_conflict_markers(diff: &BStr) -> bool {
+    diff.lines()
+        .any(|line| line.starts_with(b"+<<<<<<<") || line.starts_with(b"+>>>>>>>"))
 }
 
 impl From<VirtualBranchHunk> for GitHunk {
@@ -90,7 +188,11 @@ impl From<VirtualBranchHunk> for GitHunk {
             new_lines: val.end - val.start,
             diff_lines: val.diff,
             binary: val.binary,
+            old_blob_id: val.old_blob_id,
+            renamed_from: val.renamed_from,
             change_type: val.change_type,
+            moved_lines: val.moved_lines,
+            lines: val.lines,
         }
     }
 }
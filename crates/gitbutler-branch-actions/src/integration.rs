@@ -14,6 +14,7 @@ use gitbutler_project::access::WorktreeWritePermission;
 use gitbutler_repo::{GixRepositoryExt, SignaturePurpose};
 use gitbutler_repo::{LogUntil, RepositoryExt};
 use gitbutler_stack::{Stack, VirtualBranchesHandle};
+use serde::Serialize;
 use tracing::instrument;
 
 use crate::{branch_manager::BranchManagerExt, conflicts, VirtualBranchesExt};
@@ -146,6 +147,10 @@ pub fn update_workspace_commit(
 
     let repo: &git2::Repository = ctx.repository();
 
+    if let Err(err) = crate::base::update_local_target_branch(repo, &target) {
+        tracing::warn!("failed to update the local branch of the target: {err:?}");
+    }
+
     // get current repo head for reference
     let head_ref = repo.head()?;
     let workspace_filepath = repo.path().join("workspace");
@@ -245,9 +250,7 @@ pub fn update_workspace_commit(
     )?;
     repo.set_head(&GITBUTLER_WORKSPACE_REFERENCE.clone().to_string())?;
 
-    let mut index = repo.index()?;
-    index.read_tree(&workspace_tree)?;
-    index.write()?;
+    repo.write_index_from_tree(&workspace_tree)?;
 
     // finally, update the refs/gitbutler/ heads to the states of the current virtual branches
     for branch in &virtual_branches {
@@ -296,6 +299,145 @@ pub fn verify_branch(ctx: &CommandContext, perm: &mut WorktreeWritePermission) -
     Ok(())
 }
 
+/// Set the [base](Stack::base_oid) of branches that were stored before it was tracked to the merge base of
+/// their head with the target. This is done when opening the project for writing, as reading the
+/// virtual branches state must not write to it, and only once per branch, even if it has no merge base.
+pub(crate) fn backfill_base_oids(
+    ctx: &CommandContext,
+    _perm: &mut WorktreeWritePermission,
+) -> Result<()> {
+    let vb_state = ctx.project().virtual_branches();
+    if vb_state.get_default_target().is_err() {
+        return Ok(());
+    }
+    let branches: Vec<_> = vb_state
+        .list_all_branches()?
+        .into_iter()
+        .filter(|branch| !branch.base_oid_computed)
+        .map(|mut branch| {
+            branch.refresh_base_oid(ctx);
+            branch
+        })
+        .collect();
+    if branches.is_empty() {
+        return Ok(());
+    }
+    vb_state.set_branches(branches)
+}
+
+/// The result of [`force_reset_integration()`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForceResetOutcome {
+    /// The files with changes that aren't part of the rebuilt workspace commit, which are assigned to
+    /// the applied branches anew once it was rebuilt.
+    pub uncommitted_files: Vec<PathBuf>,
+    /// `true` if the workspace was rebuilt, which only happens if it was confirmed.
+    pub reset: bool,
+}
+
+/// Rebuild the workspace commit from the target and the applied branches and make it `HEAD`, no matter
+/// what `HEAD` currently is, to recover if the workspace is in a state that fails [`verify_branch()`].
+/// The worktree is reset to the rebuilt workspace commit, after which the changes that aren't part of it
+/// are put back and assigned to the applied branches again.
+///
+/// Without `confirmed`, nothing is changed and the files whose changes would have to be assigned anew
+/// are returned so they can be shown to the user first.
+pub(crate) fn force_reset_integration(
+    ctx: &CommandContext,
+    confirmed: bool,
+    perm: &mut WorktreeWritePermission,
+) -> Result<ForceResetOutcome> {
+    let mut uncommitted_files: Vec<_> = {
+        let repo = ctx.repository().in_memory_repo()?;
+        let rebuilt_head = rebuilt_workspace_head(ctx, &repo)?;
+        gitbutler_diff::workdir(&repo, rebuilt_head)?
+            .into_keys()
+            .collect()
+    };
+    uncommitted_files.sort();
+    if !confirmed {
+        return Ok(ForceResetOutcome {
+            uncommitted_files,
+            reset: false,
+        });
+    }
+
+    let repo = ctx.repository();
+    let worktree_tree = repo
+        .create_wd_tree()
+        .context("failed to store the worktree before resetting it")?;
+    let vb_state = ctx.project().virtual_branches();
+    let workspace_tree = repo
+        .find_commit(update_workspace_commit(&vb_state, ctx)?)?
+        .tree()?;
+    repo.checkout_tree_builder(&workspace_tree)
+        .force()
+        .checkout()
+        .context("failed to checkout the rebuilt workspace commit")?;
+    // Without any paths, the whole worktree would be checked out again.
+    if !uncommitted_files.is_empty() {
+        let mut checkout = git2::build::CheckoutBuilder::new();
+        checkout
+            .force()
+            .update_index(false)
+            .disable_pathspec_match(true);
+        for path in &uncommitted_files {
+            checkout.path(path.as_path());
+        }
+        repo.checkout_tree(worktree_tree.as_object(), Some(&mut checkout))
+            .context("failed to restore the uncommitted changes")?;
+    }
+    crate::status::get_applied_status(ctx, Some(perm))?;
+    Ok(ForceResetOutcome {
+        uncommitted_files,
+        reset: true,
+    })
+}
+
+/// Merge the heads of the applied branches onto the target like [`get_workspace_head()`] does, but into
+/// a commit that only exists in the in-memory `repo`, and without looking at the conflict state or `HEAD`
+/// of the workspace, which may be what [`force_reset_integration()`] has to recover from.
+/// Branches that conflict with the ones merged before them are left out.
+fn rebuilt_workspace_head(ctx: &CommandContext, repo: &git2::Repository) -> Result<git2::Oid> {
+    let vb_state = ctx.project().virtual_branches();
+    let target = vb_state
+        .get_default_target()
+        .context("failed to get target")?;
+    let target_commit = repo.find_commit(target.sha)?;
+    let target_tree = repo.find_real_tree(&target_commit, Default::default())?;
+
+    let mut workspace_tree = target_tree.clone();
+    let mut heads = Vec::new();
+    for branch in vb_state.list_branches_in_workspace()? {
+        let branch_head = repo.find_commit(branch.head())?;
+        let branch_tree = repo.find_real_tree(&branch_head, Default::default())?;
+        let mut merge = repo.merge_trees(&target_tree, &workspace_tree, &branch_tree, None)?;
+        if merge.has_conflicts() {
+            tracing::warn!("Merge conflict between base and {:?}", branch.name);
+            continue;
+        }
+        workspace_tree = repo.find_tree(merge.write_tree_to(repo)?)?;
+        if branch.head() != target.sha {
+            heads.push(branch_head);
+        }
+    }
+    if heads.is_empty() {
+        heads.push(target_commit);
+    }
+
+    let committer = gitbutler_repo::signature(SignaturePurpose::Committer)?;
+    let author = gitbutler_repo::signature(SignaturePurpose::Author)?;
+    Ok(repo.commit(
+        None,
+        &author,
+        &committer,
+        WORKSPACE_HEAD,
+        &workspace_tree,
+        heads.iter().collect::<Vec<_>>().as_slice(),
+    )?)
+}
+
 fn verify_head_is_set(ctx: &CommandContext) -> Result<()> {
     match ctx
         .repository()
@@ -2,21 +2,37 @@
 mod actions;
 // This is our API
 pub use actions::{
-    amend, can_apply_remote_branch, create_commit, create_virtual_branch,
-    create_virtual_branch_from_branch, delete_local_branch, fetch_from_remotes, find_commit,
-    get_base_branch_data, get_remote_branch_data, get_uncommited_files,
-    get_uncommited_files_reusable, insert_blank_commit, integrate_upstream,
-    integrate_upstream_commits, list_commit_files, list_local_branches, list_virtual_branches,
-    list_virtual_branches_cached, move_commit, move_commit_file, push_base_branch,
-    push_virtual_branch, reorder_stack, reset_files, reset_virtual_branch,
-    resolve_upstream_integration, save_and_unapply_virutal_branch, set_base_branch,
-    set_target_push_remote, squash, unapply_ownership, unapply_without_saving_virtual_branch,
-    undo_commit, update_branch_order, update_commit_message, update_virtual_branch,
-    upstream_integration_statuses,
+    abort_integration, amend, amend_dry_run, amend_with_options, apply_remote_as_changes,
+    branch_summary, branches_conflict, can_apply_remote_branch, checkout_file_from_commit,
+    commit_message_template, commit_sharedness, commit_split, create_commit,
+    create_commit_with_options, create_virtual_branch, create_virtual_branch_from_branch,
+    dedupe_integrated_commits, delete_local_branch, discard_all_changes, duplicate_branch,
+    extract_hunk_from_commit, fetch_from_remotes, fetch_from_remotes_with_interactivity,
+    finalize_merge_tool_result, find_commit, force_reset_integration, get_base_branch_data,
+    get_default_branch, get_remote_branch_data, get_status_since, get_uncommited_files,
+    get_uncommited_files_reusable, hunk_content, incoming_commits, insert_blank_commit,
+    integrate_upstream, integrate_upstream_all, integrate_upstream_commits,
+    integrate_upstream_commits_with_options, list_commit_files, list_local_branches,
+    list_virtual_branches, list_virtual_branches_cached, list_virtual_branches_read_only,
+    list_virtual_branches_with_options, move_commit, move_commit_file, pin_commit, pr_diff,
+    preview_reset_files, preview_unapply, prune_remotes, push_base_branch, push_virtual_branch,
+    push_virtual_branch_with_options, range_diff, reorder_commit, reorder_stack,
+    reorder_stack_with_options, repair_ownership, reset_files, reset_virtual_branch,
+    resolve_upstream_integration, restore_snapshot, save_and_unapply_virutal_branch,
+    set_base_branch, set_base_branch_with_local_branch, set_default_branch, set_target_push_remote,
+    snapshot_files, snapshot_upstream, squash, squash_range, stage_rename, suggest_branch_ref_name,
+    target_tree, unapply_ownership, unapply_without_saving_virtual_branch, undo_commit, undo_last,
+    unpin_commit, unpushed_commits, unsquash, update_branch_order, update_commit_message,
+    update_commit_message_with_options, update_virtual_branch, upstream_integration_statuses,
+    validate_ownership, workspace_diff, workspace_diff_stats, workspace_tree, write_conflict_sides,
 };
 
 mod r#virtual;
-pub use r#virtual::{BranchStatus, VirtualBranch, VirtualBranchHunksByPathMap, VirtualBranches};
+pub use r#virtual::{
+    AmendPreview, BehindUpstream, BranchStatus, BranchSummary, CommitOptions, ConflictingFile,
+    ForceMode, MergeResolution, PushError, UpstreamStatus, VirtualBranch,
+    VirtualBranchHunksByPathMap, VirtualBranches, WorkspaceDiffStats,
+};
 /// Avoid using these!
 /// This was previously `pub use r#virtual::*;`
 pub mod internal {
@@ -34,7 +50,7 @@ pub use base::BaseBranch;
 pub mod upstream_integration;
 
 mod integration;
-pub use integration::{update_workspace_commit, verify_branch};
+pub use integration::{update_workspace_commit, verify_branch, ForceResetOutcome};
 
 mod file;
 pub use file::{Get, RemoteBranchFile};
@@ -43,10 +59,13 @@ mod remote;
 pub use remote::{RemoteBranch, RemoteBranchData, RemoteCommit};
 
 pub mod conflicts;
+pub use conflicts::ConflictSidePaths;
 
 pub mod branch_trees;
 pub mod branch_upstream_integration;
 mod move_commits;
+mod ownership;
+pub use ownership::OwnershipConflict;
 pub mod reorder;
 pub use reorder::{SeriesOrder, StackOrder};
 mod undo_commit;
@@ -55,7 +74,9 @@ mod author;
 mod gravatar;
 mod status;
 use gitbutler_stack::VirtualBranchesHandle;
-pub use status::get_applied_status;
+pub use status::{get_applied_status, get_applied_status_with_options};
+mod status_delta;
+pub use status_delta::{BranchStatusDelta, RemovedHunk, StatusDelta};
 trait VirtualBranchesExt {
     fn virtual_branches(&self) -> VirtualBranchesHandle;
 }
@@ -68,7 +89,9 @@ impl VirtualBranchesExt for gitbutler_project::Project {
 
 mod branch;
 mod commit;
+mod editorconfig;
 mod hunk;
+pub use hunk::HunkContent;
 
 pub use branch::{
     get_branch_listing_details, list_branches, Author, BranchListing, BranchListingDetails,
@@ -287,16 +287,23 @@ mod tests {
                 start,
                 end,
                 binary: false,
+                old_blob_id: None,
+                renamed_from: None,
                 hash: Hunk::hash_diff("".as_bytes()),
                 locked: false,
                 locked_to: None,
                 change_type: gitbutler_diff::ChangeType::Modified,
                 poisoned: false,
+                conflict_markers: false,
+                moved_lines: Vec::new(),
+                lines: Vec::new(),
             }],
             modified_at: 0,
             conflicted: false,
             binary: false,
             large: false,
+            mode_change: false,
+            change_type: gitbutler_diff::ChangeType::Modified,
         }];
         source_branch_non_comitted_files
     }
@@ -315,7 +322,11 @@ mod tests {
                 new_lines,
                 diff_lines: "".into(),
                 binary: false,
+                old_blob_id: None,
+                renamed_from: None,
                 change_type: gitbutler_diff::ChangeType::Modified,
+                moved_lines: Vec::new(),
+                lines: Vec::new(),
             }],
         )]
         .into_iter()
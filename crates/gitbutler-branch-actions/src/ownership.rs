@@ -0,0 +1,117 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use gitbutler_command_context::CommandContext;
+use gitbutler_diff::Hunk;
+use gitbutler_project::access::WorktreeWritePermission;
+use gitbutler_stack::{Stack, StackId};
+use serde::Serialize;
+
+use crate::{r#virtual::hunks_overlap, VirtualBranchesExt};
+
+/// A hunk whose lines are also claimed by other branches in the workspace.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OwnershipConflict {
+    pub file_path: PathBuf,
+    pub start: u32,
+    pub end: u32,
+    /// The branch claiming the hunk, followed by the branches claiming overlapping hunks, in workspace order.
+    pub branch_ids: Vec<StackId>,
+}
+
+/// Find all hunks that overlap with hunks claimed by another of the given `branches`.
+///
+/// Hunks are visited in workspace order, and each conflict is reported for the first hunk claiming the lines,
+/// listing all later branches with overlapping hunks that aren't part of an earlier conflict.
+/// Taking away the overlapping hunks from all but the first branch of each conflict leaves no overlaps behind.
+pub(crate) fn ownership_conflicts(branches: &[Stack]) -> Vec<OwnershipConflict> {
+    contested_hunks(branches)
+        .into_iter()
+        .map(|(_, conflict)| conflict)
+        .collect()
+}
+
+/// Like [`ownership_conflicts()`], but also return the hunk of the first branch of each conflict.
+fn contested_hunks(branches: &[Stack]) -> Vec<(Hunk, OwnershipConflict)> {
+    let mut branches = branches.iter().collect::<Vec<_>>();
+    branches.sort_by_key(|branch| branch.order);
+
+    let mut conflicts: Vec<(Hunk, OwnershipConflict)> = Vec::new();
+    for branch in branches {
+        for claim in &branch.ownership.claims {
+            for hunk in &claim.hunks {
+                match conflicts.iter_mut().find(|(first, conflict)| {
+                    conflict.file_path == claim.file_path
+                        && conflict.branch_ids[0] != branch.id
+                        && hunks_overlap(first, hunk)
+                }) {
+                    Some((_, conflict)) => {
+                        if !conflict.branch_ids.contains(&branch.id) {
+                            conflict.branch_ids.push(branch.id);
+                        }
+                    }
+                    None => conflicts.push((
+                        hunk.clone(),
+                        OwnershipConflict {
+                            file_path: claim.file_path.clone(),
+                            start: hunk.start,
+                            end: hunk.end,
+                            branch_ids: vec![branch.id],
+                        },
+                    )),
+                }
+            }
+        }
+    }
+    conflicts.retain(|(_, conflict)| conflict.branch_ids.len() > 1);
+    conflicts
+}
+
+pub(crate) fn validate_ownership(ctx: &CommandContext) -> Result<Vec<OwnershipConflict>> {
+    let branches = ctx
+        .project()
+        .virtual_branches()
+        .list_branches_in_workspace()?;
+    Ok(ownership_conflicts(&branches))
+}
+
+/// Assign the lines claimed by multiple branches to the first of these branches in workspace order,
+/// and remove the overlapping hunks from all others.
+/// Returns the conflicts that were resolved.
+pub(crate) fn repair_ownership(
+    ctx: &CommandContext,
+    _perm: &mut WorktreeWritePermission,
+) -> Result<Vec<OwnershipConflict>> {
+    let vb_state = ctx.project().virtual_branches();
+    let mut branches = vb_state.list_branches_in_workspace()?;
+    let conflicts = contested_hunks(&branches);
+
+    for (contested, conflict) in &conflicts {
+        for branch in branches
+            .iter_mut()
+            .filter(|branch| conflict.branch_ids[1..].contains(&branch.id))
+        {
+            branch.ownership.claims.retain_mut(|claim| {
+                if claim.file_path != conflict.file_path || claim.hunks.is_empty() {
+                    return true;
+                }
+                claim.hunks.retain(|hunk| !hunks_overlap(hunk, contested));
+                !claim.hunks.is_empty()
+            });
+        }
+    }
+
+    for branch in branches {
+        if conflicts
+            .iter()
+            .any(|(_, conflict)| conflict.branch_ids[1..].contains(&branch.id))
+        {
+            vb_state.set_branch(branch)?;
+        }
+    }
+    Ok(conflicts
+        .into_iter()
+        .map(|(_, conflict)| conflict)
+        .collect())
+}
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::{collections::HashSet, path::Path};
 
 use crate::author::Author;
 use anyhow::{Context, Result};
@@ -101,6 +101,37 @@ pub fn list_local_branches(ctx: &CommandContext) -> Result<Vec<RemoteBranch>> {
     Ok(remote_branches)
 }
 
+/// Fetch all remotes, pruning remote-tracking branches whose branch was deleted on the remote,
+/// and return the pruned remote-tracking branches, sorted by name.
+pub(crate) fn prune_remotes(
+    ctx: &CommandContext,
+    askpass: Option<String>,
+) -> Result<Vec<RemoteRefname>> {
+    let remote_tracking_refs = || -> Result<HashSet<String>> {
+        let mut names = HashSet::new();
+        for reference in ctx.repository().references_glob("refs/remotes/*")? {
+            if let Some(name) = reference?.name() {
+                names.insert(name.to_owned());
+            }
+        }
+        Ok(names)
+    };
+
+    let before = remote_tracking_refs()?;
+    for remote in ctx.repository().remotes_as_string()? {
+        ctx.fetch(&remote, askpass.clone())
+            .with_context(|| format!("failed to fetch {remote}"))?;
+    }
+    let after = remote_tracking_refs()?;
+
+    let mut pruned: Vec<RemoteRefname> = before
+        .difference(&after)
+        .filter_map(|name| name.parse().ok())
+        .collect();
+    pruned.sort_by_key(ToString::to_string);
+    Ok(pruned)
+}
+
 pub(crate) fn get_branch_data(ctx: &CommandContext, refname: &Refname) -> Result<RemoteBranchData> {
     let default_target = default_target(&ctx.project().gb_dir())?;
 
@@ -4,8 +4,8 @@ use anyhow::{bail, Context, Result};
 use git2::{Commit, Oid};
 use gitbutler_command_context::CommandContext;
 use gitbutler_project::access::WorktreeWritePermission;
-use gitbutler_repo::rebase::cherry_rebase_group;
-use gitbutler_stack::{Series, StackId};
+use gitbutler_repo::rebase::{cherry_rebase_group_with_options, RebaseOptions};
+use gitbutler_stack::{Series, Stack, StackId};
 
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
@@ -14,6 +14,7 @@ use crate::{
     branch_trees::{
         checkout_branch_trees, compute_updated_branch_head_for_commits, BranchHeadAndTree,
     },
+    r#virtual::is_pinned,
     VirtualBranchesExt,
 };
 
@@ -28,10 +29,13 @@ use crate::{
 /// - The series themselves in the reorder request must be the same as the ones in the stack (this API is about moving commits, not series)
 /// - The number of commits in the reorder request must match the number of commits in the stack
 /// - The commit ids in the reorder request must be in the stack
+///
+/// Commits that become empty by being moved are dropped, unless `keep_empty` is `true`.
 pub fn reorder_stack(
     ctx: &CommandContext,
     branch_id: StackId,
     new_order: StackOrder,
+    keep_empty: bool,
     perm: &mut WorktreeWritePermission,
 ) -> Result<()> {
     let state = ctx.project().virtual_branches();
@@ -40,6 +44,7 @@ pub fn reorder_stack(
     let all_series = stack.list_series(ctx)?;
     let current_order = series_order(&all_series);
     new_order.validate(current_order.clone())?;
+    ensure_pinned_commits_stay(repo, &stack, &current_order, &new_order)?;
 
     let default_target = state.get_default_target()?;
     let default_target_commit = repo
@@ -54,7 +59,15 @@ pub fn reorder_stack(
         .flat_map(|s| s.commit_ids.iter())
         .cloned()
         .collect_vec();
-    let new_head = cherry_rebase_group(repo, merge_base, &ids_to_rebase)?;
+    let new_head = cherry_rebase_group_with_options(
+        repo,
+        merge_base,
+        &ids_to_rebase,
+        RebaseOptions {
+            keep_empty,
+            ..Default::default()
+        },
+    )?;
     // Calculate the new head and tree
     let BranchHeadAndTree {
         head: new_head_oid,
@@ -85,6 +98,27 @@ pub fn reorder_stack(
     Ok(())
 }
 
+/// Move `commit_id` by `offset` positions within the stack with `branch_id` in a single rebase,
+/// with positive offsets moving it towards the bottom of the stack and negative ones towards the top.
+/// The moved commit joins the series of the commit whose position it takes.
+///
+/// Like all rebased commits, the ones affected by the move keep their author and are signed if
+/// `gitbutler.signCommits` is enabled.
+///
+/// # Errors
+/// Errors out if the commit isn't in the stack, or if its new position would be outside of the stack.
+pub fn reorder_commit(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    commit_id: Oid,
+    offset: i32,
+    perm: &mut WorktreeWritePermission,
+) -> Result<()> {
+    let stack = ctx.project().virtual_branches().get_branch(branch_id)?;
+    let new_order = series_order(&stack.list_series(ctx)?).with_commit_moved(commit_id, offset)?;
+    reorder_stack(ctx, branch_id, new_order, false, perm)
+}
+
 /// Represents the order of series (branches) and changes (commits) in a stack.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -182,6 +216,72 @@ impl StackOrder {
 
         Ok(())
     }
+
+    /// Return a copy of this order with `commit_id` moved by `offset` positions, counted across all series.
+    fn with_commit_moved(&self, commit_id: Oid, offset: i32) -> Result<StackOrder> {
+        let mut commits = self
+            .series
+            .iter()
+            .enumerate()
+            .flat_map(|(series_idx, series)| {
+                series.commit_ids.iter().map(move |id| (series_idx, *id))
+            })
+            .collect_vec();
+        let subject_index = commits
+            .iter()
+            .position(|(_, id)| *id == commit_id)
+            .with_context(|| format!("Commit '{commit_id}' does not exist in the stack"))?;
+        let target_index = subject_index as i64 + offset as i64;
+        if target_index < 0 || target_index >= commits.len() as i64 {
+            bail!(
+                "Cannot move commit at index {subject_index} by offset {offset}: target index {target_index} is outside of [0, {})",
+                commits.len()
+            );
+        }
+        let target_index = target_index as usize;
+        let target_series = commits[target_index].0;
+        commits.remove(subject_index);
+        commits.insert(target_index, (target_series, commit_id));
+
+        let mut new_order = self.clone();
+        for (series_idx, series) in new_order.series.iter_mut().enumerate() {
+            series.commit_ids = commits
+                .iter()
+                .filter(|(idx, _)| *idx == series_idx)
+                .map(|(_, id)| *id)
+                .collect();
+        }
+        Ok(new_order)
+    }
+}
+
+/// Fail if a [pinned commit](Stack::pinned_commits) would end up at another position counted from the
+/// bottom of the stack.
+fn ensure_pinned_commits_stay(
+    repo: &git2::Repository,
+    stack: &Stack,
+    current_order: &StackOrder,
+    new_order: &StackOrder,
+) -> Result<()> {
+    if stack.pinned_commits.is_empty() {
+        return Ok(());
+    }
+    // The order lists the top series first, and the newest commit of each series first.
+    let bottom_up = |order: &StackOrder| {
+        order
+            .series
+            .iter()
+            .rev()
+            .flat_map(|series| series.commit_ids.iter().rev().copied())
+            .collect_vec()
+    };
+    let (current, new) = (bottom_up(current_order), bottom_up(new_order));
+    for (idx, commit_id) in current.iter().enumerate() {
+        if new.get(idx) != Some(commit_id) && is_pinned(stack, &repo.find_commit(*commit_id)?) {
+            bail!("commit {commit_id} is pinned and can't be moved");
+        }
+    }
+    Ok(())
 }
 
 pub fn series_order(all_series: &[Series<'_>]) -> StackOrder {
@@ -386,6 +486,45 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn commit_moved_across_series() -> Result<()> {
+        let id = |s: &str| Oid::from_str(s).unwrap();
+        let new_order = existing_order().with_commit_moved(id("6"), 3)?;
+        assert_eq!(new_order.series[0].commit_ids, vec![id("5"), id("4")]);
+        assert_eq!(
+            new_order.series[1].commit_ids,
+            vec![id("3"), id("6"), id("2"), id("1")]
+        );
+
+        let new_order = existing_order().with_commit_moved(id("3"), -1)?;
+        assert_eq!(
+            new_order.series[0].commit_ids,
+            vec![id("6"), id("5"), id("3"), id("4")]
+        );
+        assert_eq!(new_order.series[1].commit_ids, vec![id("2"), id("1")]);
+        Ok(())
+    }
+
+    #[test]
+    fn commit_moved_out_of_bounds_errors_out() -> Result<()> {
+        let id = |s: &str| Oid::from_str(s).unwrap();
+        assert_eq!(
+            existing_order()
+                .with_commit_moved(id("2"), 2)
+                .unwrap_err()
+                .to_string(),
+            "Cannot move commit at index 4 by offset 2: target index 6 is outside of [0, 6)"
+        );
+        assert_eq!(
+            existing_order()
+                .with_commit_moved(id("5"), -2)
+                .unwrap_err()
+                .to_string(),
+            "Cannot move commit at index 1 by offset -2: target index -1 is outside of [0, 6)"
+        );
+        Ok(())
+    }
+
     fn existing_order() -> StackOrder {
         StackOrder {
             series: vec![
@@ -187,8 +187,9 @@ pub fn push_stack(project: &Project, branch_id: StackId, with_force: bool) -> Re
         CommitOrChangeId::CommitId(merge_base.id().to_string())
     };
 
-    // First fetch, because we dont want to push integrated series
-    ctx.fetch(&default_target.push_remote_name(), None)?;
+    // First fetch, because we dont want to push integrated series.
+    // Integration is checked against the target branch, which may live on another remote than the one we push to.
+    ctx.fetch(default_target.branch.remote(), None)?;
     let gix_repo = ctx.gix_repository_for_merging_non_persisting()?;
     let cache = gix_repo.commit_graph_if_enabled()?;
     let mut graph = gix_repo.revision_graph(cache.as_ref());
@@ -322,6 +323,7 @@ pub(crate) fn stack_series(
             upstream_patches,
             pr_number: series.head.pr_number,
             archived: series.head.archived,
+            files: vec![],
         });
     }
     api_series.reverse();
@@ -15,13 +15,13 @@ use git2::Tree;
 use gitbutler_branch::BranchCreateRequest;
 use gitbutler_cherry_pick::RepositoryExt as _;
 use gitbutler_command_context::CommandContext;
-use gitbutler_diff::{diff_files_into_hunks, GitHunk, Hunk, HunkHash};
+use gitbutler_diff::{diff_files_into_hunks, DiffCache, DiffOptions, GitHunk, Hunk, HunkHash};
 use gitbutler_hunk_dependency::{
     compute_hunk_locks, HunkDependencyOptions, HunkLock, InputCommit, InputDiff, InputFile,
     InputStack,
 };
 use gitbutler_operating_modes::assure_open_workspace_mode;
-use gitbutler_project::access::WorktreeWritePermission;
+use gitbutler_project::access::{WorktreeReadPermission, WorktreeWritePermission};
 use gitbutler_repo::{LogUntil, RepositoryExt as _};
 use gitbutler_stack::{BranchOwnershipClaims, OwnershipClaim, Stack, StackId};
 use itertools::Itertools;
@@ -49,11 +49,42 @@ pub fn get_applied_status(
 /// against the current working tree directory, and it's used to avoid double-computing
 /// this expensive information.
 // TODO(kv): make this side effect free
-#[instrument(level = tracing::Level::DEBUG, skip(ctx, perm, worktree_changes))]
 pub fn get_applied_status_cached(
     ctx: &CommandContext,
     perm: Option<&mut WorktreeWritePermission>,
     worktree_changes: Option<gitbutler_diff::DiffByPathMap>,
+) -> Result<VirtualBranchesStatus> {
+    get_applied_status_with_options(ctx, perm, worktree_changes, DiffOptions::default())
+}
+
+/// Like [`get_applied_status_cached()`], but with `diff_options` to control what the hunks are
+/// annotated with, like lines that were moved within a file.
+pub fn get_applied_status_with_options(
+    ctx: &CommandContext,
+    perm: Option<&mut WorktreeWritePermission>,
+    worktree_changes: Option<gitbutler_diff::DiffByPathMap>,
+    diff_options: DiffOptions,
+) -> Result<VirtualBranchesStatus> {
+    applied_status(ctx, perm, worktree_changes, true, diff_options)
+}
+
+/// Like [`get_applied_status_cached()`], but the updated trees of the branches aren't written back
+/// to the virtual branches state, and no default branch is created for changes that have none.
+pub(crate) fn get_applied_status_read_only(
+    ctx: &CommandContext,
+    worktree_changes: Option<gitbutler_diff::DiffByPathMap>,
+    _perm: &WorktreeReadPermission,
+) -> Result<VirtualBranchesStatus> {
+    applied_status(ctx, None, worktree_changes, false, DiffOptions::default())
+}
+
+#[instrument(level = tracing::Level::DEBUG, skip(ctx, perm, worktree_changes))]
+fn applied_status(
+    ctx: &CommandContext,
+    perm: Option<&mut WorktreeWritePermission>,
+    worktree_changes: Option<gitbutler_diff::DiffByPathMap>,
+    write_back: bool,
+    diff_options: DiffOptions,
 ) -> Result<VirtualBranchesStatus> {
     assure_open_workspace_mode(ctx).context("ng applied status requires open workspace mode")?;
     let workspace_head = get_workspace_head(ctx)?;
@@ -61,10 +92,31 @@ pub fn get_applied_status_cached(
         .project()
         .virtual_branches()
         .list_branches_in_workspace()?;
-    let base_file_diffs = worktree_changes.map(Ok).unwrap_or_else(|| {
-        gitbutler_diff::workdir(ctx.repository(), workspace_head.to_owned())
-            .context("failed to diff workdir")
-    })?;
+    let base_file_diffs = {
+        let _span =
+            tracing::debug_span!("diff worktree", cached = worktree_changes.is_some()).entered();
+        let worktree_changes = worktree_changes.map(|mut files| {
+            diff_options.apply(&mut files);
+            files
+        });
+        worktree_changes.map(Ok).unwrap_or_else(|| -> Result<_> {
+            let cache_path = ctx.project().gb_dir().join("diff_cache.json");
+            let mut cache = DiffCache::load(&cache_path);
+            let files = gitbutler_diff::workdir_cached(
+                ctx.repository(),
+                workspace_head.to_owned(),
+                diff_options,
+                &mut cache,
+            )
+            .context("failed to diff workdir")?;
+            if write_back {
+                if let Err(err) = cache.save(&cache_path) {
+                    tracing::warn!("failed to store diff cache: {err:#}");
+                }
+            }
+            Ok(files)
+        })?
+    };
 
     let mut skipped_files: Vec<gitbutler_diff::FileDiff> = Vec::new();
     for file_diff in base_file_diffs.values() {
@@ -74,8 +126,9 @@ pub fn get_applied_status_cached(
     }
     let mut base_diffs: HashMap<_, _> = diff_files_into_hunks(base_file_diffs).collect();
 
-    // sort by order, so that the default branch is first (left in the ui)
-    virtual_branches.sort_by(|a, b| a.order.cmp(&b.order));
+    // sort by order, so that the default branch is first (left in the ui),
+    // and by id for equal orders so hunk assignment doesn't depend on how branches are stored
+    virtual_branches.sort_by_key(|b| (b.order, b.id));
 
     let branch_manager = ctx.branch_manager();
 
@@ -98,19 +151,28 @@ pub fn get_applied_status_cached(
     let vb_state = ctx.project().virtual_branches();
     let default_target = vb_state.get_default_target()?;
 
-    let locks = if ctx.project().use_experimental_locking {
-        compute_locks(
-            ctx,
-            &workspace_head,
-            &default_target.sha,
-            &base_diffs,
-            &virtual_branches,
-        )?
-    } else {
-        let base_tree = ctx.repository().find_commit(default_target.sha)?.tree()?;
-        compute_old_locks(ctx.repository(), &base_diffs, &virtual_branches, base_tree)?
+    let locks = {
+        let _span = tracing::debug_span!("compute hunk locks").entered();
+        if ctx.project().use_experimental_locking {
+            compute_locks(
+                ctx,
+                &workspace_head,
+                &default_target.sha,
+                &base_diffs,
+                &virtual_branches,
+            )?
+        } else {
+            let base_tree = ctx.repository().find_commit(default_target.sha)?.tree()?;
+            compute_old_locks(ctx.repository(), &base_diffs, &virtual_branches, base_tree)?
+        }
     };
 
+    let ownership_span = tracing::debug_span!(
+        "assign ownership",
+        num_branches = virtual_branches.len(),
+        num_files = base_diffs.len()
+    )
+    .entered();
     for branch in &mut virtual_branches {
         // This should never be invoked. But if it is, dont try to  make the branch name unique
         if let Err(e) = branch.initialize(ctx, true) {
@@ -131,7 +193,7 @@ pub fn get_applied_status_cached(
                     .filter_map(|claimed_hunk| {
                         // if any of the current hunks intersects with the owned hunk, we want to keep it
                         for (i, git_diff_hunk) in git_diff_hunks.iter().enumerate() {
-                            if claimed_hunk.intersects(git_diff_hunk) {
+                            if claimed_hunk.claims(git_diff_hunk) {
                                 let hash = Hunk::hash_diff(&git_diff_hunk.diff_lines);
                                 if locks.contains_key(&hash) {
                                     return None; // Defer allocation to unclaimed hunks processing
@@ -146,6 +208,7 @@ pub fn get_applied_status_cached(
                                     start: git_diff_hunk.new_start,
                                     end: git_diff_hunk.new_start + git_diff_hunk.new_lines,
                                     hash: Some(hash),
+                                    filter: None,
                                 };
                                 git_diff_hunks.remove(i);
                                 return Some(updated_hunk);
@@ -170,15 +233,7 @@ pub fn get_applied_status_cached(
         branch.ownership = BranchOwnershipClaims { claims: new_claims };
     }
 
-    let max_selected_for_changes = virtual_branches
-        .iter()
-        .filter_map(|b| b.selected_for_changes)
-        .max()
-        .unwrap_or(-1);
-    let default_vbranch_pos = virtual_branches
-        .iter()
-        .position(|b| b.selected_for_changes == Some(max_selected_for_changes))
-        .unwrap_or(0);
+    let default_vbranch_pos = default_branch_pos(&virtual_branches);
 
     // Everything claimed has been removed from `base_diffs`, here we just
     // process the remaining ones.
@@ -187,16 +242,18 @@ pub fn get_applied_status_cached(
             let hash = Hunk::hash_diff(&hunk.diff_lines);
             let locked_to = locks.get(&hash);
 
-            let vbranch_pos = if let Some(locks) = locked_to {
-                let p = virtual_branches
-                    .iter()
-                    .position(|vb| vb.id == locks[0].branch_id);
-                match p {
-                    Some(p) => p,
-                    _ => default_vbranch_pos,
-                }
-            } else {
-                default_vbranch_pos
+            // Hunks locked to a branch depend on its commits and can't be committed elsewhere,
+            // so they go to it even if it's frozen.
+            let vbranch_pos = locked_to
+                .and_then(|locks| {
+                    virtual_branches
+                        .iter()
+                        .position(|vb| vb.id == locks[0].branch_id)
+                })
+                .or(default_vbranch_pos);
+            let Some(vbranch_pos) = vbranch_pos else {
+                // All branches are frozen, so the hunk stays unassigned.
+                continue;
             };
 
             virtual_branches[vbranch_pos].ownership.put(OwnershipClaim {
@@ -212,6 +269,7 @@ pub fn get_applied_status_cached(
                 .push(hunk);
         }
     }
+    drop(ownership_span);
 
     let mut hunks_by_branch = diffs_by_branch
         .into_iter()
@@ -229,13 +287,17 @@ pub fn get_applied_status_cached(
 
     // write updated state if not resolving
     if !ctx.is_resolving() {
+        let _span = tracing::debug_span!("write branch trees").entered();
         for (vbranch, files) in &mut hunks_by_branch {
             vbranch.tree = gitbutler_diff::write::hunks_onto_oid(ctx, vbranch.head(), files)?;
-            vb_state
-                .set_branch(vbranch.clone())
-                .context(format!("failed to write virtual branch {}", vbranch.name))?;
+            if write_back {
+                vb_state
+                    .set_branch(vbranch.clone())
+                    .context(format!("failed to write virtual branch {}", vbranch.name))?;
+            }
         }
     }
+    let _span = tracing::debug_span!("materialize files").entered();
     let hunks_by_branch: Vec<(Stack, HashMap<PathBuf, Vec<VirtualBranchHunk>>)> = hunks_by_branch
         .iter()
         .map(|(branch, hunks)| {
@@ -258,6 +320,22 @@ pub fn get_applied_status_cached(
     })
 }
 
+/// Return the position of the branch in `virtual_branches`, sorted by `order` and id, that
+/// hunks which aren't claimed by any branch go to, or `None` if there is none.
+///
+/// Frozen branches don't take new hunks, so the default destination is the most recently
+/// selected branch that isn't frozen. Ties, like between branches that were never selected,
+/// go to the branch with the lowest `order`, then the lowest id. If all branches are frozen,
+/// new hunks stay unassigned until one of them is thawed.
+pub(crate) fn default_branch_pos(virtual_branches: &[Stack]) -> Option<usize> {
+    virtual_branches
+        .iter()
+        .enumerate()
+        .filter(|(_, b)| !b.frozen)
+        .max_by_key(|(pos, b)| (b.selected_for_changes, std::cmp::Reverse(*pos)))
+        .map(|(pos, _)| pos)
+}
+
 fn compute_locks(
     ctx: &CommandContext,
     workspace_head: &git2::Oid,
@@ -0,0 +1,253 @@
+//! Incremental status reports for callers that poll the workspace status at a high frequency.
+//!
+//! Each report comes with an opaque token identifying the reported state, which can be passed to the
+//! next call to only receive the hunks that changed since then. The states themselves are kept in
+//! the project's data directory, so tokens stay small no matter how large the workspace is.
+use std::{collections::BTreeMap, path::PathBuf, time::UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use gitbutler_command_context::CommandContext;
+use gitbutler_error::error::Code;
+use gitbutler_project::access::WorktreeReadPermission;
+use gitbutler_stack::StackId;
+use serde::{Deserialize, Serialize};
+
+use crate::{hunk::VirtualBranchHunk, status::get_applied_status_read_only};
+
+/// The number of reported states that are kept, so a few callers can poll independently.
+const KEPT_SNAPSHOTS: usize = 8;
+
+/// The changes to the uncommitted hunks of the applied branches since the state described by a token.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusDelta {
+    /// The token describing the reported state, to be passed to the next call.
+    pub token: String,
+    /// `true` if no usable token was passed, in which case all hunks are reported as added.
+    pub full: bool,
+    /// The changes by branch. Branches without changes are omitted unless this is a full report.
+    pub branches: Vec<BranchStatusDelta>,
+}
+
+/// The changes to the uncommitted hunks of a single branch.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchStatusDelta {
+    pub branch_id: StackId,
+    /// Hunks that didn't exist before.
+    pub added: Vec<VirtualBranchHunk>,
+    /// Hunks that start at the same line of the original file as before, but changed.
+    pub modified: Vec<VirtualBranchHunk>,
+    /// Hunks that don't exist anymore.
+    pub removed: Vec<RemovedHunk>,
+}
+
+/// A hunk that was previously reported but doesn't exist anymore.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemovedHunk {
+    pub file_path: PathBuf,
+    /// The id of the hunk as it was previously reported.
+    pub id: String,
+}
+
+/// The state a token refers to, which is all that's needed to compute the next delta.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Snapshot {
+    head: String,
+    /// The modification time of the index in nanoseconds since the epoch.
+    index_mtime: u64,
+    hunks: Vec<SnapshotHunk>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SnapshotHunk {
+    branch_id: StackId,
+    file_path: PathBuf,
+    old_start: u32,
+    id: String,
+    hash: String,
+}
+
+impl Snapshot {
+    /// The token identifying this state, which is the same for all equal states.
+    fn token(&self) -> Result<String> {
+        Ok(format!("{:x}", md5::compute(toml::to_string(self)?)))
+    }
+}
+
+/// The most recently reported states, newest first, as stored in `status_snapshots.toml`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Snapshots {
+    snapshots: Vec<StoredSnapshot>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredSnapshot {
+    token: String,
+    snapshot: Snapshot,
+}
+
+impl Snapshots {
+    fn path(ctx: &CommandContext) -> PathBuf {
+        ctx.project().gb_dir().join("status_snapshots.toml")
+    }
+
+    /// Read the stored states, or none if they can't be read, which only leads to a full report.
+    fn load(ctx: &CommandContext) -> Self {
+        std::fs::read_to_string(Self::path(ctx))
+            .ok()
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Remember `snapshot` as the newest state, identified by `token`, unless it already is.
+    fn push(&mut self, ctx: &CommandContext, token: String, snapshot: Snapshot) -> Result<()> {
+        if self
+            .snapshots
+            .first()
+            .is_some_and(|newest| newest.token == token)
+        {
+            return Ok(());
+        }
+        self.snapshots.retain(|stored| stored.token != token);
+        self.snapshots.insert(0, StoredSnapshot { token, snapshot });
+        self.snapshots.truncate(KEPT_SNAPSHOTS);
+        gitbutler_fs::write(Self::path(ctx), toml::to_string(self)?)
+    }
+}
+
+/// Compute the status of the applied branches, reporting only what changed since the state identified
+/// by `token`, or everything if there is no `token`, if the state it identifies isn't known anymore,
+/// or if `HEAD` or the index changed in the meantime.
+///
+/// Tokens that can't have been returned by a previous call are rejected.
+pub(crate) fn get_applied_status_since(
+    ctx: &CommandContext,
+    token: Option<&str>,
+    perm: &WorktreeReadPermission,
+) -> Result<StatusDelta> {
+    if let Some(token) = token {
+        if token.len() != 32 || !token.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(anyhow!("malformed status token").context(Code::Validation));
+        }
+    }
+    let mut snapshots = Snapshots::load(ctx);
+    let previous = token.and_then(|token| {
+        snapshots
+            .snapshots
+            .iter()
+            .find_map(|stored| (stored.token == token).then_some(&stored.snapshot))
+    });
+
+    let repo = ctx.repository();
+    let head = repo.head()?.peel_to_commit()?.id().to_string();
+    let index_mtime = std::fs::metadata(repo.path().join("index"))
+        .and_then(|metadata| metadata.modified())
+        .map(|mtime| {
+            mtime
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_nanos() as u64)
+        })
+        .unwrap_or_default();
+    let previous =
+        previous.filter(|previous| previous.head == head && previous.index_mtime == index_mtime);
+
+    let status = get_applied_status_read_only(ctx, None, perm)?;
+    let mut hunks = Vec::new();
+    let mut current = BTreeMap::new();
+    for (branch, files) in &status.branches {
+        for hunk in files.iter().flat_map(|file| &file.hunks) {
+            hunks.push(SnapshotHunk {
+                branch_id: branch.id,
+                file_path: hunk.file_path.clone(),
+                old_start: hunk.old_start,
+                id: hunk.id.clone(),
+                hash: format!("{:x}", hunk.hash),
+            });
+            current.insert((branch.id, hunk.file_path.clone(), hunk.old_start), hunk);
+        }
+    }
+
+    let mut deltas: BTreeMap<StackId, BranchStatusDelta> = BTreeMap::new();
+    let full = previous.is_none();
+    match &previous {
+        None => {
+            for (branch, _) in &status.branches {
+                delta_of(&mut deltas, branch.id);
+            }
+            for ((branch_id, _, _), hunk) in &current {
+                delta_of(&mut deltas, *branch_id)
+                    .added
+                    .push((*hunk).clone());
+            }
+        }
+        Some(previous) => {
+            let mut previous_hunks = BTreeMap::new();
+            for hunk in &previous.hunks {
+                previous_hunks.insert(
+                    (hunk.branch_id, hunk.file_path.clone(), hunk.old_start),
+                    hunk,
+                );
+            }
+            for (key, hunk) in &current {
+                match previous_hunks.get(key) {
+                    None => delta_of(&mut deltas, key.0).added.push((*hunk).clone()),
+                    Some(previous) if previous.hash != format!("{:x}", hunk.hash) => {
+                        delta_of(&mut deltas, key.0).modified.push((*hunk).clone())
+                    }
+                    Some(_) => {}
+                }
+            }
+            for (key, hunk) in previous_hunks {
+                if !current.contains_key(&key) {
+                    delta_of(&mut deltas, key.0).removed.push(RemovedHunk {
+                        file_path: hunk.file_path.clone(),
+                        id: hunk.id.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    // equal states must have equal tokens, no matter in which order the files were listed
+    hunks.sort_by(|a, b| {
+        (a.branch_id, &a.file_path, a.old_start).cmp(&(b.branch_id, &b.file_path, b.old_start))
+    });
+    let snapshot = Snapshot {
+        head,
+        index_mtime,
+        hunks,
+    };
+    let token = snapshot.token()?;
+    if let Err(err) = snapshots.push(ctx, token.clone(), snapshot) {
+        tracing::warn!("failed to store status snapshot: {err:#}");
+    }
+    // keep the workspace order of the branches
+    let mut branches: Vec<_> = status
+        .branches
+        .iter()
+        .filter_map(|(branch, _)| deltas.remove(&branch.id))
+        .collect();
+    // branches that were unapplied in the meantime
+    branches.extend(deltas.into_values());
+    Ok(StatusDelta {
+        token,
+        full,
+        branches,
+    })
+}
+
+fn delta_of(
+    deltas: &mut BTreeMap<StackId, BranchStatusDelta>,
+    branch_id: StackId,
+) -> &mut BranchStatusDelta {
+    deltas
+        .entry(branch_id)
+        .or_insert_with(|| BranchStatusDelta {
+            branch_id,
+            added: Vec::new(),
+            modified: Vec::new(),
+            removed: Vec::new(),
+        })
+}
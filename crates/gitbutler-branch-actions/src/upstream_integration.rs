@@ -158,8 +158,10 @@ pub fn upstream_integration_statuses(
         return Ok(BranchStatuses::UpToDate);
     };
 
+    // Local-only branches are carried over as they are, there is nothing to decide for them.
     let statuses = virtual_branches_in_workspace
         .iter()
+        .filter(|virtual_branch| !virtual_branch.local_only)
         .map(|virtual_branch| {
             let tree = repository.find_tree(virtual_branch.tree)?;
             let tree_id = git2_to_gix_object_id(tree.id());
@@ -271,7 +273,12 @@ pub(crate) fn integrate_upstream(
             bail!("Branches are all up to date")
         };
 
-        if resolutions.len() != context.virtual_branches_in_workspace.len() {
+        let branches_to_resolve = context
+            .virtual_branches_in_workspace
+            .iter()
+            .filter(|branch| !branch.local_only)
+            .count();
+        if resolutions.len() != branches_to_resolve {
             bail!("Chosen resolutions do not match quantity of applied virtual branches")
         }
 
@@ -373,6 +380,13 @@ pub(crate) fn integrate_upstream(
             ..default_target
         })?;
 
+        // Branches that weren't rebased still moved relative to the new target.
+        let mut branches = virtual_branches_state.list_branches_in_workspace()?;
+        for branch in &mut branches {
+            branch.refresh_base_oid(command_context);
+        }
+        virtual_branches_state.set_branches(branches)?;
+
         crate::integration::update_workspace_commit(&virtual_branches_state, command_context)?;
     }
 
@@ -426,8 +440,20 @@ fn compute_resolutions(
         ..
     } = context;
 
+    // Local-only branches hold nothing but uncommitted changes, which are rebased onto the new target.
+    let local_only_resolutions = virtual_branches_in_workspace
+        .iter()
+        .filter(|branch| branch.local_only)
+        .map(|branch| Resolution {
+            branch_id: branch.id,
+            branch_tree: branch.tree,
+            approach: ResolutionApproach::Rebase,
+        })
+        .collect::<Vec<_>>();
+
     let results = resolutions
         .iter()
+        .chain(&local_only_resolutions)
         .map(|resolution| {
             let Some(virtual_branch) = virtual_branches_in_workspace
                 .iter()
@@ -1201,4 +1227,34 @@ mod test {
             BranchStatuses::UpdatesRequired(vec![(branch.id, BranchStatus::SaflyUpdatable)]),
         )
     }
+
+    #[test]
+    fn test_local_only_branch_is_skipped() {
+        let test_repository = TestingRepository::open();
+        let initial_commit = test_repository.commit_tree(None, &[("foo.txt", "foo")]);
+        let old_target = test_repository.commit_tree(Some(&initial_commit), &[("foo.txt", "bar")]);
+        let new_target = test_repository.commit_tree(Some(&old_target), &[("foo.txt", "baz")]);
+
+        let tree = test_repository.commit_tree(
+            Some(&old_target),
+            &[("foo.txt", "bar"), ("local.txt", "local")],
+        );
+
+        let mut local_only_branch = make_branch(old_target.id(), tree.tree_id());
+        local_only_branch.local_only = true;
+
+        let context = UpstreamIntegrationContext {
+            _permission: None,
+            old_target,
+            new_target,
+            repository: &test_repository.repository,
+            virtual_branches_in_workspace: vec![local_only_branch],
+            target_branch_name: "main".to_string(),
+        };
+
+        assert_eq!(
+            upstream_integration_statuses(&context).unwrap(),
+            BranchStatuses::UpdatesRequired(vec![]),
+        )
+    }
 }
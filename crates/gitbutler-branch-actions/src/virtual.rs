@@ -1,40 +1,61 @@
 use crate::{
+    branch_upstream_integration,
     commit::{commit_to_vbranch_commit, VirtualBranchCommit},
     conflicts::{self, RepoConflictsExt},
-    file::VirtualBranchFile,
-    hunk::VirtualBranchHunk,
+    file::{RemoteBranchFile, VirtualBranchFile},
+    hunk::{HunkContent, VirtualBranchHunk},
     integration::get_workspace_head,
-    remote::{branch_to_remote_branch, RemoteBranch},
+    remote::{branch_to_remote_branch, commit_to_remote_commit, RemoteBranch, RemoteCommit},
     stack::stack_series,
-    status::{get_applied_status, get_applied_status_cached},
+    status::{
+        get_applied_status, get_applied_status_read_only, get_applied_status_with_options,
+        VirtualBranchesStatus,
+    },
     Get, VirtualBranchesExt,
 };
 use anyhow::{anyhow, bail, Context, Result};
-use bstr::{BString, ByteSlice};
+use bstr::{BStr, BString, ByteSlice};
 use git2_hooks::HookResult;
 use gitbutler_branch::BranchUpdateRequest;
 use gitbutler_branch::{dedup, dedup_fmt};
 use gitbutler_cherry_pick::RepositoryExt as _;
 use gitbutler_command_context::CommandContext;
-use gitbutler_commit::{commit_ext::CommitExt, commit_headers::HasCommitHeaders};
-use gitbutler_diff::{trees, GitHunk, Hunk};
-use gitbutler_error::error::Code;
+use gitbutler_commit::{
+    change_id_trailer::change_id_from_message,
+    commit_ext::CommitExt,
+    commit_headers::{CommitHeadersV2, HasCommitHeaders},
+    trailers,
+};
+use gitbutler_diff::{filter::CleanFilter, trees, DiffOptions, GitHunk, Hunk};
+use gitbutler_error::error::{Code, Marker};
 use gitbutler_operating_modes::assure_open_workspace_mode;
 use gitbutler_oxidize::{git2_signature_to_gix_signature, git2_to_gix_object_id, gix_to_git2_oid};
-use gitbutler_project::access::WorktreeWritePermission;
-use gitbutler_reference::{normalize_branch_name, Refname, RemoteRefname};
+use gitbutler_project::{
+    access::{WorktreeReadPermission, WorktreeWritePermission},
+    CommitIssueKey, DefaultTrailer, DefaultTrailerValue,
+};
+use gitbutler_reference::{normalize_branch_name, ReferenceName, Refname, RemoteRefname};
 use gitbutler_repo::{
+    credentials::Interactivity,
     rebase::{cherry_rebase, cherry_rebase_group},
     GixRepositoryExt, LogUntil, RepositoryExt,
 };
-use gitbutler_repo_actions::RepoActionsExt;
+use gitbutler_repo_actions::{RepoActionsExt, StaleLeaseError};
 use gitbutler_stack::{
-    reconcile_claims, BranchOwnershipClaims, Stack, StackId, Target, VirtualBranchesHandle,
+    commit_by_oid_or_change_id, reconcile_claims, BranchOwnershipClaims, CommitAuthor,
+    OwnershipClaim, SquashedCommits, Stack, StackId, Target, VirtualBranchesHandle,
 };
 use gitbutler_time::time::now_since_unix_epoch_ms;
-use serde::Serialize;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
-use std::{collections::HashMap, path::PathBuf, vec};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+    vec,
+};
 use tracing::instrument;
 
 // this struct is a mapping to the view `Branch` type in Typescript
@@ -59,13 +80,30 @@ pub struct VirtualBranch {
     pub order: usize,     // the order in which this branch should be displayed in the UI
     pub upstream: Option<RemoteBranch>, // the upstream branch where this branch pushes to, if any
     pub upstream_name: Option<String>, // the upstream branch where this branch will push to on next push
+    /// How the branch relates to its upstream, or `None` if it doesn't have one.
+    pub upstream_status: Option<UpstreamStatus>,
     pub base_current: bool, // is this vbranch based on the current base branch? if false, this needs to be manually merged with conflicts
+    /// The branch is based on an older commit of the target branch than the workspace, and has commits
+    /// that would have to be rebased onto the current target before further work.
+    pub needs_integration: bool,
+    /// The number of commits of the branch that aren't on its target branch.
+    pub ahead: usize,
+    /// The number of commits on its target branch, as last fetched, that aren't in the branch yet.
+    pub behind: usize,
     /// The hunks (as `[(file, [hunks])]`) which are uncommitted but assigned to this branch.
     /// This makes them committable.
     pub ownership: BranchOwnershipClaims,
     pub updated_at: u128,
     pub selected_for_changes: bool,
     pub allow_rebasing: bool,
+    /// The branch holds local changes only, which can neither be committed nor pushed.
+    pub local_only: bool,
+    /// The branch keeps the hunks it owns, but new ones are assigned to other branches.
+    pub frozen: bool,
+    /// The git hooks that don't run when committing to the branch.
+    pub skipped_hooks: Vec<String>,
+    /// The identity commits to the branch are authored with, if not the repository's.
+    pub commit_author: Option<CommitAuthor>,
     #[serde(with = "gitbutler_serde::oid")]
     pub head: git2::Oid,
     /// The merge base between the target branch and the virtual branch
@@ -74,6 +112,9 @@ pub struct VirtualBranch {
     /// The fork point between the target branch and the virtual branch
     #[serde(with = "gitbutler_serde::oid_opt", default)]
     pub fork_point: Option<git2::Oid>,
+    /// The commit the virtual branch is stacked on, as persisted with the branch
+    #[serde(with = "gitbutler_serde::oid_opt", default)]
+    pub base_oid: Option<git2::Oid>,
     pub refname: Refname,
     #[serde(with = "gitbutler_serde::oid")]
     pub tree: git2::Oid,
@@ -82,6 +123,22 @@ pub struct VirtualBranch {
     pub series: Vec<PatchSeries>,
 }
 
+/// How the head of a branch relates to the head of its upstream branch.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[serde(tag = "type", content = "subject", rename_all = "camelCase")]
+pub enum UpstreamStatus {
+    /// The upstream branch was deleted, e.g. by pruning it after it was deleted on the remote.
+    Gone,
+    /// The branch and its upstream point to the same commit.
+    UpToDate,
+    /// The branch has this many commits its upstream doesn't have.
+    Ahead(usize),
+    /// The upstream has this many commits the branch doesn't have.
+    Behind(usize),
+    /// Both have commits the other doesn't have.
+    Diverged { ahead: usize, behind: usize },
+}
+
 /// A grouping that combines multiple commits into a patch series
 ///
 /// We deviate slightly from established language as we are transitioning from lanes representing
@@ -101,6 +158,9 @@ pub struct PatchSeries {
     /// Archived represents the state when series/branch has been integrated and is below the merge base of the branch.
     /// This would occur when the branch has been merged at the remote and the workspace has been updated with that change.
     pub archived: bool,
+    /// If files are listed against the base, the changes of this series since the head of the series below it,
+    /// or since the base of the stack for the bottom series. Empty otherwise.
+    pub files: Vec<VirtualBranchFile>,
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
@@ -110,6 +170,40 @@ pub struct VirtualBranches {
     pub skipped_files: Vec<gitbutler_diff::FileDiff>,
 }
 
+/// How a push may overwrite commits on the remote branch that the pushed branch doesn't contain.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ForceMode {
+    /// Refuse to overwrite any commits, so only fast-forwards are pushed.
+    #[default]
+    No,
+    /// Overwrite the remote branch only if it's still at the commit that was last pushed to it,
+    /// like `git push --force-with-lease`.
+    WithLease,
+    /// Overwrite the remote branch, no matter which commits it has by now.
+    Force,
+}
+
+impl From<bool> for ForceMode {
+    /// Forcing is only allowed with a lease, unless [`ForceMode::Force`] is asked for explicitly.
+    fn from(with_force: bool) -> Self {
+        if with_force {
+            ForceMode::WithLease
+        } else {
+            ForceMode::No
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PushError {
+    #[error("the remote branch is at {actual} instead of {expected}, where it was when it was last pushed to")]
+    StaleLease {
+        expected: git2::Oid,
+        actual: git2::Oid,
+    },
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PushResult {
@@ -117,6 +211,9 @@ pub struct PushResult {
     pub refname: Refname,
 }
 
+/// Discard the uncommitted hunks claimed by `ownership`, which may claim any number of hunks per file.
+/// All hunks of a file are reverted with a single patch, so the ranges are all relative to the
+/// current worktree, even if reverting one of them moves the lines of the others.
 pub fn unapply_ownership(
     ctx: &CommandContext,
     ownership: &BranchOwnershipClaims,
@@ -250,6 +347,243 @@ pub(crate) fn reset_files(
     unapply_ownership(ctx, &BranchOwnershipClaims { claims }, perm)?;
     Ok(())
 }
+
+/// Discard the uncommitted changes of all applied branches, leaving their commits untouched.
+/// Returns the number of files that were reset.
+pub(crate) fn discard_all_changes(
+    ctx: &CommandContext,
+    perm: &mut WorktreeWritePermission,
+) -> Result<usize> {
+    ctx.assure_resolved()?;
+
+    let statuses = get_applied_status(ctx, Some(perm))?.branches;
+    let num_files = statuses.iter().map(|(_, files)| files.len()).sum();
+    let claims = statuses
+        .into_iter()
+        .flat_map(|(branch, _)| branch.ownership.claims)
+        .collect();
+
+    unapply_ownership(ctx, &BranchOwnershipClaims { claims }, perm)?;
+    Ok(num_files)
+}
+
+/// Return the uncommitted changes to `files` owned by `branch_id`, i.e. what [`reset_files()`] would discard,
+/// without changing the worktree.
+pub(crate) fn preview_reset_files(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    files: &[PathBuf],
+    perm: &WorktreeReadPermission,
+) -> Result<Vec<VirtualBranchFile>> {
+    let (_, branch_files) = get_applied_status_read_only(ctx, None, perm)?
+        .branches
+        .into_iter()
+        .find(|(branch, _)| branch.id == branch_id)
+        .with_context(|| {
+            format!("could not find applied branch with id {branch_id} to reset files from")
+        })?;
+    Ok(branch_files
+        .into_iter()
+        .filter(|file| files.contains(&file.path))
+        .collect())
+}
+
+/// Write `path` as it is in `commit_oid` into the worktree, or delete it if it doesn't exist there.
+/// If `path` is a directory, all files below it are restored, and the ones that aren't in `commit_oid` are deleted.
+/// The result is a regular uncommitted change, owned by the branch containing `commit_oid`, or by whichever
+/// branch the usual hunk assignment picks if no branch contains it.
+pub(crate) fn checkout_file_from_commit(
+    ctx: &CommandContext,
+    commit_oid: git2::Oid,
+    path: &Path,
+    perm: &mut WorktreeWritePermission,
+) -> Result<()> {
+    ctx.assure_resolved()?;
+    if path.is_absolute()
+        || path
+            .components()
+            .any(|c| !matches!(c, std::path::Component::Normal(_)))
+    {
+        bail!("path {} must be relative to the worktree", path.display());
+    }
+
+    let repo = ctx.repository();
+    let commit = repo
+        .find_commit(commit_oid)
+        .with_context(|| format!("failed to find commit {commit_oid}"))?;
+    let entry = match commit.tree()?.get_path(path) {
+        Ok(entry) => Some(entry),
+        Err(err) if err.code() == git2::ErrorCode::NotFound => None,
+        Err(err) => return Err(err.into()),
+    };
+    // The files to restore, with their blob and mode, which is all files below `path` for a directory.
+    let mut restored = Vec::new();
+    match entry.as_ref().map(|entry| (entry, entry.kind())) {
+        None => {}
+        Some((entry, Some(git2::ObjectType::Blob))) => {
+            restored.push((path.to_owned(), entry.id(), entry.filemode()));
+        }
+        Some((entry, Some(git2::ObjectType::Tree))) => {
+            let mut entries = Vec::new();
+            entry.to_object(repo)?.peel_to_tree()?.walk(
+                git2::TreeWalkMode::PreOrder,
+                |dir, entry| {
+                    if entry.kind() == Some(git2::ObjectType::Blob) {
+                        entries.push((
+                            format!("{dir}{}", entry.name_bytes().to_str_lossy()),
+                            entry.id(),
+                            entry.filemode(),
+                        ));
+                    }
+                    git2::TreeWalkResult::Ok
+                },
+            )?;
+            restored.extend(
+                entries
+                    .into_iter()
+                    .map(|(file, id, mode)| (path.join(file), id, mode)),
+            );
+        }
+        Some(_) => bail!(
+            "{} is neither a file nor a directory in commit {commit_oid}",
+            path.display()
+        ),
+    }
+
+    // Remove what doesn't exist at the commit, or is in the way of what does.
+    let worktree_dir = ctx.project().worktree_path();
+    let worktree_path = worktree_dir.join(path);
+    match std::fs::symlink_metadata(&worktree_path) {
+        Ok(metadata) if metadata.is_dir() => {
+            if entry.as_ref().and_then(|entry| entry.kind()) == Some(git2::ObjectType::Tree) {
+                for file in worktree_files(repo, &worktree_dir, path)? {
+                    if !restored.iter().any(|(restored, ..)| *restored == file) {
+                        remove_worktree_file(&worktree_dir.join(file))?;
+                    }
+                }
+            } else {
+                std::fs::remove_dir_all(&worktree_path)?;
+            }
+        }
+        _ => remove_worktree_file(&worktree_path)?,
+    }
+    for (file, blob_id, filemode) in &restored {
+        let worktree_path = worktree_dir.join(file);
+        remove_worktree_file(&worktree_path)?;
+        if let Some(parent) = worktree_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let blob = repo.find_blob(*blob_id)?;
+        match *filemode {
+            #[cfg(unix)]
+            0o120000 => std::os::unix::fs::symlink(blob.content().to_path()?, &worktree_path)?,
+            0o100755 => {
+                std::fs::write(&worktree_path, blob.content())?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    std::fs::set_permissions(
+                        &worktree_path,
+                        std::fs::Permissions::from_mode(0o755),
+                    )?;
+                }
+            }
+            _ => std::fs::write(&worktree_path, blob.content())?,
+        }
+    }
+
+    // Assign the changes to the branch the commit belongs to, or to the default branch otherwise.
+    let status = get_applied_status(ctx, Some(perm))?;
+    let vb_state = ctx.project().virtual_branches();
+    let mut owner = None;
+    for stack in vb_state.list_branches_in_workspace()? {
+        if stack
+            .commits(ctx)?
+            .iter()
+            .any(|commit| commit.id() == commit_oid)
+        {
+            owner = Some(stack);
+            break;
+        }
+    }
+    let Some(mut owner) = owner else {
+        return Ok(());
+    };
+    let mut ownership = owner.ownership.clone();
+    for file in status
+        .branches
+        .iter()
+        .flat_map(|(_, files)| files)
+        .filter(|file| file.path.starts_with(path))
+    {
+        ownership.put(OwnershipClaim {
+            file_path: file.path.clone(),
+            hunks: file
+                .hunks
+                .iter()
+                .map(|hunk| Hunk::new(hunk.start, hunk.end, Some(hunk.hash)))
+                .collect::<Result<_>>()?,
+        });
+    }
+    set_ownership(&vb_state, &mut owner, &ownership)?;
+    vb_state.set_branch(owner)?;
+    Ok(())
+}
+
+fn remove_worktree_file(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Return the paths of all files in the worktree below `dir`, relative to `worktree_dir`, except for ignored ones.
+fn worktree_files(
+    repo: &git2::Repository,
+    worktree_dir: &Path,
+    dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(worktree_dir.join(&dir))? {
+            let entry = entry?;
+            let path = dir.join(entry.file_name());
+            if path == Path::new(".git") || repo.status_should_ignore(&path)? {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                dirs.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Return the content before and after the change of the uncommitted hunk `claim` refers to.
+/// The claim is resolved like ownership claims are when computing the status, so it doesn't have to
+/// match the current hunk exactly.
+pub(crate) fn hunk_content(
+    ctx: &CommandContext,
+    claim: &OwnershipClaim,
+    perm: &WorktreeReadPermission,
+) -> Result<HunkContent> {
+    let [claimed_hunk] = claim.hunks.as_slice() else {
+        bail!("expected a claim of a single hunk, got {claim}");
+    };
+    get_applied_status_read_only(ctx, None, perm)?
+        .branches
+        .into_iter()
+        .flat_map(|(_, files)| files)
+        .filter(|file| file.path == claim.file_path)
+        .flat_map(|file| file.hunks)
+        .find(|hunk| !hunk.binary && claimed_hunk.claims(&GitHunk::from(hunk.clone())))
+        .map(|hunk| HunkContent::from_hunk(&hunk))
+        .with_context(|| format!("no uncommitted hunk matches {claim}"))
+}
+
 fn find_base_tree<'a>(
     repo: &'a git2::Repository,
     branch_commit: &'a git2::Commit<'a>,
@@ -279,16 +613,62 @@ pub fn list_virtual_branches(
 /// `worktree_changes` are all changed files against the current `HEAD^{tree}` and index
 /// against the current working tree directory, and it's used to avoid double-computing
 /// this expensive information.
-#[instrument(level = tracing::Level::DEBUG, skip(ctx, perm, worktree_changes))]
+///
+/// For profiling, each phase is delimited by a `debug` span: diffing the worktree, computing hunk locks
+/// and assigning ownership (within `get_applied_status_with_options`), and per branch, finding remote commits,
+/// checking for integrated commits and computing the stack series.
 pub fn list_virtual_branches_cached(
+    ctx: &CommandContext,
+    perm: &mut WorktreeWritePermission,
+    worktree_changes: Option<gitbutler_diff::DiffByPathMap>,
+) -> Result<(Vec<VirtualBranch>, Vec<gitbutler_diff::FileDiff>)> {
+    list_virtual_branches_with_options(ctx, perm, worktree_changes, false, DiffOptions::default())
+}
+
+/// Like [`list_virtual_branches_cached()`], but if `files_against_base` is set, the `files` of each branch
+/// are all changes since its recorded [base](Stack::base_oid), which are the changes of its commits
+/// along with the uncommitted changes it owns, and each of its series lists its own changes since the
+/// head of the series below it. Otherwise they are just the uncommitted changes it owns.
+/// `diff_options` control what the hunks of these files are annotated with.
+#[instrument(level = tracing::Level::DEBUG, skip(ctx, perm, worktree_changes))]
+pub fn list_virtual_branches_with_options(
     ctx: &CommandContext,
     // TODO(ST): this should really only shared access, but there is some internals
     //           that conditionally write things.
     perm: &mut WorktreeWritePermission,
     worktree_changes: Option<gitbutler_diff::DiffByPathMap>,
+    files_against_base: bool,
+    diff_options: DiffOptions,
 ) -> Result<(Vec<VirtualBranch>, Vec<gitbutler_diff::FileDiff>)> {
     assure_open_workspace_mode(ctx)
         .context("Listing virtual branches requires open workspace mode")?;
+    let status = get_applied_status_with_options(ctx, Some(perm), worktree_changes, diff_options)?;
+    virtual_branches_from_status(ctx, status, files_against_base, diff_options, true)
+}
+
+/// Like [`list_virtual_branches_cached()`], but nothing is written to the virtual branches state
+/// and conflicts aren't resolved, so it can be polled without churning the state of the project.
+/// Unlike the other variants, it fails if there are changes but no branch to assign them to.
+pub fn list_virtual_branches_read_only(
+    ctx: &CommandContext,
+    worktree_changes: Option<gitbutler_diff::DiffByPathMap>,
+    perm: &WorktreeReadPermission,
+) -> Result<(Vec<VirtualBranch>, Vec<gitbutler_diff::FileDiff>)> {
+    assure_open_workspace_mode(ctx)
+        .context("Listing virtual branches requires open workspace mode")?;
+    let status = get_applied_status_read_only(ctx, worktree_changes, perm)?;
+    virtual_branches_from_status(ctx, status, false, DiffOptions::default(), false)
+}
+
+/// Turn the `status` of the applied branches into virtual branches, resolving conflicts in files that
+/// don't have conflict markers anymore if `resolve_conflicts` is set.
+fn virtual_branches_from_status(
+    ctx: &CommandContext,
+    status: VirtualBranchesStatus,
+    files_against_base: bool,
+    diff_options: DiffOptions,
+    resolve_conflicts: bool,
+) -> Result<(Vec<VirtualBranch>, Vec<gitbutler_diff::FileDiff>)> {
     let mut branches: Vec<VirtualBranch> = Vec::new();
 
     let vb_state = ctx.project().virtual_branches();
@@ -297,7 +677,6 @@ pub fn list_virtual_branches_cached(
         .get_default_target()
         .context("failed to get default target")?;
 
-    let status = get_applied_status_cached(ctx, Some(perm), worktree_changes)?;
     let max_selected_for_changes = status
         .branches
         .iter()
@@ -313,7 +692,10 @@ pub fn list_virtual_branches_cached(
     let cache = gix_repo.commit_graph_if_enabled()?;
     let mut graph = gix_repo.revision_graph(cache.as_ref());
     for (mut branch, mut files) in status.branches {
-        update_conflict_markers(ctx, files.clone())?;
+        if resolve_conflicts {
+            update_conflict_markers(ctx, files.clone())?;
+        }
+        let target = branch_target(ctx, &branch, &default_target)?;
 
         let upstream_branch = match branch.clone().upstream {
             Some(upstream) => repo.maybe_find_branch_by_refname(&Refname::from(upstream))?,
@@ -329,7 +711,29 @@ pub fn list_virtual_branches_cached(
                 branch.name
             ))?;
 
+        let upstream_status = match (&branch.upstream, &upstram_branch_commit) {
+            (None, _) => None,
+            (Some(_), None) => Some(UpstreamStatus::Gone),
+            (Some(_), Some(upstream)) => Some(
+                match repo.graph_ahead_behind(branch.head(), upstream.id())? {
+                    (0, 0) => UpstreamStatus::UpToDate,
+                    (ahead, 0) => UpstreamStatus::Ahead(ahead),
+                    (0, behind) => UpstreamStatus::Behind(behind),
+                    (ahead, behind) => UpstreamStatus::Diverged { ahead, behind },
+                },
+            ),
+        };
+
+        let target_head = match repo.refname_to_id(&target.branch.to_string()) {
+            Ok(id) => id,
+            Err(err) if err.code() == git2::ErrorCode::NotFound => target.sha,
+            Err(err) => return Err(err).context("failed to find target branch"),
+        };
+        let (ahead, behind) = repo.graph_ahead_behind(branch.head(), target_head)?;
+
         // find upstream commits if we found an upstream reference
+        let remote_commits_span =
+            tracing::debug_span!("find remote commits", given_name = branch.name).entered();
         let (remote_commit_ids, remote_commit_data) = upstram_branch_commit
             .as_ref()
             .map(
@@ -337,13 +741,13 @@ pub fn list_virtual_branches_cached(
                     let merge_base = gix_repo
                         .merge_base_with_graph(
                             git2_to_gix_object_id(upstream.id()),
-                            git2_to_gix_object_id(default_target.sha),
+                            git2_to_gix_object_id(target.sha),
                             &mut graph,
                         )
                         .context(format!(
                             "failed to find merge base between {} and {}",
                             upstream.id(),
-                            default_target.sha
+                            target.sha
                         ))?;
                     let merge_base = gitbutler_oxidize::gix_to_git2_oid(merge_base);
                     let remote_commit_ids = HashSet::from_iter(repo.l(
@@ -366,14 +770,14 @@ pub fn list_virtual_branches_cached(
             )
             .transpose()?
             .unwrap_or_default();
+        drop(remote_commits_span);
 
         let mut is_integrated = false;
         let mut is_remote = false;
 
         // find all commits on head that are not on target.sha
-        let commits = repo.log(branch.head(), LogUntil::Commit(default_target.sha), false)?;
-        let mut check_commit =
-            IsCommitIntegrated::new(ctx, &default_target, &gix_repo, &mut graph)?;
+        let commits = repo.log(branch.head(), LogUntil::Commit(target.sha), false)?;
+        let mut check_commit = IsCommitIntegrated::new(ctx, &target, &gix_repo, &mut graph)?;
         let vbranch_commits = {
             let _span = tracing::debug_span!(
                 "is-commit-integrated",
@@ -393,8 +797,12 @@ pub fn list_virtual_branches_cached(
                     };
 
                     // only check for integration if we haven't already found an integration
+                    let mut integration_unknown = false;
                     if !is_integrated {
-                        is_integrated = check_commit.is_integrated(commit)?
+                        match check_commit.integration_state(commit)? {
+                            Some(integrated) => is_integrated = integrated,
+                            None => integration_unknown = true,
+                        }
                     };
 
                     let copied_from_remote_id = CommitData::try_from(commit)
@@ -410,19 +818,31 @@ pub fn list_virtual_branches_cached(
                         copied_from_remote_id,
                         None, // remote_commit_id is only used inside PatchSeries
                     )
+                    .map(|commit| VirtualBranchCommit {
+                        integration_unknown,
+                        ..commit
+                    })
                 })
                 .collect::<Result<Vec<_>>>()?
         };
 
         let merge_base = gix_repo
             .merge_base_with_graph(
-                git2_to_gix_object_id(default_target.sha),
+                git2_to_gix_object_id(target.sha),
                 git2_to_gix_object_id(branch.head()),
                 check_commit.graph,
             )
             .context("failed to find merge base")?;
         let merge_base = gix_to_git2_oid(merge_base);
+        // The recorded base is what the branch is stacked on, even if it was created on an
+        // older commit of the target than the merge base.
+        let files_base = branch.base_oid.unwrap_or(merge_base);
+        if files_against_base {
+            files =
+                crate::file::list_virtual_tree_files(ctx, files_base, branch.tree, diff_options)?;
+        }
         let base_current = true;
+        let needs_integration = merge_base != target.sha && merge_base != branch.head();
 
         let upstream = upstream_branch.and_then(|upstream_branch| {
             let remotes = repo.remotes().ok()?;
@@ -454,7 +874,8 @@ pub fn list_virtual_branches_cached(
         let refname = branch.refname()?.into();
 
         // TODO: Error out here once this API is stable
-        let series = match stack_series(
+        let series_span = tracing::debug_span!("stack series", given_name = branch.name).entered();
+        let mut series = match stack_series(
             ctx,
             &mut branch,
             &default_target,
@@ -473,6 +894,10 @@ pub fn list_virtual_branches_cached(
                 vec![]
             }
         };
+        if files_against_base {
+            list_series_files(ctx, &mut series, files_base, branch.tree, diff_options)?;
+        }
+        drop(series_span);
 
         let head = branch.head();
         let branch = VirtualBranch {
@@ -488,15 +913,24 @@ pub fn list_virtual_branches_cached(
             upstream_name: branch
                 .upstream
                 .and_then(|r| Refname::from(r).branch().map(Into::into)),
+            upstream_status,
             conflicted: conflicts::is_resolving(ctx),
             base_current,
+            needs_integration,
+            ahead,
+            behind,
             ownership: branch.ownership,
             updated_at: branch.updated_timestamp_ms,
             selected_for_changes: branch.selected_for_changes == Some(max_selected_for_changes),
             allow_rebasing: branch.allow_rebasing,
+            local_only: branch.local_only,
+            frozen: branch.frozen,
+            skipped_hooks: branch.skipped_hooks,
+            commit_author: branch.commit_author,
             head,
             merge_base,
             fork_point,
+            base_oid: branch.base_oid,
             refname,
             tree: branch.tree,
             series,
@@ -511,6 +945,31 @@ pub fn list_virtual_branches_cached(
     Ok((branches, status.skipped_files))
 }
 
+/// Set the `files` of each of `series`, ordered from the top of the stack, to its changes since the head of
+/// the series below it, or since `base` for the bottom series. The changes of the top series go up to the
+/// `tree` of the stack, so they include its uncommitted changes.
+fn list_series_files(
+    ctx: &CommandContext,
+    series: &mut [PatchSeries],
+    mut base: git2::Oid,
+    tree: git2::Oid,
+    diff_options: DiffOptions,
+) -> Result<()> {
+    let repo = ctx.repository();
+    let num_series = series.len();
+    for (position, series) in series.iter_mut().rev().enumerate() {
+        let head = series.patches.first().map_or(base, |patch| patch.id);
+        let series_tree = if position + 1 == num_series {
+            tree
+        } else {
+            repo.find_commit(head)?.tree_id()
+        };
+        series.files = crate::file::list_virtual_tree_files(ctx, base, series_tree, diff_options)?;
+        base = head;
+    }
+    Ok(())
+}
+
 /// The commit-data we can use for comparison to see which remote-commit was used to craete
 /// a local commit from.
 /// Note that trees can't be used for comparison as these are typically rebased.
@@ -548,6 +1007,27 @@ fn branches_with_large_files_abridged(mut branches: Vec<VirtualBranch>) -> Vec<V
     branches
 }
 
+/// Return the target `branch` is based on, which is `default_target` unless the branch was retargeted.
+/// For a retargeted branch, the target commit is the merge-base of its target branch and the workspace base.
+pub(crate) fn branch_target(
+    ctx: &CommandContext,
+    branch: &Stack,
+    default_target: &Target,
+) -> Result<Target> {
+    let Some(refname) = &branch.target else {
+        return Ok(default_target.clone());
+    };
+    let repo = ctx.repository();
+    let target_head = repo
+        .refname_to_id(&refname.to_string())
+        .with_context(|| format!("target branch {refname} of {} doesn't exist", branch.name))?;
+    Ok(Target {
+        branch: refname.clone(),
+        sha: repo.merge_base(target_head, default_target.sha)?,
+        ..default_target.clone()
+    })
+}
+
 fn is_requires_force(ctx: &CommandContext, branch: &Stack) -> Result<bool> {
     let upstream = if let Some(upstream) = &branch.upstream {
         upstream
@@ -573,6 +1053,317 @@ fn is_requires_force(ctx: &CommandContext, branch: &Stack) -> Result<bool> {
     Ok(merge_base != upstream_commit.id())
 }
 
+/// Return the commits of `branch_id` that a push would send, newest first.
+/// These are the commits between its upstream and its head, or between the target and its head
+/// if the branch was never pushed.
+pub(crate) fn unpushed_commits(ctx: &CommandContext, branch_id: StackId) -> Result<Vec<git2::Oid>> {
+    let vb_state = ctx.project().virtual_branches();
+    let branch = vb_state.get_branch_in_workspace(branch_id)?;
+    let default_target = vb_state.get_default_target()?;
+
+    let upstream_id = match &branch.upstream {
+        Some(upstream) => match ctx.repository().refname_to_id(&upstream.to_string()) {
+            Ok(id) => Some(id),
+            Err(err) if err.code() == git2::ErrorCode::NotFound => None,
+            Err(other) => return Err(other).context("failed to find upstream reference"),
+        },
+        None => None,
+    };
+
+    ctx.repository().l(
+        branch.head(),
+        LogUntil::Commit(upstream_id.unwrap_or(default_target.sha)),
+        true,
+    )
+}
+
+/// Return the commits of the upstream of `branch_id` that aren't in the branch yet, newest first.
+/// This is what integrating the upstream would bring in, as known since the last fetch.
+/// It's empty if the branch has no upstream.
+pub(crate) fn incoming_commits(
+    ctx: &CommandContext,
+    branch_id: StackId,
+) -> Result<Vec<RemoteCommit>> {
+    let vb_state = ctx.project().virtual_branches();
+    let branch = vb_state.get_branch_in_workspace(branch_id)?;
+    let Some(upstream) = &branch.upstream else {
+        return Ok(Vec::new());
+    };
+    let repo = ctx.repository();
+    let upstream_id = match repo.refname_to_id(&upstream.to_string()) {
+        Ok(id) => id,
+        Err(err) if err.code() == git2::ErrorCode::NotFound => return Ok(Vec::new()),
+        Err(other) => return Err(other).context("failed to find upstream reference"),
+    };
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(upstream_id)?;
+    revwalk.hide(branch.head())?;
+    revwalk
+        .map(|oid| Ok(commit_to_remote_commit(&repo.find_commit(oid?)?)))
+        .collect()
+}
+
+/// Return whether each commit of `branch_id` is shared, i.e. reachable from the tip of the remote target branch
+/// as known since the last fetch, or only exists locally.
+/// Rewriting shared commits rewrites history others may have.
+pub(crate) fn commit_sharedness(
+    ctx: &CommandContext,
+    branch_id: StackId,
+) -> Result<HashMap<git2::Oid, bool>> {
+    let vb_state = ctx.project().virtual_branches();
+    let default_target = vb_state.get_default_target()?;
+    let branch = vb_state.get_branch_in_workspace(branch_id)?;
+    let repo = ctx.repository();
+
+    let remote_head = repo
+        .maybe_find_branch_by_refname(&default_target.branch.clone().into())?
+        .map(|branch| branch.get().peel_to_commit())
+        .transpose()?
+        .map_or(default_target.sha, |commit| commit.id());
+    let merge_base = repo.merge_base(default_target.sha, branch.head())?;
+    let branch_commits = repo.l(branch.head(), LogUntil::Commit(merge_base), false)?;
+
+    // The commits of the branch all descend from the merge-base, so everything it reaches can be skipped.
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(remote_head)?;
+    revwalk.hide(merge_base)?;
+    let reachable = revwalk.collect::<Result<HashSet<_>, _>>()?;
+    Ok(branch_commits
+        .into_iter()
+        .map(|oid| (oid, reachable.contains(&oid)))
+        .collect())
+}
+
+/// The size of a branch, as returned by [`branch_summary()`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchSummary {
+    /// The number of files with uncommitted changes.
+    pub files: usize,
+    /// The number of uncommitted hunks.
+    pub hunks: usize,
+    /// The number of lines the uncommitted changes add, not counting binary files.
+    pub lines_added: usize,
+    /// The number of lines the uncommitted changes remove, not counting binary files.
+    pub lines_removed: usize,
+    /// The number of commits on top of the target.
+    pub commits: usize,
+}
+
+/// Summarize the size of `branch_id` without computing anything for its commits but their number,
+/// which is much cheaper than [`list_virtual_branches()`].
+///
+/// The uncommitted changes are counted from the plain worktree diff, attributing hunks by the
+/// ownership claims of the branches as they were last stored, and unclaimed hunks to the branch
+/// that would receive them. Unlike the status, no hunk locks are considered.
+pub(crate) fn branch_summary(ctx: &CommandContext, branch_id: StackId) -> Result<BranchSummary> {
+    let mut applied = ctx
+        .project()
+        .virtual_branches()
+        .list_branches_in_workspace()?;
+    applied.sort_by_key(|b| (b.order, b.id));
+    let branch = applied
+        .iter()
+        .find(|branch| branch.id == branch_id)
+        .cloned()
+        .with_context(|| format!("could not find applied branch with id {branch_id}"))?;
+    let takes_unclaimed =
+        crate::status::default_branch_pos(&applied).is_some_and(|pos| applied[pos].id == branch_id);
+
+    let repo = ctx.repository();
+    let workspace_tree = repo.find_commit(get_workspace_head(ctx)?)?.tree()?;
+    let mut diff_opts = git2::DiffOptions::new();
+    diff_opts
+        .recurse_untracked_dirs(true)
+        .include_untracked(true)
+        .show_untracked_content(true)
+        .ignore_submodules(true)
+        .context_lines(3);
+    let diff = repo.diff_tree_to_workdir_with_index(Some(&workspace_tree), Some(&mut diff_opts))?;
+
+    let mut summary = BranchSummary::default();
+    for (idx, delta) in diff.deltas().enumerate() {
+        let Some(path) = delta.new_file().path().or_else(|| delta.old_file().path()) else {
+            continue;
+        };
+        let claims_of = |branch: &Stack| {
+            branch
+                .ownership
+                .claims
+                .iter()
+                .filter(|claim| claim.file_path == path)
+                .flat_map(|claim| claim.hunks.clone())
+                .collect_vec()
+        };
+        let ours = claims_of(&branch);
+        let theirs = applied
+            .iter()
+            .filter(|other| other.id != branch_id)
+            .flat_map(claims_of)
+            .collect_vec();
+        let Some(patch) = git2::Patch::from_diff(&diff, idx)? else {
+            continue;
+        };
+
+        let mut hunks = 0;
+        for hunk_idx in 0..patch.num_hunks() {
+            let (hunk, num_lines) = patch.hunk(hunk_idx)?;
+            let (start, end) = (hunk.new_start(), hunk.new_start() + hunk.new_lines());
+            let claimed = |claims: &[Hunk]| {
+                claims.iter().any(|claim| {
+                    claim.is_whole_file() || (claim.start <= end && start <= claim.end)
+                })
+            };
+            if !(claimed(&ours) || (takes_unclaimed && !claimed(&theirs))) {
+                continue;
+            }
+            hunks += 1;
+            for line_idx in 0..num_lines {
+                match patch.line_in_hunk(hunk_idx, line_idx)?.origin_value() {
+                    git2::DiffLineType::Addition => summary.lines_added += 1,
+                    git2::DiffLineType::Deletion => summary.lines_removed += 1,
+                    _ => {}
+                }
+            }
+        }
+        // binary files and changes without content, like of the mode, show as a single hunk
+        if patch.num_hunks() == 0 && (!ours.is_empty() || (takes_unclaimed && theirs.is_empty())) {
+            hunks = 1;
+        }
+        if hunks > 0 {
+            summary.files += 1;
+            summary.hunks += hunks;
+        }
+    }
+
+    let default_target = ctx.project().virtual_branches().get_default_target()?;
+    let target = branch_target(ctx, &branch, &default_target)?;
+    let merge_base = repo.merge_base(target.sha, branch.head())?;
+    summary.commits = repo
+        .l(branch.head(), LogUntil::Commit(merge_base), false)?
+        .len();
+    Ok(summary)
+}
+
+/// A file that would conflict if two branches were applied together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictingFile {
+    /// The path of the file, relative to the worktree.
+    pub path: PathBuf,
+    /// The hunks of the first branch that conflict with the second one, relative to the merge-base of both.
+    pub a_hunks: Vec<GitHunk>,
+    /// The hunks of the second branch that conflict with the first one, relative to the merge-base of both.
+    pub b_hunks: Vec<GitHunk>,
+}
+
+/// Combine the commits and uncommitted changes of the branches `a` and `b` in memory, and return
+/// the files and hunks that would conflict, or `None` if they can be applied together.
+/// Either branch may be unapplied, in which case the changes it had when it was unapplied are used.
+pub(crate) fn branches_conflict(
+    ctx: &CommandContext,
+    a: StackId,
+    b: StackId,
+    perm: &WorktreeReadPermission,
+) -> Result<Option<Vec<ConflictingFile>>> {
+    if a == b {
+        return Err(
+            anyhow!("can't check branch {a} for conflicts with itself").context(Code::Validation)
+        );
+    }
+    let vb_state = ctx.project().virtual_branches();
+    let (a, b) = (vb_state.get_branch(a)?, vb_state.get_branch(b)?);
+    // The status has the trees of applied branches with their uncommitted changes, without persisting them.
+    let applied = get_applied_status_read_only(ctx, None, perm)?.branches;
+    let branch_tree = |branch: &Stack| {
+        applied
+            .iter()
+            .find(|(applied, _)| applied.id == branch.id)
+            .map_or(branch.tree, |(applied, _)| applied.tree)
+    };
+
+    let repo = ctx.repository().in_memory_repo()?;
+    let base_tree = repo
+        .find_commit(repo.merge_base(a.head(), b.head())?)?
+        .tree()?;
+    let (a_tree, b_tree) = (
+        repo.find_tree(branch_tree(&a))?,
+        repo.find_tree(branch_tree(&b))?,
+    );
+    let mut merge_index = repo.merge_trees(&base_tree, &a_tree, &b_tree, None)?;
+    if !merge_index.has_conflicts() {
+        return Ok(None);
+    }
+    let mut conflicting_paths: Vec<PathBuf> = merge_index
+        .conflicts()?
+        .flatten()
+        .filter_map(|conflict| conflict.our.or(conflict.their))
+        .map(|entry| gix::path::from_bstring(entry.path))
+        .collect();
+    conflicting_paths.sort();
+    conflicting_paths.dedup();
+
+    let mut a_diffs = trees(&repo, &base_tree, &a_tree, false)?;
+    let mut b_diffs = trees(&repo, &base_tree, &b_tree, false)?;
+    let conflicting = conflicting_paths
+        .into_iter()
+        .map(|path| {
+            let a_hunks = a_diffs
+                .remove(&path)
+                .map(|diff| diff.hunks)
+                .unwrap_or_default();
+            let b_hunks = b_diffs
+                .remove(&path)
+                .map(|diff| diff.hunks)
+                .unwrap_or_default();
+            // Hunks touching the same lines of the merge-base conflict, and if there are none, like
+            // for changes Git considers too close to each other, all hunks of the file do.
+            let overlapping = |hunk: &GitHunk, others: &[GitHunk]| {
+                others.iter().any(|other| old_lines_overlap(hunk, other))
+            };
+            let a_conflicting: Vec<_> = a_hunks
+                .iter()
+                .filter(|hunk| overlapping(hunk, &b_hunks))
+                .cloned()
+                .collect();
+            let b_conflicting: Vec<_> = b_hunks
+                .iter()
+                .filter(|hunk| overlapping(hunk, &a_hunks))
+                .cloned()
+                .collect();
+            if a_conflicting.is_empty() || b_conflicting.is_empty() {
+                ConflictingFile {
+                    path,
+                    a_hunks,
+                    b_hunks,
+                }
+            } else {
+                ConflictingFile {
+                    path,
+                    a_hunks: a_conflicting,
+                    b_hunks: b_conflicting,
+                }
+            }
+        })
+        .collect();
+    Ok(Some(conflicting))
+}
+
+/// Return `true` if `a` and `b` change or insert at the same or adjacent lines of the file they were both made to.
+fn old_lines_overlap(a: &GitHunk, b: &GitHunk) -> bool {
+    let (a_start, a_end) = (a.old_start, a.old_start + a.old_lines);
+    let (b_start, b_end) = (b.old_start, b.old_start + b.old_lines);
+    a_start <= b_end && b_start <= a_end
+}
+
+/// The git hooks that run when committing, each of which can be skipped per branch.
+const COMMIT_HOOKS: [&str; 4] = [
+    git2_hooks::HOOK_PRE_COMMIT,
+    git2_hooks::HOOK_PREPARE_COMMIT_MSG,
+    git2_hooks::HOOK_COMMIT_MSG,
+    git2_hooks::HOOK_POST_COMMIT,
+];
+
 pub fn update_branch(ctx: &CommandContext, branch_update: &BranchUpdateRequest) -> Result<Stack> {
     let vb_state = ctx.project().virtual_branches();
     let mut branch = vb_state.get_branch_in_workspace(branch_update.id)?;
@@ -617,6 +1408,17 @@ pub fn update_branch(ctx: &CommandContext, branch_update: &BranchUpdateRequest)
         branch.upstream = Some(remote_branch);
     };
 
+    if let Some(target) = &branch_update.target {
+        let default_target = vb_state.get_default_target()?;
+        let target: RemoteRefname = target
+            .parse()
+            .with_context(|| format!("'{target}' is not a remote branch"))?;
+        ctx.repository()
+            .refname_to_id(&target.to_string())
+            .with_context(|| format!("target branch {target} doesn't exist"))?;
+        branch.target = (target != default_target.branch).then_some(target);
+    };
+
     if let Some(notes) = branch_update.notes.clone() {
         branch.notes = notes;
     };
@@ -646,10 +1448,53 @@ pub fn update_branch(ctx: &CommandContext, branch_update: &BranchUpdateRequest)
         branch.allow_rebasing = allow_rebasing;
     };
 
+    if let Some(frozen) = branch_update.frozen {
+        branch.frozen = frozen;
+    };
+
+    if let Some(skipped_hooks) = &branch_update.skipped_hooks {
+        if let Some(unknown) = skipped_hooks
+            .iter()
+            .find(|hook| !COMMIT_HOOKS.contains(&hook.as_str()))
+        {
+            bail!(
+                "cannot skip unknown hook {unknown:?}, expected one of {}",
+                COMMIT_HOOKS.join(", ")
+            );
+        }
+        branch.skipped_hooks = skipped_hooks.clone();
+    };
+
+    if let Some(commit_author) = &branch_update.commit_author {
+        branch.commit_author = if commit_author.name.is_empty() && commit_author.email.is_empty() {
+            None
+        } else if commit_author.name.is_empty() || commit_author.email.is_empty() {
+            bail!("a commit author needs both a name and an email");
+        } else {
+            Some(commit_author.clone())
+        };
+    };
+
     vb_state.set_branch(branch.clone())?;
     Ok(branch)
 }
 
+/// Return the id of the branch that is selected for changes, and thus receives hunks no branch owns yet.
+/// It's the branch that was selected most recently, with ties going to the branch with the lowest `order`,
+/// then the lowest id. Return `None` if no applied branch was ever selected.
+pub(crate) fn default_branch(vb_state: &VirtualBranchesHandle) -> Result<Option<StackId>> {
+    Ok(vb_state
+        .list_branches_in_workspace()
+        .context("failed to list branches")?
+        .into_iter()
+        .filter_map(|b| {
+            b.selected_for_changes
+                .map(|selected| (selected, std::cmp::Reverse((b.order, b.id))))
+        })
+        .max()
+        .map(|(_, std::cmp::Reverse((_, id)))| id))
+}
+
 pub(crate) fn ensure_selected_for_changes(vb_state: &VirtualBranchesHandle) -> Result<()> {
     let mut virtual_branches = vb_state
         .list_branches_in_workspace()
@@ -705,8 +1550,356 @@ pub(crate) fn set_ownership(
     Ok(())
 }
 
-pub type BranchStatus = HashMap<PathBuf, Vec<gitbutler_diff::GitHunk>>;
-pub type VirtualBranchHunksByPathMap = HashMap<PathBuf, Vec<VirtualBranchHunk>>;
+/// Return the local branch reference the branch with `branch_id` would be saved as when it's unapplied,
+/// which is `refs/heads/<name>` for its normalized name, or the first of `<name>-2`, `<name>-3`, … that
+/// doesn't collide with an existing local branch.
+pub(crate) fn suggest_branch_ref_name(
+    ctx: &CommandContext,
+    branch_id: StackId,
+) -> Result<ReferenceName> {
+    let branch = ctx.project().virtual_branches().get_branch(branch_id)?;
+    let name = crate::branch_manager::branch_name_for_stack(ctx.repository(), &branch)?;
+    Ok(format!("refs/heads/{name}").into())
+}
+
+/// Point a new `refs/gitbutler/upstream-backup/<name>/<n>` at the last known upstream commit of the branch
+/// with `branch_id`, which is the one we last pushed, or the tip of its remote-tracking branch if we never pushed.
+/// `<n>` counts up from 1 for each backup of the branch, so earlier backups are never overwritten.
+/// Doing this before integrating a rewritten upstream keeps the old upstream commits recoverable.
+pub(crate) fn snapshot_upstream(ctx: &CommandContext, branch_id: StackId) -> Result<ReferenceName> {
+    let vb_state = ctx.project().virtual_branches();
+    let branch = vb_state.get_branch_in_workspace(branch_id)?;
+    let repo = ctx.repository();
+    let upstream_id = match (branch.upstream_head, &branch.upstream) {
+        (Some(upstream_head), _) => upstream_head,
+        (None, Some(upstream)) => repo
+            .refname_to_id(&upstream.to_string())
+            .context("failed to find upstream reference")?,
+        (None, None) => {
+            return Err(anyhow!("branch {} has no upstream", branch.name).context(Code::Validation))
+        }
+    };
+
+    let prefix = format!(
+        "refs/gitbutler/upstream-backup/{}/",
+        normalize_branch_name(&branch.name)?
+    );
+    let mut last_backup = 0;
+    for reference in repo.references_glob(&format!("{prefix}*"))? {
+        let backup = reference?
+            .name()
+            .and_then(|name| name.strip_prefix(&prefix))
+            .and_then(|n| n.parse::<u64>().ok());
+        last_backup = last_backup.max(backup.unwrap_or_default());
+    }
+    let refname = format!("{prefix}{}", last_backup + 1);
+    match repo.reference(
+        &refname,
+        upstream_id,
+        false,
+        &format!("backup of upstream of {}", branch.name),
+    ) {
+        Ok(_) => Ok(refname.into()),
+        Err(err) if err.code() == git2::ErrorCode::Exists => {
+            Err(anyhow!("upstream backup {refname} already exists").context(Code::Validation))
+        }
+        Err(err) => Err(err).context("failed to create upstream backup reference"),
+    }
+}
+
+/// Move the file at `from` to `to` in the worktree, and let the branch with `branch_id` own both the
+/// deletion of `from` and the addition of `to`, so committing it records the rename.
+pub(crate) fn stage_rename(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    from: &Path,
+    to: &Path,
+    perm: &mut WorktreeWritePermission,
+) -> Result<()> {
+    for path in [from, to] {
+        if path.is_absolute()
+            || path
+                .components()
+                .any(|c| !matches!(c, std::path::Component::Normal(_)))
+        {
+            return Err(
+                anyhow!("path {} must be relative to the worktree", path.display())
+                    .context(Code::Validation),
+            );
+        }
+    }
+    let worktree_dir = ctx.project().worktree_path();
+    let (from_path, to_path) = (worktree_dir.join(from), worktree_dir.join(to));
+    if !from_path.is_file() {
+        bail!("cannot rename {}: file not found", from.display());
+    }
+    if to_path.exists() {
+        bail!("cannot rename to {}: file already exists", to.display());
+    }
+    if let Some(parent) = to_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::rename(&from_path, &to_path)
+        .with_context(|| format!("failed to rename {} to {}", from.display(), to.display()))?;
+
+    let status = get_applied_status(ctx, Some(perm))?;
+    let vb_state = ctx.project().virtual_branches();
+    let mut branch = vb_state.get_branch_in_workspace(branch_id)?;
+    let mut ownership = branch.ownership.clone();
+    for file in status
+        .branches
+        .iter()
+        .flat_map(|(_, files)| files)
+        .filter(|file| file.path == from || file.path == to)
+    {
+        ownership.put(OwnershipClaim {
+            file_path: file.path.clone(),
+            hunks: file
+                .hunks
+                .iter()
+                .map(|hunk| Hunk::new(hunk.start, hunk.end, Some(hunk.hash)))
+                .collect::<Result<_>>()?,
+        });
+    }
+    set_ownership(&vb_state, &mut branch, &ownership)?;
+    vb_state.set_branch(branch)?;
+    Ok(())
+}
+
+/// Lay the changes of `remote_ref` since it forked from the target into the worktree as uncommitted changes
+/// owned by `branch_id`, without importing its commits.
+/// Fails without changing anything if they touch files with uncommitted changes, or conflict with the workspace.
+pub(crate) fn apply_remote_as_changes(
+    ctx: &CommandContext,
+    remote_ref: &RemoteRefname,
+    branch_id: StackId,
+    perm: &mut WorktreeWritePermission,
+) -> Result<()> {
+    ctx.assure_resolved()?;
+    let vb_state = ctx.project().virtual_branches();
+    vb_state.get_branch_in_workspace(branch_id)?;
+    let default_target = vb_state.get_default_target()?;
+
+    let repo = ctx.repository();
+    let remote_head = repo
+        .find_reference(&remote_ref.to_string())
+        .with_context(|| format!("remote branch {remote_ref} not found"))?
+        .peel_to_commit()?;
+    let merge_base = repo.merge_base(default_target.sha, remote_head.id())?;
+    let base_tree = repo.find_commit(merge_base)?.tree()?;
+    let remote_tree = remote_head.tree()?;
+    let workspace_tree = repo.find_commit(get_workspace_head(ctx)?)?.tree()?;
+    let worktree = repo.create_wd_tree()?;
+
+    let changed_paths = |old: &git2::Tree, new: &git2::Tree| -> Result<HashSet<PathBuf>> {
+        Ok(repo
+            .diff_tree_to_tree(Some(old), Some(new), None)?
+            .deltas()
+            .flat_map(|delta| [delta.old_file().path(), delta.new_file().path()])
+            .flatten()
+            .map(ToOwned::to_owned)
+            .collect())
+    };
+    let remote_paths = changed_paths(&base_tree, &remote_tree)?;
+    let uncommitted_paths = changed_paths(&workspace_tree, &worktree)?;
+    let mut overlapping: Vec<_> = remote_paths.intersection(&uncommitted_paths).collect();
+    if !overlapping.is_empty() {
+        overlapping.sort();
+        bail!(
+            "the changes of {remote_ref} touch files with uncommitted changes: {}",
+            overlapping
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    let mut merge_index = repo.merge_trees(&base_tree, &worktree, &remote_tree, None)?;
+    if merge_index.has_conflicts() {
+        let mut conflicting: Vec<_> = merge_index
+            .conflicts()?
+            .flatten()
+            .filter_map(|conflict| conflict.our.or(conflict.their))
+            .map(|entry| entry.path.to_str_lossy().into_owned())
+            .collect();
+        conflicting.sort();
+        conflicting.dedup();
+        bail!(
+            "the changes of {remote_ref} conflict with the workspace: {}",
+            conflicting.join(", ")
+        );
+    }
+    let merged_tree = repo.find_tree(merge_index.write_tree_to(repo)?)?;
+    repo.checkout_tree_builder(&merged_tree)
+        .force()
+        .checkout()
+        .context("failed to checkout tree")?;
+
+    let status = get_applied_status(ctx, Some(perm))?;
+    let mut branch = vb_state.get_branch_in_workspace(branch_id)?;
+    let mut ownership = branch.ownership.clone();
+    for file in status
+        .branches
+        .iter()
+        .flat_map(|(_, files)| files)
+        .filter(|file| remote_paths.contains(&file.path))
+    {
+        ownership.put(OwnershipClaim {
+            file_path: file.path.clone(),
+            hunks: file
+                .hunks
+                .iter()
+                .map(|hunk| Hunk::new(hunk.start, hunk.end, Some(hunk.hash)))
+                .collect::<Result<_>>()?,
+        });
+    }
+    set_ownership(&vb_state, &mut branch, &ownership)?;
+    vb_state.set_branch(branch)?;
+    Ok(())
+}
+
+/// Apply the hunks of `claim` as they were introduced by `commit_oid` to the worktree, as uncommitted changes
+/// owned by `branch_id`. The commit itself is left alone.
+/// Fails without changing anything if the hunks don't apply to the current content of the file, or if they
+/// would merge with uncommitted changes of another branch.
+pub(crate) fn extract_hunk_from_commit(
+    ctx: &CommandContext,
+    commit_oid: git2::Oid,
+    claim: &OwnershipClaim,
+    branch_id: StackId,
+    perm: &mut WorktreeWritePermission,
+) -> Result<()> {
+    ctx.assure_resolved()?;
+    let vb_state = ctx.project().virtual_branches();
+    vb_state.get_branch_in_workspace(branch_id)?;
+
+    let repo = ctx.repository();
+    let commit = repo
+        .find_commit(commit_oid)
+        .with_context(|| format!("failed to find commit {commit_oid}"))?;
+    if commit.parent_count() != 1 {
+        bail!("can only extract hunks from commits with a single parent");
+    }
+    let commit_tree = repo.find_real_tree(&commit, Default::default())?;
+    let parent_tree = repo.find_real_tree(&commit.parent(0)?, Default::default())?;
+
+    // use the same context as the listed commit files, so the hunk ranges match.
+    let mut diff_opts = git2::DiffOptions::new();
+    diff_opts
+        .pathspec(&claim.file_path)
+        .disable_pathspec_match(true)
+        .show_binary(true)
+        .context_lines(3);
+    let diff =
+        repo.diff_tree_to_tree(Some(&parent_tree), Some(&commit_tree), Some(&mut diff_opts))?;
+    let is_claimed = |hunk: &git2::DiffHunk<'_>| {
+        claim.hunks.iter().any(|claimed| {
+            claimed.start == hunk.new_start() && claimed.end == hunk.new_start() + hunk.new_lines()
+        })
+    };
+    let patch = git2::Patch::from_diff(&diff, 0)?.with_context(|| {
+        format!(
+            "commit {commit_oid} doesn't change {}",
+            claim.file_path.display()
+        )
+    })?;
+    let commit_hunks = (0..patch.num_hunks())
+        .map(|idx| Ok(patch.hunk(idx)?.0))
+        .collect::<Result<Vec<_>>>()?;
+    for claimed in &claim.hunks {
+        if !commit_hunks.iter().any(|hunk| {
+            claimed.start == hunk.new_start() && claimed.end == hunk.new_start() + hunk.new_lines()
+        }) {
+            bail!(
+                "commit {commit_oid} has no hunk {}:{claimed}",
+                claim.file_path.display()
+            );
+        }
+    }
+
+    let owners_before: HashMap<_, _> = get_applied_status(ctx, Some(perm))?
+        .branches
+        .iter()
+        .flat_map(|(branch, files)| {
+            files
+                .iter()
+                .filter(|file| file.path == claim.file_path)
+                .flat_map(|file| &file.hunks)
+                .map(|hunk| (hunk.hash, branch.id))
+        })
+        .collect();
+    let worktree_file = ctx.project().worktree_path().join(&claim.file_path);
+    let content_before = match std::fs::read(&worktree_file) {
+        Ok(content) => Some(content),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut apply_opts = git2::ApplyOptions::new();
+    apply_opts.hunk_callback(|hunk| hunk.is_some_and(|hunk| is_claimed(&hunk)));
+    repo.apply(&diff, git2::ApplyLocation::WorkDir, Some(&mut apply_opts))
+        .with_context(|| {
+            format!(
+                "the hunks of commit {commit_oid} conflict with the current content of {}",
+                claim.file_path.display()
+            )
+        })?;
+
+    // A hunk of another branch that is gone was merged with an extracted hunk, which would leave the
+    // two branches claiming the same lines.
+    let hunks_after: HashSet<_> = get_applied_status_read_only(ctx, None, perm.read_permission())?
+        .branches
+        .iter()
+        .flat_map(|(_, files)| files)
+        .filter(|file| file.path == claim.file_path)
+        .flat_map(|file| &file.hunks)
+        .map(|hunk| hunk.hash)
+        .collect();
+    if owners_before
+        .iter()
+        .any(|(hash, owner)| *owner != branch_id && !hunks_after.contains(hash))
+    {
+        match content_before {
+            Some(content) => std::fs::write(&worktree_file, content)?,
+            None => std::fs::remove_file(&worktree_file)?,
+        }
+        return Err(anyhow!(
+            "the hunks of commit {commit_oid} overlap with changes to {} owned by another branch",
+            claim.file_path.display()
+        )
+        .context(Code::Validation));
+    }
+
+    let status = get_applied_status(ctx, Some(perm))?;
+    let mut branch = vb_state.get_branch_in_workspace(branch_id)?;
+    let mut ownership = branch.ownership.clone();
+    for file in status
+        .branches
+        .iter()
+        .flat_map(|(_, files)| files)
+        .filter(|file| file.path == claim.file_path)
+    {
+        let hunks = file
+            .hunks
+            .iter()
+            .filter(|hunk| !owners_before.contains_key(&hunk.hash))
+            .map(|hunk| Hunk::new(hunk.start, hunk.end, Some(hunk.hash)))
+            .collect::<Result<Vec<_>>>()?;
+        if !hunks.is_empty() {
+            ownership.put(OwnershipClaim {
+                file_path: file.path.clone(),
+                hunks,
+            });
+        }
+    }
+    set_ownership(&vb_state, &mut branch, &ownership)?;
+    vb_state.set_branch(branch)?;
+    Ok(())
+}
+
+pub type BranchStatus = HashMap<PathBuf, Vec<gitbutler_diff::GitHunk>>;
+pub type VirtualBranchHunksByPathMap = HashMap<PathBuf, Vec<VirtualBranchHunk>>;
 
 // reset virtual branch to a specific commit
 pub(crate) fn reset_branch(
@@ -780,7 +1973,284 @@ pub(crate) fn reset_branch(
     Ok(())
 }
 
-#[allow(clippy::too_many_arguments)]
+/// Options to control how [`commit_with_options()`] creates a commit.
+#[derive(Debug, Default, Clone)]
+pub struct CommitOptions {
+    /// Run the `prepare-commit-msg`, `commit-msg`, `pre-commit` and `post-commit` hooks.
+    pub run_hooks: bool,
+    /// Commit hunks that add conflict markers even though no merge conflict is being resolved.
+    pub allow_conflict_markers: bool,
+    /// The author to use instead of the one from the environment or the Git configuration.
+    pub author: Option<git2::Signature<'static>>,
+    /// The committer to use instead of the one from the environment or the Git configuration.
+    pub committer: Option<git2::Signature<'static>>,
+    /// Strip trailing whitespace from the committed lines of text files, and add a missing final newline
+    /// if their last line is committed, unless `.editorconfig` turns that off. The files in the worktree
+    /// are left alone.
+    pub clean_whitespace: bool,
+    /// Refuse to commit text files that don't end with a newline, instead of adding it like
+    /// [`clean_whitespace`](Self::clean_whitespace) does.
+    pub require_final_newline: bool,
+    /// Commit files even if they exceed the maximum file size configured for the project.
+    pub allow_large_files: bool,
+    /// The message was edited from [`commit_message_template()`], so the scissors line and everything
+    /// below it is removed from it, like `git commit --cleanup=scissors` does.
+    pub strip_scissors: bool,
+    /// What to do if the upstream of the branch has commits the branch doesn't have yet.
+    pub behind_upstream: BehindUpstream,
+    /// How to commit the resolution of conflicts with the upstream.
+    pub merge_resolution: MergeResolution,
+    /// Canonicalize the trailer block of the message, see [`trailers::normalize_trailers()`].
+    pub normalize_trailers: bool,
+    /// A file whose content is appended to the message as its body, after its placeholders were
+    /// expanded as described in [`expand_message_template()`].
+    /// Relative paths are relative to the project directory.
+    pub message_template: Option<PathBuf>,
+    /// Fail if [`message_template`](Self::message_template) contains unknown placeholders, instead of
+    /// leaving them as they are.
+    pub fail_on_unknown_placeholders: bool,
+}
+
+/// How to commit once all conflicts of integrating the upstream are resolved.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MergeResolution {
+    /// Create a merge commit with the integrated upstream commit as second parent.
+    #[default]
+    Merge,
+    /// Create a commit with a single parent, and record the integrated upstream commit in its headers
+    /// so it's still known to be integrated, similar to a rebase.
+    Linear,
+}
+
+/// How to commit to a branch whose upstream has advanced, e.g. because a teammate pushed to it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BehindUpstream {
+    /// Commit onto the local head, so the branch diverges from its upstream and needs a force-push.
+    #[default]
+    Ignore,
+    /// Refuse to commit with [`Code::CommitBranchBehindUpstream`].
+    Fail,
+    /// Integrate the upstream commits first, like [`integrate_upstream_commits()`](crate::branch_upstream_integration::integrate_upstream_commits) does.
+    /// This is done by [`create_commit_with_options()`](crate::create_commit_with_options), as it needs write access to the worktree.
+    Integrate,
+}
+
+/// The line below which everything is removed from an edited commit message, as written by `git commit --verbose`.
+const SCISSORS_LINE: &str = "# ------------------------ >8 ------------------------";
+
+/// Return a commit message buffer for editing, which contains the uncommitted changes of `branch_id`
+/// for reference below a scissors line, similar to what `git commit --verbose` provides.
+/// Use [`CommitOptions::strip_scissors`] when committing the edited message.
+pub(crate) fn commit_message_template(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    perm: &WorktreeReadPermission,
+) -> Result<String> {
+    let (_, files) = get_applied_status_read_only(ctx, None, perm)?
+        .branches
+        .into_iter()
+        .find(|(branch, _)| branch.id == branch_id)
+        .with_context(|| format!("branch {branch_id} not found"))?;
+
+    let mut template = format!(
+        "\n{SCISSORS_LINE}\n# Do not modify or remove the line above.\n# Everything below it will be ignored.\n"
+    );
+    for file in files {
+        let path = file.path.display();
+        template.push_str(&format!("diff --git a/{path} b/{path}\n"));
+        for hunk in file.hunks.iter().filter(|hunk| !hunk.binary) {
+            template.push_str(&hunk.diff.to_str_lossy());
+        }
+    }
+    Ok(template)
+}
+
+/// Expand `template` for a commit of the changes of `branch_id` claimed by `ownership`, which are
+/// looked up in `statuses`, the applied branches along with their changes.
+fn message_template_body(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    statuses: &[(Stack, Vec<VirtualBranchFile>)],
+    ownership: Option<&BranchOwnershipClaims>,
+    template: &str,
+    options: &CommitOptions,
+) -> Result<String> {
+    let (branch, files) = statuses
+        .iter()
+        .find(|(branch, _)| branch.id == branch_id)
+        .with_context(|| format!("branch {branch_id} not found"))?;
+    let files: Vec<_> = files
+        .iter()
+        .map(|file| file.path.clone())
+        .filter(|path| {
+            ownership.map_or(true, |ownership| {
+                ownership
+                    .claims
+                    .iter()
+                    .any(|claim| &claim.file_path == path)
+            })
+        })
+        .collect();
+    expand_message_template(
+        template,
+        &branch.name,
+        &files,
+        ctx.project().commit_issue_key.as_ref(),
+        options.fail_on_unknown_placeholders,
+    )
+}
+
+/// Replace the placeholders in `template` with values from the branch named `branch_name` and the
+/// committed `files`:
+///
+/// * `{branch}` - the name of the branch.
+/// * `{ticket}` - the issue key in the branch name as configured by `issue_key`, or if that isn't set,
+///   the first ticket reference like `ABC-123`. It's empty if there is none.
+/// * `{files}` - the paths of the committed files, one per line.
+///
+/// Unknown placeholders are left as they are, unless `fail_on_unknown` is set.
+pub(crate) fn expand_message_template(
+    template: &str,
+    branch_name: &str,
+    files: &[PathBuf],
+    issue_key: Option<&CommitIssueKey>,
+    fail_on_unknown: bool,
+) -> Result<String> {
+    static PLACEHOLDER: LazyLock<regex::Regex> =
+        LazyLock::new(|| regex::Regex::new(r"\{([a-z_]+)\}").expect("valid regex"));
+    static DEFAULT_TICKET: LazyLock<regex::Regex> =
+        LazyLock::new(|| regex::Regex::new(r"[A-Z][A-Z0-9]+-[0-9]+").expect("valid regex"));
+    let ticket = match issue_key {
+        Some(issue_key) => Cow::Owned(issue_key_pattern(issue_key)?),
+        None => Cow::Borrowed(&*DEFAULT_TICKET),
+    };
+
+    let mut expanded = String::with_capacity(template.len());
+    let mut last_end = 0;
+    for captures in PLACEHOLDER.captures_iter(template) {
+        let (whole, [name]) = captures.extract();
+        let value = match name {
+            "branch" => branch_name.to_owned(),
+            "ticket" => find_issue_key(&ticket, branch_name)
+                .unwrap_or_default()
+                .to_owned(),
+            "files" => files
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            unknown if fail_on_unknown => {
+                bail!("unknown placeholder {{{unknown}}} in message template")
+            }
+            _ => whole.to_owned(),
+        };
+        let range = captures.get(0).expect("always present").range();
+        expanded.push_str(&template[last_end..range.start]);
+        expanded.push_str(&value);
+        last_end = range.end;
+    }
+    expanded.push_str(&template[last_end..]);
+    Ok(expanded)
+}
+
+/// Remove the scissors line and everything below it from `message`, along with trailing whitespace.
+fn strip_scissors(message: &str) -> &str {
+    let mut offset = 0;
+    for line in message.split_inclusive('\n') {
+        if line.trim_end() == SCISSORS_LINE {
+            return message[..offset].trim_end();
+        }
+        offset += line.len();
+    }
+    message
+}
+
+/// Prefix `message` with the issue key found in `branch_name` as configured by `issue_key`,
+/// unless the branch name has no issue key or `message` already mentions it.
+fn prefix_issue_key(
+    issue_key: &CommitIssueKey,
+    branch_name: &str,
+    message: &str,
+) -> Result<String> {
+    let pattern = issue_key_pattern(issue_key)?;
+    let Some(key) = find_issue_key(&pattern, branch_name) else {
+        return Ok(message.to_owned());
+    };
+    if mentions_issue_key(message, key) {
+        return Ok(message.to_owned());
+    }
+    Ok(format!(
+        "{}{message}",
+        issue_key.format.replace("{key}", key)
+    ))
+}
+
+fn issue_key_pattern(issue_key: &CommitIssueKey) -> Result<regex::Regex> {
+    regex::Regex::new(&issue_key.pattern)
+        .with_context(|| format!("invalid issue key pattern {:?}", issue_key.pattern))
+        .context(Code::Validation)
+}
+
+/// Return the issue key `pattern` finds in `branch_name`, which is its first capture group, or the
+/// whole match if it has none.
+fn find_issue_key<'a>(pattern: &regex::Regex, branch_name: &'a str) -> Option<&'a str> {
+    let captures = pattern.captures(branch_name)?;
+    captures
+        .get(1)
+        .or_else(|| captures.get(0))
+        .map(|key| key.as_str())
+        .filter(|key| !key.is_empty())
+}
+
+/// Return `true` if `key` is a word of its own in `message`, and not just a part of one, like `PROJ-12`
+/// is of `PROJ-123`.
+fn mentions_issue_key(message: &str, key: &str) -> bool {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    message.match_indices(key).any(|(start, _)| {
+        !message[..start]
+            .chars()
+            .next_back()
+            .is_some_and(is_word_char)
+            && !message[start + key.len()..]
+                .chars()
+                .next()
+                .is_some_and(is_word_char)
+    })
+}
+
+/// Produce the key and value of each of the project's `default_trailers` for a commit with `message`.
+fn default_trailer_values(
+    default_trailers: &[DefaultTrailer],
+    message: &str,
+    author: &git2::Signature<'_>,
+    committer: &git2::Signature<'_>,
+) -> Vec<(String, String)> {
+    let signature = |signature: &git2::Signature<'_>| {
+        format!(
+            "{} <{}>",
+            signature.name_bytes().to_str_lossy(),
+            signature.email_bytes().to_str_lossy()
+        )
+    };
+    default_trailers
+        .iter()
+        .filter_map(|trailer| {
+            let value = match &trailer.value {
+                DefaultTrailerValue::Fixed(value) => value.clone(),
+                DefaultTrailerValue::Author => signature(author),
+                DefaultTrailerValue::Committer => signature(committer),
+                DefaultTrailerValue::ChangeId => {
+                    if change_id_from_message(BStr::new(message)).is_some() {
+                        return None;
+                    }
+                    CommitHeadersV2::default().change_id
+                }
+            };
+            Some((trailer.key.clone(), value))
+        })
+        .collect()
+}
+
 pub fn commit(
     ctx: &CommandContext,
     branch_id: StackId,
@@ -788,9 +2258,105 @@ pub fn commit(
     ownership: Option<&BranchOwnershipClaims>,
     run_hooks: bool,
 ) -> Result<git2::Oid> {
-    let mut message_buffer = message.to_owned();
+    commit_with_options(
+        ctx,
+        branch_id,
+        message,
+        ownership,
+        &CommitOptions {
+            run_hooks,
+            ..Default::default()
+        },
+    )
+}
 
-    if run_hooks {
+pub fn commit_with_options(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    message: &str,
+    ownership: Option<&BranchOwnershipClaims>,
+    options: &CommitOptions,
+) -> Result<git2::Oid> {
+    let branch = ctx
+        .project()
+        .virtual_branches()
+        .try_branch_in_workspace(branch_id)?;
+    let skipped_hooks = branch
+        .as_ref()
+        .map(|branch| branch.skipped_hooks.clone())
+        .unwrap_or_default();
+    let run_hook = |hook: &str| options.run_hooks && !skipped_hooks.iter().any(|h| h == hook);
+    let mut message_buffer = if options.strip_scissors {
+        strip_scissors(message).to_owned()
+    } else {
+        message.to_owned()
+    };
+    // get the files to commit
+    let statuses = get_applied_status(ctx, None)
+        .context("failed to get status by branch")?
+        .branches;
+
+    if let Some(template_path) = &options.message_template {
+        let template = std::fs::read_to_string(ctx.project().path.join(template_path))
+            .with_context(|| {
+                format!(
+                    "failed to read message template {}",
+                    template_path.display()
+                )
+            })?;
+        let body = message_template_body(ctx, branch_id, &statuses, ownership, &template, options)?;
+        message_buffer = if message_buffer.trim().is_empty() {
+            body
+        } else {
+            format!("{}\n\n{}", message_buffer.trim_end(), body)
+        };
+    }
+    if let (Some(issue_key), Some(branch)) = (&ctx.project().commit_issue_key, &branch) {
+        message_buffer = prefix_issue_key(issue_key, &branch.name, &message_buffer)?;
+    }
+    let default_trailers = &ctx.project().default_commit_trailers;
+    if !default_trailers.is_empty() {
+        let (author, committer) = ctx
+            .repository()
+            .signatures()
+            .context("failed to get signatures")?;
+        let branch_author = branch
+            .as_ref()
+            .map(commit_author_signature)
+            .transpose()?
+            .flatten();
+        let trailers = default_trailer_values(
+            default_trailers,
+            &message_buffer,
+            options
+                .author
+                .as_ref()
+                .or(branch_author.as_ref())
+                .unwrap_or(&author),
+            options.committer.as_ref().unwrap_or(&committer),
+        );
+        message_buffer = trailers::add_trailers(&message_buffer, &trailers);
+    }
+
+    if run_hook(git2_hooks::HOOK_PREPARE_COMMIT_MSG) {
+        let hook_result = git2_hooks::hooks_prepare_commit_msg(
+            ctx.repository(),
+            Some(&["../.husky"]),
+            git2_hooks::PrepareCommitMsgSource::Message,
+            &mut message_buffer,
+        )
+        .context("failed to run hook")
+        .context(Code::CommitHookFailed)?;
+
+        if let HookResult::RunNotSuccessful { stdout, .. } = hook_result {
+            return Err(
+                anyhow!("prepare-commit-msg hook rejected: {}", stdout.trim())
+                    .context(Code::CommitHookFailed),
+            );
+        }
+    }
+
+    if run_hook(git2_hooks::HOOK_COMMIT_MSG) {
         let hook_result = git2_hooks::hooks_commit_msg(
             ctx.repository(),
             Some(&["../.husky"]),
@@ -803,7 +2369,9 @@ pub fn commit(
             return Err(anyhow!("commit-msg hook rejected: {}", stdout.trim())
                 .context(Code::CommitHookFailed));
         }
+    }
 
+    if run_hook(git2_hooks::HOOK_PRE_COMMIT) {
         let hook_result = git2_hooks::hooks_pre_commit(ctx.repository(), Some(&["../.husky"]))
             .context("failed to run hook")
             .context(Code::CommitHookFailed)?;
@@ -815,114 +2383,567 @@ pub fn commit(
         }
     }
 
+    if options.normalize_trailers {
+        message_buffer = trailers::normalize_trailers(&message_buffer);
+    }
     let message = &message_buffer;
 
-    // get the files to commit
-    let statuses = get_applied_status(ctx, None)
-        .context("failed to get status by branch")?
-        .branches;
-
     let (ref mut branch, files) = statuses
         .into_iter()
         .find(|(branch, _)| branch.id == branch_id)
         .with_context(|| format!("branch {branch_id} not found"))?;
 
+    if branch.local_only {
+        bail!(
+            "branch {} holds local-only changes and can't be committed to",
+            branch.name
+        );
+    }
+
+    if options.behind_upstream == BehindUpstream::Fail
+        && branch_upstream_integration::is_behind_upstream(ctx, branch)?
+    {
+        return Err(anyhow!(
+            "branch {} is behind its upstream, integrate the upstream commits first",
+            branch.name
+        )
+        .context(Code::CommitBranchBehindUpstream));
+    }
+
     update_conflict_markers(ctx, files.clone()).context(Code::CommitMergeConflictFailure)?;
 
-    ctx.assure_unconflicted()
-        .context(Code::CommitMergeConflictFailure)?;
+    // Only the files of this branch matter, other branches may remain conflicted while this one is committed to.
+    for file in &files {
+        if conflicts::is_conflicting(ctx, Some(&file.path))? {
+            return Err(anyhow!("{} has active conflicts", file.path.display()))
+                .context(Marker::ProjectConflict)
+                .context(Code::CommitMergeConflictFailure);
+        }
+    }
 
-    let tree_oid = if let Some(ownership) = ownership {
-        let files = files.into_iter().filter_map(|file| {
-            let hunks = file
-                .hunks
-                .into_iter()
-                .filter(|hunk| {
-                    let hunk: GitHunk = hunk.clone().into();
-                    ownership
-                        .claims
-                        .iter()
-                        .find(|f| f.file_path.eq(&file.path))
-                        .map_or(false, |f| {
-                            f.hunks.iter().any(|h| {
-                                h.start == hunk.new_start
-                                    && h.end == hunk.new_start + hunk.new_lines
+    let files: Vec<(PathBuf, Vec<VirtualBranchHunk>)> = if let Some(ownership) = ownership {
+        let mut claimed_files = Vec::new();
+        for file in files {
+            let Some(claims) = ownership
+                .claims
+                .iter()
+                .find(|f| f.file_path.eq(&file.path))
+                .map(|claim| &claim.hunks)
+            else {
+                continue;
+            };
+            let mut hunks = Vec::new();
+            for hunk in file.hunks {
+                let git_hunk: GitHunk = hunk.clone().into();
+                let hunk_end = git_hunk.new_start + git_hunk.new_lines;
+                let (filter, hunk) = match claims.iter().find(|h| {
+                    (h.start == git_hunk.new_start && h.end == hunk_end)
+                        // `path:0-0` commits untracked files as a whole
+                        || (h.is_whole_file()
+                            && git_hunk.change_type == gitbutler_diff::ChangeType::Added)
+                }) {
+                    Some(claimed) => (claimed.filter, Some(hunk)),
+                    // claims of lines within the hunk commit only the changes to these lines
+                    None => {
+                        let claimed: Vec<_> = claims
+                            .iter()
+                            .filter(|h| {
+                                !h.is_whole_file()
+                                    && git_hunk.new_start <= h.start
+                                    && h.end <= hunk_end
                             })
-                        })
-                })
-                .collect::<Vec<_>>();
-            if hunks.is_empty() {
-                None
+                            .collect();
+                        let lines: Vec<_> = claimed.iter().map(|h| h.start..=h.end).collect();
+                        // lines can only be restricted to additions or deletions all at once
+                        let filter = match claimed.iter().map(|h| h.filter).all_equal_value() {
+                            Ok(filter) => filter,
+                            Err(None) => None,
+                            Err(Some(_)) => {
+                                return Err(anyhow!(
+                                    "the lines claimed within hunk {}-{} of {} must all be restricted to the same kind of changes",
+                                    git_hunk.new_start,
+                                    hunk_end,
+                                    file.path.display()
+                                )
+                                .context(Code::Validation));
+                            }
+                        };
+                        (filter, hunk.select_lines(&lines))
+                    }
+                };
+                // the rest of a partially claimed hunk stays in the worktree
+                hunks.extend(hunk.and_then(|hunk| match filter {
+                    Some(filter) => hunk.filter_lines(filter),
+                    None => Some(hunk),
+                }));
+            }
+            if !hunks.is_empty() {
+                claimed_files.push((file.path, hunks));
+            }
+        }
+        claimed_files
+    } else {
+        files
+            .into_iter()
+            .map(|file| (file.path, file.hunks))
+            .collect()
+    };
+
+    // Active merge conflicts were ruled out above, so these markers were left behind by accident.
+    if !options.allow_conflict_markers {
+        if let Some((path, _)) = files
+            .iter()
+            .find(|(_, hunks)| hunks.iter().any(|hunk| hunk.conflict_markers))
+        {
+            return Err(
+                anyhow!("{} contains unresolved conflict markers", path.display())
+                    .context(Code::CommitUnresolvedConflictMarkers),
+            );
+        }
+    }
+
+    let committed_paths = files
+        .iter()
+        .map(|(path, _)| path.clone())
+        .collect::<Vec<_>>();
+    let tree_oid = gitbutler_diff::write::hunks_onto_commit(ctx, branch.head(), files)?;
+
+    let git_repository = ctx.repository();
+    let tree_oid = run_clean_filters(git_repository, tree_oid, &committed_paths)?;
+    let tree_oid = if options.clean_whitespace {
+        let base_tree_oid = git_repository.find_commit(branch.head())?.tree_id();
+        clean_whitespace(git_repository, base_tree_oid, tree_oid, &committed_paths)?
+    } else {
+        tree_oid
+    };
+
+    if options.require_final_newline {
+        ensure_final_newlines(git_repository, tree_oid, &committed_paths)?;
+    }
+
+    if let Some(max_size) = ctx
+        .project()
+        .max_commit_file_size
+        .filter(|_| !options.allow_large_files)
+    {
+        let tree = git_repository.find_tree(tree_oid)?;
+        for path in &committed_paths {
+            let Some(blob) = tree
+                .get_path(path)
+                .ok()
+                .and_then(|entry| git_repository.find_blob(entry.id()).ok())
+            else {
+                continue;
+            };
+            let size = blob.size() as u64;
+            if size > max_size {
+                return Err(anyhow!(
+                    "{} is {size} bytes large, which exceeds the maximum of {max_size} bytes",
+                    path.display()
+                )
+                .context(Code::CommitFileTooLarge));
+            }
+        }
+    }
+    let parent_commit = git_repository
+        .find_commit(branch.head())
+        .context(format!("failed to find commit {:?}", branch.head()))?;
+    let tree = git_repository
+        .find_tree(tree_oid)
+        .context(format!("failed to find tree {:?}", tree_oid))?;
+
+    // now write a commit, using a merge parent if it exists and all conflicts of the workspace are resolved.
+    let extra_merge_parent = if conflicts::is_conflicting(ctx, None)? {
+        None
+    } else {
+        conflicts::merge_parent(ctx)
+            .context("failed to get merge parent")
+            .context(Code::CommitMergeConflictFailure)?
+    };
+
+    let (author, committer) = git_repository
+        .signatures()
+        .context("failed to get signatures")?;
+    let branch_author = commit_author_signature(branch)?;
+    let author = options
+        .author
+        .as_ref()
+        .or(branch_author.as_ref())
+        .unwrap_or(&author);
+    let committer = options.committer.as_ref().unwrap_or(&committer);
+    let commit = |parents: &[&git2::Commit], commit_headers: Option<CommitHeadersV2>| {
+        git_repository
+            .commit_with_signature(
+                None,
+                author,
+                committer,
+                message,
+                &tree,
+                parents,
+                commit_headers,
+            )
+            .context("failed to commit")
+    };
+
+    let commit_oid = match extra_merge_parent {
+        Some(merge_parent) => {
+            let merge_parent = git_repository
+                .find_commit(merge_parent)
+                .context(format!("failed to find merge parent {:?}", merge_parent))?;
+            let commit_oid = match options.merge_resolution {
+                MergeResolution::Merge => commit(&[&parent_commit, &merge_parent], None)?,
+                MergeResolution::Linear => commit(
+                    &[&parent_commit],
+                    Some(CommitHeadersV2 {
+                        integrated_upstream: Some(merge_parent.id()),
+                        ..Default::default()
+                    }),
+                )?,
+            };
+            conflicts::clear(ctx)
+                .context("failed to clear conflicts")
+                .context(Code::CommitMergeConflictFailure)?;
+            commit_oid
+        }
+        None => commit(&[&parent_commit], None)?,
+    };
+
+    if run_hook(git2_hooks::HOOK_POST_COMMIT) {
+        git2_hooks::hooks_post_commit(ctx.repository(), Some(&["../.husky"]))
+            .context("failed to run hook")
+            .context(Code::CommitHookFailed)?;
+    }
+
+    let vb_state = ctx.project().virtual_branches();
+    branch.set_stack_head(ctx, commit_oid, Some(tree_oid))?;
+
+    crate::integration::update_workspace_commit(&vb_state, ctx)
+        .context("failed to update gitbutler workspace")?;
+
+    Ok(commit_oid)
+}
+
+/// Commit the hunks claimed by each ownership in `commits` with its message to its branch, in order,
+/// running the commit hooks for each of them if `run_hooks` is set.
+/// The claims must not overlap and must all refer to uncommitted changes of the branch they are
+/// committed to. If any of the commits fails, all branches are restored to their previous state
+/// so either all commits are created or none.
+///
+/// Return the ids of the new commits, in the order of `commits`.
+pub(crate) fn commit_split(
+    ctx: &CommandContext,
+    commits: &[(StackId, BranchOwnershipClaims, String)],
+    run_hooks: bool,
+) -> Result<Vec<git2::Oid>> {
+    if commits.is_empty() {
+        return Err(anyhow!("there is nothing to commit").context(Code::Validation));
+    }
+    for (idx, (_, ownership, _)) in commits.iter().enumerate() {
+        if ownership.claims.is_empty() {
+            return Err(anyhow!("commit {} claims no changes", idx + 1).context(Code::Validation));
+        }
+        for (_, other_ownership, _) in &commits[idx + 1..] {
+            for claim in &ownership.claims {
+                let overlaps = other_ownership.claims.iter().any(|other| {
+                    other.file_path == claim.file_path
+                        && (claim.hunks.is_empty()
+                            || other.hunks.is_empty()
+                            || claim.hunks.iter().any(|hunk| {
+                                other.hunks.iter().any(|other| hunks_overlap(hunk, other))
+                            }))
+                });
+                if overlaps {
+                    return Err(anyhow!(
+                        "{} is claimed by more than one commit",
+                        claim.file_path.display()
+                    )
+                    .context(Code::Validation));
+                }
+            }
+        }
+    }
+
+    let status = get_applied_status(ctx, None)?;
+    for (branch_id, ownership, _) in commits {
+        let files = status
+            .branches
+            .iter()
+            .find(|(branch, _)| branch.id == *branch_id)
+            .map(|(_, files)| files.as_slice())
+            .unwrap_or_default();
+        for claim in &ownership.claims {
+            let hunks = files
+                .iter()
+                .filter(|file| file.path == claim.file_path)
+                .flat_map(|file| &file.hunks)
+                .collect_vec();
+            let covered = !hunks.is_empty()
+                && claim.hunks.iter().all(|claimed| {
+                    hunks.iter().any(|hunk| {
+                        claimed.is_whole_file()
+                            || (claimed.start <= hunk.end && hunk.start <= claimed.end)
+                    })
+                });
+            if !covered {
+                return Err(anyhow!(
+                    "{} doesn't have all the claimed uncommitted changes on branch {branch_id}",
+                    claim.file_path.display()
+                )
+                .context(Code::Validation));
+            }
+        }
+    }
+
+    let vb_state = ctx.project().virtual_branches();
+    let original_branches = commits
+        .iter()
+        .map(|(branch_id, ..)| vb_state.get_branch_in_workspace(*branch_id))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut commit_ids = Vec::with_capacity(commits.len());
+    for (branch_id, ownership, message) in commits {
+        match commit(ctx, *branch_id, message, Some(ownership), run_hooks) {
+            Ok(commit_id) => commit_ids.push(commit_id),
+            Err(err) => {
+                for branch in original_branches {
+                    vb_state.set_branch(branch)?;
+                }
+                crate::integration::update_workspace_commit(&vb_state, ctx)
+                    .context("failed to update gitbutler workspace")?;
+                return Err(err.context(format!(
+                    "failed to commit to branch {branch_id}, no commits were created"
+                )));
+            }
+        }
+    }
+    Ok(commit_ids)
+}
+
+/// Return `true` if `a` and `b` claim some of the same changed lines.
+pub(crate) fn hunks_overlap(a: &Hunk, b: &Hunk) -> bool {
+    let complementary = matches!((a.filter, b.filter), (Some(a), Some(b)) if a != b);
+    !complementary
+        && (a.is_whole_file() || b.is_whole_file() || (a.start <= b.end && b.start <= a.end))
+}
+
+/// Strip trailing whitespace from the lines of the blobs at `paths` in `tree_id` that changed since `base_tree_id`,
+/// and add a missing final newline if the last line changed, returning the id of the tree with the cleaned blobs.
+/// Lines that weren't touched are left alone, so existing whitespace doesn't end up in the commit.
+///
+/// Binary files are skipped, as are files marked as `binary`, `-text` or `-whitespace` in `.gitattributes`.
+/// Setting `trim_trailing_whitespace` or `insert_final_newline` to `false` in `.editorconfig` turns off
+/// that part of the cleanup for the files it applies to.
+fn clean_whitespace(
+    repo: &git2::Repository,
+    base_tree_id: git2::Oid,
+    tree_id: git2::Oid,
+    paths: &[PathBuf],
+) -> Result<git2::Oid> {
+    let worktree_dir = repo.workdir().context("the repository has no worktree")?;
+    let base_tree = repo.find_tree(base_tree_id)?;
+    let tree = repo.find_tree(tree_id)?;
+    let mut builder = git2::build::TreeUpdateBuilder::new();
+    for path in paths {
+        let Ok(entry) = tree.get_path(path) else {
+            // the file was deleted
+            continue;
+        };
+        if entry.kind() != Some(git2::ObjectType::Blob) || entry.filemode() == 0o120000 {
+            continue;
+        }
+
+        let attr = |name: &str| -> Result<git2::AttrValue<'_>> {
+            Ok(git2::AttrValue::from_string(repo.get_attr(
+                path,
+                name,
+                git2::AttrCheckFlags::FILE_THEN_INDEX,
+            )?))
+        };
+        if attr("binary")? == git2::AttrValue::True
+            || attr("text")? == git2::AttrValue::False
+            || attr("whitespace")? == git2::AttrValue::False
+        {
+            continue;
+        }
+
+        let blob = repo.find_blob(entry.id())?;
+        if blob.is_binary() || blob.content().is_empty() {
+            continue;
+        }
+        let properties = crate::editorconfig::WhitespaceProperties::for_path(worktree_dir, path)?;
+        let trim_trailing_whitespace = properties.trim_trailing_whitespace != Some(false);
+        let insert_final_newline = properties.insert_final_newline != Some(false);
+
+        let base_blob = base_tree
+            .get_path(path)
+            .ok()
+            .and_then(|entry| repo.find_blob(entry.id()).ok());
+        let mut changed_lines = HashSet::new();
+        repo.diff_blobs(
+            base_blob.as_ref(),
+            None,
+            Some(&blob),
+            None,
+            Some(git2::DiffOptions::new().context_lines(0)),
+            None,
+            None,
+            None,
+            Some(&mut |_, _, line| {
+                if line.origin() == '+' {
+                    changed_lines.extend(line.new_lineno());
+                }
+                true
+            }),
+        )?;
+
+        let mut cleaned = BString::default();
+        for (line_number, line) in (1..).zip(blob.content().lines_with_terminator()) {
+            let changed = changed_lines.contains(&line_number);
+            let (content, terminator) = match line.strip_suffix(b"\r\n") {
+                Some(content) => (content, b"\r\n".as_slice()),
+                None => match line.strip_suffix(b"\n") {
+                    Some(content) => (content, b"\n".as_slice()),
+                    None if changed && insert_final_newline => (line, b"\n".as_slice()),
+                    None => (line, b"".as_slice()),
+                },
+            };
+            if changed && trim_trailing_whitespace {
+                cleaned.extend_from_slice(content.trim_end_with(|c| c == ' ' || c == '\t'));
             } else {
-                Some((file.path, hunks))
+                cleaned.extend_from_slice(content);
             }
-        });
-        gitbutler_diff::write::hunks_onto_commit(ctx, branch.head(), files)?
-    } else {
-        let files = files
-            .into_iter()
-            .map(|file| (file.path, file.hunks))
-            .collect::<Vec<(PathBuf, Vec<VirtualBranchHunk>)>>();
-        gitbutler_diff::write::hunks_onto_commit(ctx, branch.head(), files)?
-    };
+            cleaned.extend_from_slice(terminator);
+        }
 
-    let git_repository = ctx.repository();
-    let parent_commit = git_repository
-        .find_commit(branch.head())
-        .context(format!("failed to find commit {:?}", branch.head()))?;
-    let tree = git_repository
-        .find_tree(tree_oid)
-        .context(format!("failed to find tree {:?}", tree_oid))?;
+        if cleaned.as_slice() != blob.content() {
+            let filemode = if entry.filemode() & 0o111 != 0 {
+                git2::FileMode::BlobExecutable
+            } else {
+                git2::FileMode::Blob
+            };
+            builder.upsert(path, repo.blob(&cleaned)?, filemode);
+        }
+    }
+    Ok(builder.create_updated(repo, &tree)?)
+}
 
-    // now write a commit, using a merge parent if it exists
-    let extra_merge_parent = conflicts::merge_parent(ctx)
-        .context("failed to get merge parent")
-        .context(Code::CommitMergeConflictFailure)?;
+/// Fail if one of the non-empty blobs at `paths` in `tree_id` doesn't end with a newline.
+///
+/// Binary files are skipped, as are files marked as `binary` or `-text` in `.gitattributes`.
+fn ensure_final_newlines(
+    repo: &git2::Repository,
+    tree_id: git2::Oid,
+    paths: &[PathBuf],
+) -> Result<()> {
+    let tree = repo.find_tree(tree_id)?;
+    for path in paths {
+        let Ok(entry) = tree.get_path(path) else {
+            // the file was deleted
+            continue;
+        };
+        if entry.kind() != Some(git2::ObjectType::Blob) || entry.filemode() == 0o120000 {
+            continue;
+        }
 
-    let commit_oid = match extra_merge_parent {
-        Some(merge_parent) => {
-            let merge_parent = git_repository
-                .find_commit(merge_parent)
-                .context(format!("failed to find merge parent {:?}", merge_parent))?;
-            let commit_oid = ctx.commit(message, &tree, &[&parent_commit, &merge_parent], None)?;
-            conflicts::clear(ctx)
-                .context("failed to clear conflicts")
-                .context(Code::CommitMergeConflictFailure)?;
-            commit_oid
+        let attr = |name: &str| -> Result<git2::AttrValue<'_>> {
+            Ok(git2::AttrValue::from_string(repo.get_attr(
+                path,
+                name,
+                git2::AttrCheckFlags::FILE_THEN_INDEX,
+            )?))
+        };
+        if attr("binary")? == git2::AttrValue::True || attr("text")? == git2::AttrValue::False {
+            continue;
         }
-        None => ctx.commit(message, &tree, &[&parent_commit], None)?,
-    };
 
-    if run_hooks {
-        git2_hooks::hooks_post_commit(ctx.repository(), Some(&["../.husky"]))
-            .context("failed to run hook")
-            .context(Code::CommitHookFailed)?;
+        let blob = repo.find_blob(entry.id())?;
+        if blob.is_binary() || blob.content().is_empty() || blob.content().ends_with(b"\n") {
+            continue;
+        }
+        return Err(anyhow!("{} doesn't end with a newline", path.display())
+            .context(Code::CommitMissingFinalNewline));
     }
+    Ok(())
+}
 
-    let vb_state = ctx.project().virtual_branches();
-    branch.set_stack_head(ctx, commit_oid, Some(tree_oid))?;
+/// Pass the blobs at `paths` in `tree_id` through the `clean` command of the filter driver
+/// assigned to them with `filter=<driver>` in `.gitattributes`, like `git add` does, returning the
+/// id of the tree with the filtered blobs.
+///
+/// LFS is left to its own tooling. A driver without a `clean` command, or one that fails, leaves
+/// the blob as is unless the driver is marked as `required`. The worktree diff applies the same
+/// filters, so the committed files don't show up as changed afterwards.
+fn run_clean_filters(
+    repo: &git2::Repository,
+    tree_id: git2::Oid,
+    paths: &[PathBuf],
+) -> Result<git2::Oid> {
+    let tree = repo.find_tree(tree_id)?;
+    let mut builder = git2::build::TreeUpdateBuilder::new();
+    for path in paths {
+        let Ok(entry) = tree.get_path(path) else {
+            // the file was deleted
+            continue;
+        };
+        if entry.kind() != Some(git2::ObjectType::Blob) || entry.filemode() == 0o120000 {
+            continue;
+        }
+        let Some(filter) = CleanFilter::for_path(repo, path)? else {
+            continue;
+        };
 
-    crate::integration::update_workspace_commit(&vb_state, ctx)
-        .context("failed to update gitbutler workspace")?;
+        let blob = repo.find_blob(entry.id())?;
+        let cleaned = match filter.run(repo, path, blob.content()) {
+            Ok(cleaned) => cleaned,
+            Err(err) if filter.required => {
+                return Err(err.context(format!(
+                    "clean filter {} failed for {}",
+                    filter.driver,
+                    path.display()
+                )))
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "clean filter {} failed for {}: {err:#}",
+                    filter.driver,
+                    path.display()
+                );
+                continue;
+            }
+        };
 
-    Ok(commit_oid)
+        if cleaned != blob.content() {
+            let filemode = if entry.filemode() & 0o111 != 0 {
+                git2::FileMode::BlobExecutable
+            } else {
+                git2::FileMode::Blob
+            };
+            builder.upsert(path, repo.blob(&cleaned)?, filemode);
+        }
+    }
+    Ok(builder.create_updated(repo, &tree)?)
 }
 
 pub(crate) fn push(
     ctx: &CommandContext,
     branch_id: StackId,
-    with_force: bool,
+    force: ForceMode,
     askpass: Option<Option<StackId>>,
+    push_options: &[String],
+    interactivity: Interactivity,
 ) -> Result<PushResult> {
     let vb_state = ctx.project().virtual_branches();
 
     let default_target = vb_state.get_default_target()?;
-    let upstream_remote = match default_target.push_remote_name {
-        Some(remote) => remote.clone(),
-        None => default_target.branch.remote().to_owned(),
-    };
+    let upstream_remote = default_target.push_remote_name();
 
     let mut vbranch = vb_state.get_branch_in_workspace(branch_id)?;
+    if vbranch.local_only {
+        bail!(
+            "branch {} holds local-only changes and can't be pushed",
+            vbranch.name
+        );
+    }
     let remote_branch = if let Some(upstream_branch) = &vbranch.upstream {
         upstream_branch.clone()
     } else {
@@ -951,14 +2972,55 @@ pub(crate) fn push(
         ))
     };
 
-    ctx.push(vbranch.head(), &remote_branch, with_force, None, askpass)?;
+    if force == ForceMode::WithLease {
+        let lease = ensure_lease(ctx, &vbranch, &remote_branch, askpass, interactivity)?;
+        if let Err(err) = ctx.push_with_lease(
+            vbranch.head(),
+            &remote_branch,
+            lease,
+            askpass,
+            push_options,
+            interactivity,
+            None,
+        ) {
+            let Some(StaleLeaseError { expected }) = err.downcast_ref::<StaleLeaseError>() else {
+                return Err(err);
+            };
+            ctx.fetch_with_depth(
+                remote_branch.remote(),
+                askpass.map(|_| "modal".to_string()),
+                None,
+                interactivity,
+                None,
+            )?;
+            let actual = remote_branch_head(ctx, &remote_branch)?.unwrap_or_else(git2::Oid::zero);
+            return Err(stale_lease_error(*expected, actual));
+        }
+    } else {
+        ctx.push_with_options(
+            vbranch.head(),
+            &remote_branch,
+            force == ForceMode::Force,
+            None,
+            askpass,
+            push_options,
+            interactivity,
+            None,
+        )?;
+    }
 
     vbranch.upstream = Some(remote_branch.clone());
     vbranch.upstream_head = Some(vbranch.head());
     vb_state
         .set_branch(vbranch.clone())
         .context("failed to write target branch after push")?;
-    ctx.fetch(remote_branch.remote(), askpass.map(|_| "modal".to_string()))?;
+    ctx.fetch_with_depth(
+        remote_branch.remote(),
+        askpass.map(|_| "modal".to_string()),
+        None,
+        interactivity,
+        None,
+    )?;
 
     Ok(PushResult {
         remote: upstream_remote,
@@ -966,6 +3028,60 @@ pub(crate) fn push(
     })
 }
 
+/// Return the commit `remote_branch` has to be at on the remote for a [`ForceMode::WithLease`] push
+/// of `branch` to go through, or zero if it must not exist there.
+///
+/// If `branch` was pushed before, `remote_branch` is fetched first and this fails with
+/// [`PushError::StaleLease`] if it moved since, unless `branch` already contains its commits,
+/// as otherwise these would be lost.
+fn ensure_lease(
+    ctx: &CommandContext,
+    branch: &Stack,
+    remote_branch: &RemoteRefname,
+    askpass: Option<Option<StackId>>,
+    interactivity: Interactivity,
+) -> Result<git2::Oid> {
+    if branch.upstream_head.is_some() {
+        ctx.fetch_with_depth(
+            remote_branch.remote(),
+            askpass.map(|_| "modal".to_string()),
+            None,
+            interactivity,
+            None,
+        )?;
+    }
+    let Some(actual) = remote_branch_head(ctx, remote_branch)? else {
+        return Ok(git2::Oid::zero());
+    };
+    let Some(expected) = branch.upstream_head else {
+        return Ok(actual);
+    };
+    let repo = ctx.repository();
+    if actual == expected
+        || actual == branch.head()
+        || repo.graph_descendant_of(branch.head(), actual)?
+    {
+        return Ok(actual);
+    }
+    Err(stale_lease_error(expected, actual))
+}
+
+/// The commit `remote_branch` was at when it was last fetched, if it exists.
+fn remote_branch_head(
+    ctx: &CommandContext,
+    remote_branch: &RemoteRefname,
+) -> Result<Option<git2::Oid>> {
+    match ctx.repository().refname_to_id(&remote_branch.to_string()) {
+        Ok(head) => Ok(Some(head)),
+        Err(err) if err.code() == git2::ErrorCode::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn stale_lease_error(expected: git2::Oid, actual: git2::Oid) -> anyhow::Error {
+    anyhow::Error::from(PushError::StaleLease { expected, actual }).context(Code::Validation)
+}
+
 type MergeBaseCommitGraph<'repo, 'cache> = gix::revwalk::Graph<
     'repo,
     'cache,
@@ -978,6 +3094,7 @@ pub(crate) struct IsCommitIntegrated<'repo, 'cache, 'graph> {
     target_commit_id: gix::ObjectId,
     upstream_tree_id: gix::ObjectId,
     upstream_commits: Vec<git2::Oid>,
+    is_shallow: bool,
 }
 
 impl<'repo, 'cache, 'graph> IsCommitIntegrated<'repo, 'cache, 'graph> {
@@ -1003,41 +3120,63 @@ impl<'repo, 'cache, 'graph> IsCommitIntegrated<'repo, 'cache, 'graph> {
             target_commit_id: git2_to_gix_object_id(target.sha),
             upstream_tree_id: git2_to_gix_object_id(upstream_tree_id),
             upstream_commits,
+            is_shallow: gix_repo.is_shallow(),
         })
     }
 }
 
 impl IsCommitIntegrated<'_, '_, '_> {
+    /// Like [`Self::integration_state()`], but consider commits with unknown integration state as not integrated.
     pub(crate) fn is_integrated(&mut self, commit: &git2::Commit) -> Result<bool> {
+        Ok(self.integration_state(commit)?.unwrap_or(false))
+    }
+
+    /// Return `Some(true)` if `commit` is integrated into the target, or `None` if that can't be determined
+    /// because the history of a shallow repository doesn't reach the merge-base.
+    pub(crate) fn integration_state(&mut self, commit: &git2::Commit) -> Result<Option<bool>> {
         if self.target_commit_id == git2_to_gix_object_id(commit.id()) {
             // could not be integrated if heads are the same.
-            return Ok(false);
+            return Ok(Some(false));
         }
 
         if self.upstream_commits.is_empty() {
             // could not be integrated - there is nothing new upstream.
-            return Ok(false);
+            return Ok(Some(false));
         }
 
         if self.upstream_commits.binary_search(&commit.id()).is_ok() {
-            return Ok(true);
+            return Ok(Some(true));
         }
 
-        let merge_base_id = self.gix_repo.merge_base_with_graph(
+        // A linear merge resolution is based on the upstream commit it integrated, not on its parent.
+        let based_on = commit
+            .gitbutler_headers()
+            .and_then(|headers| headers.integrated_upstream)
+            .unwrap_or(commit.id());
+        let merge_base_id = match self.gix_repo.merge_base_with_graph(
             self.target_commit_id,
-            git2_to_gix_object_id(commit.id()),
+            git2_to_gix_object_id(based_on),
             self.graph,
-        )?;
-        if gix_to_git2_oid(merge_base_id).eq(&commit.id()) {
+        ) {
+            Ok(id) => id,
+            // Without a merge-base in the available history, it might be beyond the shallow boundary.
+            Err(gix::repository::merge_base_with_graph::Error::NotFound { .. })
+                if self.is_shallow =>
+            {
+                return Ok(None)
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if gix_to_git2_oid(merge_base_id).eq(&based_on) {
             // if merge branch is the same as branch head and there are upstream commits
             // then it's integrated
-            return Ok(true);
+            return Ok(Some(true));
         }
 
         let merge_base_tree_id = self.gix_repo.find_commit(merge_base_id)?.tree_id()?;
         if merge_base_tree_id == self.upstream_tree_id {
             // if merge base is the same as upstream tree, then it's integrated
-            return Ok(true);
+            return Ok(Some(true));
         }
 
         // try to merge our tree into the upstream tree
@@ -1054,15 +3193,242 @@ impl IsCommitIntegrated<'_, '_, '_> {
             .context("failed to merge trees")?;
 
         if merge_output.has_unresolved_conflicts(conflict_kind) {
-            return Ok(false);
+            return Ok(Some(false));
         }
 
         let merge_tree_id = merge_output.tree.write()?.detach();
 
         // if the merge_tree is the same as the new_target_tree and there are no files (uncommitted changes)
         // then the vbranch is fully merged
-        Ok(merge_tree_id == self.upstream_tree_id)
+        Ok(Some(merge_tree_id == self.upstream_tree_id))
+    }
+}
+
+/// Drop the commits of the branch with `branch_id` that introduce the same changes, by patch-id, as a commit
+/// of the remote target branch that isn't part of the branch, like the ones left behind after the branch was
+/// squash-merged upstream. The remaining commits are rebased onto the merge-base with the remote target branch.
+///
+/// Return the ids of the dropped commits, newest first.
+pub(crate) fn dedupe_integrated_commits(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    perm: &mut WorktreeWritePermission,
+) -> Result<Vec<git2::Oid>> {
+    let repo = ctx.repository();
+    let vb_state = ctx.project().virtual_branches();
+    let default_target = vb_state.get_default_target()?;
+    let mut branch = vb_state.get_branch_in_workspace(branch_id)?;
+
+    let remote_head = repo
+        .find_reference(&default_target.branch.to_string())?
+        .peel_to_commit()?
+        .id();
+    let merge_base = repo.merge_base(remote_head, branch.head())?;
+
+    let upstream_patch_ids = repo
+        .l(remote_head, LogUntil::Commit(merge_base), false)?
+        .into_iter()
+        .filter_map(|id| patch_id(repo, id).transpose())
+        .collect::<Result<HashSet<_>>>()?;
+    if upstream_patch_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let branch_commits = repo.l(branch.head(), LogUntil::Commit(merge_base), false)?;
+    let mut dropped = Vec::new();
+    let mut kept = Vec::new();
+    for id in &branch_commits {
+        match patch_id(repo, *id)? {
+            Some(patch_id) if upstream_patch_ids.contains(&patch_id) => dropped.push(*id),
+            _ => kept.push(*id),
+        }
+    }
+    if dropped.is_empty() {
+        return Ok(dropped);
+    }
+
+    // rebase the kept commits one at a time to know where each of them ends up
+    let mut rebased = HashMap::new();
+    let mut new_head = merge_base;
+    for id in kept.iter().rev() {
+        new_head = cherry_rebase_group(repo, new_head, &[*id])?;
+        rebased.insert(*id, new_head);
+    }
+
+    // the heads of the series below the top one move along with their commit, or to the
+    // nearest kept ancestor of a dropped commit, or to the merge-base if there is none
+    let old_head = branch.head();
+    let below_top = branch.heads.len().saturating_sub(1);
+    for series_head in branch
+        .heads
+        .iter_mut()
+        .take(below_top)
+        .filter(|series_head| !series_head.archived)
+    {
+        let Ok(target) =
+            commit_by_oid_or_change_id(&series_head.target, repo, old_head, merge_base)
+        else {
+            continue;
+        };
+        let new_target = branch_commits
+            .iter()
+            .skip_while(|id| **id != target.head.id())
+            .find_map(|id| rebased.get(id).copied())
+            .unwrap_or(merge_base);
+        series_head.target = repo.find_commit(new_target)?.into();
+    }
+
+    let crate::branch_trees::BranchHeadAndTree { head, tree } =
+        crate::branch_trees::compute_updated_branch_head(repo, &branch, new_head)?;
+    branch.set_stack_head(ctx, head, Some(tree))?;
+
+    crate::branch_trees::checkout_branch_trees(ctx, perm)?;
+    crate::integration::update_workspace_commit(&vb_state, ctx)
+        .context("failed to update gitbutler workspace")?;
+
+    Ok(dropped)
+}
+
+/// Return the patch-id of the changes `commit_id` makes to its parent, or `None` if it has none,
+/// is a merge or is conflicted.
+fn patch_id(repo: &git2::Repository, commit_id: git2::Oid) -> Result<Option<git2::Oid>> {
+    let commit = repo.find_commit(commit_id)?;
+    if commit.parent_count() != 1 || commit.is_conflicted() {
+        return Ok(None);
+    }
+    let diff = repo.diff_tree_to_tree(
+        Some(&commit.parent(0)?.tree()?),
+        Some(&commit.tree()?),
+        None,
+    )?;
+    if diff.deltas().len() == 0 {
+        return Ok(None);
+    }
+    Ok(Some(diff.patchid(None)?))
+}
+
+/// Compute the changes a pull request of `branch_id` against the target branch would show.
+///
+/// This is a *three-dot* diff, `target...branch`: the branch head is compared to the merge-base
+/// of the branch and the current tip of the target branch, so only changes made on the branch are listed.
+/// A *two-dot* diff, `target..branch`, compares the tip of the target branch to the branch head directly,
+/// so it would also show everything that was merged into the target since, in reverse.
+///
+/// Branches without commits have no changes.
+pub fn pr_diff(ctx: &CommandContext, branch_id: StackId) -> Result<Vec<RemoteBranchFile>> {
+    let vb_state = ctx.project().virtual_branches();
+    let default_target = vb_state.get_default_target()?;
+    let branch = vb_state.get_branch_in_workspace(branch_id)?;
+    let repo = ctx.repository();
+
+    let target_head = repo
+        .find_reference(&default_target.branch.to_string())
+        .and_then(|reference| reference.peel_to_commit())
+        .map_or(default_target.sha, |commit| commit.id());
+    let merge_base = repo
+        .merge_base(target_head, branch.head())
+        .context("failed to find merge base")?;
+    if merge_base == branch.head() {
+        return Ok(vec![]);
+    }
+
+    let old_tree = repo.find_real_tree(&repo.find_commit(merge_base)?, Default::default())?;
+    let new_tree = repo.find_real_tree(&repo.find_commit(branch.head())?, Default::default())?;
+    let diff = trees(repo, &old_tree, &new_tree, true)?;
+    Ok(diff.into_values().map(Into::into).collect())
+}
+
+/// Lists the changes between the commits `from` and `to` of `branch_id`, where `from` has to be
+/// an ancestor of `to` and both are part of the branch, or its merge base with the target branch.
+pub fn range_diff(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    from: git2::Oid,
+    to: git2::Oid,
+) -> Result<Vec<RemoteBranchFile>> {
+    let vb_state = ctx.project().virtual_branches();
+    let default_target = vb_state.get_default_target()?;
+    let branch = vb_state.get_branch(branch_id)?;
+    let repo = ctx.repository();
+
+    let merge_base = repo
+        .merge_base(default_target.sha, branch.head())
+        .context("failed to find merge base")?;
+    let branch_commits = repo.l(branch.head(), LogUntil::Commit(merge_base), false)?;
+    for oid in [from, to] {
+        if oid != merge_base && !branch_commits.contains(&oid) {
+            bail!("commit {oid} is not part of branch {}", branch.name);
+        }
+    }
+    if from != to && !repo.graph_descendant_of(to, from)? {
+        bail!("commit {from} is not an ancestor of commit {to}");
     }
+
+    let old_tree = repo.find_real_tree(&repo.find_commit(from)?, Default::default())?;
+    let new_tree = repo.find_real_tree(&repo.find_commit(to)?, Default::default())?;
+    let diff = trees(repo, &old_tree, &new_tree, true)?;
+    Ok(diff.into_values().map(Into::into).collect())
+}
+
+/// Compute the combined changes of the whole workspace against the target, i.e. the commits of all
+/// applied branches along with all uncommitted changes.
+///
+/// The worktree is compared to the target commit directly, so files touched by multiple branches
+/// are listed once, with the changes of all branches combined.
+pub fn workspace_diff(
+    ctx: &CommandContext,
+    _perm: &WorktreeReadPermission,
+) -> Result<Vec<RemoteBranchFile>> {
+    Ok(workspace_diff_raw(ctx)?
+        .into_values()
+        .map(Into::into)
+        .collect())
+}
+
+fn workspace_diff_raw(ctx: &CommandContext) -> Result<gitbutler_diff::DiffByPathMap> {
+    let default_target = ctx.project().virtual_branches().get_default_target()?;
+    gitbutler_diff::workdir(ctx.repository(), default_target.sha)
+        .context("failed to diff the workspace against the target")
+}
+
+/// Return the id of the tree of the target commit.
+pub fn target_tree(ctx: &CommandContext) -> Result<git2::Oid> {
+    let default_target = ctx.project().virtual_branches().get_default_target()?;
+    Ok(ctx.repository().find_commit(default_target.sha)?.tree_id())
+}
+
+/// Return the id of the tree of the workspace commit, i.e. the target with all applied branches merged in,
+/// without their uncommitted changes.
+pub fn workspace_tree(ctx: &CommandContext) -> Result<git2::Oid> {
+    let workspace_head = get_workspace_head(ctx)?;
+    Ok(ctx.repository().find_commit(workspace_head)?.tree_id())
+}
+
+/// The size of the combined changes of the workspace, as returned by [`workspace_diff_stats()`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceDiffStats {
+    /// The number of changed files, including binary files.
+    pub files: usize,
+    /// The number of added lines, not counting binary files.
+    pub lines_added: usize,
+    /// The number of removed lines, not counting binary files.
+    pub lines_removed: usize,
+}
+
+/// Summarize the [combined changes of the workspace](workspace_diff()) like `git diff --shortstat` would.
+pub fn workspace_diff_stats(
+    ctx: &CommandContext,
+    _perm: &WorktreeReadPermission,
+) -> Result<WorkspaceDiffStats> {
+    let default_target = ctx.project().virtual_branches().get_default_target()?;
+    let stats = gitbutler_diff::workdir_stats(ctx.repository(), default_target.sha)
+        .context("failed to diff the workspace against the target")?;
+    Ok(WorkspaceDiffStats {
+        files: stats.files_changed(),
+        lines_added: stats.insertions(),
+        lines_removed: stats.deletions(),
+    })
 }
 
 pub fn is_remote_branch_mergeable(
@@ -1108,6 +3474,31 @@ pub fn is_remote_branch_mergeable(
     Ok(mergeable)
 }
 
+/// Fail with [`Code::Validation`] unless all `commit_ids` are commits of the branch `branch_id`, excluding its base,
+/// as changes can't be moved from or to commits of other branches without rewriting their history.
+pub(crate) fn ensure_commits_on_branch(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    commit_ids: &[git2::Oid],
+) -> Result<()> {
+    let vb_state = ctx.project().virtual_branches();
+    let branch = vb_state.get_branch_in_workspace(branch_id)?;
+    let default_target = vb_state.get_default_target()?;
+    let branch_commit_oids =
+        ctx.repository()
+            .l(branch.head(), LogUntil::Commit(default_target.sha), false)?;
+    for commit_id in commit_ids {
+        if !branch_commit_oids.contains(commit_id) {
+            return Err(anyhow!(
+                "commit {commit_id} is not a commit of branch {}",
+                branch.name
+            )
+            .context(Code::Validation));
+        }
+    }
+    Ok(())
+}
+
 // this function takes a list of file ownership from a "from" commit and "moves"
 // those changes to a "to" commit in a branch. This allows users to drag changes
 // from one commit to another.
@@ -1342,7 +3733,91 @@ pub(crate) fn move_commit_file(
     let last_commit = upstream_commits.first().cloned().unwrap();
     let new_head = cherry_rebase(ctx, commit_oid, amend_commit.id(), last_commit)?;
 
-    // if that rebase worked, update the branch head and the gitbutler workspace
+    // if that rebase worked, update the branch head and the gitbutler workspace
+    if let Some(new_head) = new_head {
+        target_branch.set_stack_head(ctx, new_head, None)?;
+        crate::integration::update_workspace_commit(&vb_state, ctx)?;
+        Ok(commit_oid)
+    } else {
+        Err(anyhow!("rebase failed"))
+    }
+}
+
+// takes a list of file ownership and a commit oid and rewrites that commit to
+// add the file changes. The branch is then rebased onto the new commit
+// and the respective branch head is updated.
+// With `reset_author`, the current user becomes the author of the commit, as of now.
+pub(crate) fn amend(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    commit_oid: git2::Oid,
+    target_ownership: &BranchOwnershipClaims,
+    reset_author: bool,
+    _perm: &mut WorktreeWritePermission,
+) -> Result<git2::Oid> {
+    let applied_statuses = get_applied_status(ctx, None)?.branches;
+    let (mut target_branch, new_tree_oid) = amended_commit_tree(
+        ctx,
+        branch_id,
+        commit_oid,
+        target_ownership,
+        applied_statuses,
+    )?;
+    let vb_state = ctx.project().virtual_branches();
+    let amend_commit = ctx
+        .repository()
+        .find_commit(commit_oid)
+        .context("failed to find commit")?;
+    let new_tree = ctx
+        .repository()
+        .find_tree(new_tree_oid)
+        .context("failed to find new tree")?;
+
+    let author = if reset_author {
+        match commit_author_signature(&target_branch)? {
+            Some(author) => author,
+            None => {
+                let (author, _committer) = ctx
+                    .repository()
+                    .signatures()
+                    .context("failed to get signatures")?;
+                author
+            }
+        }
+    } else {
+        amend_commit.author()
+    };
+    let parents: Vec<_> = amend_commit.parents().collect();
+    let commit_oid = ctx
+        .repository()
+        .commit_with_signature(
+            None,
+            &author,
+            &amend_commit.committer(),
+            &amend_commit.message_bstr().to_str_lossy(),
+            &new_tree,
+            &parents.iter().collect::<Vec<_>>(),
+            amend_commit.gitbutler_headers(),
+        )
+        .context("failed to create commit")?;
+
+    // now rebase upstream commits, if needed
+    let upstream_commits = ctx.repository().l(
+        target_branch.head(),
+        LogUntil::Commit(amend_commit.id()),
+        false,
+    )?;
+    // if there are no upstream commits, we're done
+    if upstream_commits.is_empty() {
+        target_branch.set_stack_head(ctx, commit_oid, None)?;
+        crate::integration::update_workspace_commit(&vb_state, ctx)?;
+        return Ok(commit_oid);
+    }
+
+    let last_commit = upstream_commits.first().cloned().unwrap();
+
+    let new_head = cherry_rebase(ctx, commit_oid, amend_commit.id(), last_commit)?;
+
     if let Some(new_head) = new_head {
         target_branch.set_stack_head(ctx, new_head, None)?;
         crate::integration::update_workspace_commit(&vb_state, ctx)?;
@@ -1352,16 +3827,82 @@ pub(crate) fn move_commit_file(
     }
 }
 
-// takes a list of file ownership and a commit oid and rewrites that commit to
-// add the file changes. The branch is then rebased onto the new commit
-// and the respective branch head is updated
-pub(crate) fn amend(
+/// What amending a commit would result in, as computed by [`amend_dry_run()`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AmendPreview {
+    /// The files changed by the amended commit compared to its parent.
+    pub files: Vec<RemoteBranchFile>,
+    /// The paths that would be conflicted in the commits above the amended commit once they are rebased onto it.
+    pub conflicting_paths: Vec<PathBuf>,
+}
+
+/// Compute what [`amend()`] would do without changing the branch: the files of the amended commit,
+/// and the conflicts of rebasing the commits above it, which is done in memory.
+pub(crate) fn amend_dry_run(
     ctx: &CommandContext,
     branch_id: StackId,
     commit_oid: git2::Oid,
     target_ownership: &BranchOwnershipClaims,
-    _perm: &mut WorktreeWritePermission,
-) -> Result<git2::Oid> {
+    perm: &WorktreeReadPermission,
+) -> Result<AmendPreview> {
+    let applied_statuses = get_applied_status_read_only(ctx, None, perm)?.branches;
+    let (target_branch, new_tree_oid) = amended_commit_tree(
+        ctx,
+        branch_id,
+        commit_oid,
+        target_ownership,
+        applied_statuses,
+    )?;
+    let repo = ctx.repository().in_memory_repo()?;
+    let amend_commit = repo.find_commit(commit_oid)?;
+    let new_tree = repo.find_tree(new_tree_oid)?;
+
+    let parent_tree = repo.find_real_tree(&amend_commit.parent(0)?, Default::default())?;
+    let files = gitbutler_diff::trees(&repo, &parent_tree, &new_tree, true)?
+        .into_values()
+        .map(Into::into)
+        .collect();
+
+    let descendants = repo.l(
+        target_branch.head(),
+        LogUntil::Commit(amend_commit.id()),
+        false,
+    )?;
+    let mut conflicting_paths = Vec::new();
+    if !descendants.is_empty() {
+        let parents: Vec<_> = amend_commit.parents().collect();
+        let amended_commit_oid = repo.commit(
+            None,
+            &amend_commit.author(),
+            &amend_commit.committer(),
+            &amend_commit.message_bstr().to_str_lossy(),
+            &new_tree,
+            &parents.iter().collect::<Vec<_>>(),
+        )?;
+        let new_head = cherry_rebase_group(&repo, amended_commit_oid, &descendants)?;
+        for commit in repo.log(new_head, LogUntil::Commit(amended_commit_oid), false)? {
+            conflicting_paths.extend(crate::commit::conflict_entries(&repo, &commit)?.paths());
+        }
+        conflicting_paths.sort();
+        conflicting_paths.dedup();
+    }
+
+    Ok(AmendPreview {
+        files,
+        conflicting_paths,
+    })
+}
+
+/// Check that `commit_oid` in `branch_id` can be amended with the hunks claimed by `target_ownership`
+/// in `applied_statuses`, and return the branch along with the tree the amended commit would have.
+fn amended_commit_tree(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    commit_oid: git2::Oid,
+    target_ownership: &BranchOwnershipClaims,
+    mut applied_statuses: Vec<(Stack, Vec<VirtualBranchFile>)>,
+) -> Result<(Stack, git2::Oid)> {
     ctx.assure_resolved()?;
     let vb_state = ctx.project().virtual_branches();
 
@@ -1375,9 +3916,7 @@ pub(crate) fn amend(
 
     let default_target = vb_state.get_default_target()?;
 
-    let mut applied_statuses = get_applied_status(ctx, None)?.branches;
-
-    let (ref mut target_branch, target_status) = applied_statuses
+    let (target_branch, target_status) = applied_statuses
         .iter_mut()
         .find(|(b, _)| b.id == branch_id)
         .ok_or_else(|| anyhow!("could not find branch {branch_id} in status list"))?;
@@ -1400,8 +3939,7 @@ pub(crate) fn amend(
     }
 
     // find commit oid
-    let amend_commit = ctx
-        .repository()
+    ctx.repository()
         .find_commit(commit_oid)
         .context("failed to find commit")?;
 
@@ -1439,49 +3977,9 @@ pub(crate) fn amend(
 
     // apply diffs_to_amend to the commit tree
     let new_tree_oid = gitbutler_diff::write::hunks_onto_commit(ctx, commit_oid, &diffs_to_amend)?;
-    let new_tree = ctx
-        .repository()
-        .find_tree(new_tree_oid)
-        .context("failed to find new tree")?;
-
-    let parents: Vec<_> = amend_commit.parents().collect();
-    let commit_oid = ctx
-        .repository()
-        .commit_with_signature(
-            None,
-            &amend_commit.author(),
-            &amend_commit.committer(),
-            &amend_commit.message_bstr().to_str_lossy(),
-            &new_tree,
-            &parents.iter().collect::<Vec<_>>(),
-            amend_commit.gitbutler_headers(),
-        )
-        .context("failed to create commit")?;
-
-    // now rebase upstream commits, if needed
-    let upstream_commits = ctx.repository().l(
-        target_branch.head(),
-        LogUntil::Commit(amend_commit.id()),
-        false,
-    )?;
-    // if there are no upstream commits, we're done
-    if upstream_commits.is_empty() {
-        target_branch.set_stack_head(ctx, commit_oid, None)?;
-        crate::integration::update_workspace_commit(&vb_state, ctx)?;
-        return Ok(commit_oid);
-    }
-
-    let last_commit = upstream_commits.first().cloned().unwrap();
-
-    let new_head = cherry_rebase(ctx, commit_oid, amend_commit.id(), last_commit)?;
-
-    if let Some(new_head) = new_head {
-        target_branch.set_stack_head(ctx, new_head, None)?;
-        crate::integration::update_workspace_commit(&vb_state, ctx)?;
-        Ok(commit_oid)
-    } else {
-        Err(anyhow!("rebase failed"))
-    }
+    let amended_paths = diffs_to_amend.keys().cloned().collect::<Vec<_>>();
+    let new_tree_oid = run_clean_filters(ctx.repository(), new_tree_oid, &amended_paths)?;
+    Ok((target_branch.clone(), new_tree_oid))
 }
 
 // create and insert a blank commit (no tree change) either above or below a commit
@@ -1564,6 +4062,10 @@ pub(crate) fn squash(ctx: &CommandContext, branch_id: StackId, commit_id: git2::
         bail!("Can not squash conflicted commits");
     }
 
+    if is_pinned(&branch, &commit_to_squash) || is_pinned(&branch, &parent_commit) {
+        bail!("can not squash pinned commits");
+    }
+
     let pushed_commit_oids = branch.upstream_head.map_or_else(
         || Ok(vec![]),
         |upstream_head| {
@@ -1586,12 +4088,13 @@ pub(crate) fn squash(ctx: &CommandContext, branch_id: StackId, commit_id: git2::
     //  * has the message combined of the target commit and parent commit
     //  * has parents of the parents commit.
     let parents: Vec<_> = parent_commit.parents().collect();
+    let author = commit_author_signature(&branch)?.unwrap_or_else(|| commit_to_squash.author());
 
     let new_commit_oid = ctx
         .repository()
         .commit_with_signature(
             None,
-            &commit_to_squash.author(),
+            &author,
             &commit_to_squash.committer(),
             &format!(
                 "{}\n{}",
@@ -1605,6 +4108,13 @@ pub(crate) fn squash(ctx: &CommandContext, branch_id: StackId, commit_id: git2::
         )
         .context("failed to commit")?;
 
+    record_squashed_commits(
+        ctx.repository(),
+        &mut branch,
+        new_commit_oid,
+        &[&parent_commit, &commit_to_squash],
+    )?;
+
     let ids_to_rebase = {
         let ids = branch_commit_oids
             .split(|oid| oid.eq(&commit_id))
@@ -1627,16 +4137,385 @@ pub(crate) fn squash(ctx: &CommandContext, branch_id: StackId, commit_id: git2::
     }
 }
 
+/// Squashes the contiguous range of commits between `from_commit_id` and `to_commit_id` (inclusive,
+/// in either order) into the oldest of them, joining their messages with blank lines.
+pub(crate) fn squash_range(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    from_commit_id: git2::Oid,
+    to_commit_id: git2::Oid,
+) -> Result<()> {
+    ctx.assure_resolved()?;
+
+    let vb_state = ctx.project().virtual_branches();
+    let mut branch = vb_state.get_branch_in_workspace(branch_id)?;
+    let default_target = vb_state.get_default_target()?;
+    let repo = ctx.repository();
+    let branch_commit_oids = repo.l(branch.head(), LogUntil::Commit(default_target.sha), false)?;
+
+    let position = |commit_id: git2::Oid| {
+        branch_commit_oids
+            .iter()
+            .position(|oid| *oid == commit_id)
+            .with_context(|| format!("commit {commit_id} not in the branch"))
+    };
+    let (from_idx, to_idx) = (position(from_commit_id)?, position(to_commit_id)?);
+    // `branch_commit_oids` is ordered from the head down to the base.
+    let (newest_idx, oldest_idx) = (from_idx.min(to_idx), from_idx.max(to_idx));
+    if newest_idx == oldest_idx {
+        bail!("a squash range needs at least two commits");
+    }
+
+    let commits = branch_commit_oids[newest_idx..=oldest_idx]
+        .iter()
+        .rev()
+        .map(|oid| repo.find_commit(*oid))
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to find commits to squash")?;
+
+    for pair in commits.windows(2) {
+        if pair[1].parent_count() != 1 || pair[1].parent_id(0)? != pair[0].id() {
+            bail!("can not squash non-contiguous commits");
+        }
+    }
+    if commits
+        .iter()
+        .any(|commit| commit.is_conflicted() || is_pinned(&branch, commit))
+    {
+        bail!("can not squash conflicted or pinned commits");
+    }
+
+    let pushed_commit_oids = branch.upstream_head.map_or_else(
+        || Ok(vec![]),
+        |upstream_head| repo.l(upstream_head, LogUntil::Commit(default_target.sha), false),
+    )?;
+    if !branch.allow_rebasing && commits.iter().any(|c| pushed_commit_oids.contains(&c.id())) {
+        // rewriting a pushed commit will cause a force push that is not allowed
+        bail!("force push not allowed");
+    }
+
+    let oldest = &commits[0];
+    let newest = &commits[commits.len() - 1];
+    let message = commits
+        .iter()
+        .map(|commit| commit.message_bstr().to_str_lossy().trim_end().to_owned())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let parents: Vec<_> = oldest.parents().collect();
+    let author = commit_author_signature(&branch)?.unwrap_or_else(|| oldest.author());
+
+    let new_commit_oid = repo
+        .commit_with_signature(
+            None,
+            &author,
+            &oldest.committer(),
+            &message,
+            &newest.tree().context("failed to find tree")?,
+            &parents.iter().collect::<Vec<_>>(),
+            oldest.gitbutler_headers(),
+        )
+        .context("failed to commit")?;
+
+    record_squashed_commits(
+        repo,
+        &mut branch,
+        new_commit_oid,
+        &commits.iter().collect::<Vec<_>>(),
+    )?;
+
+    let ids_to_rebase = branch_commit_oids[..newest_idx].to_vec();
+    match cherry_rebase_group(repo, new_commit_oid, &ids_to_rebase) {
+        Ok(new_head_id) => {
+            branch.set_stack_head(ctx, new_head_id, None)?;
+
+            crate::integration::update_workspace_commit(&vb_state, ctx)
+                .context("failed to update gitbutler workspace")?;
+            Ok(())
+        }
+        Err(err) => Err(err.context("rebase error").context(Code::Unknown)),
+    }
+}
+
+/// Returns the signature of the identity commits to `branch` are authored with, unless it's the repository's.
+fn commit_author_signature(branch: &Stack) -> Result<Option<git2::Signature<'static>>> {
+    branch
+        .commit_author
+        .as_ref()
+        .map(|author| author.signature())
+        .transpose()
+        .context("invalid commit author of branch")
+}
+
+/// Returns `true` if `commit` is one of the [pinned commits](Stack::pinned_commits) of `branch`.
+pub(crate) fn is_pinned(branch: &Stack, commit: &git2::Commit<'_>) -> bool {
+    commit
+        .change_id()
+        .is_some_and(|change_id| branch.pinned_commits.contains(&change_id))
+}
+
+/// Pin `commit_oid` in `branch_id` so it keeps its position in the branch, or unpin it if `pinned` is `false`.
+/// Pins are kept by change-id so they survive the commit being rewritten, which is why commits without one
+/// can't be pinned.
+pub(crate) fn set_commit_pinned(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    commit_oid: git2::Oid,
+    pinned: bool,
+) -> Result<()> {
+    let vb_state = ctx.project().virtual_branches();
+    let mut branch = vb_state.get_branch_in_workspace(branch_id)?;
+    let default_target = vb_state.get_default_target()?;
+    let repo = ctx.repository();
+    if !repo
+        .l(branch.head(), LogUntil::Commit(default_target.sha), false)?
+        .contains(&commit_oid)
+    {
+        bail!("commit {commit_oid} not in the branch")
+    }
+
+    let commit = repo.find_commit(commit_oid)?;
+    let Some(change_id) = commit.change_id() else {
+        return Err(anyhow!(
+            "commit {commit_oid} has no change-id to keep track of it when it's rewritten"
+        )
+        .context(Code::Validation));
+    };
+    if pinned {
+        if !branch.pinned_commits.contains(&change_id) {
+            branch.pinned_commits.push(change_id);
+        }
+    } else {
+        branch.pinned_commits.retain(|pinned| *pinned != change_id);
+    }
+    vb_state.set_branch(branch)?;
+    Ok(())
+}
+
+/// Remember which commits were squashed into `squashed_commit_oid`, so that [`unsquash`] can split it up again.
+/// Commits that were squashed themselves contribute the commits they were squashed from.
+/// Nothing is recorded if the squashed commit has no change-id.
+fn record_squashed_commits(
+    repo: &git2::Repository,
+    branch: &mut Stack,
+    squashed_commit_oid: git2::Oid,
+    commits: &[&git2::Commit<'_>],
+) -> Result<()> {
+    let Some(change_id) = repo.find_commit(squashed_commit_oid)?.change_id() else {
+        return Ok(());
+    };
+
+    let mut squashed_commits = Vec::new();
+    for commit in commits {
+        let previous_squash = commit.change_id().and_then(|change_id| {
+            branch
+                .squashed_commits
+                .iter()
+                .position(|squashed| squashed.change_id == change_id)
+        });
+        match previous_squash {
+            Some(idx) => {
+                let previous_squash = branch.squashed_commits.remove(idx);
+                release_squashed_commits(repo, &previous_squash)?;
+                squashed_commits.extend(previous_squash.commits);
+            }
+            None => squashed_commits.push(commit.id()),
+        }
+    }
+
+    if let Some(idx) = branch
+        .squashed_commits
+        .iter()
+        .position(|squashed| squashed.change_id == change_id)
+    {
+        release_squashed_commits(repo, &branch.squashed_commits.remove(idx))?;
+    }
+    for commit_id in &squashed_commits {
+        repo.reference(
+            &squashed_commit_refname(&change_id, *commit_id),
+            *commit_id,
+            true,
+            "keep squashed commit",
+        )
+        .context("failed to create reference for squashed commit")?;
+    }
+    branch.squashed_commits.push(SquashedCommits {
+        change_id,
+        commits: squashed_commits,
+    });
+    Ok(())
+}
+
+/// The reference keeping `commit_id`, which was squashed into the commit with `change_id`, reachable.
+fn squashed_commit_refname(change_id: &str, commit_id: git2::Oid) -> String {
+    format!("refs/gitbutler/squashed/{change_id}/{commit_id}")
+}
+
+/// Delete the references keeping the commits of `squashed` reachable, as they aren't needed anymore.
+fn release_squashed_commits(repo: &git2::Repository, squashed: &SquashedCommits) -> Result<()> {
+    for commit_id in &squashed.commits {
+        match repo.find_reference(&squashed_commit_refname(&squashed.change_id, *commit_id)) {
+            Ok(mut reference) => reference.delete()?,
+            Err(err) if err.code() == git2::ErrorCode::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the changes `commit` made to its first parent.
+fn commit_diff<'repo>(
+    repo: &'repo git2::Repository,
+    commit: &git2::Commit<'_>,
+) -> Result<git2::Diff<'repo>> {
+    let parent_tree = commit.parent(0)?.tree()?;
+    let mut opts = git2::DiffOptions::new();
+    opts.show_binary(true);
+    Ok(repo.diff_tree_to_tree(Some(&parent_tree), Some(&commit.tree()?), Some(&mut opts))?)
+}
+
+/// Splits a commit that was created by [`squash`] back into the commits that were squashed into it,
+/// reapplying the changes of the commits recorded at squash time. The last of these commits keeps
+/// the tree of the squashed commit.
+pub(crate) fn unsquash(
+    ctx: &CommandContext,
+    branch_id: StackId,
+    commit_id: git2::Oid,
+) -> Result<()> {
+    ctx.assure_resolved()?;
+
+    let vb_state = ctx.project().virtual_branches();
+    let mut branch = vb_state.get_branch_in_workspace(branch_id)?;
+    let default_target = vb_state.get_default_target()?;
+    let repo = ctx.repository();
+    let branch_commit_oids = repo.l(branch.head(), LogUntil::Commit(default_target.sha), false)?;
+
+    if !branch_commit_oids.contains(&commit_id) {
+        bail!("commit {commit_id} not in the branch")
+    }
+
+    let commit = repo
+        .find_commit(commit_id)
+        .context("failed to find commit")?;
+    if commit.is_conflicted() {
+        bail!("Can not unsquash conflicted commits");
+    }
+
+    let pushed_commit_oids = branch.upstream_head.map_or_else(
+        || Ok(vec![]),
+        |upstream_head| repo.l(upstream_head, LogUntil::Commit(default_target.sha), false),
+    )?;
+    if pushed_commit_oids.contains(&commit_id) && !branch.allow_rebasing {
+        // splitting a pushed commit will cause a force push that is not allowed
+        bail!("force push not allowed");
+    }
+
+    let squashed_idx = commit
+        .change_id()
+        .and_then(|change_id| {
+            branch
+                .squashed_commits
+                .iter()
+                .position(|squashed| squashed.change_id == change_id)
+        })
+        .with_context(|| {
+            format!(
+                "can not unsquash commit {commit_id} as no squashed commits were recorded for it, \
+                 it may have been squashed before squashes were recorded"
+            )
+        })?;
+    let squashed = branch.squashed_commits.remove(squashed_idx);
+    let squashed_commits = squashed
+        .commits
+        .iter()
+        .map(|commit_id| {
+            repo.find_commit(*commit_id)
+                .with_context(|| format!("failed to find squashed commit {commit_id}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let Some((last, first)) = squashed_commits.split_last() else {
+        bail!("no squashed commits were recorded for commit {commit_id}");
+    };
+
+    let commit_headers = |squashed_commit: &git2::Commit<'_>| {
+        squashed_commit
+            .change_id()
+            .map(|change_id| CommitHeadersV2 {
+                change_id,
+                conflicted: None,
+                integrated_upstream: None,
+            })
+    };
+
+    let mut parents: Vec<_> = commit.parents().collect();
+    let mut tree = commit.parent(0)?.tree()?;
+    for squashed_commit in first {
+        let diff = commit_diff(repo, squashed_commit)?;
+        let mut index = repo.apply_to_tree(&tree, &diff, None).with_context(|| {
+            format!(
+                "the changes of squashed commit {:?} no longer apply",
+                squashed_commit.summary().unwrap_or_default()
+            )
+        })?;
+        tree = repo.find_tree(index.write_tree_to(repo)?)?;
+        let new_commit_oid = repo
+            .commit_with_signature(
+                None,
+                &commit.author(),
+                &commit.committer(),
+                &squashed_commit.message_bstr().to_str_lossy(),
+                &tree,
+                &parents.iter().collect::<Vec<_>>(),
+                commit_headers(squashed_commit),
+            )
+            .context("failed to commit")?;
+        parents = vec![repo.find_commit(new_commit_oid)?];
+    }
+    let new_commit_oid = repo
+        .commit_with_signature(
+            None,
+            &commit.author(),
+            &commit.committer(),
+            &last.message_bstr().to_str_lossy(),
+            &commit.tree().context("failed to find tree")?,
+            &parents.iter().collect::<Vec<_>>(),
+            commit_headers(last),
+        )
+        .context("failed to commit")?;
+
+    let ids_to_rebase = branch_commit_oids
+        .split(|oid| oid.eq(&commit_id))
+        .next()
+        .unwrap_or_default()
+        .to_vec();
+    match cherry_rebase_group(repo, new_commit_oid, &ids_to_rebase) {
+        Ok(new_head_id) => {
+            branch.set_stack_head(ctx, new_head_id, None)?;
+            release_squashed_commits(repo, &squashed)?;
+
+            crate::integration::update_workspace_commit(&vb_state, ctx)
+                .context("failed to update gitbutler workspace")?;
+            Ok(())
+        }
+        Err(err) => Err(err.context("rebase error").context(Code::Unknown)),
+    }
+}
+
 // changes a commit message for commit_oid, rebases everything above it, updates branch head if successful
 pub(crate) fn update_commit_message(
     ctx: &CommandContext,
     branch_id: StackId,
     commit_id: git2::Oid,
     message: &str,
+    normalize_trailers: bool,
 ) -> Result<()> {
     if message.is_empty() {
         bail!("commit message can not be empty");
     }
+    let message = if normalize_trailers {
+        Cow::Owned(trailers::normalize_trailers(message))
+    } else {
+        Cow::Borrowed(message)
+    };
     ctx.assure_unconflicted()?;
 
     let vb_state = ctx.project().virtual_branches();
@@ -1677,7 +4556,7 @@ pub(crate) fn update_commit_message(
             None,
             &target_commit.author(),
             &target_commit.committer(),
-            message,
+            &message,
             &target_commit.tree().context("failed to find tree")?,
             &parents.iter().collect::<Vec<_>>(),
             target_commit.gitbutler_headers(),
@@ -1728,3 +4607,40 @@ fn update_conflict_markers(ctx: &CommandContext, files: Vec<VirtualBranchFile>)
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integration_is_unknown_beyond_the_shallow_boundary() -> Result<()> {
+        let (ctx, _tmp) =
+            gitbutler_testsupport::writable::fixture("shallow-clone.sh", "shallow-clone")?;
+        let repo = ctx.repository();
+        let target_branch = repo.find_reference("refs/remotes/origin/HEAD")?.resolve()?;
+        let target_head = target_branch.peel_to_commit()?;
+        let target = Target {
+            branch: target_branch.name().context("valid name")?.parse()?,
+            remote_url: String::new(),
+            // The target was set before its last commit was fetched.
+            sha: target_head.parent_id(0)?,
+            push_remote_name: None,
+            local_branch_name: None,
+        };
+        let feature = repo
+            .find_reference("refs/remotes/origin/feature")?
+            .peel_to_commit()?;
+
+        let gix_repo = ctx.gix_repository_for_merging_non_persisting()?;
+        let cache = gix_repo.commit_graph_if_enabled()?;
+        let mut graph = gix_repo.revision_graph(cache.as_ref());
+        let mut check_commit = IsCommitIntegrated::new(&ctx, &target, &gix_repo, &mut graph)?;
+        assert_eq!(
+            check_commit.integration_state(&feature)?,
+            None,
+            "the merge-base with the target was cut off by the shallow clone"
+        );
+        assert_eq!(check_commit.integration_state(&target_head)?, Some(true));
+        Ok(())
+    }
+}
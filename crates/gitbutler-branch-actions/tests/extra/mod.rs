@@ -182,6 +182,57 @@ fn track_binary_files() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn track_text_to_binary_transition() -> Result<()> {
+    let suite = Suite::default();
+    let Case { ctx, project, .. } = &suite.new_case();
+
+    let text = "line1\nline2\nline3\n";
+    std::fs::write(Path::new(&project.path).join("data"), text)?;
+    commit_all(ctx.repository());
+
+    set_test_target(ctx)?;
+
+    let mut guard = project.exclusive_worktree_access();
+    let branch1_id = ctx
+        .branch_manager()
+        .create_virtual_branch(&BranchCreateRequest::default(), guard.write_permission())
+        .expect("failed to create virtual branch")
+        .id;
+
+    let binary: [u8; 8] = [0, 159, 146, 150, 0, 1, 2, 3];
+    std::fs::write(Path::new(&project.path).join("data"), binary)?;
+
+    let (branches, _) = internal::list_virtual_branches(ctx, guard.write_permission())?;
+    let file = &branches[0].files[0];
+    assert!(file.binary);
+    assert_eq!(
+        file.hunks.len(),
+        1,
+        "the whole file is a single binary change"
+    );
+    let hunk = &file.hunks[0];
+    assert!(hunk.binary);
+    let old_oid = git2::Oid::hash_object(git2::ObjectType::Blob, text.as_bytes())?;
+    let new_oid = git2::Oid::hash_object(git2::ObjectType::Blob, &binary)?;
+    assert_eq!(hunk.old_blob_id, Some(old_oid.to_string()));
+    assert_eq!(hunk.diff, new_oid.to_string().as_str());
+
+    internal::commit(ctx, branch1_id, "make it binary", None, false)?;
+
+    let (branches, _) = internal::list_virtual_branches(ctx, guard.write_permission())?;
+    assert!(branches[0].files.is_empty());
+    let commit = ctx.repository().find_commit(branches[0].commits[0].id)?;
+    let entry = commit.tree()?.get_path(Path::new("data"))?;
+    assert_eq!(
+        entry.id(),
+        new_oid,
+        "the commit stores the binary content, not a text diff of it"
+    );
+
+    Ok(())
+}
+
 #[test]
 fn create_branch_with_ownership() -> Result<()> {
     let suite = Suite::default();
@@ -372,6 +423,67 @@ fn hunk_expantion() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn new_hunks_without_selected_branch_go_to_lowest_order_then_id() -> Result<()> {
+    let suite = Suite::default();
+    let Case { ctx, project, .. } = &suite.new_case();
+
+    set_test_target(ctx)?;
+
+    let branch_manager = ctx.branch_manager();
+    let mut guard = project.exclusive_worktree_access();
+    let mut branch_ids = [
+        branch_manager
+            .create_virtual_branch(&BranchCreateRequest::default(), guard.write_permission())?
+            .id,
+        branch_manager
+            .create_virtual_branch(&BranchCreateRequest::default(), guard.write_permission())?
+            .id,
+    ];
+    branch_ids.sort();
+
+    // Neither branch was ever selected and both are at the same position.
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    for id in branch_ids {
+        let mut branch = vb_state.get_branch(id)?;
+        branch.selected_for_changes = None;
+        branch.order = 0;
+        vb_state.set_branch(branch)?;
+    }
+
+    std::fs::write(Path::new(&project.path).join("first.txt"), "first\n")?;
+    let owner_of = |path: &str| -> Result<_> {
+        let statuses = get_applied_status(ctx, None)?.branches;
+        Ok(statuses
+            .iter()
+            .find(|(_, files)| files.iter().any(|file| file.path == Path::new(path)))
+            .map(|(branch, _)| branch.id))
+    };
+    assert_eq!(
+        owner_of("first.txt")?,
+        Some(branch_ids[0]),
+        "equal orders are decided by the lowest id"
+    );
+
+    let mut branch = vb_state.get_branch(branch_ids[0])?;
+    branch.order = 1;
+    vb_state.set_branch(branch)?;
+
+    std::fs::write(Path::new(&project.path).join("second.txt"), "second\n")?;
+    assert_eq!(
+        owner_of("second.txt")?,
+        Some(branch_ids[1]),
+        "the lowest order wins over the id"
+    );
+    assert_eq!(
+        owner_of("first.txt")?,
+        Some(branch_ids[0]),
+        "owned hunks stay where they are"
+    );
+
+    Ok(())
+}
+
 #[test]
 fn get_status_files_by_branch_no_hunks_no_branches() -> Result<()> {
     let suite = Suite::default();
@@ -742,6 +854,7 @@ fn commit_id_can_be_generated_or_specified() -> Result<()> {
             Some(CommitHeadersV2 {
                 change_id: "my-change-id".to_string(),
                 conflicted: None,
+                integrated_upstream: None,
             }),
         )
         .expect("failed to commit");
@@ -828,6 +941,7 @@ fn merge_vbranch_upstream_clean_rebase() -> Result<()> {
         remote_url: "origin".to_string(),
         sha: target_oid,
         push_remote_name: None,
+        local_branch_name: None,
     })?;
 
     // add some uncommitted work
@@ -881,6 +995,7 @@ fn merge_vbranch_upstream_clean_rebase() -> Result<()> {
         ctx,
         branch1.id,
         guard.write_permission(),
+        false,
     )?;
 
     let (branches, _) = internal::list_virtual_branches(ctx, guard.write_permission())?;
@@ -956,6 +1071,7 @@ fn merge_vbranch_upstream_conflict() -> Result<()> {
         remote_url: "origin".to_string(),
         sha: target_oid,
         push_remote_name: None,
+        local_branch_name: None,
     })?;
 
     // add some uncommitted work
@@ -995,6 +1111,7 @@ fn merge_vbranch_upstream_conflict() -> Result<()> {
         ctx,
         branch1.id,
         guard.write_permission(),
+        false,
     )?;
 
     let (branches, _) = internal::list_virtual_branches(ctx, guard.write_permission())?;
@@ -1015,6 +1132,93 @@ fn merge_vbranch_upstream_conflict() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn integrate_upstream_commits_for_all_branches() -> Result<()> {
+    let suite = Suite::default();
+    let Case { ctx, project, .. } = &mut suite.new_case();
+
+    let file_path = Path::new("test.txt");
+    std::fs::write(Path::new(&project.path).join(file_path), "line1\n")?;
+    commit_all(ctx.repository());
+    let target_oid = ctx.repository().head().unwrap().target().unwrap();
+
+    std::fs::write(Path::new(&project.path).join(file_path), "line1\nours\n")?;
+    commit_all(ctx.repository());
+    let last_push = ctx.repository().head().unwrap().target().unwrap();
+
+    // coworker adds some work
+    std::fs::write(
+        Path::new(&project.path).join(file_path),
+        "line1\nours\ncoworker work\n",
+    )?;
+    commit_all(ctx.repository());
+    let coworker_work = ctx.repository().head().unwrap().target().unwrap();
+    ctx.repository().reference(
+        "refs/remotes/origin/master",
+        coworker_work,
+        true,
+        "update target",
+    )?;
+
+    std::fs::write(Path::new(&project.path).join(file_path), "line1\nours\n")?;
+
+    set_test_target(ctx)?;
+    let vb_state = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    vb_state.set_default_target(Target {
+        branch: "refs/remotes/origin/master".parse().unwrap(),
+        remote_url: "origin".to_string(),
+        sha: target_oid,
+        push_remote_name: None,
+        local_branch_name: None,
+    })?;
+    update_workspace_commit(&vb_state, ctx)?;
+
+    let branch_manager = ctx.branch_manager();
+    let mut guard = project.exclusive_worktree_access();
+    let mut branch = branch_manager
+        .create_virtual_branch(&BranchCreateRequest::default(), guard.write_permission())?;
+    branch.upstream = Some("refs/remotes/origin/master".parse().unwrap());
+    branch.set_stack_head(ctx, last_push, None)?;
+    // a branch without upstream isn't part of the result
+    branch_manager
+        .create_virtual_branch(&BranchCreateRequest::default(), guard.write_permission())?;
+    // neither is one whose remote-tracking branch was pruned
+    let mut pruned = branch_manager
+        .create_virtual_branch(&BranchCreateRequest::default(), guard.write_permission())?;
+    pruned.upstream = Some("refs/remotes/origin/pruned".parse().unwrap());
+    vb_state.set_branch(pruned)?;
+
+    let outcomes = internal::branch_upstream_integration::integrate_upstream_commits_for_all(
+        ctx,
+        guard.write_permission(),
+    )?;
+    assert_eq!(
+        outcomes,
+        [(
+            branch.id,
+            internal::branch_upstream_integration::IntegrationOutcome::Integrated
+        )]
+    );
+    assert_eq!(
+        std::fs::read_to_string(Path::new(&project.path).join(file_path))?,
+        "line1\nours\ncoworker work\n"
+    );
+
+    let outcomes = internal::branch_upstream_integration::integrate_upstream_commits_for_all(
+        ctx,
+        guard.write_permission(),
+    )?;
+    assert_eq!(
+        outcomes,
+        [(
+            branch.id,
+            internal::branch_upstream_integration::IntegrationOutcome::UpToDate
+        )]
+    );
+
+    Ok(())
+}
+
 #[test]
 fn unapply_ownership_partial() -> Result<()> {
     let suite = Suite::default();
@@ -1066,6 +1270,97 @@ fn unapply_ownership_partial() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn unapply_ownership_partial_with_multiple_ranges() -> Result<()> {
+    let suite = Suite::default();
+    let content: String = (1..=30).map(|n| format!("line{n}\n")).collect();
+    let Case { ctx, project, .. } = &suite.new_case_with_files(HashMap::from([(
+        PathBuf::from("test.txt"),
+        content.as_str(),
+    )]));
+
+    set_test_target(ctx)?;
+
+    let changed = content
+        .replace("line3\n", "first\n")
+        .replace("line15\n", "second\n")
+        .replace("line27\n", "third\n");
+    std::fs::write(Path::new(&project.path).join("test.txt"), &changed)?;
+
+    let branch_manager = ctx.branch_manager();
+    let mut guard = project.exclusive_worktree_access();
+    branch_manager
+        .create_virtual_branch(&BranchCreateRequest::default(), guard.write_permission())
+        .expect("failed to create virtual branch");
+
+    let (branches, _) = internal::list_virtual_branches(ctx, guard.write_permission())?;
+    assert_eq!(branches[0].files[0].hunks.len(), 3);
+
+    internal::unapply_ownership(
+        ctx,
+        &"test.txt:1-7,24-31".parse().unwrap(),
+        guard.write_permission(),
+    )?;
+
+    assert_eq!(
+        std::fs::read_to_string(Path::new(&project.path).join("test.txt"))?,
+        content.replace("line15\n", "second\n"),
+        "only the hunk in between survives"
+    );
+    let (branches, _) = internal::list_virtual_branches(ctx, guard.write_permission())?;
+    assert_eq!(branches.len(), 1);
+    assert_eq!(branches[0].files.len(), 1);
+    assert_eq!(branches[0].files[0].hunks.len(), 1);
+    assert_eq!(branches[0].ownership.claims.len(), 1);
+    let claim = &branches[0].ownership.claims[0];
+    assert_eq!(claim.file_path, Path::new("test.txt"));
+    assert_eq!(
+        claim
+            .hunks
+            .iter()
+            .map(|hunk| (hunk.start, hunk.end))
+            .collect::<Vec<_>>(),
+        [(12, 19)],
+        "the remaining claim is recomputed"
+    );
+
+    // Reverting the 10 lines added at the top moves the hunk at worktree line 24 to lines 11-17,
+    // right next to the range of the first hunk. Both ranges still refer to the current worktree.
+    let added: String = (1..=10).map(|n| format!("added{n}\n")).collect();
+    let changed = content
+        .replacen("line4\n", &format!("{added}line4\n"), 1)
+        .replace("line14\n", "fourth\n")
+        .replace("line28\n", "fifth\n");
+    std::fs::write(Path::new(&project.path).join("test.txt"), &changed)?;
+
+    let (branches, _) = internal::list_virtual_branches(ctx, guard.write_permission())?;
+    assert_eq!(branches[0].files[0].hunks.len(), 3);
+
+    internal::unapply_ownership(
+        ctx,
+        &"test.txt:1-17,21-28".parse().unwrap(),
+        guard.write_permission(),
+    )?;
+
+    assert_eq!(
+        std::fs::read_to_string(Path::new(&project.path).join("test.txt"))?,
+        content.replace("line28\n", "fifth\n"),
+        "ranges that become adjacent once the first one is reverted are both reverted"
+    );
+    let (branches, _) = internal::list_virtual_branches(ctx, guard.write_permission())?;
+    assert_eq!(branches[0].files[0].hunks.len(), 1);
+    assert_eq!(
+        branches[0].ownership.claims[0]
+            .hunks
+            .iter()
+            .map(|hunk| (hunk.start, hunk.end))
+            .collect::<Vec<_>>(),
+        [(25, 31)]
+    );
+
+    Ok(())
+}
+
 #[test]
 fn unapply_branch() -> Result<()> {
     let suite = Suite::default();
@@ -1426,6 +1721,7 @@ fn upstream_integrated_vbranch() -> Result<()> {
         remote_url: "http://origin.com/project".to_string(),
         sha: base_commit,
         push_remote_name: None,
+        local_branch_name: None,
     })?;
     ctx.repository()
         .remote("origin", "http://origin.com/project")?;
@@ -1874,7 +2170,7 @@ fn commit_executable_and_symlinks() -> Result<()> {
     let file_path3 = Path::new("test3.txt");
     let src = Path::new(&project.path).join("test2.txt");
     let dst = Path::new(&project.path).join(file_path3);
-    symlink(src, dst)?;
+    symlink(src, &dst)?;
 
     // add executable
     let file_path4 = Path::new("test4.bin");
@@ -1916,6 +2212,158 @@ fn commit_executable_and_symlinks() -> Result<()> {
     assert_eq!(list[3].0, "test4.bin");
     assert_eq!(list[3].1, "100755");
 
+    // retarget the symlink, first to an existing file and then to one that doesn't exist
+    for target in ["test.txt", "missing.txt"] {
+        std::fs::remove_file(&dst)?;
+        symlink(target, &dst)?;
+
+        internal::commit(ctx, branch1_id, "retarget", None, false)?;
+
+        let (branches, _) = internal::list_virtual_branches(ctx, guard.write_permission())?;
+        let branch1 = &branches.iter().find(|b| b.id == branch1_id).unwrap();
+        assert_eq!(branch1.files.len(), 0);
+
+        let commit = ctx.repository().find_commit(branch1.commits[0].id)?;
+        let list = tree_to_entry_list(ctx.repository(), &commit.tree()?);
+        assert_eq!(list[2].0, "test3.txt");
+        assert_eq!(list[2].1, "120000");
+        assert_eq!(list[2].2, target);
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn commit_mode_change_only() -> Result<()> {
+    let suite = Suite::default();
+    let Case { project, ctx, .. } =
+        &suite.new_case_with_files(HashMap::from([(PathBuf::from("script.sh"), "echo hi\n")]));
+
+    set_test_target(ctx)?;
+
+    let mut guard = project.exclusive_worktree_access();
+    let branch1_id = ctx
+        .branch_manager()
+        .create_virtual_branch(&BranchCreateRequest::default(), guard.write_permission())
+        .expect("failed to create virtual branch")
+        .id;
+
+    let script = Path::new(&project.path).join("script.sh");
+    let permissions = std::fs::metadata(&script)?.permissions();
+    std::fs::set_permissions(&script, Permissions::from_mode(permissions.mode() | 0o111))?;
+
+    let (branches, _) = internal::list_virtual_branches(ctx, guard.write_permission())?;
+    let file = &branches[0].files[0];
+    assert_eq!(file.path, Path::new("script.sh"));
+    assert!(file.mode_change);
+    assert_eq!(file.hunks.len(), 1);
+    assert!(file.hunks[0].diff.is_empty());
+
+    internal::commit(ctx, branch1_id, "make script executable", None, false)?;
+
+    let (branches, _) = internal::list_virtual_branches(ctx, guard.write_permission())?;
+    assert_eq!(branches[0].files.len(), 0);
+    let commit = ctx.repository().find_commit(branches[0].commits[0].id)?;
+    let mode_of = |tree: &git2::Tree| {
+        tree_to_entry_list(ctx.repository(), tree)
+            .into_iter()
+            .find(|entry| entry.0 == "script.sh")
+            .map(|(_, mode, content, _)| (mode, content))
+    };
+    assert_eq!(
+        mode_of(&commit.parent(0)?.tree()?),
+        Some(("100644".into(), "echo hi\n".into()))
+    );
+    assert_eq!(
+        mode_of(&commit.tree()?),
+        Some(("100755".into(), "echo hi\n".into())),
+        "only the mode changed"
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn commit_tree_is_reproducible() -> Result<()> {
+    let suite = Suite::default();
+    let Case { project, ctx, .. } =
+        &suite.new_case_with_files(HashMap::from([(PathBuf::from("a.txt"), "a\n")]));
+
+    set_test_target(ctx)?;
+
+    let mut guard = project.exclusive_worktree_access();
+    let branch1_id = ctx
+        .branch_manager()
+        .create_virtual_branch(&BranchCreateRequest::default(), guard.write_permission())
+        .expect("failed to create virtual branch")
+        .id;
+
+    // `a-b.txt` sorts before the `a` directory, which sorts before `a.txt`
+    let worktree = Path::new(&project.path);
+    std::fs::write(worktree.join("a-b.txt"), "a-b\n")?;
+    std::fs::create_dir(worktree.join("a"))?;
+    std::fs::write(worktree.join("a").join("c.txt"), "c\n")?;
+    std::fs::create_dir(worktree.join("empty"))?;
+    // only the user executable bit makes a file executable
+    std::fs::write(worktree.join("run.sh"), "echo run\n")?;
+    std::fs::set_permissions(worktree.join("run.sh"), Permissions::from_mode(0o744))?;
+    std::fs::write(worktree.join("group.sh"), "echo group\n")?;
+    std::fs::set_permissions(worktree.join("group.sh"), Permissions::from_mode(0o654))?;
+
+    internal::commit(ctx, branch1_id, "known content", None, false)?;
+
+    let (branches, _) = internal::list_virtual_branches(ctx, guard.write_permission())?;
+    let commit = ctx.repository().find_commit(branches[0].commits[0].id)?;
+    // as computed by `git add -A && git write-tree` for the same files
+    assert_eq!(
+        commit.tree_id().to_string(),
+        "121314cafa22a39471d1940bedb0718ae4252323"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn files_are_labeled_with_their_change_type() -> Result<()> {
+    let suite = Suite::default();
+    let Case { project, ctx, .. } = &suite.new_case_with_files(HashMap::from([
+        (PathBuf::from("modified.txt"), "line\n"),
+        (PathBuf::from("deleted.txt"), "line\n"),
+    ]));
+
+    set_test_target(ctx)?;
+
+    let mut guard = project.exclusive_worktree_access();
+    ctx.branch_manager()
+        .create_virtual_branch(&BranchCreateRequest::default(), guard.write_permission())
+        .expect("failed to create virtual branch");
+
+    std::fs::write(
+        Path::new(&project.path).join("modified.txt"),
+        "line\nline\n",
+    )?;
+    std::fs::remove_file(Path::new(&project.path).join("deleted.txt"))?;
+    std::fs::write(Path::new(&project.path).join("untracked.txt"), "new\n")?;
+
+    let (branches, _) = internal::list_virtual_branches(ctx, guard.write_permission())?;
+    assert_eq!(branches.len(), 1, "labels don't affect ownership");
+    let mut files = branches[0]
+        .files
+        .iter()
+        .map(|file| (file.path.to_str().unwrap(), file.change_type))
+        .collect::<Vec<_>>();
+    files.sort_by_key(|(path, _)| *path);
+    assert_eq!(
+        files,
+        [
+            ("deleted.txt", gitbutler_diff::ChangeType::Deleted),
+            ("modified.txt", gitbutler_diff::ChangeType::Modified),
+            ("untracked.txt", gitbutler_diff::ChangeType::Added),
+        ]
+    );
+
     Ok(())
 }
 
@@ -2062,6 +2510,67 @@ fn pre_commit_hook_rejection() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn pre_commit_hook_skipped_for_branch() -> Result<()> {
+    let suite = Suite::default();
+    let Case { project, ctx, .. } = &suite.new_case_with_files(HashMap::from([
+        (PathBuf::from("test.txt"), "line1\nline2\nline3\nline4\n"),
+        (PathBuf::from("test2.txt"), "line5\nline6\nline7\nline8\n"),
+    ]));
+
+    set_test_target(ctx)?;
+
+    let branch_manager = ctx.branch_manager();
+    let mut guard = project.exclusive_worktree_access();
+    let branch1_id = branch_manager
+        .create_virtual_branch(&BranchCreateRequest::default(), guard.write_permission())
+        .expect("failed to create virtual branch")
+        .id;
+
+    std::fs::write(
+        Path::new(&project.path).join("test.txt"),
+        "line0\nline1\nline2\nline3\nline4\n",
+    )?;
+
+    let hook = b"#!/bin/sh
+    echo 'rejected'
+    exit 1
+            ";
+
+    git2_hooks::create_hook(ctx.repository(), git2_hooks::HOOK_PRE_COMMIT, hook);
+
+    let err = internal::update_branch(
+        ctx,
+        &BranchUpdateRequest {
+            id: branch1_id,
+            skipped_hooks: Some(vec!["pre-push".into()]),
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "cannot skip unknown hook \"pre-push\", expected one of pre-commit, commit-msg, post-commit"
+    );
+
+    internal::update_branch(
+        ctx,
+        &BranchUpdateRequest {
+            id: branch1_id,
+            skipped_hooks: Some(vec!["pre-commit".into()]),
+            ..Default::default()
+        },
+    )?;
+
+    internal::commit(ctx, branch1_id, "test commit", None, true)?;
+
+    let (branches, _) = internal::list_virtual_branches(ctx, guard.write_permission())?;
+    assert_eq!(branches[0].commits.len(), 1);
+    assert_eq!(branches[0].skipped_hooks, ["pre-commit"]);
+
+    Ok(())
+}
+
 #[test]
 fn post_commit_hook() -> Result<()> {
     let suite = Suite::default();
@@ -2141,6 +2650,83 @@ fn commit_msg_hook_rejection() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn prepare_commit_msg_hook_rewrites_message() -> Result<()> {
+    let suite = Suite::default();
+    let Case { project, ctx, .. } = &suite.new_case_with_files(HashMap::from([(
+        PathBuf::from("test.txt"),
+        "line1\nline2\nline3\nline4\n",
+    )]));
+
+    set_test_target(ctx)?;
+
+    let branch_manager = ctx.branch_manager();
+    let mut guard = project.exclusive_worktree_access();
+    let branch1_id = branch_manager
+        .create_virtual_branch(&BranchCreateRequest::default(), guard.write_permission())
+        .expect("failed to create virtual branch")
+        .id;
+
+    std::fs::write(
+        Path::new(&project.path).join("test.txt"),
+        "line0\nline1\nline2\nline3\nline4\n",
+    )?;
+
+    let hook = b"#!/bin/sh
+    test \"$2\" = message || exit 1
+    printf 'ABC-1 %s' \"$(cat \"$1\")\" > \"$1\"
+            ";
+
+    git2_hooks::create_hook(ctx.repository(), git2_hooks::HOOK_PREPARE_COMMIT_MSG, hook);
+
+    let commit_id = internal::commit(ctx, branch1_id, "test commit", None, true)?;
+
+    let commit = ctx.repository().find_commit(commit_id)?;
+    assert_eq!(commit.message(), Some("ABC-1 test commit"));
+
+    Ok(())
+}
+
+#[test]
+fn prepare_commit_msg_hook_rejection() -> Result<()> {
+    let suite = Suite::default();
+    let Case { project, ctx, .. } = &suite.new_case_with_files(HashMap::from([(
+        PathBuf::from("test.txt"),
+        "line1\nline2\nline3\nline4\n",
+    )]));
+
+    set_test_target(ctx)?;
+
+    let branch_manager = ctx.branch_manager();
+    let mut guard = project.exclusive_worktree_access();
+    let branch1_id = branch_manager
+        .create_virtual_branch(&BranchCreateRequest::default(), guard.write_permission())
+        .expect("failed to create virtual branch")
+        .id;
+
+    std::fs::write(
+        Path::new(&project.path).join("test.txt"),
+        "line0\nline1\nline2\nline3\nline4\n",
+    )?;
+
+    let hook = b"#!/bin/sh
+    echo 'rejected'
+    exit 1
+            ";
+
+    git2_hooks::create_hook(ctx.repository(), git2_hooks::HOOK_PREPARE_COMMIT_MSG, hook);
+
+    let res = internal::commit(ctx, branch1_id, "test commit", None, true);
+
+    let err = res.unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "prepare-commit-msg hook rejected: rejected"
+    );
+
+    Ok(())
+}
+
 fn walk<C>(tree: &git2::Tree, mut callback: C) -> Result<()>
 where
     C: FnMut(&str, &TreeEntry) -> TreeWalkResult,
@@ -78,3 +78,18 @@ git clone remote multiple-commits-empty-top
 
   $CLI branch series my_stack -s "top-series"
 )
+
+git clone remote five-independent-commits
+(cd five-independent-commits
+  git config user.name "Author"
+  git config user.email "author@example.com"
+
+  git branch existing-branch
+  $CLI project add --switch-to-workspace "$(git rev-parse --symbolic-full-name @{u})"
+
+  $CLI branch create --set-default my_stack
+  for n in 1 2 3 4 5; do
+    echo "change$n" > "file$n"
+    $CLI branch commit my_stack -m "commit $n"
+  done
+)
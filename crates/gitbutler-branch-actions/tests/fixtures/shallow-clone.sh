@@ -0,0 +1,26 @@
+#!/usr/bin/env bash
+set -eu -o pipefail
+
+git init remote
+(cd remote
+  echo first > file
+  git add . && git commit -m "init"
+
+  git checkout -b feature
+  echo feature > feature
+  git add . && git commit -m "feature"
+
+  git checkout -
+  echo second >> file
+  git commit -am "second"
+)
+
+# Only the tips of both branches are cloned, so they don't share any history.
+git clone --depth 1 --no-single-branch "file://$PWD/remote" shallow-clone
+(cd remote
+  echo third >> file
+  git commit -am "third"
+)
+(cd shallow-clone
+  git fetch
+)
@@ -2,7 +2,10 @@ use std::collections::HashMap;
 
 use anyhow::Result;
 use git2::Oid;
-use gitbutler_branch_actions::{list_virtual_branches, reorder_stack, SeriesOrder, StackOrder};
+use gitbutler_branch_actions::{
+    insert_blank_commit, list_virtual_branches, pin_commit, reorder_commit, reorder_stack,
+    reorder_stack_with_options, unpin_commit, SeriesOrder, StackOrder,
+};
 use gitbutler_command_context::CommandContext;
 use gitbutler_stack::VirtualBranchesHandle;
 use itertools::Itertools;
@@ -306,6 +309,251 @@ fn reorder_stack_into_empty_top() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn pinned_commit_can_not_be_moved() -> Result<()> {
+    let (ctx, _temp_dir) = command_ctx("multiple-commits")?;
+    let test_ctx = test_ctx(&ctx)?;
+    let pinned = test_ctx.bottom_commits["commit 1"];
+    pin_commit(ctx.project(), test_ctx.stack.id, pinned)?;
+
+    let moving_pinned_commit = order(vec![
+        vec![
+            test_ctx.top_commits["commit 6"],
+            test_ctx.top_commits["commit 5"],
+            test_ctx.top_commits["commit 4"],
+        ],
+        vec![
+            test_ctx.bottom_commits["commit 3"],
+            pinned,
+            test_ctx.bottom_commits["commit 2"],
+        ],
+    ]);
+    let err = reorder_stack(ctx.project(), test_ctx.stack.id, moving_pinned_commit).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("commit {pinned} is pinned and can't be moved")
+    );
+
+    // commits above it can still be reordered
+    reorder_stack(
+        ctx.project(),
+        test_ctx.stack.id,
+        order(vec![
+            vec![
+                test_ctx.top_commits["commit 6"],
+                test_ctx.top_commits["commit 4"],
+                test_ctx.top_commits["commit 5"],
+            ],
+            vec![
+                test_ctx.bottom_commits["commit 3"],
+                test_ctx.bottom_commits["commit 2"],
+                pinned,
+            ],
+        ]),
+    )?;
+    assert_eq!(
+        vb_commits(&ctx)[0].msgs(),
+        vec!["commit 6", "commit 4", "commit 5"]
+    );
+
+    unpin_commit(ctx.project(), test_ctx.stack.id, pinned)?;
+    let test_ctx = self::test_ctx(&ctx)?;
+    reorder_stack(
+        ctx.project(),
+        test_ctx.stack.id,
+        order(vec![
+            vec![
+                test_ctx.top_commits["commit 6"],
+                test_ctx.top_commits["commit 4"],
+                test_ctx.top_commits["commit 5"],
+            ],
+            vec![
+                test_ctx.bottom_commits["commit 3"],
+                pinned,
+                test_ctx.bottom_commits["commit 2"],
+            ],
+        ]),
+    )?;
+    assert_eq!(
+        vb_commits(&ctx)[1].msgs(),
+        vec!["commit 3", "commit 1", "commit 2"]
+    );
+    Ok(())
+}
+
+#[test]
+fn pins_survive_rewriting_the_pinned_commit() -> Result<()> {
+    let (ctx, _temp_dir) = command_ctx("multiple-commits")?;
+    let test_ctx = test_ctx(&ctx)?;
+    let pinned = test_ctx.bottom_commits["commit 2"];
+    pin_commit(ctx.project(), test_ctx.stack.id, pinned)?;
+
+    // Swapping the commits around it rewrites it in place.
+    reorder_stack(
+        ctx.project(),
+        test_ctx.stack.id,
+        order(vec![
+            vec![
+                test_ctx.top_commits["commit 6"],
+                test_ctx.top_commits["commit 5"],
+                test_ctx.top_commits["commit 4"],
+            ],
+            vec![
+                test_ctx.bottom_commits["commit 1"],
+                pinned,
+                test_ctx.bottom_commits["commit 3"],
+            ],
+        ]),
+    )?;
+    let test_ctx = self::test_ctx(&ctx)?;
+    let rewritten = test_ctx.bottom_commits["commit 2"];
+    assert_ne!(rewritten, pinned);
+
+    let err = reorder_stack(
+        ctx.project(),
+        test_ctx.stack.id,
+        order(vec![
+            vec![
+                test_ctx.top_commits["commit 6"],
+                test_ctx.top_commits["commit 5"],
+                test_ctx.top_commits["commit 4"],
+            ],
+            vec![
+                rewritten,
+                test_ctx.bottom_commits["commit 1"],
+                test_ctx.bottom_commits["commit 3"],
+            ],
+        ]),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("commit {rewritten} is pinned and can't be moved")
+    );
+    Ok(())
+}
+
+#[test]
+fn commits_that_become_empty_are_dropped() -> Result<()> {
+    let (ctx, _temp_dir) = command_ctx("multiple-commits")?;
+    let test_ctx = test_ctx_with_blank_commit_on_top(&ctx)?;
+    reorder_stack(
+        ctx.project(),
+        test_ctx.stack.id,
+        order(vec![
+            vec![
+                test_ctx.top_commits["commit 6"],
+                test_ctx.top_commits[""],
+                test_ctx.top_commits["commit 5"],
+                test_ctx.top_commits["commit 4"],
+            ],
+            vec![
+                test_ctx.bottom_commits["commit 3"],
+                test_ctx.bottom_commits["commit 2"],
+                test_ctx.bottom_commits["commit 1"],
+            ],
+        ]),
+    )?;
+    let commits = vb_commits(&ctx);
+    assert_eq!(commits[0].msgs(), vec!["commit 6", "commit 5", "commit 4"]);
+    Ok(())
+}
+
+#[test]
+fn commits_that_become_empty_can_be_kept() -> Result<()> {
+    let (ctx, _temp_dir) = command_ctx("multiple-commits")?;
+    let test_ctx = test_ctx_with_blank_commit_on_top(&ctx)?;
+    reorder_stack_with_options(
+        ctx.project(),
+        test_ctx.stack.id,
+        order(vec![
+            vec![
+                test_ctx.top_commits["commit 6"],
+                test_ctx.top_commits[""],
+                test_ctx.top_commits["commit 5"],
+                test_ctx.top_commits["commit 4"],
+            ],
+            vec![
+                test_ctx.bottom_commits["commit 3"],
+                test_ctx.bottom_commits["commit 2"],
+                test_ctx.bottom_commits["commit 1"],
+            ],
+        ]),
+        true,
+    )?;
+    let commits = vb_commits(&ctx);
+    assert_eq!(
+        commits[0].msgs(),
+        vec!["commit 6", "", "commit 5", "commit 4"]
+    );
+    Ok(())
+}
+
+/// Like [`test_ctx()`], but with an empty commit with an empty message on top of the stack.
+fn test_ctx_with_blank_commit_on_top(ctx: &CommandContext) -> Result<TestContext> {
+    let test_ctx = test_ctx(ctx)?;
+    insert_blank_commit(
+        ctx.project(),
+        test_ctx.stack.id,
+        test_ctx.top_commits["commit 6"],
+        -1,
+    )?;
+    let test_ctx = self::test_ctx(ctx)?;
+    assert_eq!(vb_commits(ctx)[0].msgs()[0], "");
+    Ok(test_ctx)
+}
+
+#[test]
+fn reorder_commit_by_offset() -> Result<()> {
+    let (ctx, _temp_dir) = command_ctx("five-independent-commits")?;
+    let repo = ctx.repository();
+    let handle = VirtualBranchesHandle::new(ctx.project().gb_dir());
+    let stack = handle
+        .list_all_branches()?
+        .into_iter()
+        .find(|b| b.name == "my_stack")
+        .unwrap();
+    let old_head = repo.find_commit(stack.head())?;
+    assert_eq!(old_head.message(), Some("commit 5"));
+
+    // Move the top commit down by three, so it ends up between commit 1 and 2
+    reorder_commit(ctx.project(), stack.id, old_head.id(), 3)?;
+
+    let new_head = repo.find_commit(handle.get_branch(stack.id)?.head())?;
+    let merge_base = old_head
+        .parent(0)?
+        .parent(0)?
+        .parent(0)?
+        .parent(0)?
+        .parent_id(0)?;
+    let mut commits = vec![];
+    let mut commit = new_head.clone();
+    while commit.id() != merge_base {
+        let parent = commit.parent(0)?;
+        let diff = repo.diff_tree_to_tree(Some(&parent.tree()?), Some(&commit.tree()?), None)?;
+        assert_eq!(diff.deltas().len(), 1, "every commit still adds one file");
+        assert_eq!(commit.author().name(), old_head.author().name());
+        commits.push(commit.message().unwrap().to_string());
+        commit = parent;
+    }
+    assert_eq!(
+        commits,
+        ["commit 4", "commit 3", "commit 2", "commit 5", "commit 1"]
+    );
+    assert_eq!(
+        new_head.tree_id(),
+        old_head.tree_id(),
+        "the stack ends up with the same content"
+    );
+
+    let err = reorder_commit(ctx.project(), stack.id, new_head.id(), 5).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Cannot move commit at index 0 by offset 5: target index 5 is outside of [0, 5)"
+    );
+    Ok(())
+}
+
 fn order(series: Vec<Vec<Oid>>) -> StackOrder {
     StackOrder {
         series: vec![
@@ -1,5 +1,5 @@
 use gitbutler_branch::{BranchCreateRequest, BranchUpdateRequest};
-use gitbutler_branch_actions::list_commit_files;
+use gitbutler_branch_actions::{list_commit_files, CommitOptions};
 use gitbutler_stack::BranchOwnershipClaims;
 
 use super::*;
@@ -270,3 +270,103 @@ fn non_existing_ownership() {
         );
     }
 }
+
+#[test]
+fn dry_run_leaves_branch_untouched() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )
+    .unwrap();
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())
+            .unwrap();
+
+    fs::write(repository.path().join("file.txt"), "content").unwrap();
+    let commit_oid =
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit one", None, false)
+            .unwrap();
+    fs::write(repository.path().join("file2.txt"), "content2").unwrap();
+    let head_oid =
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit two", None, false)
+            .unwrap();
+
+    fs::write(repository.path().join("file3.txt"), "content3").unwrap();
+    let to_amend: BranchOwnershipClaims = "file3.txt:1-2".parse().unwrap();
+    let preview =
+        gitbutler_branch_actions::amend_dry_run(project, branch_id, commit_oid, &to_amend)?;
+
+    let mut paths: Vec<_> = preview.files.iter().map(|f| f.path.clone()).collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        vec![PathBuf::from("file.txt"), PathBuf::from("file3.txt")]
+    );
+    assert!(preview.conflicting_paths.is_empty());
+
+    let branch = gitbutler_branch_actions::list_virtual_branches(project)
+        .unwrap()
+        .0
+        .into_iter()
+        .find(|b| b.id == branch_id)
+        .unwrap();
+    assert_eq!(branch.head, head_oid);
+    assert_eq!(branch.commits.len(), 2);
+    assert_eq!(branch.files.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn reset_author_only_when_requested() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("file.txt"), "content")?;
+    let original_author = git2::Signature::new(
+        "original",
+        "original@example.com",
+        &git2::Time::new(1_000_000_000, 0),
+    )?;
+    let commit_oid = gitbutler_branch_actions::create_commit_with_options(
+        project,
+        branch_id,
+        "commit",
+        None,
+        &CommitOptions {
+            author: Some(original_author),
+            ..Default::default()
+        },
+    )?;
+
+    fs::write(repository.path().join("file2.txt"), "content2")?;
+    let to_amend: BranchOwnershipClaims = "file2.txt:1-2".parse()?;
+    let commit_oid = gitbutler_branch_actions::amend(project, branch_id, commit_oid, &to_amend)?;
+    let commit = repository.find_commit(commit_oid)?;
+    assert_eq!(commit.author().name(), Some("original"));
+    assert_eq!(commit.author().when().seconds(), 1_000_000_000);
+
+    fs::write(repository.path().join("file3.txt"), "content3")?;
+    let to_amend: BranchOwnershipClaims = "file3.txt:1-2".parse()?;
+    let commit_oid = gitbutler_branch_actions::amend_with_options(
+        project, branch_id, commit_oid, &to_amend, true,
+    )?;
+    let commit = repository.find_commit(commit_oid)?;
+    assert_ne!(commit.author().name(), Some("original"));
+    assert_ne!(commit.author().email(), Some("original@example.com"));
+    assert!(commit.author().when().seconds() > 1_000_000_000);
+    Ok(())
+}
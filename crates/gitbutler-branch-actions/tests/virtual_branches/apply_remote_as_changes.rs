@@ -0,0 +1,103 @@
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_reference::LocalRefname;
+
+use super::*;
+
+#[test]
+fn apply_remote_branch_as_uncommitted_changes() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    let branch_name: LocalRefname = "refs/heads/branch".parse()?;
+    repository.checkout(&branch_name);
+    fs::write(repository.path().join("file.txt"), "first\n")?;
+    repository.commit_all("first");
+    fs::write(repository.path().join("other.txt"), "second\n")?;
+    repository.commit_all("second");
+    repository.push_branch(&branch_name);
+    repository.checkout(&"refs/heads/master".parse()?);
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    let branch_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            selected_for_changes: Some(false),
+            ..Default::default()
+        },
+    )?;
+
+    gitbutler_branch_actions::apply_remote_as_changes(
+        project,
+        &"refs/remotes/origin/branch".parse()?,
+        branch_id,
+    )?;
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        "first\n"
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("other.txt"))?,
+        "second\n"
+    );
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let branch = branches.iter().find(|b| b.id == branch_id).unwrap();
+    assert!(branch.commits.is_empty(), "no commits are imported");
+    let mut paths: Vec<_> = branch
+        .files
+        .iter()
+        .map(|file| file.path.display().to_string())
+        .collect();
+    paths.sort();
+    assert_eq!(paths, ["file.txt", "other.txt"]);
+    assert!(
+        branches
+            .iter()
+            .filter(|b| b.id != branch_id)
+            .all(|b| b.files.is_empty()),
+        "the changes are owned by the given branch"
+    );
+    Ok(())
+}
+
+#[test]
+fn refuse_to_touch_uncommitted_changes() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    let branch_name: LocalRefname = "refs/heads/branch".parse()?;
+    repository.checkout(&branch_name);
+    fs::write(repository.path().join("file.txt"), "theirs\n")?;
+    repository.commit_all("theirs");
+    repository.push_branch(&branch_name);
+    repository.checkout(&"refs/heads/master".parse()?);
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "ours\n")?;
+
+    let err = gitbutler_branch_actions::apply_remote_as_changes(
+        project,
+        &"refs/remotes/origin/branch".parse()?,
+        branch_id,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "the changes of refs/remotes/origin/branch touch files with uncommitted changes: file.txt"
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        "ours\n",
+        "nothing was changed"
+    );
+    Ok(())
+}
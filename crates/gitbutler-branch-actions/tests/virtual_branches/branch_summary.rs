@@ -0,0 +1,88 @@
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_branch_actions::BranchSummary;
+
+use super::*;
+
+#[test]
+fn count_uncommitted_changes_and_commits() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join("file.txt"), "a\nb\nc\n")?;
+    repository.commit_all("add file");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    assert_eq!(
+        gitbutler_branch_actions::branch_summary(project, branch_id)?,
+        BranchSummary::default()
+    );
+
+    fs::write(repository.path().join("committed.txt"), "committed\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "commit", None, false)?;
+    fs::write(repository.path().join("file.txt"), "a\nB\nc\n")?;
+    fs::write(repository.path().join("new.txt"), "x\ny\n")?;
+
+    assert_eq!(
+        gitbutler_branch_actions::branch_summary(project, branch_id)?,
+        BranchSummary {
+            files: 2,
+            hunks: 2,
+            lines_added: 3,
+            lines_removed: 1,
+            commits: 1,
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn only_count_changes_the_branch_owns() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch1_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("a.txt"), "a\n")?;
+    gitbutler_branch_actions::list_virtual_branches(project)?;
+
+    let branch2_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+    )?;
+    fs::write(repository.path().join("b.txt"), "b\nb\n")?;
+
+    assert_eq!(
+        gitbutler_branch_actions::branch_summary(project, branch1_id)?,
+        BranchSummary {
+            files: 1,
+            hunks: 1,
+            lines_added: 1,
+            ..Default::default()
+        },
+        "the claimed file"
+    );
+    assert_eq!(
+        gitbutler_branch_actions::branch_summary(project, branch2_id)?,
+        BranchSummary {
+            files: 1,
+            hunks: 1,
+            lines_added: 2,
+            ..Default::default()
+        },
+        "the new file goes to the branch selected for changes"
+    );
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+
+use bstr::ByteSlice;
+
+use gitbutler_branch::BranchCreateRequest;
+
+use super::*;
+
+#[test]
+fn report_conflicting_files_of_two_branches() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join("file.txt"), "a\nb\nc\n")?;
+    repository.commit_all("add file");
+    repository.push();
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+
+    let unapplied_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "a\nours\nc\n")?;
+    gitbutler_branch_actions::create_commit(project, unapplied_id, "ours", None, false)?;
+    gitbutler_branch_actions::save_and_unapply_virutal_branch(project, unapplied_id)?;
+
+    let conflicting_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "a\ntheirs\nc\n")?;
+    gitbutler_branch_actions::list_virtual_branches(project)?;
+
+    let conflicting =
+        gitbutler_branch_actions::branches_conflict(project, unapplied_id, conflicting_id)?
+            .expect("both change the second line");
+    assert_eq!(conflicting.len(), 1);
+    assert_eq!(conflicting[0].path, PathBuf::from("file.txt"));
+    let changed_lines = |hunks: &[gitbutler_diff::GitHunk]| {
+        hunks
+            .iter()
+            .map(|hunk| {
+                let diff = hunk.diff_lines.to_str().unwrap();
+                (
+                    hunk.old_start,
+                    hunk.old_lines,
+                    diff[diff.find('\n').unwrap() + 1..].to_owned(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        changed_lines(&conflicting[0].a_hunks),
+        [(2, 1, "-b\n+ours\n".to_owned())]
+    );
+    assert_eq!(
+        changed_lines(&conflicting[0].b_hunks),
+        [(2, 1, "-b\n+theirs\n".to_owned())]
+    );
+
+    let clean_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+    )?;
+    fs::write(repository.path().join("other.txt"), "other\n")?;
+    gitbutler_branch_actions::list_virtual_branches(project)?;
+
+    assert_eq!(
+        gitbutler_branch_actions::branches_conflict(project, unapplied_id, clean_id)?,
+        None
+    );
+    assert_eq!(
+        gitbutler_branch_actions::branches_conflict(project, conflicting_id, clean_id)?,
+        None
+    );
+    Ok(())
+}
+
+#[test]
+fn reject_checking_a_branch_against_itself() -> anyhow::Result<()> {
+    let Test { project, .. } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    let err =
+        gitbutler_branch_actions::branches_conflict(project, branch_id, branch_id).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<gitbutler_error::error::Code>(),
+        Some(&gitbutler_error::error::Code::Validation)
+    );
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use std::path::Path;
+
+use gitbutler_branch::BranchCreateRequest;
+
+use super::*;
+
+#[test]
+fn restore_and_delete_files_as_of_a_commit() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "first\n")?;
+    let first_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "first", None, false)?;
+    fs::write(repository.path().join("file.txt"), "second\n")?;
+    fs::write(repository.path().join("new.txt"), "new\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "second", None, false)?;
+
+    gitbutler_branch_actions::checkout_file_from_commit(project, first_id, Path::new("file.txt"))?;
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        "first\n"
+    );
+
+    gitbutler_branch_actions::checkout_file_from_commit(project, first_id, Path::new("new.txt"))?;
+    assert!(
+        !repository.path().join("new.txt").exists(),
+        "files absent at the commit are deleted"
+    );
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let branch = branches.iter().find(|b| b.id == branch_id).unwrap();
+    let mut paths: Vec<_> = branch
+        .files
+        .iter()
+        .map(|file| file.path.display().to_string())
+        .collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        ["file.txt", "new.txt"],
+        "both are owned by the branch"
+    );
+    Ok(())
+}
+
+#[test]
+fn restored_files_are_owned_by_the_branch_of_the_commit() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "first\n")?;
+    let first_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "first", None, false)?;
+    fs::write(repository.path().join("file.txt"), "second\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "second", None, false)?;
+
+    let other_branch_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+    )?;
+    gitbutler_branch_actions::checkout_file_from_commit(project, first_id, Path::new("file.txt"))?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let files = |id: gitbutler_stack::StackId| {
+        branches
+            .iter()
+            .find(|b| b.id == id)
+            .unwrap()
+            .files
+            .iter()
+            .map(|file| file.path.display().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(files(branch_id), ["file.txt"]);
+    assert!(
+        files(other_branch_id).is_empty(),
+        "the branch selected for changes doesn't get it"
+    );
+    Ok(())
+}
+
+#[test]
+fn restore_directories() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    let dir = repository.path().join("dir");
+    fs::create_dir_all(dir.join("sub"))?;
+    fs::write(dir.join("a.txt"), "a\n")?;
+    fs::write(dir.join("sub/b.txt"), "b\n")?;
+    let commit_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "add dir", None, false)?;
+
+    fs::write(dir.join("a.txt"), "changed\n")?;
+    fs::remove_file(dir.join("sub/b.txt"))?;
+    fs::write(dir.join("c.txt"), "c\n")?;
+
+    gitbutler_branch_actions::checkout_file_from_commit(project, commit_id, Path::new("dir"))?;
+    assert_eq!(fs::read_to_string(dir.join("a.txt"))?, "a\n");
+    assert_eq!(fs::read_to_string(dir.join("sub/b.txt"))?, "b\n");
+    assert!(
+        !dir.join("c.txt").exists(),
+        "files that aren't in the directory at the commit are deleted"
+    );
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert!(
+        branches.iter().all(|b| b.files.is_empty()),
+        "the directory is exactly as it was committed"
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn restore_file_mode() -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    let script = repository.path().join("script.sh");
+    fs::write(&script, "#!/bin/sh\n")?;
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+    std::os::unix::fs::symlink("script.sh", repository.path().join("link"))?;
+    let commit_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "add files", None, false)?;
+
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o644))?;
+    fs::remove_file(repository.path().join("link"))?;
+    fs::write(repository.path().join("link"), "not a link\n")?;
+
+    gitbutler_branch_actions::checkout_file_from_commit(
+        project,
+        commit_id,
+        Path::new("script.sh"),
+    )?;
+    gitbutler_branch_actions::checkout_file_from_commit(project, commit_id, Path::new("link"))?;
+
+    assert_eq!(fs::metadata(&script)?.permissions().mode() & 0o111, 0o111);
+    assert_eq!(
+        fs::read_link(repository.path().join("link"))?,
+        Path::new("script.sh")
+    );
+    Ok(())
+}
+
+#[test]
+fn reject_paths_outside_the_worktree() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let head = git2::Repository::open(repository.path())?
+        .head()?
+        .peel_to_commit()?
+        .id();
+    let err = gitbutler_branch_actions::checkout_file_from_commit(
+        project,
+        head,
+        Path::new("../file.txt"),
+    )
+    .unwrap_err();
+    assert!(format!("{err:#}").contains("must be relative to the worktree"));
+    Ok(())
+}
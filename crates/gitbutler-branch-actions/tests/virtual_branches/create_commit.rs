@@ -1,7 +1,11 @@
 use gitbutler_branch::{BranchCreateRequest, BranchUpdateRequest};
-use gitbutler_branch_actions::VirtualBranch;
+use gitbutler_branch_actions::{BehindUpstream, CommitOptions, MergeResolution, VirtualBranch};
+use gitbutler_command_context::CommandContext;
+use gitbutler_commit::commit_headers::HasCommitHeaders;
+use gitbutler_error::error::Code;
 use gitbutler_id::id::Id;
-use gitbutler_stack::Stack;
+use gitbutler_project::{CommitIssueKey, DefaultTrailer, DefaultTrailerValue};
+use gitbutler_stack::{CommitAuthor, Stack, VirtualBranchesHandle};
 
 use super::*;
 
@@ -115,6 +119,1124 @@ fn should_reset_into_same_branch() {
     assert_eq!(files.len(), 1);
 }
 
+#[test]
+fn commit_untracked_file_with_whole_file_claim() {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    repository.gen_file("tracked.txt", 3);
+    commit_and_push_initial(repository);
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )
+    .unwrap();
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())
+            .unwrap();
+
+    repository.write_file("tracked.txt", &["changed".to_string()]);
+    repository.write_file("untracked.txt", &["one".to_string(), "two".to_string()]);
+
+    let commit_id = gitbutler_branch_actions::create_commit(
+        project,
+        branch_id,
+        "add untracked file",
+        Some(&"untracked.txt:0-0".parse().unwrap()),
+        false,
+    )
+    .unwrap();
+
+    let committed_files = gitbutler_branch_actions::list_commit_files(project, commit_id).unwrap();
+    assert_eq!(committed_files.len(), 1);
+    assert_eq!(
+        committed_files[0].path.display().to_string(),
+        "untracked.txt"
+    );
+
+    let branch = get_virtual_branch(project, branch_id);
+    assert_eq!(branch.commits.len(), 1);
+    assert_eq!(branch.files.len(), 1);
+    assert_eq!(branch.files[0].path.display().to_string(), "tracked.txt");
+}
+
+#[test]
+fn commit_only_deletions_of_hunk() {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    fs::write(
+        repository.path().join("file.txt"),
+        "one\ntwo\nthree\nfour\nfive\n",
+    )
+    .unwrap();
+    commit_and_push_initial(repository);
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )
+    .unwrap();
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())
+            .unwrap();
+
+    fs::write(
+        repository.path().join("file.txt"),
+        "one\nTWO\nthree\nfive\n",
+    )
+    .unwrap();
+
+    let commit_id = gitbutler_branch_actions::create_commit(
+        project,
+        branch_id,
+        "remove lines",
+        Some(&"file.txt:1-5-".parse().unwrap()),
+        false,
+    )
+    .unwrap();
+
+    let commit = repository.find_commit(commit_id).unwrap();
+    let entry = commit
+        .tree()
+        .unwrap()
+        .get_path("file.txt".as_ref())
+        .unwrap();
+    let blob = repository.local_repository.find_blob(entry.id()).unwrap();
+    assert_eq!(blob.content(), b"one\nthree\nfive\n");
+
+    let branch = get_virtual_branch(project, branch_id);
+    assert_eq!(branch.files.len(), 1);
+    assert_eq!(branch.files[0].hunks.len(), 1);
+    let diff = branch.files[0].hunks[0].diff.to_string();
+    assert!(diff.contains("+TWO\n"), "{diff}");
+    assert!(!diff.contains("-four\n"), "{diff}");
+}
+
+#[test]
+fn commit_lines_within_hunk() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    let lines = |f: fn(u32) -> String| (1..=10).map(f).collect::<String>();
+    fs::write(
+        repository.path().join("test.txt"),
+        lines(|n| format!("line{n}\n")),
+    )?;
+    commit_and_push_initial(repository);
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(
+        repository.path().join("test.txt"),
+        lines(|n| format!("LINE{n}\n")),
+    )?;
+    let branch = get_virtual_branch(project, branch_id);
+    assert_eq!(
+        branch.files[0].hunks.len(),
+        1,
+        "a single hunk for lines 1 to 10"
+    );
+
+    let commit_id = gitbutler_branch_actions::create_commit(
+        project,
+        branch_id,
+        "lines 3 and 4",
+        Some(&"test.txt:3-4".parse()?),
+        false,
+    )?;
+
+    let commit = repository.find_commit(commit_id)?;
+    let entry = commit.tree()?.get_path("test.txt".as_ref())?;
+    let blob = repository.local_repository.find_blob(entry.id())?;
+    assert_eq!(
+        blob.content(),
+        b"line1\nline2\nLINE3\nLINE4\nline5\nline6\nline7\nline8\nline9\nline10\n",
+        "only the changes to lines 3 and 4 are committed, in place"
+    );
+
+    let branch = get_virtual_branch(project, branch_id);
+    assert_eq!(branch.files.len(), 1);
+    assert_eq!(branch.files[0].hunks.len(), 1);
+    let diff = branch.files[0].hunks[0].diff.to_string();
+    assert!(diff.contains("-line1\n"), "{diff}");
+    assert!(diff.contains("+LINE1\n"), "{diff}");
+    assert!(diff.contains("+LINE10\n"), "{diff}");
+    assert!(!diff.contains("+LINE3\n"), "{diff}");
+    assert!(!diff.contains("+LINE4\n"), "{diff}");
+    Ok(())
+}
+
+#[test]
+fn commit_disjoint_line_ranges_within_hunk() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    let lines = |f: fn(u32) -> String| (1..=10).map(f).collect::<String>();
+    fs::write(
+        repository.path().join("test.txt"),
+        lines(|n| format!("line{n}\n")),
+    )?;
+    commit_and_push_initial(repository);
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(
+        repository.path().join("test.txt"),
+        lines(|n| format!("LINE{n}\n")),
+    )?;
+    let branch = get_virtual_branch(project, branch_id);
+    assert_eq!(branch.files[0].hunks.len(), 1);
+
+    let commit_id = gitbutler_branch_actions::create_commit(
+        project,
+        branch_id,
+        "lines 3 and 6 to 7",
+        Some(&"test.txt:3-3,6-7".parse()?),
+        false,
+    )?;
+
+    let commit = repository.find_commit(commit_id)?;
+    let entry = commit.tree()?.get_path("test.txt".as_ref())?;
+    let blob = repository.local_repository.find_blob(entry.id())?;
+    assert_eq!(
+        blob.content(),
+        b"line1\nline2\nLINE3\nline4\nline5\nLINE6\nLINE7\nline8\nline9\nline10\n",
+        "both claimed ranges are committed, the lines between them are not"
+    );
+
+    let branch = get_virtual_branch(project, branch_id);
+    let diff = branch.files[0].hunks[0].diff.to_string();
+    assert!(diff.contains("+LINE4\n"), "{diff}");
+    assert!(diff.contains("+LINE5\n"), "{diff}");
+    assert!(!diff.contains("+LINE3\n"), "{diff}");
+    assert!(!diff.contains("+LINE6\n"), "{diff}");
+    assert!(!diff.contains("+LINE7\n"), "{diff}");
+    Ok(())
+}
+
+#[test]
+fn refuse_line_ranges_within_hunk_with_different_filters() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    let lines = |f: fn(u32) -> String| (1..=10).map(f).collect::<String>();
+    fs::write(
+        repository.path().join("test.txt"),
+        lines(|n| format!("line{n}\n")),
+    )?;
+    commit_and_push_initial(repository);
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(
+        repository.path().join("test.txt"),
+        lines(|n| format!("LINE{n}\n")),
+    )?;
+    let branch = get_virtual_branch(project, branch_id);
+    assert_eq!(branch.files[0].hunks.len(), 1);
+
+    let err = gitbutler_branch_actions::create_commit(
+        project,
+        branch_id,
+        "additions of line 3 and deletions of lines 6 to 7",
+        Some(&"test.txt:3-3+,6-7-".parse()?),
+        false,
+    )
+    .unwrap_err();
+    assert_eq!(err.downcast_ref::<Code>(), Some(&Code::Validation));
+
+    let branch = get_virtual_branch(project, branch_id);
+    assert!(branch.commits.is_empty(), "nothing was committed");
+    assert_eq!(branch.files[0].hunks.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn local_only_branch_can_not_be_committed_or_pushed() {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )
+    .unwrap();
+
+    let branch_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            local_only: Some(true),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    repository.write_file("local.txt", &["machine specific".to_string()]);
+
+    let branch = get_virtual_branch(project, branch_id);
+    assert!(branch.local_only);
+    assert_eq!(branch.files.len(), 1);
+
+    assert!(
+        gitbutler_branch_actions::create_commit(project, branch_id, "local", None, false).is_err()
+    );
+    assert!(
+        gitbutler_branch_actions::push_virtual_branch(project, branch_id, false, None).is_err()
+    );
+
+    let branch = get_virtual_branch(project, branch_id);
+    assert_eq!(branch.commits.len(), 0);
+    assert_eq!(branch.files.len(), 1);
+}
+
+#[test]
+fn refuse_to_commit_stray_conflict_markers() {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )
+    .unwrap();
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())
+            .unwrap();
+
+    repository.write_file(
+        "file.txt",
+        &[
+            "<<<<<<< ours".to_string(),
+            "one".to_string(),
+            "=======".to_string(),
+            "two".to_string(),
+            ">>>>>>> theirs".to_string(),
+        ],
+    );
+
+    let branch = get_virtual_branch(project, branch_id);
+    assert!(branch.files[0].hunks[0].conflict_markers);
+
+    let err = gitbutler_branch_actions::create_commit(project, branch_id, "markers", None, false)
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Code>(),
+        Some(&Code::CommitUnresolvedConflictMarkers)
+    );
+    assert_eq!(get_virtual_branch(project, branch_id).commits.len(), 0);
+
+    gitbutler_branch_actions::create_commit_with_options(
+        project,
+        branch_id,
+        "markers",
+        None,
+        &CommitOptions {
+            allow_conflict_markers: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(get_virtual_branch(project, branch_id).commits.len(), 1);
+}
+
+#[test]
+fn commit_identity_from_environment() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    // the same values are used by all tests that care about the environment
+    super::list::init_env();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    repository.write_file("file.txt", &["content".to_string()]);
+    let commit_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "env", None, false)?;
+
+    let commit = repository.find_commit(commit_id)?;
+    assert_eq!(commit.author().name(), Some("author"));
+    assert_eq!(commit.author().email(), Some("author@example.com"));
+    assert_eq!(commit.author().when().seconds(), 946684800);
+    assert_eq!(commit.committer().name(), Some("committer"));
+    assert_eq!(commit.committer().email(), Some("committer@example.com"));
+    assert_eq!(commit.committer().when().seconds(), 946771200);
+
+    repository.write_file("other.txt", &["content".to_string()]);
+    let explicit_author = git2::Signature::new(
+        "explicit",
+        "explicit@example.com",
+        &git2::Time::new(1_000_000_000, 0),
+    )?;
+    let commit_id = gitbutler_branch_actions::create_commit_with_options(
+        project,
+        branch_id,
+        "explicit",
+        None,
+        &CommitOptions {
+            author: Some(explicit_author),
+            ..Default::default()
+        },
+    )?;
+    let commit = repository.find_commit(commit_id)?;
+    assert_eq!(commit.author().name(), Some("explicit"));
+    assert_eq!(commit.author().when().seconds(), 1_000_000_000);
+    assert_eq!(
+        commit.committer().name(),
+        Some("committer"),
+        "the committer still comes from the environment"
+    );
+    Ok(())
+}
+
+#[test]
+fn commit_author_of_branch() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    // the same values are used by all tests that care about the environment
+    super::list::init_env();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    gitbutler_branch_actions::update_virtual_branch(
+        project,
+        BranchUpdateRequest {
+            id: branch_id,
+            commit_author: Some(CommitAuthor {
+                name: "pair".into(),
+                email: "pair@example.com".into(),
+            }),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(
+        get_virtual_branch(project, branch_id)
+            .commit_author
+            .map(|author| author.name),
+        Some("pair".into())
+    );
+
+    repository.write_file("file.txt", &["content".to_string()]);
+    let commit_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "paired", None, false)?;
+    let commit = repository.find_commit(commit_id)?;
+    assert_eq!(commit.author().name(), Some("pair"));
+    assert_eq!(commit.author().email(), Some("pair@example.com"));
+    assert_eq!(
+        commit.committer().name(),
+        Some("committer"),
+        "the committer still comes from the environment"
+    );
+    assert_eq!(commit.committer().email(), Some("committer@example.com"));
+
+    gitbutler_branch_actions::update_virtual_branch(
+        project,
+        BranchUpdateRequest {
+            id: branch_id,
+            commit_author: Some(CommitAuthor {
+                name: String::new(),
+                email: String::new(),
+            }),
+            ..Default::default()
+        },
+    )?;
+    repository.write_file("other.txt", &["content".to_string()]);
+    let commit_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "alone", None, false)?;
+    let commit = repository.find_commit(commit_id)?;
+    assert_eq!(
+        commit.author().name(),
+        Some("author"),
+        "without a branch author, the repository identity is used again"
+    );
+    Ok(())
+}
+
+#[test]
+fn clean_whitespace_in_committed_blobs_only() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    let dirty = "one  \ntwo\t\nthree ";
+    fs::write(repository.path().join("file.txt"), dirty)?;
+    let binary = b"bin  \n\0";
+    fs::write(repository.path().join("file.bin"), binary)?;
+
+    let commit_id = gitbutler_branch_actions::create_commit_with_options(
+        project,
+        branch_id,
+        "clean",
+        None,
+        &CommitOptions {
+            clean_whitespace: true,
+            ..Default::default()
+        },
+    )?;
+
+    let repo = git2::Repository::open(repository.path())?;
+    let tree = repo.find_commit(commit_id)?.tree()?;
+    let blob_content = |path: &str| -> anyhow::Result<Vec<u8>> {
+        let entry = tree.get_path(path::Path::new(path))?;
+        Ok(repo.find_blob(entry.id())?.content().to_vec())
+    };
+    assert_eq!(blob_content("file.txt")?, b"one\ntwo\nthree\n");
+    assert_eq!(
+        blob_content("file.bin")?,
+        binary,
+        "binary files are left alone"
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        dirty,
+        "the worktree isn't touched"
+    );
+    Ok(())
+}
+
+#[test]
+fn clean_whitespace_only_touches_changed_lines() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join("file.txt"), "one  \ntwo  \nthree")?;
+    fs::write(repository.path().join("notes.md"), "keep  \n")?;
+    fs::write(
+        repository.path().join(".editorconfig"),
+        "root = true\n[*.md]\ntrim_trailing_whitespace = false\n",
+    )?;
+    repository.commit_all("dirty");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("file.txt"), "one  \nTWO  \nthree")?;
+    fs::write(repository.path().join("notes.md"), "keep  \nadded  \n")?;
+
+    let commit_id = gitbutler_branch_actions::create_commit_with_options(
+        project,
+        branch_id,
+        "clean",
+        None,
+        &CommitOptions {
+            clean_whitespace: true,
+            ..Default::default()
+        },
+    )?;
+
+    let repo = git2::Repository::open(repository.path())?;
+    let tree = repo.find_commit(commit_id)?.tree()?;
+    let blob_content = |path: &str| -> anyhow::Result<Vec<u8>> {
+        let entry = tree.get_path(path::Path::new(path))?;
+        Ok(repo.find_blob(entry.id())?.content().to_vec())
+    };
+    assert_eq!(
+        blob_content("file.txt")?,
+        b"one  \nTWO\nthree",
+        "untouched lines keep their whitespace, and so does the untouched last line"
+    );
+    assert_eq!(
+        blob_content("notes.md")?,
+        b"keep  \nadded  \n",
+        ".editorconfig turns trimming off"
+    );
+    Ok(())
+}
+
+#[test]
+fn commit_requiring_final_newline_rejects_files_without_it() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join(".gitattributes"), "*.raw -text\n")?;
+    repository.commit_all("attributes");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("file.txt"), "no newline")?;
+    fs::write(repository.path().join("file.raw"), "no newline either")?;
+    fs::write(repository.path().join("file.bin"), b"bin\0")?;
+
+    let options = CommitOptions {
+        require_final_newline: true,
+        ..Default::default()
+    };
+    let err = gitbutler_branch_actions::create_commit_with_options(
+        project, branch_id, "strict", None, &options,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Code>(),
+        Some(&Code::CommitMissingFinalNewline)
+    );
+    assert!(format!("{err:#}").contains("file.txt doesn't end with a newline"));
+    assert_eq!(get_virtual_branch(project, branch_id).commits.len(), 0);
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        "no newline",
+        "nothing is fixed"
+    );
+
+    fs::write(repository.path().join("file.txt"), "newline\n")?;
+    gitbutler_branch_actions::create_commit_with_options(
+        project, branch_id, "strict", None, &options,
+    )?;
+    assert_eq!(get_virtual_branch(project, branch_id).commits.len(), 1);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn run_clean_filter_on_committed_blobs() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    let repo = git2::Repository::open(repository.path())?;
+    repo.config()?.set_str("filter.upper.clean", "tr a-z A-Z")?;
+    fs::write(
+        repository.path().join(".gitattributes"),
+        "*.up filter=upper\n",
+    )?;
+    fs::write(repository.path().join("file.up"), "hello\n")?;
+    fs::write(repository.path().join("file.txt"), "hello\n")?;
+
+    let commit_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "filtered", None, false)?;
+
+    let tree = repo.find_commit(commit_id)?.tree()?;
+    let blob_content = |path: &str| -> anyhow::Result<Vec<u8>> {
+        let entry = tree.get_path(path::Path::new(path))?;
+        Ok(repo.find_blob(entry.id())?.content().to_vec())
+    };
+    assert_eq!(blob_content("file.up")?, b"HELLO\n");
+    assert_eq!(
+        blob_content("file.txt")?,
+        b"hello\n",
+        "files without a filter are stored as they are"
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.up"))?,
+        "hello\n",
+        "the worktree isn't touched"
+    );
+    assert!(
+        get_virtual_branch(project, branch_id).files.is_empty(),
+        "the worktree file is filtered before comparing it to the commit, so nothing is left to commit"
+    );
+    Ok(())
+}
+
+#[test]
+fn refuse_to_commit_files_exceeding_max_size() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project_id,
+        projects,
+        ..
+    } = &Test::default();
+
+    let project = &projects.update(&projects::UpdateRequest {
+        id: *project_id,
+        max_commit_file_size: Some(10),
+        ..Default::default()
+    })?;
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("small.txt"), "small")?;
+    fs::write(repository.path().join("large.bin"), "more than ten bytes")?;
+
+    let err = gitbutler_branch_actions::create_commit(project, branch_id, "large", None, false)
+        .unwrap_err();
+    assert_eq!(err.downcast_ref::<Code>(), Some(&Code::CommitFileTooLarge));
+    assert!(format!("{err:#}").contains("large.bin"));
+    assert_eq!(get_virtual_branch(project, branch_id).commits.len(), 0);
+
+    gitbutler_branch_actions::create_commit_with_options(
+        project,
+        branch_id,
+        "large",
+        None,
+        &CommitOptions {
+            allow_large_files: true,
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(get_virtual_branch(project, branch_id).commits.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn strip_scissors_from_edited_template() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "content\n")?;
+
+    let template = gitbutler_branch_actions::commit_message_template(project, branch_id)?;
+    assert!(template.contains("# ------------------------ >8 ------------------------\n"));
+    assert!(template.contains("diff --git a/file.txt b/file.txt\n"));
+    assert!(template.contains("+content\n"));
+
+    let message = format!("subject\n\nbody\n{template}");
+    let commit_id = gitbutler_branch_actions::create_commit_with_options(
+        project,
+        branch_id,
+        &message,
+        None,
+        &CommitOptions {
+            strip_scissors: true,
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(
+        repository.find_commit(commit_id)?.message(),
+        Some("subject\n\nbody")
+    );
+    Ok(())
+}
+
+#[test]
+fn commit_with_message_template() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            name: Some("ABC-12-fix".into()),
+            ..Default::default()
+        },
+    )?;
+    fs::write(repository.path().join("file.txt"), "content\n")?;
+
+    let tmp = tempfile::tempdir()?;
+    let template_path = tmp.path().join("template.txt");
+    fs::write(
+        &template_path,
+        "Branch: {branch}\nTicket: {ticket}\nKeep: {unknown}\n\n{files}",
+    )?;
+
+    let options = CommitOptions {
+        message_template: Some(template_path),
+        ..Default::default()
+    };
+    let commit_id = gitbutler_branch_actions::create_commit_with_options(
+        project, branch_id, "subject", None, &options,
+    )?;
+    assert_eq!(
+        repository.find_commit(commit_id)?.message(),
+        Some("subject\n\nBranch: ABC-12-fix\nTicket: ABC-12\nKeep: {unknown}\n\nfile.txt")
+    );
+
+    fs::write(repository.path().join("file.txt"), "content2\n")?;
+    let err = gitbutler_branch_actions::create_commit_with_options(
+        project,
+        branch_id,
+        "subject",
+        None,
+        &CommitOptions {
+            fail_on_unknown_placeholders: true,
+            ..options
+        },
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("{unknown}"), "{err:#}");
+    Ok(())
+}
+
+#[test]
+fn commit_to_clean_branch_while_another_is_conflicted() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let clean_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("clean.txt"), "clean\n")?;
+    gitbutler_branch_actions::list_virtual_branches(project)?;
+
+    let conflicted_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+    )?;
+    fs::write(
+        repository.path().join("conflicted.txt"),
+        "<<<<<<< ours\na\n=======\nb\n>>>>>>> theirs\n",
+    )?;
+    gitbutler_branch_actions::list_virtual_branches(project)?;
+
+    // Simulate a conflict left behind by an upstream merge.
+    let repo = git2::Repository::open(repository.path())?;
+    let git_dir = repo.path();
+    fs::write(git_dir.join("conflicts"), "conflicted.txt\n")?;
+    let merge_parent = repo.head()?.peel_to_commit()?.id();
+    fs::write(git_dir.join("base_merge_parent"), merge_parent.to_string())?;
+
+    let commit_id =
+        gitbutler_branch_actions::create_commit(project, clean_id, "clean commit", None, false)?;
+    assert_eq!(
+        repository.find_commit(commit_id)?.parent_count(),
+        1,
+        "the merge parent belongs to the conflicted branch"
+    );
+    assert!(
+        git_dir.join("conflicts").exists(),
+        "the other branch is still conflicted"
+    );
+
+    let err = gitbutler_branch_actions::create_commit(
+        project,
+        conflicted_id,
+        "conflicted commit",
+        None,
+        false,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Code>(),
+        Some(&Code::CommitMergeConflictFailure)
+    );
+    Ok(())
+}
+
+#[test]
+fn change_id_trailer_is_written_and_preserved() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    git2::Repository::open(repository.path())?
+        .config()?
+        .set_bool("gitbutler.changeIdTrailer", true)?;
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "content\n")?;
+
+    gitbutler_branch_actions::create_commit(
+        project,
+        branch_id,
+        "subject\n\nbody\n\nChange-Id: I0123",
+        None,
+        false,
+    )?;
+    assert_eq!(
+        get_virtual_branch(project, branch_id).commits[0]
+            .change_id
+            .as_deref(),
+        Some("I0123"),
+        "an existing trailer is adopted as the change-id"
+    );
+
+    fs::write(repository.path().join("file.txt"), "other content\n")?;
+    let commit_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "subject", None, false)?;
+    let change_id = get_virtual_branch(project, branch_id).commits[0]
+        .change_id
+        .clone()
+        .expect("every GitButler commit has a change-id");
+    assert_eq!(
+        repository.find_commit(commit_id)?.message(),
+        Some(format!("subject\n\nChange-Id: {change_id}\n").as_str())
+    );
+
+    gitbutler_branch_actions::update_commit_message(project, branch_id, commit_id, "reworded")?;
+    let commit = &get_virtual_branch(project, branch_id).commits[0];
+    assert_eq!(commit.change_id.as_deref(), Some(change_id.as_str()));
+    assert_eq!(
+        repository.find_commit(commit.id)?.message(),
+        Some(format!("reworded\n\nChange-Id: {change_id}\n").as_str())
+    );
+    Ok(())
+}
+
+#[test]
+fn commit_to_branch_behind_its_upstream() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "content\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "mine", None, false)?;
+    gitbutler_branch_actions::push_virtual_branch(project, branch_id, false, None)?;
+
+    // A teammate pushes on top of it.
+    let upstream_name = get_virtual_branch(project, branch_id)
+        .upstream
+        .expect("pushed")
+        .name
+        .to_string();
+    let repo = git2::Repository::open(repository.path())?;
+    let pushed = repo.find_reference(&upstream_name)?.peel_to_commit()?;
+    let mut builder = git2::build::TreeUpdateBuilder::new();
+    builder.upsert(
+        "teammate.txt",
+        repo.blob(b"theirs\n")?,
+        git2::FileMode::Blob,
+    );
+    let tree = repo.find_tree(builder.create_updated(&repo, &pushed.tree()?)?)?;
+    let signature = git2::Signature::now("Teammate", "teammate@example.com")?;
+    let teammate_id = repo.commit(
+        Some(&upstream_name),
+        &signature,
+        &signature,
+        "theirs",
+        &tree,
+        &[&pushed],
+    )?;
+
+    fs::write(repository.path().join("file.txt"), "more content\n")?;
+    let err = gitbutler_branch_actions::create_commit_with_options(
+        project,
+        branch_id,
+        "more",
+        None,
+        &CommitOptions {
+            behind_upstream: BehindUpstream::Fail,
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Code>(),
+        Some(&Code::CommitBranchBehindUpstream)
+    );
+    assert_eq!(get_virtual_branch(project, branch_id).commits.len(), 1);
+
+    let commit_id = gitbutler_branch_actions::create_commit_with_options(
+        project,
+        branch_id,
+        "more",
+        None,
+        &CommitOptions {
+            behind_upstream: BehindUpstream::Integrate,
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(
+        repository.find_commit(commit_id)?.parent(0)?.id(),
+        teammate_id,
+        "the upstream commits were integrated first"
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("teammate.txt"))?,
+        "theirs\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn commit_merge_resolution_linearly() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("resolved.txt"), "resolved\n")?;
+
+    // Simulate an upstream merge whose conflicts were all resolved.
+    let repo = git2::Repository::open(repository.path())?;
+    let merge_parent = repo.head()?.peel_to_commit()?.id();
+    fs::write(
+        repo.path().join("base_merge_parent"),
+        merge_parent.to_string(),
+    )?;
+
+    let commit_id = gitbutler_branch_actions::create_commit_with_options(
+        project,
+        branch_id,
+        "resolution",
+        None,
+        &CommitOptions {
+            merge_resolution: MergeResolution::Linear,
+            ..Default::default()
+        },
+    )?;
+    let commit = repository.find_commit(commit_id)?;
+    assert_eq!(commit.parent_count(), 1);
+    assert_eq!(
+        commit
+            .gitbutler_headers()
+            .and_then(|headers| headers.integrated_upstream),
+        Some(merge_parent),
+        "the integrated upstream commit is recorded instead"
+    );
+    assert!(
+        !repo.path().join("base_merge_parent").exists(),
+        "the merge is concluded"
+    );
+    Ok(())
+}
+
+#[test]
+fn linear_merge_resolution_is_integrated_with_its_upstream() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("resolved.txt"), "resolved\n")?;
+
+    // The resolution integrates the target commit, which is already part of the target branch.
+    let repo = git2::Repository::open(repository.path())?;
+    let target_id = repo
+        .find_reference("refs/remotes/origin/master")?
+        .peel_to_commit()?
+        .id();
+    fs::write(repo.path().join("base_merge_parent"), target_id.to_string())?;
+    gitbutler_branch_actions::create_commit_with_options(
+        project,
+        branch_id,
+        "resolution",
+        None,
+        &CommitOptions {
+            merge_resolution: MergeResolution::Linear,
+            ..Default::default()
+        },
+    )?;
+
+    // Something new arrives upstream, so integration is checked at all.
+    let remote_repo = git2::Repository::open(repo.find_remote("origin")?.url().unwrap())?;
+    let remote_head = remote_repo
+        .find_reference("refs/heads/master")?
+        .peel_to_commit()?;
+    let mut tree = remote_repo.treebuilder(Some(&remote_head.tree()?))?;
+    tree.insert("upstream.txt", remote_repo.blob(b"upstream\n")?, 0o100644)?;
+    let tree = remote_repo.find_tree(tree.write()?)?;
+    let signature = git2::Signature::now("test", "test@example.com")?;
+    remote_repo.commit(
+        Some("refs/heads/master"),
+        &signature,
+        &signature,
+        "upstream",
+        &tree,
+        &[&remote_head],
+    )?;
+    gitbutler_branch_actions::fetch_from_remotes(project, None, None)?;
+
+    let branch = get_virtual_branch(project, branch_id);
+    assert_eq!(branch.commits.len(), 1);
+    assert!(
+        branch.commits[0].is_integrated,
+        "the upstream commit it integrated is part of the target"
+    );
+    Ok(())
+}
+
 fn commit_and_push_initial(repository: &TestProject) {
     repository.commit_all("initial commit");
     repository.push();
@@ -128,3 +1250,407 @@ fn get_virtual_branch(project: &Project, branch_id: Id<Stack>) -> VirtualBranch
         .find(|b| b.id == branch_id)
         .unwrap()
 }
+
+#[test]
+fn add_default_trailers_of_project() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project_id,
+        projects,
+        ..
+    } = &Test::default();
+
+    let project = &projects.update(&projects::UpdateRequest {
+        id: *project_id,
+        default_commit_trailers: Some(vec![
+            DefaultTrailer {
+                key: "Signed-off-by".into(),
+                value: DefaultTrailerValue::Committer,
+            },
+            DefaultTrailer {
+                key: "Team".into(),
+                value: DefaultTrailerValue::Fixed("core".into()),
+            },
+            DefaultTrailer {
+                key: "Change-Id".into(),
+                value: DefaultTrailerValue::ChangeId,
+            },
+        ]),
+        ..Default::default()
+    })?;
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    let committer = git2::Signature::now("committer", "committer@example.com")?;
+    fs::write(repository.path().join("file.txt"), "content")?;
+    let commit_id = gitbutler_branch_actions::create_commit_with_options(
+        project,
+        branch_id,
+        "subject\n\nsigned-off-by: committer <committer@example.com>\nTeam: core",
+        None,
+        &CommitOptions {
+            committer: Some(committer),
+            ..Default::default()
+        },
+    )?;
+
+    let commit = repository.find_commit(commit_id)?;
+    let message = commit.message().unwrap();
+    let (trailers, change_id) = message.rsplit_once("\nChange-Id: ").unwrap();
+    assert_eq!(
+        trailers, "subject\n\nsigned-off-by: committer <committer@example.com>\nTeam: core",
+        "trailers already in the message aren't added again"
+    );
+    assert_eq!(
+        commit.gitbutler_headers().map(|headers| headers.change_id),
+        Some(change_id.to_owned()),
+        "the generated change-id is also used for the headers"
+    );
+    Ok(())
+}
+
+#[test]
+fn prefix_issue_key_of_branch_name() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project_id,
+        projects,
+        ..
+    } = &Test::default();
+
+    let project = &projects.update(&projects::UpdateRequest {
+        id: *project_id,
+        commit_issue_key: Some(CommitIssueKey {
+            pattern: "[A-Z]+-[0-9]+".into(),
+            format: "[{key}] ".into(),
+        }),
+        ..Default::default()
+    })?;
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            name: Some("feature/PROJ-123-thing".into()),
+            ..Default::default()
+        },
+    )?;
+    let commit = |message| -> anyhow::Result<String> {
+        fs::write(repository.path().join("file.txt"), message)?;
+        let commit_id =
+            gitbutler_branch_actions::create_commit(project, branch_id, message, None, false)?;
+        Ok(repository
+            .find_commit(commit_id)?
+            .message()
+            .unwrap()
+            .to_owned())
+    };
+
+    assert_eq!(commit("do the thing")?, "[PROJ-123] do the thing");
+    assert_eq!(
+        commit("PROJ-123: do it again")?,
+        "PROJ-123: do it again",
+        "the key is only added once"
+    );
+    assert_eq!(
+        commit("follow up on PROJ-1234")?,
+        "[PROJ-123] follow up on PROJ-1234",
+        "other keys that start with the key don't count"
+    );
+
+    gitbutler_branch_actions::update_virtual_branch(
+        project,
+        BranchUpdateRequest {
+            id: branch_id,
+            name: Some("no-issue-here".into()),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(
+        commit("unrelated")?,
+        "unrelated",
+        "branch names without issue key leave the message alone"
+    );
+    Ok(())
+}
+
+#[test]
+fn ticket_placeholder_uses_the_issue_key_pattern() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project_id,
+        projects,
+        ..
+    } = &Test::default();
+
+    let project = &projects.update(&projects::UpdateRequest {
+        id: *project_id,
+        commit_issue_key: Some(CommitIssueKey {
+            pattern: "#([0-9]+)".into(),
+            format: "#{key} ".into(),
+        }),
+        ..Default::default()
+    })?;
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            name: Some("fix-#42-crash".into()),
+            ..Default::default()
+        },
+    )?;
+    fs::write(repository.path().join("file.txt"), "content\n")?;
+
+    let tmp = tempfile::tempdir()?;
+    let template_path = tmp.path().join("template.txt");
+    fs::write(&template_path, "Fixes: {ticket}")?;
+    let commit_id = gitbutler_branch_actions::create_commit_with_options(
+        project,
+        branch_id,
+        "subject",
+        None,
+        &CommitOptions {
+            message_template: Some(template_path),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(
+        repository.find_commit(commit_id)?.message(),
+        Some("subject\n\nFixes: 42"),
+        "the key isn't prefixed as the template already mentions it"
+    );
+    Ok(())
+}
+
+#[test]
+fn commit_split_across_branches() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch1_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("a.txt"), "a\n")?;
+    gitbutler_branch_actions::list_virtual_branches(project)?;
+    let branch2_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+    )?;
+    fs::write(repository.path().join("b.txt"), "b\n")?;
+
+    assert_eq!(
+        gitbutler_branch_actions::commit_split(
+            project,
+            &[
+                (branch1_id, "a.txt:1-2".parse()?, "a".into()),
+                (branch2_id, "a.txt:1-2\nb.txt:1-2".parse()?, "b".into()),
+            ],
+            false,
+        )
+        .unwrap_err()
+        .downcast_ref::<Code>(),
+        Some(&Code::Validation),
+        "claims must be disjoint"
+    );
+    assert_eq!(
+        gitbutler_branch_actions::commit_split(
+            project,
+            &[
+                (branch1_id, "a.txt:1-2".parse()?, "a".into()),
+                (branch2_id, "a.txt:5-6".parse()?, "b".into()),
+            ],
+            false,
+        )
+        .unwrap_err()
+        .downcast_ref::<Code>(),
+        Some(&Code::Validation),
+        "claims must refer to changes of their branch"
+    );
+    assert!(
+        get_virtual_branch(project, branch1_id).commits.is_empty(),
+        "nothing is committed if a claim is invalid"
+    );
+
+    let commit_ids = gitbutler_branch_actions::commit_split(
+        project,
+        &[
+            (branch1_id, "a.txt:1-2".parse()?, "a".into()),
+            (branch2_id, "b.txt:1-2".parse()?, "b".into()),
+        ],
+        false,
+    )?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    for ((branch_id, file), commit_id) in [(branch1_id, "a.txt"), (branch2_id, "b.txt")]
+        .into_iter()
+        .zip(commit_ids)
+    {
+        let branch = branches.iter().find(|b| b.id == branch_id).unwrap();
+        assert_eq!(branch.commits.len(), 1);
+        assert_eq!(branch.commits[0].id, commit_id);
+        assert!(branch.files.is_empty());
+        let tree = repository.find_commit(commit_id)?.tree()?;
+        assert!(tree.get_name(file).is_some());
+    }
+    Ok(())
+}
+
+#[test]
+fn commit_split_creates_no_commit_if_one_fails() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch1_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("a.txt"), "a\n")?;
+    gitbutler_branch_actions::list_virtual_branches(project)?;
+    let local_only_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            local_only: Some(true),
+            ..Default::default()
+        },
+    )?;
+    fs::write(repository.path().join("b.txt"), "b\n")?;
+
+    let err = gitbutler_branch_actions::commit_split(
+        project,
+        &[
+            (branch1_id, "a.txt:1-2".parse()?, "a".into()),
+            (local_only_id, "b.txt:1-2".parse()?, "b".into()),
+        ],
+        false,
+    )
+    .unwrap_err();
+    assert!(format!("{err:#}").contains("no commits were created"));
+
+    let branch1 = get_virtual_branch(project, branch1_id);
+    assert!(
+        branch1.commits.is_empty(),
+        "the first commit was rolled back"
+    );
+    assert_eq!(branch1.files.len(), 1);
+    assert_eq!(branch1.files[0].path, path::Path::new("a.txt"));
+    Ok(())
+}
+
+#[test]
+fn concurrent_commits_keep_the_index_intact() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch1_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("one.txt"), "one")?;
+    gitbutler_branch_actions::list_virtual_branches(project)?;
+
+    let branch2_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+    )?;
+    fs::write(repository.path().join("two.txt"), "two")?;
+
+    std::thread::scope(|scope| {
+        let commits: Vec<_> = [(branch1_id, "one"), (branch2_id, "two")]
+            .into_iter()
+            .map(|(branch_id, message)| {
+                scope.spawn(move || {
+                    gitbutler_branch_actions::create_commit(
+                        project, branch_id, message, None, false,
+                    )
+                    .unwrap();
+                })
+            })
+            .collect();
+        // Readers that don't take the worktree lock, like the file watcher or `git` itself,
+        // must never see a partially written index while the commits are in progress.
+        let worktree_dir = repository.path();
+        scope.spawn(move || {
+            while !commits.iter().all(|commit| commit.is_finished()) {
+                let repo = git2::Repository::open(worktree_dir).unwrap();
+                repo.index().unwrap().write_tree().unwrap();
+            }
+        });
+    });
+
+    let repo = git2::Repository::open(repository.path())?;
+    let index_tree = repo.index()?.write_tree()?;
+    assert_eq!(
+        index_tree,
+        repo.head()?.peel_to_commit()?.tree_id(),
+        "the index matches the workspace commit after both commits"
+    );
+    let branches = gitbutler_branch_actions::list_virtual_branches(project)?.0;
+    assert!(branches
+        .iter()
+        .all(|branch| branch.files.is_empty() && branch.commits.len() == 1));
+    Ok(())
+}
+
+#[test]
+fn index_locked_by_another_process_is_left_alone() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "content")?;
+
+    // another process, like `git add`, is writing the index
+    let git_dir = repository.path().join(".git");
+    let index_before = fs::read(git_dir.join("index"))?;
+    let lock_path = git_dir.join("index.lock");
+    fs::write(&lock_path, "being written by someone else")?;
+
+    let err = gitbutler_branch_actions::create_commit(project, branch_id, "commit", None, false)
+        .unwrap_err();
+    assert!(
+        format!("{err:#}").contains("locked by another process"),
+        "{err:#}"
+    );
+    assert_eq!(
+        fs::read_to_string(&lock_path)?,
+        "being written by someone else",
+        "the lock of the other process is respected"
+    );
+    assert_eq!(fs::read(git_dir.join("index"))?, index_before);
+
+    fs::remove_file(&lock_path)?;
+    let ctx = CommandContext::open(project)?;
+    gitbutler_branch_actions::update_workspace_commit(
+        &VirtualBranchesHandle::new(project.gb_dir()),
+        &ctx,
+    )?;
+    let repo = git2::Repository::open(repository.path())?;
+    assert_eq!(
+        repo.index()?.write_tree()?,
+        repo.head()?.peel_to_commit()?.tree_id(),
+        "once the lock is released, the index is written"
+    );
+    Ok(())
+}
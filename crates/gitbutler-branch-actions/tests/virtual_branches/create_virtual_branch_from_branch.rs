@@ -91,7 +91,7 @@ fn integration() {
 
     {
         // should mark commits as integrated
-        gitbutler_branch_actions::fetch_from_remotes(project, None).unwrap();
+        gitbutler_branch_actions::fetch_from_remotes(project, None, None).unwrap();
 
         let branch = gitbutler_branch_actions::list_virtual_branches(project)
             .unwrap()
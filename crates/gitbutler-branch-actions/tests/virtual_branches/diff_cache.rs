@@ -0,0 +1,56 @@
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_diff::DiffCache;
+
+use super::*;
+
+#[test]
+fn only_changed_files_are_diffed_again() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    for n in 0..10 {
+        fs::write(
+            repository.path().join(format!("file{n}.txt")),
+            format!("content{n}\n"),
+        )?;
+    }
+    let repo = &repository.local_repository;
+    let head = repo.head()?.peel_to_commit()?.id();
+
+    let mut cache = DiffCache::default();
+    let files = gitbutler_diff::workdir_cached(repo, head, Default::default(), &mut cache)?;
+    assert_eq!(files.len(), 10);
+    assert_eq!(cache.files_diffed(), 10);
+
+    fs::write(repository.path().join("file3.txt"), "changed\n")?;
+    let files = gitbutler_diff::workdir_cached(repo, head, Default::default(), &mut cache)?;
+    assert_eq!(cache.files_diffed(), 1, "only the edited file is diffed");
+    assert_eq!(files, gitbutler_diff::workdir(repo, head)?);
+
+    let cache_path = repository.path().join("diff_cache.json");
+    cache.save(&cache_path)?;
+    let mut cache = DiffCache::load(&cache_path);
+    fs::remove_file(&cache_path)?;
+    gitbutler_diff::workdir_cached(repo, head, Default::default(), &mut cache)?;
+    assert_eq!(cache.files_diffed(), 0, "the cache survives a round-trip");
+    cache.save(&cache_path)?;
+    assert!(!cache_path.exists(), "an unchanged cache isn't written");
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(branches[0].files.len(), 10);
+    fs::write(repository.path().join("file3.txt"), "changed again\n")?;
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let file = branches[0]
+        .files
+        .iter()
+        .find(|file| file.path == path::Path::new("file3.txt"))
+        .expect("file is listed");
+    assert_eq!(file.hunks[0].diff, "@@ -0,0 +1 @@\n+changed again\n");
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_oplog::OplogExt;
+
+use super::*;
+
+#[test]
+fn discard_uncommitted_changes_of_all_branches() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join("tracked.txt"), "original\n")?;
+    repository.commit_all("add file");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch1_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("committed.txt"), "committed\n")?;
+    gitbutler_branch_actions::create_commit(project, branch1_id, "commit", None, false)?;
+    fs::write(repository.path().join("tracked.txt"), "changed\n")?;
+    gitbutler_branch_actions::list_virtual_branches(project)?;
+
+    gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+    )?;
+    fs::write(repository.path().join("new.txt"), "new\n")?;
+
+    assert_eq!(gitbutler_branch_actions::discard_all_changes(project)?, 2);
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert!(branches.iter().all(|branch| branch.files.is_empty()));
+    let branch1 = branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert_eq!(branch1.commits.len(), 1, "commits are left alone");
+    assert_eq!(
+        fs::read_to_string(repository.path().join("tracked.txt"))?,
+        "original\n"
+    );
+    assert!(!repository.path().join("new.txt").exists());
+    assert!(repository.path().join("committed.txt").exists());
+
+    let snapshots = project.list_snapshots(10, None)?;
+    gitbutler_branch_actions::restore_snapshot(project, snapshots[0].commit_id)?;
+    assert_eq!(
+        fs::read_to_string(repository.path().join("tracked.txt"))?,
+        "changed\n"
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("new.txt"))?,
+        "new\n"
+    );
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_command_context::CommandContext;
+use gitbutler_stack::VirtualBranchesHandle;
+
+use super::*;
+
+#[test]
+fn copies_commits_but_not_uncommitted_changes() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "one")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "first", None, false)?;
+    fs::write(repository.path().join("file.txt"), "two")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "second", None, false)?;
+    fs::write(repository.path().join("uncommitted.txt"), "not yet")?;
+
+    let copy_id = gitbutler_branch_actions::duplicate_branch(
+        project,
+        branch_id,
+        BranchCreateRequest::default(),
+    )?;
+    assert_ne!(copy_id, branch_id);
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let original = branches.iter().find(|b| b.id == branch_id).unwrap();
+    let copy = branches.iter().find(|b| b.id == copy_id).unwrap();
+
+    assert_eq!(copy.name, format!("{} copy", original.name));
+    let descriptions = |branch: &gitbutler_branch_actions::VirtualBranch| {
+        branch
+            .commits
+            .iter()
+            .map(|c| c.description.to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(descriptions(copy), ["second", "first"]);
+    assert_eq!(descriptions(original), ["second", "first"]);
+    for (copied, source) in copy.commits.iter().zip(&original.commits) {
+        assert_ne!(copied.id, source.id, "copies are new commit objects");
+        assert_ne!(copied.change_id, source.change_id);
+        assert_eq!(
+            repository.find_commit(copied.id)?.tree_id(),
+            repository.find_commit(source.id)?.tree_id()
+        );
+    }
+
+    assert_eq!(original.files.len(), 1, "uncommitted changes stay put");
+    assert!(copy.files.is_empty());
+    Ok(())
+}
+
+#[test]
+fn copies_keep_all_parents_of_merge_commits() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "one")?;
+    let first_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "first", None, false)?;
+
+    // merge a commit that isn't part of the branch into it
+    let repo = &repository.local_repository;
+    let signature = git2::Signature::now("test", "test@example.com")?;
+    let first = repo.find_commit(first_id)?;
+    let mut builder = repo.treebuilder(Some(&first.tree()?))?;
+    builder.insert("side.txt", repo.blob(b"side")?, 0o100644)?;
+    let side_tree = repo.find_tree(builder.write()?)?;
+    let side_id = repo.commit(None, &signature, &signature, "side", &side_tree, &[&first])?;
+    let side = repo.find_commit(side_id)?;
+    let merge_id = repo.commit(
+        None,
+        &signature,
+        &signature,
+        "merge",
+        &side_tree,
+        &[&first, &side],
+    )?;
+    fs::write(repository.path().join("side.txt"), "side")?;
+    let ctx = CommandContext::open(project)?;
+    let mut stack = VirtualBranchesHandle::new(project.gb_dir()).get_branch(branch_id)?;
+    stack.set_stack_head(&ctx, merge_id, Some(side_tree.id()))?;
+
+    let copy_id = gitbutler_branch_actions::duplicate_branch(
+        project,
+        branch_id,
+        BranchCreateRequest::default(),
+    )?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let copy = branches.iter().find(|b| b.id == copy_id).unwrap();
+    assert_eq!(copy.commits.len(), 2);
+    let copied_merge = repo.find_commit(copy.commits[0].id)?;
+    assert_ne!(copied_merge.id(), merge_id);
+    assert_eq!(
+        copied_merge.parent_ids().collect::<Vec<_>>(),
+        [copy.commits[1].id, side_id],
+        "the first parent is remapped to its copy, the merged one is kept"
+    );
+    assert_ne!(copy.commits[1].id, first_id);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn signs_copies_if_commits_are_signed() -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "one")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "first", None, false)?;
+
+    let program = repository.path().join(".git/fake-gpg");
+    fs::write(
+        &program,
+        "#!/bin/sh\ncat > /dev/null\nprintf -- '-----BEGIN PGP SIGNATURE-----\\n\\nstub\\n-----END PGP SIGNATURE-----\\n'\n",
+    )?;
+    fs::set_permissions(&program, fs::Permissions::from_mode(0o755))?;
+    let repo = git2::Repository::open(repository.path())?;
+    let mut config = repo.config()?;
+    config.set_str("gpg.format", "openpgp")?;
+    config.set_str("gpg.program", program.to_str().unwrap())?;
+    config.set_str("user.signingkey", "ABCDEF0123456789")?;
+    config.set_bool("gitbutler.signCommits", true)?;
+
+    let copy_id = gitbutler_branch_actions::duplicate_branch(
+        project,
+        branch_id,
+        BranchCreateRequest::default(),
+    )?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let copy = branches.iter().find(|b| b.id == copy_id).unwrap();
+    assert_eq!(copy.commits.len(), 1);
+    let (signature, _signed_data) = repo.extract_signature(&copy.commits[0].id, None)?;
+    assert!(signature.as_str().unwrap().contains("PGP SIGNATURE"));
+
+    fs::write(&program, "#!/bin/sh\nexit 1\n")?;
+    assert!(gitbutler_branch_actions::duplicate_branch(
+        project,
+        branch_id,
+        BranchCreateRequest::default(),
+    )
+    .is_err());
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(
+        branches.len(),
+        2,
+        "no empty branch is left behind if the copies can't be signed"
+    );
+    Ok(())
+}
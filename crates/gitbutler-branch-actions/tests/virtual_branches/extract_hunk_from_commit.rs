@@ -0,0 +1,168 @@
+use gitbutler_branch::BranchCreateRequest;
+
+use super::*;
+
+#[test]
+fn applies_only_the_claimed_hunk() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    let lines = (1..=20).map(|n| format!("line {n}\n")).collect::<Vec<_>>();
+    fs::write(repository.path().join("file.txt"), lines.concat())?;
+    repository.commit_all("lines");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    // a commit that isn't part of the workspace, changing the second and the eighteenth line.
+    let repo = &repository.local_repository;
+    let base = repo.head()?.peel_to_commit()?;
+    let mut changed = lines.clone();
+    changed[1] = "changed 2\n".into();
+    changed[17] = "changed 18\n".into();
+    let mut tree = repo.treebuilder(Some(&base.tree()?))?;
+    tree.insert(
+        "file.txt",
+        repo.blob(changed.concat().as_bytes())?,
+        git2::FileMode::Blob.into(),
+    )?;
+    let signature = git2::Signature::now("test", "test@example.com")?;
+    let commit_oid = repo.commit(
+        None,
+        &signature,
+        &signature,
+        "historical",
+        &repo.find_tree(tree.write()?)?,
+        &[&base],
+    )?;
+
+    assert!(gitbutler_branch_actions::extract_hunk_from_commit(
+        project,
+        commit_oid,
+        &"file.txt:8-9".parse()?,
+        branch_id,
+    )
+    .is_err());
+
+    gitbutler_branch_actions::extract_hunk_from_commit(
+        project,
+        commit_oid,
+        &"file.txt:15-21".parse()?,
+        branch_id,
+    )?;
+    let mut expected = lines.clone();
+    expected[17] = "changed 18\n".into();
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        expected.concat()
+    );
+    assert_eq!(
+        repo.find_commit(commit_oid)?.message(),
+        Some("historical"),
+        "the source commit is left alone"
+    );
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let branch = branches.iter().find(|b| b.id == branch_id).unwrap();
+    assert_eq!(branch.files.len(), 1);
+    assert_eq!(branch.files[0].hunks.len(), 1);
+
+    let err = gitbutler_branch_actions::extract_hunk_from_commit(
+        project,
+        commit_oid,
+        &"file.txt:15-21".parse()?,
+        branch_id,
+    )
+    .unwrap_err();
+    assert!(format!("{err:#}").contains("conflict with the current content of file.txt"));
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        expected.concat(),
+        "nothing changes on conflict"
+    );
+    Ok(())
+}
+
+#[test]
+fn refuses_hunks_that_merge_with_changes_of_another_branch() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    let lines = (1..=20).map(|n| format!("line {n}\n")).collect::<Vec<_>>();
+    fs::write(repository.path().join("file.txt"), lines.concat())?;
+    repository.commit_all("lines");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let other_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    let mut uncommitted = lines.clone();
+    uncommitted[3] = "changed 4\n".into();
+    fs::write(repository.path().join("file.txt"), uncommitted.concat())?;
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let other = branches.iter().find(|b| b.id == other_id).unwrap();
+    assert_eq!(other.files.len(), 1);
+
+    let branch_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+    )?;
+
+    // a commit that isn't part of the workspace, changing the second line, close to the
+    // uncommitted change of the other branch.
+    let repo = &repository.local_repository;
+    let base = repo.head()?.peel_to_commit()?;
+    let mut changed = lines.clone();
+    changed[1] = "changed 2\n".into();
+    let mut tree = repo.treebuilder(Some(&base.tree()?))?;
+    tree.insert(
+        "file.txt",
+        repo.blob(changed.concat().as_bytes())?,
+        git2::FileMode::Blob.into(),
+    )?;
+    let signature = git2::Signature::now("test", "test@example.com")?;
+    let commit_oid = repo.commit(
+        None,
+        &signature,
+        &signature,
+        "historical",
+        &repo.find_tree(tree.write()?)?,
+        &[&base],
+    )?;
+
+    let err = gitbutler_branch_actions::extract_hunk_from_commit(
+        project,
+        commit_oid,
+        &"file.txt:1-5".parse()?,
+        branch_id,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.downcast_ref(),
+        Some(&gitbutler_error::error::Code::Validation)
+    );
+    assert!(format!("{err:#}").contains("owned by another branch"));
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        uncommitted.concat(),
+        "the worktree is restored"
+    );
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let branch = branches.iter().find(|b| b.id == branch_id).unwrap();
+    assert!(branch.files.is_empty());
+    let other = branches.iter().find(|b| b.id == other_id).unwrap();
+    assert_eq!(other.files[0].hunks.len(), 1);
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use super::*;
+
+#[test]
+fn honors_narrowed_fetch_refspecs_of_a_remote() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+
+    let repo = git2::Repository::open(repository.path())?;
+    repo.config()?.set_multivar(
+        "remote.origin.fetch",
+        ".*",
+        "+refs/heads/master:refs/remotes/origin/master",
+    )?;
+
+    // A teammate advances the target and pushes another branch.
+    let remote_url = repo.find_remote("origin")?.url().unwrap().to_owned();
+    let remote_repo = git2::Repository::open(remote_url)?;
+    let head = remote_repo.head()?.peel_to_commit()?;
+    let signature = git2::Signature::now("test", "test@example.com")?;
+    let new_head = remote_repo.commit(
+        Some("refs/heads/master"),
+        &signature,
+        &signature,
+        "upstream change",
+        &head.tree()?,
+        &[&head],
+    )?;
+    remote_repo.reference("refs/heads/feature", new_head, false, "")?;
+
+    let result = gitbutler_branch_actions::fetch_from_remotes(project, None, None)?;
+    assert!(
+        matches!(result, gitbutler_project::FetchResult::Fetched { .. }),
+        "{result:?}"
+    );
+
+    assert_eq!(
+        repo.refname_to_id("refs/remotes/origin/master")?,
+        new_head,
+        "the narrowed refspec still includes the target"
+    );
+    assert_eq!(
+        gitbutler_branch_actions::get_base_branch_data(project)?.current_sha,
+        new_head
+    );
+    assert!(
+        repo.find_reference("refs/remotes/origin/feature").is_err(),
+        "branches outside of the refspec aren't fetched"
+    );
+    Ok(())
+}
+
+#[test]
+fn honors_negative_fetch_refspecs_of_a_remote() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+
+    let repo = git2::Repository::open(repository.path())?;
+    // A value that matches none of the existing ones adds it.
+    repo.config()?
+        .set_multivar("remote.origin.fetch", "^$", "^refs/heads/wip/*")?;
+
+    let remote_url = repo.find_remote("origin")?.url().unwrap().to_owned();
+    let remote_repo = git2::Repository::open(remote_url)?;
+    let head = remote_repo.head()?.peel_to_commit()?.id();
+    remote_repo.reference("refs/heads/feature", head, false, "")?;
+    remote_repo.reference("refs/heads/wip/experiment", head, false, "")?;
+
+    gitbutler_branch_actions::fetch_from_remotes(project, None, None)?;
+
+    assert_eq!(repo.refname_to_id("refs/remotes/origin/feature")?, head);
+    assert!(
+        repo.find_reference("refs/remotes/origin/wip/experiment")
+            .is_err(),
+        "branches excluded by a negative refspec aren't fetched"
+    );
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_stack::OwnershipClaim;
+
+use super::*;
+
+#[test]
+fn content_before_and_after_the_change() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join("file.txt"), "a\nb\nc\n")?;
+    repository.commit_all("add file");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "a\nB\nc\n")?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let hunk = &branches[0].files[0].hunks[0];
+    let claim: OwnershipClaim = format!("file.txt:{}-{}", hunk.start, hunk.end).parse()?;
+    let content = gitbutler_branch_actions::hunk_content(project, &claim)?;
+    assert_eq!(content.old_start, 1);
+    assert_eq!(content.new_start, 1);
+    assert_eq!(content.before, ["a".into(), "b".into(), "c".into()]);
+    assert_eq!(content.after, ["a".into(), "B".into(), "c".into()]);
+
+    // A claim that is slightly off still resolves to the same hunk.
+    let shifted = gitbutler_branch_actions::hunk_content(project, &"file.txt:2-2".parse()?)?;
+    assert_eq!(shifted, content);
+
+    let err =
+        gitbutler_branch_actions::hunk_content(project, &"file.txt:100-110".parse()?).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "no uncommitted hunk matches file.txt:100-110"
+    );
+    Ok(())
+}
@@ -0,0 +1,512 @@
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_branch_actions::upstream_integration::{Resolution, ResolutionApproach};
+use gitbutler_command_context::CommandContext;
+use gitbutler_stack::VirtualBranchesHandle;
+
+use super::*;
+
+#[test]
+fn files_against_base_include_committed_changes() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("committed.txt"), "committed\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "commit", None, false)?;
+    fs::write(repository.path().join("uncommitted.txt"), "uncommitted\n")?;
+
+    let paths = |files_against_base| -> anyhow::Result<Vec<path::PathBuf>> {
+        let (branches, _) = gitbutler_branch_actions::list_virtual_branches_with_options(
+            project,
+            files_against_base,
+            Default::default(),
+        )?;
+        let mut paths: Vec<_> = branches[0]
+            .files
+            .iter()
+            .map(|file| file.path.clone())
+            .collect();
+        paths.sort();
+        Ok(paths)
+    };
+
+    assert_eq!(
+        paths(false)?,
+        [path::Path::new("uncommitted.txt")],
+        "by default, only uncommitted changes are listed"
+    );
+    assert_eq!(
+        paths(true)?,
+        [
+            path::Path::new("committed.txt"),
+            path::Path::new("uncommitted.txt")
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn files_against_base_use_the_recorded_base() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join("a.txt"), "a\n")?;
+    let first_commit_oid = repository.commit_all("first");
+    fs::write(repository.path().join("b.txt"), "b\n")?;
+    repository.commit_all("second");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("committed.txt"), "committed\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "commit", None, false)?;
+
+    // the branch is stacked on an older commit of the target
+    let vb_state = VirtualBranchesHandle::new(project.gb_dir());
+    let mut branch = vb_state.get_branch(branch_id)?;
+    branch.base_oid = Some(first_commit_oid);
+    vb_state.set_branch(branch)?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches_with_options(
+        project,
+        true,
+        Default::default(),
+    )?;
+    let mut paths: Vec<_> = branches[0]
+        .files
+        .iter()
+        .map(|file| file.path.clone())
+        .collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        [path::Path::new("b.txt"), path::Path::new("committed.txt")]
+    );
+    Ok(())
+}
+
+#[test]
+fn files_against_base_of_series_start_at_the_series_below() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("bottom.txt"), "bottom\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "bottom", None, false)?;
+
+    let ctx = CommandContext::open(project)?;
+    let mut stack = VirtualBranchesHandle::new(project.gb_dir()).get_branch(branch_id)?;
+    stack.add_series_top_of_stack(&ctx, "top-series".into(), None)?;
+    fs::write(repository.path().join("top.txt"), "top\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "top", None, false)?;
+    fs::write(repository.path().join("uncommitted.txt"), "uncommitted\n")?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches_with_options(
+        project,
+        true,
+        Default::default(),
+    )?;
+    let sorted = |paths: Vec<&path::PathBuf>| {
+        let mut paths: Vec<_> = paths.into_iter().cloned().collect();
+        paths.sort();
+        paths
+    };
+    assert_eq!(
+        sorted(branches[0].files.iter().map(|file| &file.path).collect()),
+        [
+            path::Path::new("bottom.txt"),
+            path::Path::new("top.txt"),
+            path::Path::new("uncommitted.txt")
+        ]
+    );
+    let series = &branches[0].series;
+    assert_eq!(series.len(), 2);
+    assert_eq!(
+        sorted(series[0].files.iter().map(|file| &file.path).collect()),
+        [
+            path::Path::new("top.txt"),
+            path::Path::new("uncommitted.txt")
+        ],
+        "the top series starts at the head of the bottom series"
+    );
+    assert_eq!(
+        sorted(series[1].files.iter().map(|file| &file.path).collect()),
+        [path::Path::new("bottom.txt")]
+    );
+    Ok(())
+}
+
+#[test]
+fn base_oid_follows_integration() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    // make sure we have an undiscovered commit in the remote branch
+    fs::write(repository.path().join("file.txt"), "one")?;
+    let first_commit_oid = repository.commit_all("first");
+    fs::write(repository.path().join("file.txt"), "two")?;
+    let second_commit_oid = repository.commit_all("second");
+    repository.push();
+    repository.reset_hard(Some(first_commit_oid));
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    let base_oid = || -> anyhow::Result<Option<git2::Oid>> {
+        let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+        Ok(branches[0].base_oid)
+    };
+    assert_eq!(base_oid()?, Some(first_commit_oid), "set on creation");
+
+    fs::write(repository.path().join("another_file.txt"), "virtual")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "virtual commit", None, false)?;
+    assert_eq!(
+        base_oid()?,
+        Some(first_commit_oid),
+        "committing keeps the base"
+    );
+
+    let branch = VirtualBranchesHandle::new(project.gb_dir()).get_branch(branch_id)?;
+    gitbutler_branch_actions::integrate_upstream(
+        project,
+        &[Resolution {
+            branch_id,
+            branch_tree: branch.tree,
+            approach: ResolutionApproach::Rebase,
+        }],
+        None,
+    )?;
+    assert_eq!(
+        base_oid()?,
+        Some(second_commit_oid),
+        "the rebased branch is stacked on the new target"
+    );
+    Ok(())
+}
+
+#[test]
+fn older_base_shows_later_target_changes_as_pending() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join("a.txt"), "a\n")?;
+    let first_commit_oid = repository.commit_all("first");
+    fs::write(repository.path().join("b.txt"), "b\n")?;
+    repository.commit_all("second");
+    fs::write(repository.path().join("c.txt"), "c\n")?;
+    repository.commit_all("third");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+
+    let repo = &repository.local_repository;
+    let signature = git2::Signature::now("test", "test@example.com")?;
+    let empty_tree = repo.find_tree(repo.treebuilder(None)?.write()?)?;
+    let unrelated = repo.commit(None, &signature, &signature, "unrelated", &empty_tree, &[])?;
+    let err = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            base_sha: Some(unrelated),
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "base commit {unrelated} isn't reachable from the target refs/remotes/origin/master"
+        )
+    );
+
+    let branch_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            base_sha: Some(first_commit_oid),
+            ..Default::default()
+        },
+    )?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let branch = branches.iter().find(|b| b.id == branch_id).unwrap();
+    assert_eq!(branch.base_oid, Some(first_commit_oid));
+    assert!(branch.commits.is_empty());
+    let mut paths: Vec<_> = branch.files.iter().map(|file| file.path.clone()).collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        [path::Path::new("b.txt"), path::Path::new("c.txt")],
+        "the changes of the target after the base are pending"
+    );
+    Ok(())
+}
+
+#[test]
+fn base_oid_is_backfilled_from_merge_base() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join("a.txt"), "a\n")?;
+    let first_commit_oid = repository.commit_all("first");
+    fs::write(repository.path().join("b.txt"), "b\n")?;
+    repository.commit_all("second");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            base_sha: Some(first_commit_oid),
+            ..Default::default()
+        },
+    )?;
+
+    // a branch stored before the base was tracked
+    let vb_state = VirtualBranchesHandle::new(project.gb_dir());
+    let mut branch = vb_state.get_branch(branch_id)?;
+    branch.base_oid = None;
+    branch.base_oid_computed = false;
+    vb_state.set_branch(branch)?;
+    assert_eq!(
+        vb_state.get_branch(branch_id)?.base_oid,
+        None,
+        "reading the state doesn't write to it"
+    );
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(
+        branches[0].base_oid,
+        Some(first_commit_oid),
+        "the merge base of the branch, not the target"
+    );
+    assert_eq!(
+        vb_state.get_branch(branch_id)?.base_oid,
+        Some(first_commit_oid)
+    );
+
+    // a branch whose base couldn't be found
+    let mut branch = vb_state.get_branch(branch_id)?;
+    branch.base_oid = None;
+    vb_state.set_branch(branch)?;
+    gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(
+        vb_state.get_branch(branch_id)?.base_oid,
+        None,
+        "the base is only backfilled once"
+    );
+    Ok(())
+}
+
+#[test]
+fn read_only_listing_leaves_state_untouched() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "content\n")?;
+    gitbutler_branch_actions::list_virtual_branches(project)?;
+
+    fn mtimes(
+        dir: &path::Path,
+        out: &mut Vec<(PathBuf, std::time::SystemTime)>,
+    ) -> anyhow::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                mtimes(&entry.path(), out)?;
+            } else {
+                out.push((entry.path(), entry.metadata()?.modified()?));
+            }
+        }
+        Ok(())
+    }
+    let snapshot = || -> anyhow::Result<_> {
+        let mut out = Vec::new();
+        mtimes(&project.gb_dir(), &mut out)?;
+        out.sort();
+        Ok(out)
+    };
+
+    fs::write(repository.path().join("another_file.txt"), "content\n")?;
+    let before = snapshot()?;
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches_read_only(project)?;
+    assert_eq!(before, snapshot()?, "nothing was written");
+
+    let mut paths: Vec<_> = branches[0]
+        .files
+        .iter()
+        .map(|file| file.path.clone())
+        .collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        [
+            path::Path::new("another_file.txt"),
+            path::Path::new("file.txt")
+        ],
+        "new changes are listed even though they aren't stored yet"
+    );
+    Ok(())
+}
+
+#[test]
+fn ahead_and_behind_the_target() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("one.txt"), "one\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "one", None, false)?;
+    fs::write(repository.path().join("two.txt"), "two\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "two", None, false)?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!((branches[0].ahead, branches[0].behind), (2, 0));
+
+    // the target branch advances by one commit
+    let repo = git2::Repository::open(repository.path())?;
+    let target_head = repo.find_commit(repo.refname_to_id("refs/remotes/origin/master")?)?;
+    let signature = git2::Signature::now("test", "test@email.com")?;
+    repo.commit(
+        Some("refs/remotes/origin/master"),
+        &signature,
+        &signature,
+        "remote change",
+        &target_head.tree()?,
+        &[&target_head],
+    )?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!((branches[0].ahead, branches[0].behind), (2, 1));
+    Ok(())
+}
+
+#[test]
+fn moved_lines_are_detected_if_requested() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    let moved_block = "fn moved_function() {\n    println!(\"relocated\");\n}\n";
+    let filler: String = (0..10).map(|n| format!("line {n}\n")).collect();
+    fs::write(
+        repository.path().join("file.rs"),
+        format!("{moved_block}{filler}"),
+    )?;
+    repository.commit_all("add file");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(
+        repository.path().join("file.rs"),
+        format!("{filler}{moved_block}"),
+    )?;
+
+    let moved_lines = |detect_moved_lines| -> anyhow::Result<Vec<usize>> {
+        let (branches, _) = gitbutler_branch_actions::list_virtual_branches_with_options(
+            project,
+            false,
+            gitbutler_diff::DiffOptions {
+                detect_moved_lines,
+                ..Default::default()
+            },
+        )?;
+        Ok(branches[0].files[0]
+            .hunks
+            .iter()
+            .map(|hunk| hunk.moved_lines.len())
+            .collect())
+    };
+
+    assert_eq!(moved_lines(false)?, [0, 0], "off by default");
+    assert_eq!(
+        moved_lines(true)?,
+        [3, 3],
+        "the removed and the added block are both annotated"
+    );
+    Ok(())
+}
+
+#[test]
+fn structured_lines_are_listed_if_requested() -> anyhow::Result<()> {
+    use gitbutler_diff::LineKind::*;
+
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join("file.txt"), "a\nb\nc\n")?;
+    repository.commit_all("add file");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "a\nB\nc\n")?;
+
+    let lines = |structured_lines| -> anyhow::Result<Vec<gitbutler_diff::DiffLine>> {
+        let (branches, _) = gitbutler_branch_actions::list_virtual_branches_with_options(
+            project,
+            false,
+            gitbutler_diff::DiffOptions {
+                structured_lines,
+                ..Default::default()
+            },
+        )?;
+        Ok(branches[0].files[0].hunks[0].lines.clone())
+    };
+
+    assert!(lines(false)?.is_empty(), "off by default");
+    let line = |kind, old_line, new_line, content: &str| gitbutler_diff::DiffLine {
+        kind,
+        old_line,
+        new_line,
+        content: content.into(),
+    };
+    assert_eq!(
+        lines(true)?,
+        [
+            line(Context, Some(1), Some(1), "a"),
+            line(Removed, Some(2), None, "b"),
+            line(Added, None, Some(2), "B"),
+            line(Context, Some(3), Some(3), "c"),
+        ]
+    );
+    Ok(())
+}
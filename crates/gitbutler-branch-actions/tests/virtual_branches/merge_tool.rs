@@ -0,0 +1,80 @@
+use std::path::Path;
+
+use gitbutler_branch::BranchCreateRequest;
+
+use super::*;
+
+#[test]
+fn write_sides_and_finalize_the_result() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "ours\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "ours", None, false)?;
+
+    // Record a conflicting merge of a commit that adds the same file differently.
+    let repo = git2::Repository::open(repository.path())?;
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let merge_base = repo.find_commit(branches[0].merge_base)?;
+    let mut tree = repo.treebuilder(Some(&merge_base.tree()?))?;
+    tree.insert("file.txt", repo.blob(b"theirs\n")?, 0o100644)?;
+    let tree = repo.find_tree(tree.write()?)?;
+    let signature = git2::Signature::now("test", "test@example.com")?;
+    let theirs = repo.commit(
+        None,
+        &signature,
+        &signature,
+        "theirs",
+        &tree,
+        &[&merge_base],
+    )?;
+    fs::write(repo.path().join("conflicts"), "file.txt\n")?;
+    fs::write(repo.path().join("base_merge_parent"), theirs.to_string())?;
+    fs::write(
+        repository.path().join("file.txt"),
+        "<<<<<<< ours\nours\n=======\ntheirs\n>>>>>>> theirs\n",
+    )?;
+
+    let sides =
+        gitbutler_branch_actions::write_conflict_sides(project, branch_id, Path::new("file.txt"))?;
+    assert_eq!(
+        fs::read_to_string(&sides.base)?,
+        "",
+        "absent in the merge base"
+    );
+    assert_eq!(fs::read_to_string(&sides.ours)?, "ours\n");
+    assert_eq!(fs::read_to_string(&sides.theirs)?, "theirs\n");
+
+    let err =
+        gitbutler_branch_actions::write_conflict_sides(project, branch_id, Path::new("other.txt"))
+            .unwrap_err();
+    assert_eq!(err.to_string(), "other.txt is not conflicting");
+
+    let merged = tempfile::NamedTempFile::new()?;
+    fs::write(merged.path(), "ours and theirs\n")?;
+    gitbutler_branch_actions::finalize_merge_tool_result(
+        project,
+        Path::new("file.txt"),
+        merged.path(),
+    )?;
+
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        "ours and theirs\n"
+    );
+    assert!(!sides.ours.exists(), "the sides are cleaned up");
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let file = &branches[0].files[0];
+    assert_eq!(file.path, Path::new("file.txt"));
+    assert!(
+        !file.conflicted,
+        "the file is no longer tracked as conflicting"
+    );
+    Ok(())
+}
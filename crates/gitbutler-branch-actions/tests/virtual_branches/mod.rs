@@ -56,28 +56,52 @@ impl Test {
 }
 
 mod amend;
+mod apply_remote_as_changes;
 mod apply_virtual_branch;
+mod branch_summary;
 mod branch_trees;
+mod branches_conflict;
+mod checkout_file_from_commit;
 mod create_commit;
 mod create_virtual_branch_from_branch;
+mod diff_cache;
+mod discard_all_changes;
+mod duplicate_branch;
+mod extract_hunk_from_commit;
+mod fetch_from_remotes;
+mod hunk_content;
 mod init;
 mod insert_blank_commit;
 mod list;
 mod list_details;
+mod list_virtual_branches;
 mod locking;
+mod merge_tool;
 mod move_commit_file;
 mod move_commit_to_vbranch;
+mod needs_integration;
 mod oplog;
+mod pr_diff;
+mod preview_reset_files;
+mod preview_unapply;
+mod prune_remotes;
+mod range_diff;
 mod references;
+mod rename_detection;
 mod reset_virtual_branch;
+mod retarget_branch;
 mod save_and_unapply_virtual_branch;
 mod selected_for_changes;
 mod set_base_branch;
 mod squash;
+mod stage_rename;
+mod status_since;
 mod unapply_ownership;
 mod unapply_without_saving_virtual_branch;
 mod undo_commit;
 mod update_commit_message;
 mod upstream;
+mod validate_ownership;
 mod verify_branch;
+mod workspace_diff;
 mod workspace_migration;
@@ -1,6 +1,8 @@
 use gitbutler_branch::BranchCreateRequest;
 use gitbutler_branch_actions::list_commit_files;
 use gitbutler_commit::commit_ext::CommitExt;
+use gitbutler_error::error::Code;
+use gitbutler_oplog::OplogExt;
 use gitbutler_stack::BranchOwnershipClaims;
 
 use super::*;
@@ -115,6 +117,89 @@ fn move_file_up() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn move_file_between_branches_is_rejected() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let base_id = repository
+        .local_repository
+        .refname_to_id("refs/remotes/origin/master")?;
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "content")?;
+    let commit1_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit one", None, false)?;
+    fs::write(repository.path().join("file2.txt"), "content2")?;
+    let commit2_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit two", None, false)?;
+
+    let other_branch_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+    )?;
+    fs::write(repository.path().join("other.txt"), "other")?;
+    let foreign_commit_id = gitbutler_branch_actions::create_commit(
+        project,
+        other_branch_id,
+        "foreign commit",
+        None,
+        false,
+    )?;
+
+    let snapshots_before = project.list_snapshots(100, None)?.len();
+    let to_amend: BranchOwnershipClaims = "file2.txt:1-2".parse()?;
+    for (from, to) in [
+        (commit2_id, foreign_commit_id),
+        (foreign_commit_id, commit1_id),
+        (commit2_id, base_id),
+    ] {
+        let err =
+            gitbutler_branch_actions::move_commit_file(project, branch_id, from, to, &to_amend)
+                .unwrap_err();
+        assert_eq!(err.downcast_ref::<Code>(), Some(&Code::Validation));
+    }
+    assert_eq!(
+        project.list_snapshots(100, None)?.len(),
+        snapshots_before,
+        "nothing was attempted"
+    );
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let commit_ids = |id| {
+        branches
+            .iter()
+            .find(|b| b.id == id)
+            .unwrap()
+            .commits
+            .iter()
+            .map(|c| c.id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(commit_ids(branch_id), [commit2_id, commit1_id]);
+    assert_eq!(commit_ids(other_branch_id), [foreign_commit_id]);
+
+    gitbutler_branch_actions::move_commit_file(
+        project, branch_id, commit2_id, commit1_id, &to_amend,
+    )?;
+    assert_eq!(
+        project.list_snapshots(100, None)?.len(),
+        snapshots_before + 1
+    );
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let branch = branches.iter().find(|b| b.id == branch_id).unwrap();
+    assert_eq!(list_commit_files(project, branch.commits[1].id)?.len(), 2);
+    Ok(())
+}
+
 // This test is failing because the file is not being moved up to the correct commit
 // This is out of scope for the first release, but should be fixed in the future
 // where you can take overlapping hunks between commits and resolve a move between them
@@ -0,0 +1,53 @@
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_stack::VirtualBranchesHandle;
+
+use super::*;
+
+#[test]
+fn branches_with_commits_behind_the_target_need_integration() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let with_commits_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "content")?;
+    gitbutler_branch_actions::create_commit(project, with_commits_id, "commit", None, false)?;
+    let empty_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert!(branches.iter().all(|b| !b.needs_integration));
+
+    // Let the target advance past the base of both branches.
+    let vb_state = VirtualBranchesHandle::new(project.gb_dir());
+    let mut target = vb_state.get_default_target()?;
+    let repo = git2::Repository::open(repository.path())?;
+    let old_target = repo.find_commit(target.sha)?;
+    let mut tree = repo.treebuilder(Some(&old_target.tree()?))?;
+    tree.insert("upstream.txt", repo.blob(b"upstream")?, 0o100644)?;
+    let tree = repo.find_tree(tree.write()?)?;
+    let signature = git2::Signature::now("test", "test@example.com")?;
+    target.sha = repo.commit(
+        None,
+        &signature,
+        &signature,
+        "upstream",
+        &tree,
+        &[&old_target],
+    )?;
+    vb_state.set_default_target(target)?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let with_commits = branches.iter().find(|b| b.id == with_commits_id).unwrap();
+    assert!(with_commits.needs_integration);
+    let empty = branches.iter().find(|b| b.id == empty_id).unwrap();
+    assert!(
+        !empty.needs_integration,
+        "without commits there is nothing to rebase"
+    );
+    Ok(())
+}
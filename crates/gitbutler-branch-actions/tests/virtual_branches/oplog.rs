@@ -2,7 +2,10 @@ use std::{io::Write, path::Path, time::Duration};
 
 use gitbutler_branch::BranchCreateRequest;
 use gitbutler_branch_actions::list_commit_files;
-use gitbutler_oplog::OplogExt;
+use gitbutler_oplog::{
+    entry::{OperationKind, SnapshotDetails},
+    OplogExt,
+};
 use gitbutler_stack::VirtualBranchesHandle;
 use itertools::Itertools;
 
@@ -88,6 +91,175 @@ fn workdir_vbranch_restore() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn snapshot_files_and_restore() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let base_sha = gitbutler_branch_actions::get_base_branch_data(project)?.current_sha;
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "content")?;
+    let commit_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit one", None, false)?;
+    gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    let repo = git2::Repository::open(repository.path())?;
+    let committed_blob = repo
+        .find_commit(commit_id)?
+        .tree()?
+        .get_path(Path::new("file.txt"))?
+        .id();
+    assert_eq!(repo.find_blob(committed_blob)?.content(), b"content");
+
+    let snapshots = project.list_snapshots(10, None)?;
+    assert_eq!(snapshots.len(), 3);
+
+    let files = gitbutler_branch_actions::snapshot_files(project, snapshots[0].commit_id)?;
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, Path::new("file.txt"));
+    assert_eq!(files[0].hunks.len(), 1);
+    assert_eq!(files[0].hunks[0].new_lines, 1);
+    let first_files = gitbutler_branch_actions::snapshot_files(project, snapshots[2].commit_id)?;
+    assert!(
+        first_files
+            .iter()
+            .all(|file| file.path != Path::new("file.txt")),
+        "the first snapshot has no predecessor and lists its files, taken before the file was written"
+    );
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(branches.len(), 2);
+    let branch = branches
+        .iter()
+        .find(|branch| branch.id == branch_id)
+        .unwrap();
+    assert_eq!(
+        branch
+            .commits
+            .iter()
+            .map(|commit| commit.id)
+            .collect::<Vec<_>>(),
+        vec![commit_id]
+    );
+
+    // restore to the state right before the commit was created
+    let restore_snapshot_id =
+        gitbutler_branch_actions::restore_snapshot(project, snapshots[1].commit_id)?;
+    let snapshots_after_restore = project.list_snapshots(10, None)?;
+    assert_eq!(snapshots_after_restore.len(), 4);
+    assert_eq!(
+        snapshots_after_restore[0].commit_id, restore_snapshot_id,
+        "the state before the restore is recorded in a new snapshot"
+    );
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(branches.len(), 1);
+    assert_eq!(branches[0].id, branch_id);
+    assert_eq!(branches[0].commits.len(), 0);
+    assert_eq!(
+        branches[0].head, base_sha,
+        "the branch points back to the base"
+    );
+    assert!(
+        repo.find_commit(branches[0].head)?
+            .tree()?
+            .get_path(Path::new("file.txt"))
+            .is_err(),
+        "the file isn't committed anymore"
+    );
+    assert_eq!(branches[0].files.len(), 1);
+    assert_eq!(branches[0].files[0].path, Path::new("file.txt"));
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        "content"
+    );
+    Ok(())
+}
+
+#[test]
+fn undo_last_operation() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "content")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "commit one", None, false)?;
+
+    // an operation that didn't change anything
+    project.create_snapshot(
+        SnapshotDetails::new(OperationKind::Unknown),
+        project.exclusive_worktree_access().write_permission(),
+    )?;
+
+    assert_eq!(
+        gitbutler_branch_actions::undo_last(project)?,
+        OperationKind::CreateCommit
+    );
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(branches.len(), 1);
+    assert_eq!(branches[0].commits.len(), 0, "the commit is gone");
+    assert_eq!(
+        branches[0]
+            .files
+            .iter()
+            .map(|file| file.path.as_path())
+            .collect::<Vec<_>>(),
+        [Path::new("file.txt")],
+        "its changes are uncommitted again"
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        "content"
+    );
+    Ok(())
+}
+
+#[test]
+fn undo_twice_undoes_two_operations() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("one.txt"), "one")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "commit one", None, false)?;
+    fs::write(repository.path().join("two.txt"), "two")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "commit two", None, false)?;
+
+    assert_eq!(
+        gitbutler_branch_actions::undo_last(project)?,
+        OperationKind::CreateCommit
+    );
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(branches[0].commits.len(), 1);
+    assert_eq!(branches[0].commits[0].description, "commit one");
+
+    assert_eq!(
+        gitbutler_branch_actions::undo_last(project)?,
+        OperationKind::CreateCommit,
+        "the second undo doesn't redo the first one"
+    );
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(branches[0].commits.len(), 0, "both commits are gone");
+    assert_eq!(branches[0].files.len(), 2);
+    Ok(())
+}
+
 fn wd_file_count(worktree_dir: &&Path) -> anyhow::Result<usize> {
     Ok(glob::glob(&worktree_dir.join("file*").to_string_lossy())?.count())
 }
@@ -0,0 +1,50 @@
+use gitbutler_branch::BranchCreateRequest;
+
+use super::*;
+
+#[test]
+fn only_lists_changes_since_the_merge_base() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    // make sure the target moves on after the branch was created
+    fs::write(repository.path().join("file.txt"), "one")?;
+    let first_commit_oid = repository.commit_all("first");
+    fs::write(repository.path().join("upstream.txt"), "upstream")?;
+    repository.commit_all("second");
+    repository.push();
+    repository.reset_hard(Some(first_commit_oid));
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    assert!(
+        gitbutler_branch_actions::pr_diff(project, branch_id)?.is_empty(),
+        "a branch without commits has nothing to show"
+    );
+
+    fs::write(repository.path().join("file.txt"), "two")?;
+    fs::write(repository.path().join("uncommitted.txt"), "not yet")?;
+    gitbutler_branch_actions::create_commit(
+        project,
+        branch_id,
+        "commit",
+        Some(&"file.txt:1-2".parse()?),
+        false,
+    )?;
+
+    let files = gitbutler_branch_actions::pr_diff(project, branch_id)?;
+    assert_eq!(
+        files
+            .iter()
+            .map(|file| file.path.as_path())
+            .collect::<Vec<_>>(),
+        [path::Path::new("file.txt")],
+        "neither upstream changes nor uncommitted ones are part of the PR"
+    );
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use bstr::ByteSlice;
+use gitbutler_branch::BranchCreateRequest;
+
+use super::*;
+
+#[test]
+fn lists_changes_without_touching_the_worktree() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join("modified.txt"), "old\n")?;
+    fs::write(repository.path().join("deleted.txt"), "gone soon\n")?;
+    repository.commit_all("add files");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("modified.txt"), "new\n")?;
+    fs::remove_file(repository.path().join("deleted.txt"))?;
+    fs::write(repository.path().join("untouched.txt"), "not asked for\n")?;
+
+    let mut files = gitbutler_branch_actions::preview_reset_files(
+        project,
+        branch_id,
+        &["modified.txt".into(), "deleted.txt".into()],
+    )?;
+    files.sort_by_key(|file| file.path.clone());
+
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].path, path::Path::new("deleted.txt"));
+    assert_eq!(files[0].change_type, gitbutler_diff::ChangeType::Deleted);
+    assert_eq!(files[1].path, path::Path::new("modified.txt"));
+    assert_eq!(
+        files[1].hunks[0].diff.to_str_lossy(),
+        "@@ -1 +1 @@\n-old\n+new\n"
+    );
+
+    assert_eq!(
+        fs::read_to_string(repository.path().join("modified.txt"))?,
+        "new\n"
+    );
+    assert!(!repository.path().join("deleted.txt").exists());
+    assert!(repository.path().join("untouched.txt").exists());
+    Ok(())
+}
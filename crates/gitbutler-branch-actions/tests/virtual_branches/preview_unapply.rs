@@ -0,0 +1,39 @@
+use gitbutler_branch::BranchCreateRequest;
+
+use super::*;
+
+#[test]
+fn lists_restored_and_removed_files_without_unapplying() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join("restored.txt"), "file2\n")?;
+    repository.commit_all("add file");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::remove_file(repository.path().join("restored.txt"))?;
+    fs::write(repository.path().join("removed.txt"), "file3\n")?;
+
+    let mut files = gitbutler_branch_actions::preview_unapply(project, branch_id)?;
+    files.sort_by_key(|file| file.path.clone());
+
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].path, path::Path::new("removed.txt"));
+    assert_eq!(files[0].change_type, gitbutler_diff::ChangeType::Deleted);
+    assert_eq!(files[1].path, path::Path::new("restored.txt"));
+    assert_eq!(files[1].change_type, gitbutler_diff::ChangeType::Added);
+
+    assert!(!repository.path().join("restored.txt").exists());
+    assert!(repository.path().join("removed.txt").exists());
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(branches.len(), 1);
+    assert_eq!(branches[0].files.len(), 2);
+    Ok(())
+}
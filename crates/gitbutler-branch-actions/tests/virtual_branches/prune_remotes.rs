@@ -0,0 +1,42 @@
+use gitbutler_reference::{LocalRefname, RemoteRefname};
+
+use super::*;
+
+#[test]
+fn prune_branches_deleted_on_the_remote() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    for name in ["refs/heads/gone", "refs/heads/kept"] {
+        let branch: LocalRefname = name.parse()?;
+        repository.checkout(&branch);
+        repository.push_branch(&branch);
+    }
+    repository.checkout(&"refs/heads/master".parse()?);
+    repository.fetch();
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+
+    // A teammate deletes the branch on the remote.
+    let repo = git2::Repository::open(repository.path())?;
+    let remote_url = repo.find_remote("origin")?.url().unwrap().to_owned();
+    git2::Repository::open(remote_url)?
+        .find_reference("refs/heads/gone")?
+        .delete()?;
+
+    let gone: RemoteRefname = "refs/remotes/origin/gone".parse()?;
+    assert_eq!(
+        gitbutler_branch_actions::prune_remotes(project, None)?,
+        [gone]
+    );
+    assert!(repo.find_reference("refs/remotes/origin/gone").is_err());
+    assert!(repo.find_reference("refs/remotes/origin/kept").is_ok());
+
+    assert!(
+        gitbutler_branch_actions::prune_remotes(project, None)?.is_empty(),
+        "nothing left to prune"
+    );
+    Ok(())
+}
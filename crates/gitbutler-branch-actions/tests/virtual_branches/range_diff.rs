@@ -0,0 +1,51 @@
+use gitbutler_branch::BranchCreateRequest;
+
+use super::*;
+
+#[test]
+fn lists_changes_between_two_commits_of_a_branch() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("one.txt"), "one")?;
+    let first = gitbutler_branch_actions::create_commit(project, branch_id, "first", None, false)?;
+    fs::write(repository.path().join("two.txt"), "two")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "second", None, false)?;
+    fs::write(repository.path().join("three.txt"), "three")?;
+    let third = gitbutler_branch_actions::create_commit(project, branch_id, "third", None, false)?;
+
+    let files = gitbutler_branch_actions::range_diff(project, branch_id, first, third)?;
+    let mut paths: Vec<_> = files.iter().map(|file| file.path.as_path()).collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        [path::Path::new("three.txt"), path::Path::new("two.txt")],
+        "changes of the first commit aren't included"
+    );
+
+    assert!(gitbutler_branch_actions::range_diff(project, branch_id, third, third)?.is_empty());
+
+    let err = gitbutler_branch_actions::range_diff(project, branch_id, third, first).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("commit {third} is not an ancestor of commit {first}")
+    );
+
+    let other_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let other_name = &branches.iter().find(|b| b.id == other_id).unwrap().name;
+    let err = gitbutler_branch_actions::range_diff(project, other_id, first, third).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("commit {first} is not part of branch {other_name}")
+    );
+    Ok(())
+}
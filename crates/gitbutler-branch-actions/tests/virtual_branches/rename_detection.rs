@@ -0,0 +1,141 @@
+use gitbutler_branch::BranchCreateRequest;
+
+use super::*;
+
+#[test]
+fn rename_with_changes_is_a_single_file() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    let content: String = (1..=10).map(|n| format!("line{n}\n")).collect();
+    fs::write(repository.path().join("file.txt"), &content)?;
+    repository.commit_all("add file");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::remove_file(repository.path().join("file.txt"))?;
+    let changed = content.replace("line5\n", "changed\n");
+    fs::write(repository.path().join("renamed.txt"), &changed)?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let files = &branches[0].files;
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, path::Path::new("renamed.txt"));
+    assert_eq!(files[0].hunks.len(), 1);
+    let hunk = &files[0].hunks[0];
+    assert_eq!(
+        hunk.renamed_from.as_deref(),
+        Some(path::Path::new("file.txt"))
+    );
+    assert!(hunk.diff.to_string().contains("-line5\n+changed\n"));
+    assert_eq!(
+        branches[0].ownership.claims[0].file_path,
+        path::Path::new("renamed.txt"),
+        "ownership is keyed on the new path"
+    );
+
+    let commit_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "rename", None, false)?;
+    let tree = repository.find_commit(commit_id)?.tree()?;
+    assert!(tree.get_path(path::Path::new("file.txt")).is_err());
+    let entry = tree.get_path(path::Path::new("renamed.txt"))?;
+    let blob = repository.local_repository.find_blob(entry.id())?;
+    assert_eq!(blob.content(), changed.as_bytes());
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert!(branches[0].files.is_empty());
+    Ok(())
+}
+
+#[test]
+fn binary_rename_is_a_single_file() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    let content: Vec<u8> = (0..4096).map(|n| (n % 256) as u8).collect();
+    fs::write(repository.path().join("image.bin"), &content)?;
+    repository.commit_all("add binary file");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::rename(
+        repository.path().join("image.bin"),
+        repository.path().join("moved.bin"),
+    )?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let files = &branches[0].files;
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, path::Path::new("moved.bin"));
+    assert_eq!(
+        files[0].hunks[0].renamed_from.as_deref(),
+        Some(path::Path::new("image.bin"))
+    );
+
+    let commit_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "move", None, false)?;
+    let tree = repository.find_commit(commit_id)?.tree()?;
+    assert!(tree.get_path(path::Path::new("image.bin")).is_err());
+    let entry = tree.get_path(path::Path::new("moved.bin"))?;
+    let blob = repository.local_repository.find_blob(entry.id())?;
+    assert_eq!(blob.content(), content.as_slice());
+    Ok(())
+}
+
+#[test]
+fn threshold_is_configurable() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    let content: String = (1..=10).map(|n| format!("line{n}\n")).collect();
+    fs::write(repository.path().join("file.txt"), &content)?;
+    repository.commit_all("add file");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::remove_file(repository.path().join("file.txt"))?;
+    fs::write(
+        repository.path().join("renamed.txt"),
+        content.replace("line5\n", "changed\n"),
+    )?;
+
+    let paths = || -> anyhow::Result<Vec<path::PathBuf>> {
+        let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+        let mut paths: Vec<_> = branches[0]
+            .files
+            .iter()
+            .map(|file| file.path.clone())
+            .collect();
+        paths.sort();
+        Ok(paths)
+    };
+    assert_eq!(paths()?, [path::Path::new("renamed.txt")]);
+
+    repository
+        .local_repository
+        .config()?
+        .set_i32(gitbutler_diff::RENAME_DETECTION_THRESHOLD, 100)?;
+    assert_eq!(
+        paths()?,
+        [path::Path::new("file.txt"), path::Path::new("renamed.txt")],
+        "files that aren't similar enough are a deletion and an addition"
+    );
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use gitbutler_branch::{BranchCreateRequest, BranchUpdateRequest};
+
+use super::*;
+
+#[test]
+fn integration_is_checked_against_the_new_target() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "fix")?;
+    let commit_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "fix", None, false)?;
+
+    // The fix was merged into the release branch, but not into master.
+    let repo = git2::Repository::open(repository.path())?;
+    repo.reference("refs/remotes/origin/release", commit_id, true, "release")?;
+
+    let is_integrated = || -> anyhow::Result<bool> {
+        let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+        let branch = branches.into_iter().find(|b| b.id == branch_id).unwrap();
+        assert_eq!(branch.head, commit_id, "commits are never rewritten");
+        Ok(branch.commits[0].is_integrated)
+    };
+    assert!(!is_integrated()?);
+
+    let retarget = |target: &str| {
+        gitbutler_branch_actions::update_virtual_branch(
+            project,
+            BranchUpdateRequest {
+                id: branch_id,
+                target: Some(target.into()),
+                ..Default::default()
+            },
+        )
+    };
+    retarget("refs/remotes/origin/release")?;
+    assert!(is_integrated()?);
+
+    retarget("refs/remotes/origin/master")?;
+    assert!(!is_integrated()?);
+
+    assert!(retarget("refs/remotes/origin/missing").is_err());
+    Ok(())
+}
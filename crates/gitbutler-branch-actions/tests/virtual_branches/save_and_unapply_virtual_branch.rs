@@ -48,3 +48,107 @@ fn delete_if_empty() {
     let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project).unwrap();
     assert_eq!(branches.len(), 0);
 }
+
+#[test]
+fn reapplying_a_renamed_branch_restores_its_stack() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let stack_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    std::fs::write(repository.path().join("file.txt"), "content")?;
+    gitbutler_branch_actions::create_commit(project, stack_id, "commit", None, false)?;
+
+    let refname = gitbutler_branch_actions::save_and_unapply_virutal_branch(project, stack_id)?;
+    let repo = git2::Repository::open(repository.path())?;
+    let mut branch = repo.find_branch(
+        refname.strip_prefix("refs/heads/").unwrap(),
+        git2::BranchType::Local,
+    )?;
+    branch.rename("renamed", false)?;
+    assert_eq!(
+        repo.config()?
+            .get_string("branch.renamed.gitbutlerStackId")?,
+        stack_id.to_string(),
+        "the stack id travels with the branch"
+    );
+
+    let restored_id = gitbutler_branch_actions::create_virtual_branch_from_branch(
+        project,
+        &"refs/heads/renamed".parse()?,
+        None,
+        None,
+    )?;
+    assert_eq!(restored_id, stack_id);
+    Ok(())
+}
+
+#[test]
+fn suggest_branch_ref_name_avoids_existing_branches() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &gitbutler_branch::BranchCreateRequest {
+            name: Some("my branch".into()),
+            ..Default::default()
+        },
+    )?;
+
+    let suggestion = gitbutler_branch_actions::suggest_branch_ref_name(project, branch_id)?;
+    assert_eq!(suggestion.as_str(), "refs/heads/my-branch");
+
+    let repo = git2::Repository::open(repository.path())?;
+    let head = repo.head()?.peel_to_commit()?;
+    repo.branch("my-branch", &head, false)?;
+    repo.branch("my-branch-2/nested", &head, false)?;
+
+    let suggestion = gitbutler_branch_actions::suggest_branch_ref_name(project, branch_id)?;
+    assert_eq!(suggestion.as_str(), "refs/heads/my-branch-3");
+
+    let refname = gitbutler_branch_actions::save_and_unapply_virutal_branch(project, branch_id)?;
+    assert_eq!(
+        refname, suggestion,
+        "the suggested name is the one that is used"
+    );
+    Ok(())
+}
+
+#[test]
+fn suggest_branch_ref_name_avoids_parents_of_existing_branches() -> anyhow::Result<()> {
+    let Test {
+        project,
+        repository,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &gitbutler_branch::BranchCreateRequest {
+            name: Some("feat/x".into()),
+            ..Default::default()
+        },
+    )?;
+
+    let repo = git2::Repository::open(repository.path())?;
+    let head = repo.head()?.peel_to_commit()?;
+    repo.branch("feat", &head, false)?;
+
+    let suggestion = gitbutler_branch_actions::suggest_branch_ref_name(project, branch_id)?;
+    assert_eq!(
+        suggestion.as_str(),
+        "refs/heads/feat-x",
+        "refs/heads/feat/x can't be created while refs/heads/feat exists"
+    );
+    Ok(())
+}
@@ -385,3 +385,171 @@ fn new_locked_hunk_without_modifying_existing() {
     assert_eq!(branches[0].files.len(), 1);
     assert_eq!(branches[1].files.len(), 1);
 }
+
+#[test]
+fn new_hunks_skip_frozen_branch() {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )
+    .unwrap();
+
+    let frozen_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())
+            .unwrap();
+    let other_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())
+            .unwrap();
+
+    std::fs::write(repository.path().join("file one.txt"), "one").unwrap();
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project).unwrap();
+    let frozen = branches.iter().find(|b| b.id == frozen_id).unwrap();
+    assert!(frozen.selected_for_changes);
+    assert_eq!(frozen.files.len(), 1);
+
+    gitbutler_branch_actions::update_virtual_branch(
+        project,
+        BranchUpdateRequest {
+            id: frozen_id,
+            frozen: Some(true),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    std::fs::write(repository.path().join("file two.txt"), "two").unwrap();
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project).unwrap();
+
+    let frozen = branches.iter().find(|b| b.id == frozen_id).unwrap();
+    assert!(frozen.frozen);
+    assert!(frozen.active, "a frozen branch stays applied");
+    assert_eq!(frozen.files.len(), 1, "it keeps what it already owned");
+    assert_eq!(frozen.files[0].path.display().to_string(), "file one.txt");
+
+    let other = branches.iter().find(|b| b.id == other_id).unwrap();
+    assert_eq!(other.files.len(), 1);
+    assert_eq!(other.files[0].path.display().to_string(), "file two.txt");
+
+    gitbutler_branch_actions::update_virtual_branch(
+        project,
+        BranchUpdateRequest {
+            id: frozen_id,
+            frozen: Some(false),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    std::fs::write(repository.path().join("file three.txt"), "three").unwrap();
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project).unwrap();
+    let thawed = branches.iter().find(|b| b.id == frozen_id).unwrap();
+    assert!(!thawed.frozen);
+    assert_eq!(
+        thawed.files.len(),
+        2,
+        "new hunks go to the selected branch again"
+    );
+}
+
+#[test]
+fn new_hunks_stay_unassigned_if_all_branches_are_frozen() {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )
+    .unwrap();
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())
+            .unwrap();
+    std::fs::write(repository.path().join("file one.txt"), "one").unwrap();
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project).unwrap();
+    assert_eq!(branches[0].files.len(), 1);
+    gitbutler_branch_actions::update_virtual_branch(
+        project,
+        BranchUpdateRequest {
+            id: branch_id,
+            frozen: Some(true),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    std::fs::write(repository.path().join("file two.txt"), "two").unwrap();
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project).unwrap();
+    assert_eq!(branches.len(), 1, "no branch is created for the new hunk");
+    assert_eq!(
+        branches[0].files.len(),
+        1,
+        "the frozen branch doesn't take it"
+    );
+    assert_eq!(
+        branches[0].files[0].path.display().to_string(),
+        "file one.txt"
+    );
+
+    gitbutler_branch_actions::update_virtual_branch(
+        project,
+        BranchUpdateRequest {
+            id: branch_id,
+            frozen: Some(false),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project).unwrap();
+    assert_eq!(
+        branches[0].files.len(),
+        2,
+        "once thawed, the branch picks up the unassigned hunk"
+    );
+}
+
+#[test]
+fn set_and_get_default_branch() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    assert_eq!(gitbutler_branch_actions::get_default_branch(project)?, None);
+
+    let b1_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    let b2_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    assert_eq!(
+        gitbutler_branch_actions::get_default_branch(project)?,
+        Some(b1_id)
+    );
+
+    gitbutler_branch_actions::set_default_branch(project, b2_id)?;
+    assert_eq!(
+        gitbutler_branch_actions::get_default_branch(project)?,
+        Some(b2_id)
+    );
+
+    std::fs::write(repository.path().join("file.txt"), "content")?;
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let b1 = branches.iter().find(|b| b.id == b1_id).unwrap();
+    let b2 = branches.iter().find(|b| b.id == b2_id).unwrap();
+    assert!(!b1.selected_for_changes, "only one branch holds the marker");
+    assert!(b2.selected_for_changes);
+    assert!(b1.files.is_empty());
+    assert_eq!(b2.files.len(), 1, "new changes go to the default branch");
+    Ok(())
+}
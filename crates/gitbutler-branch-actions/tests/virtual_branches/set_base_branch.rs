@@ -1,3 +1,8 @@
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_branch_actions::upstream_integration::{Resolution, ResolutionApproach};
+use gitbutler_reference::Refname;
+use gitbutler_stack::VirtualBranchesHandle;
+
 use super::*;
 
 #[test]
@@ -11,6 +16,109 @@ fn success() {
     .unwrap();
 }
 
+#[test]
+fn fork_with_upstream_remote() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+    let repo = &repository.local_repository;
+
+    // pull from `upstream`, push to the fork at `origin`
+    repo.remote_rename("origin", "upstream")?;
+    let (fork, _tmp) = gitbutler_testsupport::empty_bare_repository();
+    repo.remote("origin", fork.path().to_str().unwrap())?;
+
+    // make sure we have an undiscovered commit in the upstream branch
+    fs::write(repository.path().join("file.txt"), "one")?;
+    let first_commit_oid = repository.commit_all("first");
+    fs::write(repository.path().join("file.txt"), "two")?;
+    repository.commit_all("second");
+    repo.find_remote("upstream")?
+        .push(&["refs/heads/master:refs/heads/master"], None)?;
+    repository.reset_hard(Some(first_commit_oid));
+
+    let base = gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/upstream/master".parse()?,
+    )?;
+    assert_eq!(base.remote_name, "upstream");
+    assert_eq!(base.upstream_commits.len(), 1);
+    gitbutler_branch_actions::set_target_push_remote(project, "origin")?;
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("another_file.txt"), "virtual")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "virtual commit", None, false)?;
+
+    let pushed = gitbutler_branch_actions::push_virtual_branch(project, branch_id, false, None)?;
+    assert_eq!(pushed.remote, "origin");
+    let Refname::Remote(pushed_ref) = pushed.refname else {
+        panic!("pushing yields a remote reference")
+    };
+    assert_eq!(pushed_ref.remote(), "origin");
+    fork.find_reference(&format!("refs/heads/{}", pushed_ref.branch()))?;
+
+    let branch = VirtualBranchesHandle::new(project.gb_dir()).get_branch(branch_id)?;
+    gitbutler_branch_actions::integrate_upstream(
+        project,
+        &[Resolution {
+            branch_id,
+            branch_tree: branch.tree,
+            approach: ResolutionApproach::Rebase,
+        }],
+        None,
+    )?;
+
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        "two"
+    );
+    let base = gitbutler_branch_actions::get_base_branch_data(project)?;
+    assert_eq!(base.remote_name, "upstream");
+    assert_eq!(base.upstream_commits.len(), 0);
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(branches[0].commits.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn local_branch_with_different_name_follows_target() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+    let repo = &repository.local_repository;
+
+    gitbutler_branch_actions::set_base_branch_with_local_branch(
+        project,
+        &"refs/remotes/origin/master".parse()?,
+        Some("trunk"),
+    )?;
+    let target_head = repo.refname_to_id("refs/remotes/origin/master")?;
+    assert_eq!(repo.refname_to_id("refs/heads/trunk")?, target_head);
+    let trunk = repo.find_branch("trunk", git2::BranchType::Local)?;
+    assert_eq!(trunk.upstream()?.name()?, Some("origin/master"));
+
+    // a new commit shows up in the target branch
+    let signature = git2::Signature::now("test", "test@email.com")?;
+    let head_commit = repo.find_commit(target_head)?;
+    let new_target_head = repo.commit(
+        Some("refs/remotes/origin/master"),
+        &signature,
+        &signature,
+        "remote change",
+        &head_commit.tree()?,
+        &[&head_commit],
+    )?;
+    gitbutler_branch_actions::integrate_upstream(project, &[], None)?;
+
+    assert_eq!(repo.refname_to_id("refs/heads/trunk")?, new_target_head);
+    Ok(())
+}
+
 mod error {
     use gitbutler_reference::RemoteRefname;
 
@@ -293,3 +293,286 @@ fn root_forbidden() {
         "can not squash root commit"
     );
 }
+
+#[test]
+fn range() {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )
+    .unwrap();
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())
+            .unwrap();
+
+    {
+        fs::write(repository.path().join("file one.txt"), "one").unwrap();
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit one", None, false)
+            .unwrap()
+    };
+
+    let commit_two_oid = {
+        fs::write(repository.path().join("file two.txt"), "two").unwrap();
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit two", None, false)
+            .unwrap()
+    };
+
+    {
+        fs::write(repository.path().join("file three.txt"), "three").unwrap();
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit three", None, false)
+            .unwrap()
+    };
+
+    let commit_four_oid = {
+        fs::write(repository.path().join("file four.txt"), "four").unwrap();
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit four", None, false)
+            .unwrap()
+    };
+    let tree_before = repository.find_commit(commit_four_oid).unwrap().tree_id();
+
+    gitbutler_branch_actions::squash_range(project, branch_id, commit_two_oid, commit_four_oid)
+        .unwrap();
+
+    let branch = gitbutler_branch_actions::list_virtual_branches(project)
+        .unwrap()
+        .0
+        .into_iter()
+        .find(|b| b.id == branch_id)
+        .unwrap();
+
+    let descriptions = branch
+        .commits
+        .iter()
+        .map(|c| c.description.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        descriptions,
+        vec!["commit two\n\ncommit three\n\ncommit four", "commit one"]
+    );
+
+    let squashed = repository.find_commit(branch.commits[0].id).unwrap();
+    assert_eq!(squashed.tree_id(), tree_before);
+    assert_eq!(squashed.parent_id(0).unwrap(), branch.commits[1].id);
+}
+
+#[test]
+fn range_crossing_base_forbidden() {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )
+    .unwrap();
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())
+            .unwrap();
+
+    let commit_one_oid = {
+        fs::write(repository.path().join("file one.txt"), "").unwrap();
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit one", None, false)
+            .unwrap()
+    };
+    let base_oid = repository
+        .find_commit(commit_one_oid)
+        .unwrap()
+        .parent_id(0)
+        .unwrap();
+
+    assert_eq!(
+        gitbutler_branch_actions::squash_range(project, branch_id, base_oid, commit_one_oid)
+            .unwrap_err()
+            .to_string(),
+        format!("commit {base_oid} not in the branch")
+    );
+}
+
+#[test]
+fn unsquash_restores_squashed_commits() {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )
+    .unwrap();
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())
+            .unwrap();
+
+    {
+        fs::write(repository.path().join("file one.txt"), "one\n").unwrap();
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit one", None, false)
+            .unwrap()
+    };
+
+    {
+        fs::write(repository.path().join("file two.txt"), "two\n").unwrap();
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit two", None, false)
+            .unwrap()
+    };
+
+    let commit_three_oid = {
+        fs::write(repository.path().join("file three.txt"), "three\n").unwrap();
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit three", None, false)
+            .unwrap()
+    };
+
+    let branch = |project| {
+        gitbutler_branch_actions::list_virtual_branches(project)
+            .unwrap()
+            .0
+            .into_iter()
+            .find(|b| b.id == branch_id)
+            .unwrap()
+    };
+    let change_ids = branch(project)
+        .commits
+        .iter()
+        .map(|c| c.change_id.clone())
+        .collect::<Vec<_>>();
+
+    gitbutler_branch_actions::squash(project, branch_id, commit_three_oid).unwrap();
+    let squashed_oid = branch(project).commits[0].id;
+    gitbutler_branch_actions::squash(project, branch_id, squashed_oid).unwrap();
+    assert_eq!(branch(project).commits.len(), 1);
+
+    let repo = git2::Repository::open(repository.path()).unwrap();
+    let squashed_refs = |repo: &git2::Repository| {
+        repo.references_glob("refs/gitbutler/squashed/*")
+            .unwrap()
+            .count()
+    };
+    assert_eq!(
+        squashed_refs(&repo),
+        3,
+        "the squashed commits are kept reachable"
+    );
+
+    let squashed_oid = branch(project).commits[0].id;
+    gitbutler_branch_actions::unsquash(project, branch_id, squashed_oid).unwrap();
+    assert_eq!(squashed_refs(&repo), 0);
+
+    let branch = branch(project);
+    let descriptions = branch
+        .commits
+        .iter()
+        .map(|c| c.description.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        descriptions,
+        vec!["commit three", "commit two", "commit one"]
+    );
+    assert_eq!(
+        branch
+            .commits
+            .iter()
+            .map(|c| c.change_id.clone())
+            .collect::<Vec<_>>(),
+        change_ids,
+        "the squashed commits keep their change-ids"
+    );
+    let commit_two = repo.find_commit(branch.commits[1].id).unwrap();
+    let diff = repo
+        .diff_tree_to_tree(
+            Some(&commit_two.parent(0).unwrap().tree().unwrap()),
+            Some(&commit_two.tree().unwrap()),
+            None,
+        )
+        .unwrap();
+    assert_eq!(
+        diff.deltas().len(),
+        1,
+        "each commit has its own changes again"
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file three.txt")).unwrap(),
+        "three\n"
+    );
+}
+
+#[test]
+fn unsquash_requires_recorded_squash() {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )
+    .unwrap();
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())
+            .unwrap();
+
+    let commit_oid = {
+        fs::write(repository.path().join("file one.txt"), "one\n").unwrap();
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit one", None, false)
+            .unwrap()
+    };
+
+    let err = gitbutler_branch_actions::unsquash(project, branch_id, commit_oid).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("no squashed commits were recorded"));
+}
+
+#[test]
+fn pinned_commit_can_not_be_squashed() {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )
+    .unwrap();
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())
+            .unwrap();
+
+    fs::write(repository.path().join("file one.txt"), "").unwrap();
+    let commit_one_oid =
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit one", None, false)
+            .unwrap();
+    fs::write(repository.path().join("file two.txt"), "").unwrap();
+    let commit_two_oid =
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit two", None, false)
+            .unwrap();
+
+    gitbutler_branch_actions::pin_commit(project, branch_id, commit_one_oid).unwrap();
+    assert_eq!(
+        gitbutler_branch_actions::squash(project, branch_id, commit_two_oid)
+            .unwrap_err()
+            .to_string(),
+        "can not squash pinned commits"
+    );
+
+    gitbutler_branch_actions::unpin_commit(project, branch_id, commit_one_oid).unwrap();
+    gitbutler_branch_actions::squash(project, branch_id, commit_two_oid).unwrap();
+}
@@ -0,0 +1,122 @@
+use gitbutler_branch::BranchCreateRequest;
+
+use super::*;
+
+#[test]
+fn rename_is_owned_and_committed_by_one_branch() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(
+        repository.path().join("file.txt"),
+        "content that stays the same\n",
+    )?;
+    repository.commit_all("add file");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+
+    let default_branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    let (from, to) = (
+        path::Path::new("file.txt"),
+        path::Path::new("dir/renamed.txt"),
+    );
+    gitbutler_branch_actions::stage_rename(project, branch_id, from, to)?;
+    assert!(!repository.path().join(from).exists());
+    assert!(repository.path().join(to).is_file());
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let files_of = |id| {
+        let mut paths = branches
+            .iter()
+            .find(|b| b.id == id)
+            .unwrap()
+            .files
+            .iter()
+            .map(|file| file.path.clone())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    };
+    assert_eq!(files_of(branch_id), [to], "the rename is a single change");
+    assert!(files_of(default_branch_id).is_empty());
+
+    let commit_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "rename", None, false)?;
+    let commit = repository.find_commit(commit_id)?;
+    let repo = git2::Repository::open(repository.path())?;
+    let mut diff = repo.diff_tree_to_tree(
+        Some(&commit.parent(0)?.tree()?),
+        Some(&commit.tree()?),
+        None,
+    )?;
+    diff.find_similar(None)?;
+    let deltas = diff.deltas().collect::<Vec<_>>();
+    assert_eq!(deltas.len(), 1);
+    assert_eq!(deltas[0].status(), git2::Delta::Renamed);
+    assert_eq!(deltas[0].old_file().path(), Some(from));
+    assert_eq!(deltas[0].new_file().path(), Some(to));
+    Ok(())
+}
+
+#[test]
+fn refuse_to_overwrite_existing_files() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("a.txt"), "a")?;
+    fs::write(repository.path().join("b.txt"), "b")?;
+    assert!(gitbutler_branch_actions::stage_rename(
+        project,
+        branch_id,
+        path::Path::new("a.txt"),
+        path::Path::new("b.txt")
+    )
+    .is_err());
+    assert_eq!(fs::read_to_string(repository.path().join("b.txt"))?, "b");
+    Ok(())
+}
+
+#[test]
+fn refuse_paths_outside_of_the_worktree() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("a.txt"), "a")?;
+    let outside = repository.path().join("..").join("outside.txt");
+    for (from, to) in [
+        (path::Path::new("a.txt"), path::Path::new("../outside.txt")),
+        (path::Path::new("a.txt"), outside.as_path()),
+        (path::Path::new("../a.txt"), path::Path::new("b.txt")),
+    ] {
+        let err = gitbutler_branch_actions::stage_rename(project, branch_id, from, to).unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&gitbutler_error::error::Code::Validation)
+        );
+    }
+    assert_eq!(fs::read_to_string(repository.path().join("a.txt"))?, "a");
+    assert!(!outside.exists());
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use gitbutler_branch::BranchCreateRequest;
+
+use super::*;
+
+#[test]
+fn reports_only_changes_since_token() {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join("file.txt"), "one\ntwo\n").unwrap();
+    repository.commit_all("initial commit");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )
+    .unwrap();
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())
+            .unwrap();
+
+    fs::write(repository.path().join("file.txt"), "one\ntwo\nthree\n").unwrap();
+    let status = gitbutler_branch_actions::get_status_since(project, None).unwrap();
+    assert!(status.full);
+    assert_eq!(status.branches.len(), 1);
+    assert_eq!(status.branches[0].branch_id, branch_id);
+    assert_eq!(status.branches[0].added.len(), 1);
+
+    let unchanged =
+        gitbutler_branch_actions::get_status_since(project, Some(&status.token)).unwrap();
+    assert!(!unchanged.full);
+    assert!(unchanged.branches.is_empty());
+
+    fs::write(
+        repository.path().join("file.txt"),
+        "one\ntwo\nthree\nfour\n",
+    )
+    .unwrap();
+    fs::write(repository.path().join("other.txt"), "other\n").unwrap();
+    let delta =
+        gitbutler_branch_actions::get_status_since(project, Some(&unchanged.token)).unwrap();
+    assert!(!delta.full);
+    assert_eq!(delta.branches.len(), 1);
+    let branch = &delta.branches[0];
+    assert_eq!(branch.modified.len(), 1);
+    assert_eq!(branch.modified[0].file_path, PathBuf::from("file.txt"));
+    assert_eq!(branch.added.len(), 1);
+    assert_eq!(branch.added[0].file_path, PathBuf::from("other.txt"));
+    assert!(branch.removed.is_empty());
+
+    fs::remove_file(repository.path().join("other.txt")).unwrap();
+    let delta = gitbutler_branch_actions::get_status_since(project, Some(&delta.token)).unwrap();
+    let branch = &delta.branches[0];
+    assert!(branch.added.is_empty() && branch.modified.is_empty());
+    assert_eq!(branch.removed.len(), 1);
+    assert_eq!(branch.removed[0].file_path, PathBuf::from("other.txt"));
+}
+
+#[test]
+fn tokens_are_small_and_opaque() {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )
+    .unwrap();
+    gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())
+        .unwrap();
+
+    let empty = gitbutler_branch_actions::get_status_since(project, None).unwrap();
+    for n in 0..50 {
+        fs::write(
+            repository.path().join(format!("file{n}.txt")),
+            format!("content{n}\n"),
+        )
+        .unwrap();
+    }
+    let status = gitbutler_branch_actions::get_status_since(project, Some(&empty.token)).unwrap();
+    assert_eq!(status.branches[0].added.len(), 50);
+    assert_eq!(
+        status.token.len(),
+        empty.token.len(),
+        "the token doesn't grow with the workspace"
+    );
+
+    let unknown = "0".repeat(status.token.len());
+    let status = gitbutler_branch_actions::get_status_since(project, Some(&unknown)).unwrap();
+    assert!(status.full, "unknown states lead to a full report");
+    assert!(gitbutler_branch_actions::get_status_since(project, Some("garbage")).is_err());
+}
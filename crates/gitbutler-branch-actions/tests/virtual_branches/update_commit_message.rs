@@ -338,3 +338,49 @@ fn empty() {
         "commit message can not be empty"
     );
 }
+
+#[test]
+fn normalize_trailers_when_rewording() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "content\n")?;
+    let commit_id = gitbutler_branch_actions::create_commit_with_options(
+        project,
+        branch_id,
+        "subject\n\nAcked-by:A\nAcked-by: A",
+        None,
+        &gitbutler_branch_actions::CommitOptions {
+            normalize_trailers: true,
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(
+        repository.find_commit(commit_id)?.message(),
+        Some("subject\n\nAcked-by: A")
+    );
+
+    gitbutler_branch_actions::update_commit_message_with_options(
+        project,
+        branch_id,
+        commit_id,
+        "reworded\n\nAcked-by: A\nTested-by:B\nAcked-by: C",
+        true,
+    )?;
+    let branch = gitbutler_branch_actions::list_virtual_branches(project)?
+        .0
+        .into_iter()
+        .find(|b| b.id == branch_id)
+        .unwrap();
+    assert_eq!(
+        repository.find_commit(branch.head)?.message(),
+        Some("reworded\n\nAcked-by: A\nAcked-by: C\nTested-by: B")
+    );
+    Ok(())
+}
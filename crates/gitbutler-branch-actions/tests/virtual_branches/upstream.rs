@@ -1,4 +1,9 @@
 use gitbutler_branch::BranchCreateRequest;
+use gitbutler_branch_actions::{ForceMode, PushError, UpstreamStatus};
+use gitbutler_command_context::CommandContext;
+use gitbutler_error::error::Code;
+use gitbutler_repo::credentials::Interactivity;
+use gitbutler_stack::VirtualBranchesHandle;
 
 use super::*;
 
@@ -56,6 +61,83 @@ fn detect_upstream_commits() {
     }
 }
 
+#[test]
+fn list_unpushed_commits() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("file.txt"), "content")?;
+    let oid1 = gitbutler_branch_actions::create_commit(project, branch_id, "one", None, false)?;
+    fs::write(repository.path().join("file.txt"), "content2")?;
+    let oid2 = gitbutler_branch_actions::create_commit(project, branch_id, "two", None, false)?;
+
+    // without an upstream, everything since the base would be pushed.
+    assert_eq!(
+        gitbutler_branch_actions::unpushed_commits(project, branch_id)?,
+        [oid2, oid1]
+    );
+
+    gitbutler_branch_actions::push_virtual_branch(project, branch_id, false, None)?;
+    assert!(gitbutler_branch_actions::unpushed_commits(project, branch_id)?.is_empty());
+
+    fs::write(repository.path().join("file.txt"), "content3")?;
+    let oid3 = gitbutler_branch_actions::create_commit(project, branch_id, "three", None, false)?;
+    assert_eq!(
+        gitbutler_branch_actions::unpushed_commits(project, branch_id)?,
+        [oid3]
+    );
+    Ok(())
+}
+
+#[test]
+fn list_incoming_commits() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+    let repo = &repository.local_repository;
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("file.txt"), "content")?;
+    let oid = gitbutler_branch_actions::create_commit(project, branch_id, "one", None, false)?;
+
+    // without an upstream, nothing can come in.
+    assert!(gitbutler_branch_actions::incoming_commits(project, branch_id)?.is_empty());
+
+    let pushed = gitbutler_branch_actions::push_virtual_branch(project, branch_id, false, None)?;
+    assert!(gitbutler_branch_actions::incoming_commits(project, branch_id)?.is_empty());
+
+    // a teammate pushes on top
+    let signature = git2::Signature::now("teammate", "teammate@example.com")?;
+    let commit = repo.find_commit(oid)?;
+    let incoming_oid = repo.commit(
+        Some(&pushed.refname.to_string()),
+        &signature,
+        &signature,
+        "from teammate",
+        &commit.tree()?,
+        &[&commit],
+    )?;
+
+    let incoming = gitbutler_branch_actions::incoming_commits(project, branch_id)?;
+    assert_eq!(incoming.len(), 1);
+    assert_eq!(incoming[0].id, incoming_oid.to_string());
+    assert_eq!(incoming[0].description, "from teammate");
+    assert_eq!(incoming[0].author.name, "teammate");
+    Ok(())
+}
+
 #[test]
 fn detect_integrated_commits() {
     let Test {
@@ -121,3 +203,660 @@ fn detect_integrated_commits() {
         assert!(branches[0].commits[2].is_integrated);
     }
 }
+
+#[test]
+fn invalid_push_options_are_rejected() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("file.txt"), "content")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "one", None, false)?;
+
+    let err = gitbutler_branch_actions::push_virtual_branch_with_options(
+        project,
+        branch_id,
+        ForceMode::No,
+        None,
+        &["merge_request.create\nmerge_request.target=main".into()],
+        Interactivity::Interactive,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"invalid push option "merge_request.create\nmerge_request.target=main""#
+    );
+
+    let branch = gitbutler_branch_actions::list_virtual_branches(project)?
+        .0
+        .into_iter()
+        .find(|branch| branch.id == branch_id)
+        .unwrap();
+    assert!(branch.upstream.is_none(), "nothing was pushed");
+    Ok(())
+}
+
+/// Make the remote of `repository` echo the push options it receives and record them in the returned file,
+/// with support for push options advertised if `advertise` is set.
+fn remote_recording_push_options(
+    repository: &TestProject,
+    advertise: bool,
+) -> anyhow::Result<PathBuf> {
+    let remote_url = repository
+        .local_repository
+        .find_remote("origin")?
+        .url()
+        .unwrap()
+        .to_owned();
+    let remote = git2::Repository::open(remote_url)?;
+    remote
+        .config()?
+        .set_bool("receive.advertisePushOptions", advertise)?;
+
+    let hooks_dir = remote.path().join("hooks");
+    fs::create_dir_all(&hooks_dir)?;
+    let hook = hooks_dir.join("pre-receive");
+    fs::write(
+        &hook,
+        r#"#!/bin/sh
+i=0
+while [ "$i" -lt "${GIT_PUSH_OPTION_COUNT:-0}" ]; do
+  eval "echo \"\$GIT_PUSH_OPTION_$i\"" | tee -a push-options
+  i=$((i + 1))
+done
+"#,
+    )?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(remote.path().join("push-options"))
+}
+
+#[test]
+fn push_options_are_sent_to_the_remote() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+    let received_push_options = remote_recording_push_options(repository, true)?;
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("file.txt"), "content")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "one", None, false)?;
+
+    gitbutler_branch_actions::push_virtual_branch_with_options(
+        project,
+        branch_id,
+        ForceMode::No,
+        None,
+        &[
+            "merge_request.create".into(),
+            "merge_request.target=main".into(),
+        ],
+        Interactivity::Interactive,
+    )?;
+    assert_eq!(
+        fs::read_to_string(received_push_options)?,
+        "merge_request.create\nmerge_request.target=main\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn push_options_fail_if_the_remote_does_not_support_them() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+    let received_push_options = remote_recording_push_options(repository, false)?;
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("file.txt"), "content")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "one", None, false)?;
+
+    let err = gitbutler_branch_actions::push_virtual_branch_with_options(
+        project,
+        branch_id,
+        ForceMode::No,
+        None,
+        &["merge_request.create".into()],
+        Interactivity::Interactive,
+    )
+    .unwrap_err();
+    assert_eq!(err.downcast_ref::<Code>(), Some(&Code::Validation));
+    assert!(
+        format!("{err:#}").contains("the remote doesn't support push options"),
+        "{err:#}"
+    );
+    assert!(!received_push_options.exists(), "nothing was pushed");
+    Ok(())
+}
+
+#[test]
+fn commits_reachable_from_the_remote_target_are_shared() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("one.txt"), "one")?;
+    let one = gitbutler_branch_actions::create_commit(project, branch_id, "one", None, false)?;
+    fs::write(repository.path().join("two.txt"), "two")?;
+    let two = gitbutler_branch_actions::create_commit(project, branch_id, "two", None, false)?;
+
+    let sharedness = gitbutler_branch_actions::commit_sharedness(project, branch_id)?;
+    assert_eq!(sharedness, [(one, false), (two, false)].into());
+
+    // the first commit made it into the target upstream
+    repository
+        .local_repository
+        .reference("refs/remotes/origin/master", one, true, "merged")?;
+
+    let sharedness = gitbutler_branch_actions::commit_sharedness(project, branch_id)?;
+    assert_eq!(sharedness, [(one, true), (two, false)].into());
+    Ok(())
+}
+
+#[test]
+fn upstream_status() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    let upstream_status = || -> anyhow::Result<Option<UpstreamStatus>> {
+        let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+        Ok(branches
+            .into_iter()
+            .find(|branch| branch.id == branch_id)
+            .unwrap()
+            .upstream_status)
+    };
+
+    fs::write(repository.path().join("file.txt"), "one")?;
+    let oid = gitbutler_branch_actions::create_commit(project, branch_id, "one", None, false)?;
+    assert_eq!(upstream_status()?, None, "never pushed");
+
+    let pushed = gitbutler_branch_actions::push_virtual_branch(project, branch_id, false, None)?;
+    assert_eq!(upstream_status()?, Some(UpstreamStatus::UpToDate));
+
+    fs::write(repository.path().join("file.txt"), "two")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "two", None, false)?;
+    assert_eq!(upstream_status()?, Some(UpstreamStatus::Ahead(1)));
+
+    // a teammate pushes on top of the first commit
+    let repo = &repository.local_repository;
+    let signature = git2::Signature::now("teammate", "teammate@example.com")?;
+    let commit = repo.find_commit(oid)?;
+    repo.commit(
+        Some(&pushed.refname.to_string()),
+        &signature,
+        &signature,
+        "from teammate",
+        &commit.tree()?,
+        &[&commit],
+    )?;
+    assert_eq!(
+        upstream_status()?,
+        Some(UpstreamStatus::Diverged {
+            ahead: 1,
+            behind: 1
+        })
+    );
+
+    repo.find_reference(&pushed.refname.to_string())?.delete()?;
+    assert_eq!(upstream_status()?, Some(UpstreamStatus::Gone));
+    Ok(())
+}
+
+#[test]
+fn snapshot_upstream_keeps_the_last_pushed_commit() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("file.txt"), "content")?;
+    let pushed = gitbutler_branch_actions::create_commit(project, branch_id, "one", None, false)?;
+    assert!(
+        gitbutler_branch_actions::snapshot_upstream(project, branch_id).is_err(),
+        "there is no upstream yet"
+    );
+
+    gitbutler_branch_actions::push_virtual_branch(project, branch_id, false, None)?;
+    fs::write(repository.path().join("file.txt"), "content2")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "two", None, false)?;
+
+    let refname = gitbutler_branch_actions::snapshot_upstream(project, branch_id)?;
+    assert!(refname.starts_with("refs/gitbutler/upstream-backup/"));
+    let repo = git2::Repository::open(repository.path())?;
+    assert_eq!(repo.refname_to_id(&refname)?, pushed);
+
+    gitbutler_branch_actions::push_virtual_branch(project, branch_id, false, None)?;
+    let second_refname = gitbutler_branch_actions::snapshot_upstream(project, branch_id)?;
+    assert_ne!(
+        second_refname, refname,
+        "backups don't overwrite each other"
+    );
+    assert_eq!(repo.refname_to_id(&refname)?, pushed);
+    assert_ne!(repo.refname_to_id(&second_refname)?, pushed);
+    Ok(())
+}
+
+#[test]
+fn dedupe_commits_integrated_by_a_squash_merge() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("a.txt"), "a\n")?;
+    let integrated = gitbutler_branch_actions::create_commit(project, branch_id, "a", None, false)?;
+    fs::write(repository.path().join("b.txt"), "b\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "b", None, false)?;
+
+    // A teammate squash-merges the first commit upstream.
+    let repo = git2::Repository::open(repository.path())?;
+    let remote_url = repo.find_remote("origin")?.url().unwrap().to_owned();
+    let remote_repo = git2::Repository::open(remote_url)?;
+    let head = remote_repo.head()?.peel_to_commit()?;
+    let mut tree = remote_repo.treebuilder(Some(&head.tree()?))?;
+    tree.insert("a.txt", remote_repo.blob(b"a\n")?, 0o100644)?;
+    let tree = remote_repo.find_tree(tree.write()?)?;
+    let signature = git2::Signature::now("test", "test@example.com")?;
+    remote_repo.commit(
+        Some("refs/heads/master"),
+        &signature,
+        &signature,
+        "a (squashed)",
+        &tree,
+        &[&head],
+    )?;
+    gitbutler_branch_actions::fetch_from_remotes(project, None, None)?;
+
+    assert_eq!(
+        gitbutler_branch_actions::dedupe_integrated_commits(project, branch_id)?,
+        [integrated]
+    );
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(branches[0].commits.len(), 1);
+    assert_eq!(branches[0].commits[0].description, "b");
+    assert!(
+        !repository.path().join("a.txt").exists(),
+        "the changes of the dropped commit are only upstream now"
+    );
+    assert!(repository.path().join("b.txt").exists());
+
+    assert!(
+        gitbutler_branch_actions::dedupe_integrated_commits(project, branch_id)?.is_empty(),
+        "nothing left to drop"
+    );
+    Ok(())
+}
+
+#[test]
+fn abort_conflicted_integration() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("file.txt"), "local\n")?;
+    let local = gitbutler_branch_actions::create_commit(project, branch_id, "local", None, false)?;
+    gitbutler_branch_actions::push_virtual_branch(project, branch_id, false, None)?;
+
+    force_push_conflicting_change(repository.path())?;
+    gitbutler_branch_actions::fetch_from_remotes(project, None, None)?;
+
+    assert!(
+        gitbutler_branch_actions::abort_integration(project, branch_id).is_err(),
+        "there is no conflicted integration yet"
+    );
+    gitbutler_branch_actions::integrate_upstream_commits(project, branch_id, None)?;
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert!(branches[0].commits.iter().any(|commit| commit.conflicted));
+
+    gitbutler_branch_actions::abort_integration(project, branch_id)?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(branches[0].commits.len(), 1);
+    assert_eq!(branches[0].commits[0].id, local);
+    assert!(!branches[0].commits[0].conflicted);
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        "local\n",
+        "no conflict markers are left behind"
+    );
+    Ok(())
+}
+
+#[test]
+fn abort_integration_only_matches_the_branch() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("file.txt"), "local\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "local", None, false)?;
+    gitbutler_branch_actions::push_virtual_branch(project, branch_id, false, None)?;
+    force_push_conflicting_change(repository.path())?;
+    gitbutler_branch_actions::fetch_from_remotes(project, None, None)?;
+    let other_branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    gitbutler_branch_actions::integrate_upstream_commits(project, branch_id, None)?;
+    assert!(
+        gitbutler_branch_actions::abort_integration(project, other_branch_id).is_err(),
+        "the conflicts of another branch don't make this one abortable"
+    );
+
+    // an integration into the other branch, even one that does nothing, comes in between
+    let _ = gitbutler_branch_actions::integrate_upstream_commits(project, other_branch_id, None);
+    let err = gitbutler_branch_actions::abort_integration(project, branch_id).unwrap_err();
+    assert!(
+        err.to_string().contains("other operations happened since"),
+        "{err:#}"
+    );
+    Ok(())
+}
+
+#[test]
+fn abort_conflicted_integration_restores_uncommitted_changes() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("file.txt"), "local\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "local", None, false)?;
+    gitbutler_branch_actions::push_virtual_branch(project, branch_id, false, None)?;
+    force_push_conflicting_change(repository.path())?;
+    gitbutler_branch_actions::fetch_from_remotes(project, None, None)?;
+
+    fs::write(
+        repository.path().join("uncommitted.txt"),
+        "work in progress\n",
+    )?;
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    let commits_before = branches[0].commits.len();
+    assert_eq!(branches[0].files.len(), 1);
+
+    gitbutler_branch_actions::integrate_upstream_commits(project, branch_id, None)?;
+    gitbutler_branch_actions::abort_integration(project, branch_id)?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(branches[0].commits.len(), commits_before);
+    assert!(branches[0].commits.iter().all(|commit| !commit.conflicted));
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        "local\n"
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("uncommitted.txt"))?,
+        "work in progress\n",
+        "uncommitted changes are restored along with the branch"
+    );
+    assert_eq!(branches[0].files.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn dedupe_moves_series_heads_off_dropped_commits() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("a.txt"), "a\n")?;
+    let kept = gitbutler_branch_actions::create_commit(project, branch_id, "a", None, false)?;
+    fs::write(repository.path().join("b.txt"), "b\n")?;
+    let integrated = gitbutler_branch_actions::create_commit(project, branch_id, "b", None, false)?;
+
+    let ctx = CommandContext::open(project)?;
+    let handle = VirtualBranchesHandle::new(project.gb_dir());
+    let mut stack = handle.get_branch(branch_id)?;
+    stack.add_series_top_of_stack(&ctx, "top-series".into(), None)?;
+    fs::write(repository.path().join("c.txt"), "c\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "c", None, false)?;
+
+    // A teammate squash-merges the head commit of the bottom series upstream.
+    let repo = git2::Repository::open(repository.path())?;
+    let remote_url = repo.find_remote("origin")?.url().unwrap().to_owned();
+    let remote_repo = git2::Repository::open(remote_url)?;
+    let head = remote_repo.head()?.peel_to_commit()?;
+    let mut tree = remote_repo.treebuilder(Some(&head.tree()?))?;
+    tree.insert("b.txt", remote_repo.blob(b"b\n")?, 0o100644)?;
+    let tree = remote_repo.find_tree(tree.write()?)?;
+    let signature = git2::Signature::now("test", "test@example.com")?;
+    remote_repo.commit(
+        Some("refs/heads/master"),
+        &signature,
+        &signature,
+        "b (squashed)",
+        &tree,
+        &[&head],
+    )?;
+    gitbutler_branch_actions::fetch_from_remotes(project, None, None)?;
+
+    assert_eq!(
+        gitbutler_branch_actions::dedupe_integrated_commits(project, branch_id)?,
+        [integrated]
+    );
+
+    let stack = handle.get_branch(branch_id)?;
+    let series = stack.list_series(&ctx)?;
+    let messages = |commits: &[git2::Commit]| {
+        commits
+            .iter()
+            .map(|c| c.summary().unwrap_or_default().to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(series.len(), 2);
+    assert_eq!(
+        messages(&series[0].local_commits),
+        ["a"],
+        "the bottom series head moved to the kept commit below the dropped one"
+    );
+    assert_eq!(series[0].local_commits[0].id(), kept, "it wasn't rebased");
+    assert_eq!(series[1].head.name, "top-series");
+    assert_eq!(messages(&series[1].local_commits), ["c"]);
+    Ok(())
+}
+
+#[test]
+fn integrate_upstream_keeps_local_version_of_merge_ours_files() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(
+        repository.path().join(".gitattributes"),
+        "file.txt merge=ours\n",
+    )?;
+    repository.commit_all("mark file.txt as merge=ours");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    fs::write(repository.path().join("file.txt"), "local\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "local", None, false)?;
+    gitbutler_branch_actions::push_virtual_branch(project, branch_id, false, None)?;
+    force_push_conflicting_change(repository.path())?;
+    gitbutler_branch_actions::fetch_from_remotes(project, None, None)?;
+
+    gitbutler_branch_actions::integrate_upstream_commits(project, branch_id, None)?;
+
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(
+        branches[0]
+            .commits
+            .iter()
+            .map(|commit| commit.description.to_string())
+            .collect::<Vec<_>>(),
+        ["local", "remote"]
+    );
+    assert!(
+        branches[0].commits.iter().all(|commit| !commit.conflicted),
+        "the conflict in file.txt is resolved by its merge attribute"
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        "local\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn force_push_with_stale_lease_is_rejected() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "content\n")?;
+    let pushed_id =
+        gitbutler_branch_actions::create_commit(project, branch_id, "one", None, false)?;
+    let pushed = gitbutler_branch_actions::push_virtual_branch(project, branch_id, false, None)?;
+    let remote_head = || -> anyhow::Result<git2::Oid> {
+        let repo = &repository.local_repository;
+        let remote_url = repo.find_remote("origin")?.url().unwrap().to_owned();
+        let remote_ref = pushed
+            .refname
+            .to_string()
+            .replace("refs/remotes/origin/", "refs/heads/");
+        Ok(git2::Repository::open(remote_url)?.refname_to_id(&remote_ref)?)
+    };
+
+    // a coworker pushes after the last sync, and we amend our commit without knowing
+    force_push_conflicting_change(repository.path())?;
+    let coworker_id = remote_head()?;
+    fs::write(repository.path().join("file.txt"), "amended\n")?;
+    gitbutler_branch_actions::amend(project, branch_id, pushed_id, &"file.txt:1-2".parse()?)?;
+
+    let err =
+        gitbutler_branch_actions::push_virtual_branch(project, branch_id, true, None).unwrap_err();
+    match err.downcast_ref::<PushError>() {
+        Some(PushError::StaleLease { expected, actual }) => {
+            assert_eq!(*expected, pushed_id);
+            assert_eq!(*actual, coworker_id);
+        }
+        None => panic!("expected a stale lease, got {err:?}"),
+    }
+    assert_eq!(err.downcast_ref::<Code>(), Some(&Code::Validation));
+    assert_eq!(remote_head()?, coworker_id, "the coworker's commit is kept");
+
+    gitbutler_branch_actions::push_virtual_branch_with_options(
+        project,
+        branch_id,
+        ForceMode::Force,
+        None,
+        &[],
+        Interactivity::Interactive,
+    )?;
+    let branch = gitbutler_branch_actions::list_virtual_branches(project)?
+        .0
+        .into_iter()
+        .find(|branch| branch.id == branch_id)
+        .unwrap();
+    assert_eq!(remote_head()?, branch.head, "an explicit force overwrites");
+
+    fs::write(repository.path().join("file.txt"), "amended again\n")?;
+    let amended_id =
+        gitbutler_branch_actions::amend(project, branch_id, branch.head, &"file.txt:1-2".parse()?)?;
+    gitbutler_branch_actions::push_virtual_branch(project, branch_id, true, None)?;
+    assert_eq!(
+        remote_head()?,
+        amended_id,
+        "the lease holds as nobody else pushed"
+    );
+    Ok(())
+}
+
+/// Rewrite the only non-default branch of the `origin` remote of the repository at `repo_path`
+/// with a commit that changes `file.txt` to `remote\n`, like a teammate force-pushing would.
+fn force_push_conflicting_change(repo_path: &std::path::Path) -> anyhow::Result<()> {
+    let repo = git2::Repository::open(repo_path)?;
+    let remote_url = repo.find_remote("origin")?.url().unwrap().to_owned();
+    let remote_repo = git2::Repository::open(remote_url)?;
+    let base = remote_repo
+        .find_reference("refs/heads/master")?
+        .peel_to_commit()?;
+    let mut tree = remote_repo.treebuilder(Some(&base.tree()?))?;
+    tree.insert("file.txt", remote_repo.blob(b"remote\n")?, 0o100644)?;
+    let tree = remote_repo.find_tree(tree.write()?)?;
+    let remote_branch = remote_repo
+        .references_glob("refs/heads/*")?
+        .flatten()
+        .find(|reference| reference.name() != Some("refs/heads/master"))
+        .and_then(|reference| reference.name().map(ToOwned::to_owned))
+        .unwrap();
+    let signature = git2::Signature::now("test", "test@example.com")?;
+    let rewritten = remote_repo.commit(None, &signature, &signature, "remote", &tree, &[&base])?;
+    remote_repo.reference(&remote_branch, rewritten, true, "force-push")?;
+    Ok(())
+}
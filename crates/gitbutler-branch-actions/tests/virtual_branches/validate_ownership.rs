@@ -0,0 +1,112 @@
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_stack::{BranchOwnershipClaims, VirtualBranchesHandle};
+
+use super::Test;
+
+#[test]
+fn detect_and_repair_hunks_claimed_by_multiple_branches() -> anyhow::Result<()> {
+    let Test { project, .. } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+
+    let branch1_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    let branch2_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    assert!(gitbutler_branch_actions::validate_ownership(project)?.is_empty());
+
+    let vb_state = VirtualBranchesHandle::new(project.gb_dir());
+    let mut branch1 = vb_state.get_branch_in_workspace(branch1_id)?;
+    branch1.ownership = BranchOwnershipClaims {
+        claims: vec!["file.txt:1-5,11-15".parse()?],
+    };
+    vb_state.set_branch(branch1)?;
+    let mut branch2 = vb_state.get_branch_in_workspace(branch2_id)?;
+    branch2.ownership = BranchOwnershipClaims {
+        claims: vec!["file.txt:1-5".parse()?, "other.txt:1-2".parse()?],
+    };
+    vb_state.set_branch(branch2)?;
+
+    let conflicts = gitbutler_branch_actions::validate_ownership(project)?;
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].file_path.to_str(), Some("file.txt"));
+    assert_eq!((conflicts[0].start, conflicts[0].end), (1, 5));
+    assert_eq!(conflicts[0].branch_ids, vec![branch1_id, branch2_id]);
+
+    let repaired = gitbutler_branch_actions::repair_ownership(project)?;
+    assert_eq!(repaired, conflicts);
+    assert!(gitbutler_branch_actions::validate_ownership(project)?.is_empty());
+
+    assert_eq!(
+        vb_state
+            .get_branch_in_workspace(branch1_id)?
+            .ownership
+            .to_string(),
+        "file.txt:1-5,11-15\n",
+        "the first branch in the workspace keeps the contested hunk"
+    );
+    assert_eq!(
+        vb_state
+            .get_branch_in_workspace(branch2_id)?
+            .ownership
+            .to_string(),
+        "other.txt:1-2\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn detect_and_repair_overlapping_hunks_of_multiple_branches() -> anyhow::Result<()> {
+    let Test { project, .. } = &Test::default();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+
+    let branch1_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    let branch2_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+
+    let vb_state = VirtualBranchesHandle::new(project.gb_dir());
+    let mut branch1 = vb_state.get_branch_in_workspace(branch1_id)?;
+    branch1.ownership = BranchOwnershipClaims {
+        claims: vec!["file.txt:1-5,11-15".parse()?],
+    };
+    vb_state.set_branch(branch1)?;
+    let mut branch2 = vb_state.get_branch_in_workspace(branch2_id)?;
+    branch2.ownership = BranchOwnershipClaims {
+        claims: vec!["file.txt:3-8,20-25".parse()?],
+    };
+    vb_state.set_branch(branch2)?;
+
+    let conflicts = gitbutler_branch_actions::validate_ownership(project)?;
+    assert_eq!(
+        conflicts.len(),
+        1,
+        "only the ranges that intersect conflict"
+    );
+    assert_eq!(conflicts[0].file_path.to_str(), Some("file.txt"));
+    assert_eq!((conflicts[0].start, conflicts[0].end), (1, 5));
+    assert_eq!(conflicts[0].branch_ids, vec![branch1_id, branch2_id]);
+
+    let repaired = gitbutler_branch_actions::repair_ownership(project)?;
+    assert_eq!(repaired, conflicts);
+    assert!(gitbutler_branch_actions::validate_ownership(project)?.is_empty());
+
+    assert_eq!(
+        vb_state
+            .get_branch_in_workspace(branch1_id)?
+            .ownership
+            .to_string(),
+        "file.txt:1-5,11-15\n"
+    );
+    assert_eq!(
+        vb_state
+            .get_branch_in_workspace(branch2_id)?
+            .ownership
+            .to_string(),
+        "file.txt:20-25\n",
+        "the overlapping hunk is taken away, the other one stays"
+    );
+    Ok(())
+}
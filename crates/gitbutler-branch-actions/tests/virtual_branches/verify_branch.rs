@@ -21,3 +21,55 @@ fn should_fail_on_incorrect_branch() {
         "<verification-failed>: project is on refs/heads/somebranch. Please checkout gitbutler/workspace to continue"
     );
 }
+
+#[test]
+fn force_reset_integration_recovers_lost_workspace_commit() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    let base = gitbutler_branch_actions::set_base_branch(
+        project,
+        &"refs/remotes/origin/master".parse().unwrap(),
+    )?;
+    let branch_id = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &gitbutler_branch::BranchCreateRequest::default(),
+    )?;
+    fs::write(repository.path().join("file.txt"), "content")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "commit", None, false)?;
+
+    // lose the workspace commit, and have a change that isn't part of it
+    repository.local_repository.reference(
+        "refs/heads/gitbutler/workspace",
+        base.base_sha,
+        true,
+        "wedge",
+    )?;
+    fs::write(repository.path().join("other.txt"), "other")?;
+    gitbutler_branch_actions::list_virtual_branches(project).unwrap_err();
+
+    let outcome = gitbutler_branch_actions::force_reset_integration(project, false)?;
+    assert!(!outcome.reset);
+    assert_eq!(outcome.uncommitted_files, [PathBuf::from("other.txt")]);
+    gitbutler_branch_actions::list_virtual_branches(project).unwrap_err();
+
+    let outcome = gitbutler_branch_actions::force_reset_integration(project, true)?;
+    assert!(outcome.reset);
+    let (branches, _) = gitbutler_branch_actions::list_virtual_branches(project)?;
+    assert_eq!(branches.len(), 1);
+    assert_eq!(branches[0].commits.len(), 1);
+    assert_eq!(branches[0].files.len(), 1);
+    assert_eq!(branches[0].files[0].path, PathBuf::from("other.txt"));
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt"))?,
+        "content"
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("other.txt"))?,
+        "other"
+    );
+    Ok(())
+}
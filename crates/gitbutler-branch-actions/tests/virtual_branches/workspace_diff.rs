@@ -0,0 +1,131 @@
+use gitbutler_branch::BranchCreateRequest;
+use gitbutler_branch_actions::WorkspaceDiffStats;
+
+use super::*;
+
+#[test]
+fn combines_all_branches_and_uncommitted_changes() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join("file.txt"), "one")?;
+    repository.commit_all("first");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    assert!(gitbutler_branch_actions::workspace_diff(project)?.is_empty());
+
+    let branch_one =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "two")?;
+    gitbutler_branch_actions::create_commit(project, branch_one, "one", None, false)?;
+
+    let branch_two = gitbutler_branch_actions::create_virtual_branch(
+        project,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+    )?;
+    fs::write(repository.path().join("other.txt"), "other")?;
+    gitbutler_branch_actions::create_commit(project, branch_two, "two", None, false)?;
+
+    // the file committed to the first branch also has uncommitted changes
+    fs::write(repository.path().join("file.txt"), "three")?;
+    fs::write(repository.path().join("uncommitted.txt"), "not yet")?;
+
+    let mut files = gitbutler_branch_actions::workspace_diff(project)?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    assert_eq!(
+        files
+            .iter()
+            .map(|file| file.path.as_path())
+            .collect::<Vec<_>>(),
+        [
+            path::Path::new("file.txt"),
+            path::Path::new("other.txt"),
+            path::Path::new("uncommitted.txt")
+        ]
+    );
+    assert_eq!(files[0].hunks.len(), 1);
+    assert_eq!(
+        files[0].hunks[0].diff_lines,
+        "@@ -1 +1 @@\n-one\n\\ No newline at end of file\n+three\n\\ No newline at end of file\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn stats_count_binary_files_without_lines() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join("file.txt"), "one\ntwo\n")?;
+    repository.commit_all("first");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    assert_eq!(
+        gitbutler_branch_actions::workspace_diff_stats(project)?,
+        WorkspaceDiffStats::default()
+    );
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "one\nthree\nfour\n")?;
+    gitbutler_branch_actions::create_commit(project, branch_id, "commit", None, false)?;
+    fs::write(repository.path().join("image.bin"), [0, 159, 146, 150])?;
+
+    assert_eq!(
+        gitbutler_branch_actions::workspace_diff_stats(project)?,
+        WorkspaceDiffStats {
+            files: 2,
+            lines_added: 2,
+            lines_removed: 1,
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn target_and_workspace_trees() -> anyhow::Result<()> {
+    let Test {
+        repository,
+        project,
+        ..
+    } = &Test::default();
+
+    fs::write(repository.path().join("file.txt"), "one")?;
+    let target_oid = repository.commit_all("first");
+    repository.push();
+
+    gitbutler_branch_actions::set_base_branch(project, &"refs/remotes/origin/master".parse()?)?;
+    let repo = &repository.local_repository;
+    let target_tree = repo.find_commit(target_oid)?.tree_id();
+    assert_eq!(gitbutler_branch_actions::target_tree(project)?, target_tree);
+    assert_eq!(
+        gitbutler_branch_actions::workspace_tree(project)?,
+        target_tree
+    );
+
+    let branch_id =
+        gitbutler_branch_actions::create_virtual_branch(project, &BranchCreateRequest::default())?;
+    fs::write(repository.path().join("file.txt"), "two")?;
+    let commit_oid =
+        gitbutler_branch_actions::create_commit(project, branch_id, "commit", None, false)?;
+    fs::write(repository.path().join("uncommitted.txt"), "not yet")?;
+
+    assert_eq!(gitbutler_branch_actions::target_tree(project)?, target_tree);
+    assert_eq!(
+        gitbutler_branch_actions::workspace_tree(project)?,
+        repo.find_commit(commit_oid)?.tree_id(),
+        "uncommitted changes aren't part of the workspace tree"
+    );
+    Ok(())
+}
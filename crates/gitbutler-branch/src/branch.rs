@@ -1,5 +1,5 @@
 use bstr::{BStr, ByteSlice};
-use gitbutler_stack::{BranchOwnershipClaims, StackId};
+use gitbutler_stack::{BranchOwnershipClaims, CommitAuthor, StackId};
 use serde::{Deserialize, Serialize, Serializer};
 use std::ops::Deref;
 
@@ -11,8 +11,18 @@ pub struct BranchUpdateRequest {
     pub ownership: Option<BranchOwnershipClaims>,
     pub order: Option<usize>,
     pub upstream: Option<String>, // just the branch name, so not refs/remotes/origin/branchA, just branchA
+    /// The full name of the remote branch to base this branch on, like `refs/remotes/origin/release`.
+    /// Setting it to the default target makes the branch follow the default target again.
+    pub target: Option<String>,
     pub selected_for_changes: Option<bool>,
     pub allow_rebasing: Option<bool>,
+    /// Stop or resume assigning new hunks to the branch.
+    pub frozen: Option<bool>,
+    /// Replace the names of the git hooks that don't run when committing to the branch.
+    pub skipped_hooks: Option<Vec<String>>,
+    /// Author commits to the branch with this identity, or with the repository's identity again
+    /// if both its name and email are empty.
+    pub commit_author: Option<CommitAuthor>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -21,6 +31,12 @@ pub struct BranchCreateRequest {
     pub ownership: Option<BranchOwnershipClaims>,
     pub order: Option<usize>,
     pub selected_for_changes: Option<bool>,
+    /// Create a branch for changes that must never be committed or pushed.
+    pub local_only: Option<bool>,
+    /// Base the branch on this commit of the target instead of its head, so the changes the target
+    /// made since then show up as uncommitted changes again.
+    #[serde(with = "gitbutler_serde::oid_opt", default)]
+    pub base_sha: Option<git2::Oid>,
 }
 
 /// The identity of a branch as to allow to group similar branches together.
@@ -54,6 +54,7 @@ pub mod ownership {
                     hash: None,
                     start: from_line,
                     end: to_line,
+                    filter: None,
                 }],
             }],
         };
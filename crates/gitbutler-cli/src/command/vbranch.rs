@@ -110,8 +110,12 @@ fn set_default_branch(project: &Project, branch: &Stack) -> Result<()> {
             ownership: None,
             order: None,
             upstream: None,
+            target: None,
             selected_for_changes: Some(true),
             allow_rebasing: None,
+            frozen: None,
+            skipped_hooks: None,
+            commit_author: None,
         },
     )
 }
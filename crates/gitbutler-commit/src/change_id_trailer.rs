@@ -0,0 +1,39 @@
+use bstr::{BStr, ByteSlice};
+
+use crate::trailers::{parse_trailer, split_trailers};
+
+/// The key of the Gerrit-style trailer that carries a commit's change-id in its message.
+pub const CHANGE_ID_TRAILER: &str = "Change-Id";
+
+/// Returns the value of the last `Change-Id` trailer in `message`, if any.
+pub fn change_id_from_message(message: &BStr) -> Option<String> {
+    let message = message.to_str_lossy();
+    let (_, trailers) = split_trailers(&message);
+    trailers
+        .iter()
+        .rev()
+        .find_map(|line| change_id_value(line))
+        .map(ToOwned::to_owned)
+}
+
+/// Returns `message` with all `Change-Id` trailers replaced by a single one carrying `change_id`.
+/// Other trailers are kept in place and the new trailer is appended after them.
+pub fn with_change_id_trailer(message: &str, change_id: &str) -> String {
+    let (body, mut trailers) = split_trailers(message);
+    trailers.retain(|line| change_id_value(line).is_none());
+    let trailer = format!("{CHANGE_ID_TRAILER}: {change_id}");
+    trailers.push(&trailer);
+
+    let body = body.trim_end();
+    if body.is_empty() {
+        format!("{}\n", trailers.join("\n"))
+    } else {
+        format!("{body}\n\n{}\n", trailers.join("\n"))
+    }
+}
+
+fn change_id_value(line: &str) -> Option<&str> {
+    parse_trailer(line)
+        .filter(|(key, value)| key.eq_ignore_ascii_case(CHANGE_ID_TRAILER) && !value.is_empty())
+        .map(|(_, value)| value)
+}
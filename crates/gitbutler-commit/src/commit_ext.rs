@@ -1,6 +1,6 @@
 use bstr::BStr;
 
-use crate::commit_headers::HasCommitHeaders;
+use crate::{change_id_trailer::change_id_from_message, commit_headers::HasCommitHeaders};
 
 /// Extension trait for `git2::Commit`.
 ///
@@ -8,6 +8,7 @@ use crate::commit_headers::HasCommitHeaders;
 pub trait CommitExt {
     /// Obtain the commit-message as bytes, but without assuming any encoding.
     fn message_bstr(&self) -> &BStr;
+    /// The change-id from the GitButler headers, or from a `Change-Id` trailer if the headers are absent.
     fn change_id(&self) -> Option<String>;
     fn is_signed(&self) -> bool;
     fn is_conflicted(&self) -> bool;
@@ -19,7 +20,9 @@ impl CommitExt for git2::Commit<'_> {
     }
 
     fn change_id(&self) -> Option<String> {
-        self.gitbutler_headers().map(|headers| headers.change_id)
+        self.gitbutler_headers()
+            .map(|headers| headers.change_id)
+            .or_else(|| change_id_from_message(self.message_bstr()))
     }
     fn is_signed(&self) -> bool {
         self.header_field_bytes("gpgsig").is_ok()
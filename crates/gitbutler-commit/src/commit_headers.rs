@@ -20,6 +20,7 @@ const V2_HEADERS_VERSION: &str = "2";
 
 const V2_CHANGE_ID_HEADER: &str = "gitbutler-change-id";
 const V2_CONFLICTED_HEADER: &str = "gitbutler-conflicted";
+const V2_INTEGRATED_UPSTREAM_HEADER: &str = "gitbutler-integrated-upstream";
 #[derive(Debug, Clone)]
 pub struct CommitHeadersV2 {
     /// A property we can use to determine if two different commits are
@@ -30,6 +31,10 @@ pub struct CommitHeadersV2 {
     /// commit. This is only written if the property is present. Conflicted
     /// commits should never make it into the main trunk.
     pub conflicted: Option<u64>,
+    /// The upstream commit that was integrated by resolving its conflicts in this commit, which was
+    /// then committed with a single parent instead of as a merge commit.
+    /// It's written only if the property is present.
+    pub integrated_upstream: Option<git2::Oid>,
 }
 
 impl Default for CommitHeadersV2 {
@@ -38,6 +43,7 @@ impl Default for CommitHeadersV2 {
             // Change ID using base16 encoding
             change_id: Uuid::new_v4().to_string(),
             conflicted: None,
+            integrated_upstream: None,
         }
     }
 }
@@ -47,6 +53,7 @@ impl From<CommitHeadersV1> for CommitHeadersV2 {
         CommitHeadersV2 {
             change_id: commit_headers_v1.change_id,
             conflicted: None,
+            integrated_upstream: None,
         }
     }
 }
@@ -64,6 +71,12 @@ impl From<CommitHeadersV2> for Vec<(BString, BString)> {
         if let Some(conflicted) = hdr.conflicted {
             out.push((V2_CONFLICTED_HEADER.into(), conflicted.to_string().into()));
         }
+        if let Some(integrated_upstream) = hdr.integrated_upstream {
+            out.push((
+                V2_INTEGRATED_UPSTREAM_HEADER.into(),
+                integrated_upstream.to_string().into(),
+            ));
+        }
         out
     }
 }
@@ -92,9 +105,15 @@ impl HasCommitHeaders for git2::Commit<'_> {
                     Err(_) => None,
                 };
 
+                let integrated_upstream = self
+                    .header_field_bytes(V2_INTEGRATED_UPSTREAM_HEADER)
+                    .ok()
+                    .and_then(|value| git2::Oid::from_str(value.as_str()?).ok());
+
                 Some(CommitHeadersV2 {
                     change_id,
                     conflicted,
+                    integrated_upstream,
                 })
             } else {
                 // Must be for a version we don't recognise
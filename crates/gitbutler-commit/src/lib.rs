@@ -1,2 +1,4 @@
+pub mod change_id_trailer;
 pub mod commit_ext;
 pub mod commit_headers;
+pub mod trailers;
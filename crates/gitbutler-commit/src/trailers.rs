@@ -0,0 +1,133 @@
+//! Handling of the trailer block at the end of commit messages, like `Signed-off-by: Name <email>`.
+
+/// Returns `message` with its trailer block canonicalized similar to `git interpret-trailers`:
+/// each trailer is written as `Key: value`, trailers with the same key are grouped in the order
+/// in which their keys first appear, and duplicates are removed.
+///
+/// The subject and body are left untouched, as is the whole message if its last paragraph
+/// isn't a trailer block, for instance because there is no blank line in front of it.
+pub fn normalize_trailers(message: &str) -> String {
+    let (body, lines) = split_trailers(message);
+    if lines.is_empty() {
+        return message.to_owned();
+    }
+
+    let mut trailers: Vec<(&str, String)> = Vec::new();
+    for line in lines {
+        match parse_trailer(line) {
+            Some((key, value)) => trailers.push((key, value.to_owned())),
+            // A continuation of the previous trailer's value.
+            None => {
+                if let Some((_, value)) = trailers.last_mut() {
+                    value.push('\n');
+                    value.push_str(line);
+                }
+            }
+        }
+    }
+
+    let mut groups: Vec<(&str, Vec<String>)> = Vec::new();
+    for (key, value) in trailers {
+        match groups
+            .iter_mut()
+            .find(|(existing, _)| existing.eq_ignore_ascii_case(key))
+        {
+            Some((_, values)) if values.contains(&value) => {}
+            Some((_, values)) => values.push(value),
+            None => groups.push((key, vec![value])),
+        }
+    }
+
+    let mut out = body.trim_end().to_owned();
+    if !out.is_empty() {
+        out.push_str("\n\n");
+    }
+    let block = groups
+        .iter()
+        .flat_map(|(key, values)| {
+            values.iter().map(move |value| {
+                if value.is_empty() {
+                    format!("{key}:")
+                } else {
+                    format!("{key}: {value}")
+                }
+            })
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    out.push_str(&block);
+    if message.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+/// Returns `message` with `trailers`, given as key and value, appended to its trailer block,
+/// which is started if the message doesn't have one yet.
+/// Trailers that are already present with the same value are skipped, comparing keys case-insensitively,
+/// so the same trailer never appears twice.
+pub fn add_trailers(message: &str, trailers: &[(String, String)]) -> String {
+    let (_, lines) = split_trailers(message);
+    let mut present: Vec<(&str, &str)> = lines
+        .iter()
+        .filter_map(|line| parse_trailer(line))
+        .collect();
+    let mut block = Vec::new();
+    for (key, value) in trailers {
+        let value = value.trim();
+        if present
+            .iter()
+            .any(|(k, v)| k.eq_ignore_ascii_case(key) && *v == value)
+        {
+            continue;
+        }
+        present.push((key, value));
+        block.push(format!("{key}: {value}"));
+    }
+    if block.is_empty() {
+        return message.to_owned();
+    }
+
+    let mut out = message.trim_end().to_owned();
+    if lines.is_empty() && !out.is_empty() {
+        out.push_str("\n\n");
+    } else if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(&block.join("\n"));
+    if message.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+/// Splits `message` into its body and the lines of its trailing trailer block.
+/// The last paragraph only counts as trailers if it isn't the subject, it starts with a trailer,
+/// and every other line is a trailer or the indented continuation of one.
+pub(crate) fn split_trailers(message: &str) -> (&str, Vec<&str>) {
+    let trimmed = message.trim_end();
+    let Some(idx) = trimmed.rfind("\n\n") else {
+        return (trimmed, vec![]);
+    };
+    let (body, last) = (&trimmed[..idx], trimmed[idx..].trim_start());
+    let lines: Vec<&str> = last.lines().collect();
+    let is_trailer_block = lines
+        .first()
+        .is_some_and(|line| parse_trailer(line).is_some())
+        && lines
+            .iter()
+            .all(|line| parse_trailer(line).is_some() || line.starts_with([' ', '\t']));
+    if is_trailer_block {
+        (body, lines)
+    } else {
+        (trimmed, vec![])
+    }
+}
+
+/// Parses `line` as `Key: value`, returning the key and the trimmed value.
+pub(crate) fn parse_trailer(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim_end();
+    let is_key = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    is_key.then(|| (key, value.trim()))
+}
@@ -0,0 +1,53 @@
+use gitbutler_commit::trailers::{add_trailers, normalize_trailers};
+
+#[test]
+fn separators_duplicates_and_grouping() {
+    let message = "subject\n\nbody\n\nSigned-off-by:A <a@example.com>\nReviewed-by : B\nsigned-off-by: A <a@example.com>\nSigned-off-by: C <c@example.com>\n";
+    assert_eq!(
+        normalize_trailers(message),
+        "subject\n\nbody\n\nSigned-off-by: A <a@example.com>\nSigned-off-by: C <c@example.com>\nReviewed-by: B\n"
+    );
+}
+
+#[test]
+fn continuation_lines_stay_with_their_trailer() {
+    let message = "subject\n\nFixes: a long\n  description\nAcked-by: A";
+    assert_eq!(
+        normalize_trailers(message),
+        "subject\n\nFixes: a long\n  description\nAcked-by: A"
+    );
+}
+
+#[test]
+fn messages_without_trailer_block_are_untouched() {
+    for message in [
+        "subject",
+        "subject\nSigned-off-by:A",
+        "subject\n\nbody\nSigned-off-by:A\n",
+        "subject\n\nSigned-off-by:A\nnot a trailer\n",
+    ] {
+        assert_eq!(normalize_trailers(message), message);
+    }
+}
+
+#[test]
+fn added_trailers_extend_the_trailer_block_without_duplicates() {
+    let trailers = [
+        ("Signed-off-by".to_owned(), "A <a@example.com>".to_owned()),
+        ("Reviewed-by".to_owned(), "B".to_owned()),
+        ("Reviewed-by".to_owned(), "B".to_owned()),
+    ];
+    assert_eq!(
+        add_trailers("subject\n", &trailers),
+        "subject\n\nSigned-off-by: A <a@example.com>\nReviewed-by: B\n"
+    );
+    assert_eq!(
+        add_trailers("subject\n\nsigned-off-by: A <a@example.com>", &trailers),
+        "subject\n\nsigned-off-by: A <a@example.com>\nReviewed-by: B",
+        "keys are compared case-insensitively"
+    );
+    assert_eq!(
+        add_trailers("subject\n\nbody", &trailers[..1]),
+        "subject\n\nbody\n\nSigned-off-by: A <a@example.com>"
+    );
+}
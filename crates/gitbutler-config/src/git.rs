@@ -10,12 +10,16 @@ pub struct GbConfig {
     pub signing_format: Option<String>,
     pub gpg_program: Option<String>,
     pub gpg_ssh_program: Option<String>,
+    pub change_id_trailer: Option<bool>,
 }
 const SIGN_COMMITS: &str = "gitbutler.signCommits";
+/// Git's own switch for signing commits, used if [`SIGN_COMMITS`] isn't set.
+const COMMIT_GPGSIGN: &str = "commit.gpgsign";
 const SIGNING_KEY: &str = "user.signingKey";
 const SIGNING_FORMAT: &str = "gpg.format";
 const GPG_PROGRAM: &str = "gpg.program";
 const GPG_SSH_PROGRAM: &str = "gpg.ssh.program";
+const CHANGE_ID_TRAILER: &str = "gitbutler.changeIdTrailer";
 
 pub trait GitConfig {
     fn gb_config(&self) -> Result<GbConfig>;
@@ -24,17 +28,22 @@ pub trait GitConfig {
 
 impl GitConfig for git2::Repository {
     fn gb_config(&self) -> Result<GbConfig> {
-        let sign_commits = get_bool(self, SIGN_COMMITS)?;
+        let sign_commits = match get_bool(self, SIGN_COMMITS)? {
+            Some(sign_commits) => Some(sign_commits),
+            None => get_bool(self, COMMIT_GPGSIGN)?,
+        };
         let signing_key = get_string(self, SIGNING_KEY)?;
         let signing_format = get_string(self, SIGNING_FORMAT)?;
         let gpg_program = get_string(self, GPG_PROGRAM)?;
         let gpg_ssh_program = get_string(self, GPG_SSH_PROGRAM)?;
+        let change_id_trailer = get_bool(self, CHANGE_ID_TRAILER)?;
         Ok(GbConfig {
             sign_commits,
             signing_key,
             signing_format,
             gpg_program,
             gpg_ssh_program,
+            change_id_trailer,
         })
     }
     fn set_gb_config(&self, config: GbConfig) -> Result<()> {
@@ -53,6 +62,9 @@ impl GitConfig for git2::Repository {
         if let Some(gpg_ssh_program) = config.gpg_ssh_program {
             set_local_string(self, GPG_SSH_PROGRAM, &gpg_ssh_program)?;
         }
+        if let Some(change_id_trailer) = config.change_id_trailer {
+            set_local_bool(self, CHANGE_ID_TRAILER, change_id_trailer)?;
+        }
         Ok(())
     }
 }
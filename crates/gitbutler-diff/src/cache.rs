@@ -0,0 +1,210 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{Context, Result};
+use bstr::BString;
+use serde::{Deserialize, Serialize};
+
+use crate::{ChangeType, FileDiff, GitHunk};
+
+/// The per-file results of diffing the worktree, keyed by the blob and the file metadata they were
+/// computed from, so that files that didn't change since don't have to be diffed again.
+///
+/// Use it with [`workdir_cached()`](crate::workdir_cached()).
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DiffCache {
+    files: HashMap<PathBuf, CachedFile>,
+    /// The number of files that were diffed by the last use of the cache.
+    #[serde(skip)]
+    pub(crate) files_diffed: usize,
+    /// Whether entries were added or removed since the cache was loaded or saved.
+    #[serde(skip)]
+    changed: bool,
+}
+
+/// Lifecycle
+impl DiffCache {
+    /// Read the cache from `path`, or start with an empty one if there is none or it can't be read.
+    pub fn load(path: &Path) -> Self {
+        std::fs::read(path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the cache to `path` if its entries changed since it was loaded or last saved.
+    /// The file is replaced atomically, so concurrent readers never see a partial cache.
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        let data = serde_json::to_vec(self)?;
+        gitbutler_fs::write(path, data)
+            .with_context(|| format!("failed to write diff cache to {}", path.display()))?;
+        self.changed = false;
+        Ok(())
+    }
+}
+
+impl DiffCache {
+    /// The number of files that had to be diffed by the last call to [`workdir_cached()`](crate::workdir_cached()),
+    /// as all others were taken from the cache.
+    pub fn files_diffed(&self) -> usize {
+        self.files_diffed
+    }
+
+    pub(crate) fn get(&self, path: &Path, key: &CacheKey) -> Option<FileDiff> {
+        let cached = self.files.get(path).filter(|cached| cached.key == *key)?;
+        Some(FileDiff {
+            path: path.to_owned(),
+            hunks: cached
+                .hunks
+                .iter()
+                .map(CachedHunk::to_hunk)
+                .collect::<Option<_>>()?,
+            old_path: None,
+            skipped: cached.skipped,
+            binary: cached.binary,
+            old_size_bytes: cached.old_size_bytes,
+            new_size_bytes: cached.new_size_bytes,
+        })
+    }
+
+    pub(crate) fn insert(&mut self, key: CacheKey, file: &FileDiff) {
+        self.changed = true;
+        self.files.insert(
+            file.path.clone(),
+            CachedFile {
+                key,
+                hunks: file.hunks.iter().map(CachedHunk::from_hunk).collect(),
+                skipped: file.skipped,
+                binary: file.binary,
+                old_size_bytes: file.old_size_bytes,
+                new_size_bytes: file.new_size_bytes,
+            },
+        );
+    }
+
+    /// Forget all files for which `keep` returns `false`.
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(&Path) -> bool) {
+        let len = self.files.len();
+        self.files.retain(|path, _| keep(path));
+        self.changed |= self.files.len() != len;
+    }
+}
+
+/// What the diff of a file in the worktree depends on.
+///
+/// Like the index does, the worktree file is identified by its metadata instead of its content,
+/// so that files don't have to be read to find out whether they changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct CacheKey {
+    /// The blob the worktree file is compared to, or the null id if the file is new.
+    old_id: String,
+    /// The modification time of the worktree file in nanoseconds since the epoch, or 0 if the file was deleted.
+    mtime_ns: u64,
+    /// The size of the worktree file in bytes, or 0 if the file was deleted.
+    size: u64,
+    /// The inode of the worktree file, or 0 if the file was deleted or there are no inodes.
+    ino: u64,
+    old_mode: u32,
+    new_mode: u32,
+}
+
+impl CacheKey {
+    /// Compute the key of the file at `file_path` in the worktree whose changes are described by `delta`.
+    /// Returns `None` if the diff of the file shouldn't be cached, like for symlinks or files larger
+    /// than `max_size_bytes`.
+    pub(crate) fn new(
+        file_path: &Path,
+        delta: &git2::DiffDelta<'_>,
+        max_size_bytes: u64,
+    ) -> Result<Option<Self>> {
+        let (mtime_ns, size, ino) = match std::fs::symlink_metadata(file_path) {
+            Ok(metadata) if metadata.is_file() && metadata.len() <= max_size_bytes => {
+                let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+                (mtime.as_nanos() as u64, metadata.len(), inode(&metadata))
+            }
+            Ok(_) => return Ok(None),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (0, 0, 0),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Some(CacheKey {
+            old_id: delta.old_file().id().to_string(),
+            mtime_ns,
+            size,
+            ino,
+            old_mode: delta.old_file().mode().into(),
+            new_mode: delta.new_file().mode().into(),
+        }))
+    }
+}
+
+#[cfg(unix)]
+fn inode(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &std::fs::Metadata) -> u64 {
+    0
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedFile {
+    key: CacheKey,
+    hunks: Vec<CachedHunk>,
+    skipped: bool,
+    binary: bool,
+    old_size_bytes: u64,
+    new_size_bytes: u64,
+}
+
+/// A [`GitHunk`] as computed by diffing, without the information that is added to it afterwards.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedHunk {
+    old_start: u32,
+    old_lines: u32,
+    new_start: u32,
+    new_lines: u32,
+    /// The hex-encoded lines of the diff, as they aren't necessarily valid UTF-8.
+    diff: String,
+    binary: bool,
+    old_blob_id: Option<String>,
+    change_type: ChangeType,
+}
+
+impl CachedHunk {
+    fn from_hunk(hunk: &GitHunk) -> Self {
+        CachedHunk {
+            old_start: hunk.old_start,
+            old_lines: hunk.old_lines,
+            new_start: hunk.new_start,
+            new_lines: hunk.new_lines,
+            diff: hex::encode(&hunk.diff_lines),
+            binary: hunk.binary,
+            old_blob_id: hunk.old_blob_id.clone(),
+            change_type: hunk.change_type,
+        }
+    }
+
+    fn to_hunk(&self) -> Option<GitHunk> {
+        Some(GitHunk {
+            old_start: self.old_start,
+            old_lines: self.old_lines,
+            new_start: self.new_start,
+            new_lines: self.new_lines,
+            diff_lines: BString::from(hex::decode(&self.diff).ok()?).into(),
+            binary: self.binary,
+            old_blob_id: self.old_blob_id.clone(),
+            renamed_from: None,
+            change_type: self.change_type,
+            moved_lines: Vec::new(),
+            lines: Vec::new(),
+        })
+    }
+}
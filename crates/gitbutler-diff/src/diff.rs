@@ -1,4 +1,10 @@
-use std::{borrow::Cow, collections::HashMap, path::PathBuf, str};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    ops::RangeInclusive,
+    path::PathBuf,
+    str,
+};
 
 use anyhow::{Context, Result};
 use bstr::{BStr, BString, ByteSlice, ByteVec};
@@ -8,6 +14,14 @@ use gitbutler_serde::BStringForFrontend;
 use serde::{Deserialize, Serialize};
 use tracing::instrument;
 
+use crate::{
+    cache::{CacheKey, DiffCache},
+    filter::drop_unchanged_after_clean,
+    hunk::LineFilter,
+    lines::{annotate_lines, DiffLine},
+    moved::{annotate_moved_lines, MovedLine},
+};
+
 pub type DiffByPathMap = HashMap<PathBuf, FileDiff>;
 
 /// The type of change
@@ -50,7 +64,23 @@ pub struct GitHunk {
     #[serde(rename = "diff")]
     pub diff_lines: BStringForFrontend,
     pub binary: bool,
+    /// For the binary hunk of a file whose content changed from text to binary or back, this is the
+    /// hex hash of the blob before the change, with [`diff_lines`](Self::diff_lines) holding the one after it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_blob_id: Option<String>,
+    /// For the hunks of a file that was renamed, this is the path it had before, which is removed
+    /// when the hunk is written to a tree along with writing the file at its new path.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub renamed_from: Option<PathBuf>,
     pub change_type: ChangeType,
+    /// Lines of this hunk that were moved within the same file.
+    /// Only filled in if [`DiffOptions::detect_moved_lines`] is set.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub moved_lines: Vec<MovedLine>,
+    /// The lines of [`diff_lines`](Self::diff_lines) along with their line numbers.
+    /// Only filled in if [`DiffOptions::structured_lines`] is set.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lines: Vec<DiffLine>,
 }
 
 /// Lifecycle
@@ -65,7 +95,11 @@ impl GitHunk {
             new_lines: 0,
             diff_lines: hex_id.into(),
             binary: true,
+            old_blob_id: None,
+            renamed_from: None,
             change_type,
+            moved_lines: Vec::new(),
+            lines: Vec::new(),
         }
     }
 
@@ -78,7 +112,20 @@ impl GitHunk {
             new_lines: 0,
             diff_lines: Default::default(),
             binary: false,
+            old_blob_id: None,
+            renamed_from: None,
             change_type: ChangeType::Added,
+            moved_lines: Vec::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    /// Return a hunk without content that signals that only the mode or the path of a file changed,
+    /// like when it was made executable or renamed.
+    fn generic_mode_change() -> Self {
+        Self {
+            change_type: ChangeType::Modified,
+            ..Self::generic_new_file()
         }
     }
 }
@@ -107,6 +154,214 @@ impl GitHunk {
     }
 }
 
+/// Transformation
+impl GitHunk {
+    /// Return a copy of this hunk with only its added or only its removed lines, as chosen by `filter`,
+    /// or `None` if it is binary or has no such lines.
+    ///
+    /// Removed lines turn into context if only additions are kept, and added lines are dropped if only
+    /// deletions are kept, so the result still applies to the same version of the file as this hunk.
+    pub fn filter_lines(&self, filter: LineFilter) -> Option<GitHunk> {
+        if self.binary {
+            return None;
+        }
+        let mut header_suffix: &[u8] = b"\n";
+        let mut body = BString::default();
+        let (mut old_lines, mut new_lines, mut kept) = (0, 0, 0);
+        let mut dropped_previous_line = false;
+        for line in self.diff_lines.lines_with_terminator() {
+            match (line.first(), filter) {
+                (Some(b'@'), _) => {
+                    if let Some(pos) = line.get(2..).and_then(|rest| rest.find(b"@@")) {
+                        header_suffix = &line[pos + 4..];
+                    }
+                    continue;
+                }
+                // 'no newline at end of file' markers belong to the line before them.
+                (Some(b'\\'), _) => {
+                    if !dropped_previous_line {
+                        body.push_str(line);
+                    }
+                    continue;
+                }
+                (Some(b'+'), LineFilter::Additions) => {
+                    new_lines += 1;
+                    kept += 1;
+                    body.push_str(line);
+                }
+                (Some(b'-'), LineFilter::Deletions) => {
+                    old_lines += 1;
+                    kept += 1;
+                    body.push_str(line);
+                }
+                (Some(b'+'), LineFilter::Deletions) => {
+                    dropped_previous_line = true;
+                    continue;
+                }
+                (Some(b'-'), LineFilter::Additions) => {
+                    old_lines += 1;
+                    new_lines += 1;
+                    body.push(b' ');
+                    body.push_str(&line[1..]);
+                }
+                _ => {
+                    old_lines += 1;
+                    new_lines += 1;
+                    body.push_str(line);
+                }
+            }
+            dropped_previous_line = false;
+        }
+        if kept == 0 {
+            return None;
+        }
+
+        Some(self.with_body(header_suffix, body, old_lines, new_lines))
+    }
+
+    /// Return a copy of this hunk with only the changes to the lines in any of the ranges in `lines`,
+    /// numbered as in the new version of the file, or `None` if it is binary or doesn't change any
+    /// of these lines.
+    ///
+    /// A removed line counts as being at the position of the added line that replaces it, which is
+    /// the one at the same offset within the same block of changes, or else at the position of the
+    /// line that follows the block. Like with [`filter_lines()`](Self::filter_lines()), removed lines
+    /// that aren't selected turn into context and added lines that aren't selected are dropped.
+    pub fn select_lines(&self, lines: &[RangeInclusive<u32>]) -> Option<GitHunk> {
+        if self.binary {
+            return None;
+        }
+        let mut header_suffix: &[u8] = b"\n";
+        let mut body = BString::default();
+        let mut counts = LineCounts::default();
+        let mut line_number = self.new_start;
+        let (mut removed, mut added) = (Vec::<BString>::new(), Vec::<BString>::new());
+        for line in self.diff_lines.lines_with_terminator() {
+            match line.first() {
+                Some(b'@') => {
+                    if let Some(pos) = line.get(2..).and_then(|rest| rest.find(b"@@")) {
+                        header_suffix = &line[pos + 4..];
+                    }
+                }
+                // 'no newline at end of file' markers belong to the line before them.
+                Some(b'\\') => match added.last_mut().or(removed.last_mut()) {
+                    Some(previous) => previous.push_str(line),
+                    None => body.push_str(line),
+                },
+                Some(b'-') if added.is_empty() => removed.push(line.into()),
+                Some(b'+') => added.push(line.into()),
+                _ => {
+                    line_number = select_block_lines(
+                        lines,
+                        line_number,
+                        &mut removed,
+                        &mut added,
+                        &mut body,
+                        &mut counts,
+                    );
+                    if line.first() == Some(&b'-') {
+                        removed.push(line.into());
+                    } else {
+                        line_number += 1;
+                        counts.old += 1;
+                        counts.new += 1;
+                        body.push_str(line);
+                    }
+                }
+            }
+        }
+        select_block_lines(
+            lines,
+            line_number,
+            &mut removed,
+            &mut added,
+            &mut body,
+            &mut counts,
+        );
+        if counts.kept == 0 {
+            return None;
+        }
+        Some(self.with_body(header_suffix, body, counts.old, counts.new))
+    }
+
+    fn with_body(
+        &self,
+        header_suffix: &[u8],
+        body: BString,
+        old_lines: u32,
+        new_lines: u32,
+    ) -> GitHunk {
+        let mut diff_lines = BString::from(format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, old_lines, self.new_start, new_lines
+        ));
+        diff_lines.push_str(header_suffix);
+        diff_lines.push_str(body);
+        GitHunk {
+            old_start: self.old_start,
+            old_lines,
+            new_start: self.new_start,
+            new_lines,
+            diff_lines: diff_lines.into(),
+            binary: false,
+            old_blob_id: None,
+            renamed_from: self.renamed_from.clone(),
+            change_type: self.change_type,
+            moved_lines: Vec::new(),
+            lines: Vec::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct LineCounts {
+    old: u32,
+    new: u32,
+    kept: u32,
+}
+
+/// Write the `removed` lines followed by the `added` lines of a block of changes that starts at
+/// `line_number` in the new file to `body`, keeping only the changes to `lines`, and return the
+/// line number after the block.
+fn select_block_lines(
+    lines: &[RangeInclusive<u32>],
+    line_number: u32,
+    removed: &mut Vec<BString>,
+    added: &mut Vec<BString>,
+    body: &mut BString,
+    counts: &mut LineCounts,
+) -> u32 {
+    // Interleave the lines so unselected removals that turn into context stay in place.
+    for i in 0..removed.len().max(added.len()) {
+        if let Some(line) = removed.get(i) {
+            let position = line_number + i.min(added.len()) as u32;
+            counts.old += 1;
+            if lines.iter().any(|lines| lines.contains(&position)) {
+                counts.kept += 1;
+                body.push_str(line);
+            } else {
+                counts.new += 1;
+                body.push(b' ');
+                body.push_str(&line[1..]);
+            }
+        }
+        if let Some(line) = added.get(i) {
+            if lines
+                .iter()
+                .any(|lines| lines.contains(&(line_number + i as u32)))
+            {
+                counts.new += 1;
+                counts.kept += 1;
+                body.push_str(line);
+            }
+        }
+    }
+    let next_line_number = line_number + added.len() as u32;
+    removed.clear();
+    added.clear();
+    next_line_number
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct FileDiff {
@@ -114,6 +369,9 @@ pub struct FileDiff {
     /// Hunks might be empty if nothing about the files content is known, which happens
     /// if the content is skipped due to it being a large file.
     pub hunks: Vec<GitHunk>,
+    /// If the file was renamed, this is the path it had before, and `hunks` are the changes to its content.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<PathBuf>,
     pub skipped: bool,
     /// This is `true` if this is a file with undiffable content. Then, `hunks` might be a single
     /// hunk that is the hash of the binary blob in Git.
@@ -122,13 +380,133 @@ pub struct FileDiff {
     pub new_size_bytes: u64,
 }
 
-#[instrument(level = tracing::Level::DEBUG, skip(repo))]
+/// Options to control what is computed in addition to the plain diff.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DiffOptions {
+    /// If `true`, annotate lines that were moved within the same file with [`GitHunk::moved_lines`],
+    /// similar to `git diff --color-moved`.
+    pub detect_moved_lines: bool,
+    /// If `true`, fill in [`GitHunk::lines`] so hunks can be rendered without parsing the unified diff.
+    pub structured_lines: bool,
+}
+
+impl DiffOptions {
+    /// Annotate the hunks of `files` as requested by these options, for diffs that were
+    /// computed without them.
+    pub fn apply(&self, files: &mut DiffByPathMap) {
+        if self.detect_moved_lines {
+            annotate_moved_lines(files);
+        }
+        if self.structured_lines {
+            annotate_lines(files);
+        }
+    }
+}
+
 pub fn workdir(repo: &git2::Repository, commit_oid: git2::Oid) -> Result<DiffByPathMap> {
+    workdir_with_options(repo, commit_oid, DiffOptions::default())
+}
+
+/// Like [`workdir()`], but with `options` to control additional processing.
+#[instrument(level = tracing::Level::DEBUG, skip(repo))]
+pub fn workdir_with_options(
+    repo: &git2::Repository,
+    commit_oid: git2::Oid,
+    options: DiffOptions,
+) -> Result<DiffByPathMap> {
+    let commit = repo
+        .find_commit(commit_oid)
+        .context("failed to find commit")?;
+    let old_tree = repo.find_real_tree(&commit, Default::default())?;
+
+    let diff = diff_workdir(repo, &old_tree, &mut workdir_diff_options())?;
+    let mut files = hunks_by_filepath(Some(repo), &diff)?;
+    drop_unchanged_after_clean(repo, &old_tree, &mut files);
+    options.apply(&mut files);
+    Ok(files)
+}
+
+/// Summarize the changes between `commit_oid` and the worktree of `repo` that [`workdir()`] lists,
+/// like `git diff --shortstat` would.
+pub fn workdir_stats(repo: &git2::Repository, commit_oid: git2::Oid) -> Result<git2::DiffStats> {
+    let commit = repo
+        .find_commit(commit_oid)
+        .context("failed to find commit")?;
+    let old_tree = repo.find_real_tree(&commit, Default::default())?;
+    Ok(diff_workdir(repo, &old_tree, &mut workdir_diff_options())?.stats()?)
+}
+
+/// Like [`workdir_with_options()`], but only diffs the files whose size, modification time or inode changed
+/// since they were last diffed with `cache`, taking the hunks of all other files from `cache`.
+/// `cache` is updated with the newly diffed files.
+#[instrument(level = tracing::Level::DEBUG, skip(repo, cache))]
+pub fn workdir_cached(
+    repo: &git2::Repository,
+    commit_oid: git2::Oid,
+    options: DiffOptions,
+    cache: &mut DiffCache,
+) -> Result<DiffByPathMap> {
     let commit = repo
         .find_commit(commit_oid)
         .context("failed to find commit")?;
     let old_tree = repo.find_real_tree(&commit, Default::default())?;
+    let workdir = repo.workdir().context("repository has no worktree")?;
+
+    // Computing the changed files is cheap, it's producing their hunks that we want to avoid.
+    let diff = diff_workdir(repo, &old_tree, &mut workdir_diff_options())?;
+    let mut files = DiffByPathMap::new();
+    let mut keys = HashMap::new();
+    let mut deltas_to_diff = Vec::new();
+    for (idx, delta) in diff.deltas().enumerate() {
+        let path = delta
+            .new_file()
+            .path()
+            .or_else(|| delta.old_file().path())
+            .context("failed to get file name from diff")?
+            .to_owned();
+        let key = match delta.status() {
+            git2::Delta::Renamed | git2::Delta::Copied => None,
+            _ => CacheKey::new(&workdir.join(&path), &delta, LARGE_FILE_LIMIT_BYTES)?,
+        };
+        match key.as_ref().and_then(|key| cache.get(&path, key)) {
+            Some(file) => {
+                files.insert(path, file);
+            }
+            None => {
+                deltas_to_diff.push(idx);
+                if let Some(key) = key {
+                    keys.insert(path, key);
+                }
+            }
+        }
+    }
+
+    // Only produce the hunks of the files that aren't cached, from the same diff.
+    let diffed = hunks_by_filepath_from_lines(Some(repo), |on_line| {
+        for idx in deltas_to_diff {
+            if let Some(mut patch) = git2::Patch::from_diff(&diff, idx)? {
+                patch.print(on_line)?;
+            }
+        }
+        Ok(())
+    })?;
+    cache.files_diffed = diffed.len();
+    for (path, file) in diffed {
+        if let Some(key) = keys.remove(&path) {
+            cache.insert(key, &file);
+        }
+        files.insert(path, file);
+    }
+    cache.retain(|path| files.contains_key(path));
+    drop_unchanged_after_clean(repo, &old_tree, &mut files);
+    options.apply(&mut files);
+    Ok(files)
+}
 
+/// Files larger than this are not diffed, and are marked as skipped instead.
+const LARGE_FILE_LIMIT_BYTES: u64 = 50_000_000;
+
+fn workdir_diff_options() -> git2::DiffOptions {
     let mut diff_opts = git2::DiffOptions::new();
     diff_opts
         .recurse_untracked_dirs(true)
@@ -137,7 +515,15 @@ pub fn workdir(repo: &git2::Repository, commit_oid: git2::Oid) -> Result<DiffByP
         .show_untracked_content(true)
         .ignore_submodules(true)
         .context_lines(3);
+    diff_opts
+}
 
+/// Diff `old_tree` against the worktree of `repo` with `diff_opts`, with renames detected.
+fn diff_workdir<'repo>(
+    repo: &'repo git2::Repository,
+    old_tree: &git2::Tree,
+    diff_opts: &mut git2::DiffOptions,
+) -> Result<git2::Diff<'repo>> {
     let mut index = repo.index()?;
     // Just a hack to resolve conflicts, which don't get diffed.
     // Diffed conflicts are something we need though.
@@ -156,9 +542,28 @@ pub fn workdir(repo: &git2::Repository, commit_oid: git2::Oid) -> Result<DiffByP
     for conflict_path_to_resolve in paths_to_add {
         index.add_path(conflict_path_to_resolve.as_ref())?;
     }
-    repo.ignore_large_files_in_diffs(50_000_000)?;
-    let diff = repo.diff_tree_to_workdir_with_index(Some(&old_tree), Some(&mut diff_opts))?;
-    hunks_by_filepath(Some(repo), &diff)
+    repo.ignore_large_files_in_diffs(LARGE_FILE_LIMIT_BYTES)?;
+    let mut diff = repo.diff_tree_to_workdir_with_index(Some(old_tree), Some(diff_opts))?;
+    // Untracked files are the new side of renames in the worktree, so they have to be considered too.
+    diff.find_similar(Some(
+        git2::DiffFindOptions::new()
+            .renames(true)
+            .for_untracked(true)
+            .rename_threshold(rename_detection_threshold(repo)?),
+    ))?;
+    Ok(diff)
+}
+
+/// The git configuration key holding the similarity in percent above which a deleted and an added file
+/// are considered a rename, like `diff.renames` does for `git status`.
+pub const RENAME_DETECTION_THRESHOLD: &str = "gitbutler.renameDetectionThreshold";
+
+fn rename_detection_threshold(repo: &git2::Repository) -> Result<u16> {
+    match repo.config()?.get_i32(RENAME_DETECTION_THRESHOLD) {
+        Ok(threshold) => Ok(threshold.clamp(0, 100) as u16),
+        Err(err) if err.code() == git2::ErrorCode::NotFound => Ok(50),
+        Err(err) => Err(err.into()),
+    }
 }
 
 pub fn trees(
@@ -166,6 +571,23 @@ pub fn trees(
     old_tree: &git2::Tree,
     new_tree: &git2::Tree,
     include_context: bool,
+) -> Result<DiffByPathMap> {
+    trees_with_options(
+        repo,
+        old_tree,
+        new_tree,
+        include_context,
+        DiffOptions::default(),
+    )
+}
+
+/// Like [`trees()`], but with `options` to control additional processing.
+pub fn trees_with_options(
+    repo: &git2::Repository,
+    old_tree: &git2::Tree,
+    new_tree: &git2::Tree,
+    include_context: bool,
+    options: DiffOptions,
 ) -> Result<DiffByPathMap> {
     let mut diff_opts = git2::DiffOptions::new();
     let context_lines = match include_context {
@@ -178,7 +600,9 @@ pub fn trees(
         .context_lines(context_lines);
 
     let diff = repo.diff_tree_to_tree(Some(old_tree), Some(new_tree), Some(&mut diff_opts))?;
-    hunks_by_filepath(None, &diff)
+    let mut files = hunks_by_filepath(None, &diff)?;
+    options.apply(&mut files);
+    Ok(files)
 }
 
 /// Transform `diff` into a mapping of `worktree-relative path -> FileDiff`, where `FileDiff` is
@@ -191,6 +615,17 @@ pub fn trees(
 pub fn hunks_by_filepath(
     repo: Option<&git2::Repository>,
     diff: &git2::Diff,
+) -> Result<DiffByPathMap> {
+    hunks_by_filepath_from_lines(repo, |on_line| diff.print(git2::DiffFormat::Patch, on_line))
+}
+
+/// Like [`hunks_by_filepath()`], but with the lines of the diff produced by `print`, which
+/// passes each of them to the callback it receives.
+fn hunks_by_filepath_from_lines(
+    repo: Option<&git2::Repository>,
+    print: impl FnOnce(
+        &mut dyn FnMut(git2::DiffDelta<'_>, Option<git2::DiffHunk<'_>>, git2::DiffLine<'_>) -> bool,
+    ) -> Result<(), git2::Error>,
 ) -> Result<DiffByPathMap> {
     enum LineOrHexHash<'a> {
         Line(Cow<'a, BStr>),
@@ -198,115 +633,169 @@ pub fn hunks_by_filepath(
     }
     // find all the hunks
     let mut diff_files = HashMap::new();
+    let mut mode_changes = HashSet::new();
+    let mut binary_transitions = HashMap::new();
     let mut err = None;
 
-    diff.print(
-        git2::DiffFormat::Patch,
-        |delta, hunk, line: git2::DiffLine<'_>| {
-            let change_type: ChangeType = delta.status().into();
-            let file_path = delta.new_file().path().unwrap_or_else(|| {
-                delta
-                    .old_file()
-                    .path()
-                    .expect("failed to get file name from diff")
-            });
-
-            let new_start = hunk.as_ref().map_or(0, git2::DiffHunk::new_start);
-            let new_lines = hunk.as_ref().map_or(0, git2::DiffHunk::new_lines);
-            let old_start = hunk.as_ref().map_or(0, git2::DiffHunk::old_start);
-            let old_lines = hunk.as_ref().map_or(0, git2::DiffHunk::old_lines);
-
-            use git2::DiffLineType as D;
-            let line = match line.origin_value() {
-                D::Addition | D::Deletion | D::Context => {
-                    let mut buf = BString::new(Vec::with_capacity(line.content().len() + 1));
-                    buf.push_char(line.origin());
-                    buf.push_str(line.content());
-                    Some(LineOrHexHash::Line(buf.into()))
-                }
-                D::Binary => {
-                    if let Some((full_path, repo)) = repo
-                        .and_then(|repo| repo.workdir())
-                        .map(|workdir| workdir.join(file_path))
-                        .zip(repo)
-                    {
-                        if !delta.new_file().id().is_zero() && full_path.exists() {
-                            let oid = repo.blob_path(full_path.as_path()).unwrap();
-                            if delta.new_file().id() != oid {
-                                err = Some(format!("we only store the file which is already known by the diff system, but it was different: {} != {}", delta.new_file().id(), oid));
-                                return false
-                            }
+    let mut on_line = |delta: git2::DiffDelta<'_>,
+                       hunk: Option<git2::DiffHunk<'_>>,
+                       line: git2::DiffLine<'_>| {
+        let change_type: ChangeType = delta.status().into();
+        let file_path = delta.new_file().path().unwrap_or_else(|| {
+            delta
+                .old_file()
+                .path()
+                .expect("failed to get file name from diff")
+        });
+
+        let new_start = hunk.as_ref().map_or(0, git2::DiffHunk::new_start);
+        let new_lines = hunk.as_ref().map_or(0, git2::DiffHunk::new_lines);
+        let old_start = hunk.as_ref().map_or(0, git2::DiffHunk::old_start);
+        let old_lines = hunk.as_ref().map_or(0, git2::DiffHunk::old_lines);
+
+        use git2::DiffLineType as D;
+        let line = match line.origin_value() {
+            D::Addition | D::Deletion | D::Context => {
+                let mut buf = BString::new(Vec::with_capacity(line.content().len() + 1));
+                buf.push_char(line.origin());
+                buf.push_str(line.content());
+                Some(LineOrHexHash::Line(buf.into()))
+            }
+            D::Binary => {
+                if let Some((full_path, repo)) = repo
+                    .and_then(|repo| repo.workdir())
+                    .map(|workdir| workdir.join(file_path))
+                    .zip(repo)
+                {
+                    if !delta.new_file().id().is_zero() && full_path.exists() {
+                        let oid = repo.blob_path(full_path.as_path()).unwrap();
+                        if delta.new_file().id() != oid {
+                            err = Some(format!("we only store the file which is already known by the diff system, but it was different: {} != {}", delta.new_file().id(), oid));
+                            return false;
                         }
                     }
-                    Some(LineOrHexHash::HexHashOfBinaryBlob(delta.new_file().id().to_string()))
-                }
-                D::FileHeader => None,
-                D::HunkHeader | D::ContextEOFNL | D::AddEOFNL | D::DeleteEOFNL => {
-                    Some(LineOrHexHash::Line(line.content().as_bstr().into()))
                 }
-            };
+                Some(LineOrHexHash::HexHashOfBinaryBlob(
+                    delta.new_file().id().to_string(),
+                ))
+            }
+            D::FileHeader => None,
+            D::HunkHeader | D::ContextEOFNL | D::AddEOFNL | D::DeleteEOFNL => {
+                Some(LineOrHexHash::Line(line.content().as_bstr().into()))
+            }
+        };
 
-            match line {
-                None => {
-                    let existing = diff_files
-                        .insert(file_path.to_path_buf(),
-                            FileDiff {
-                                path: file_path.to_path_buf(),
-                                hunks: Vec::new(),
-                                skipped: false,
-                                binary: delta.new_file().is_binary(),
-                                old_size_bytes: delta.old_file().size(),
-                                new_size_bytes: delta.new_file().size(),
-                        });
-                    if existing.is_some() {
-                        err = Some(format!("Encountered an invalid internal state related to the diff: {existing:?}"));
-                        return false;
-                    }
+        match line {
+            None => {
+                if change_type == ChangeType::Modified
+                    && delta.old_file().mode() != delta.new_file().mode()
+                {
+                    mode_changes.insert(file_path.to_path_buf());
                 }
-                Some(line) => {
-                    let hunks = &mut diff_files.get_mut(file_path).expect("File header inserts the hunk-list").hunks;
-                    let same_hunk = hunks.last_mut().filter(|previous_hunk| {
-                        previous_hunk.old_start == old_start
-                            && previous_hunk.old_lines == old_lines
-                            && previous_hunk.new_start == new_start
-                            && previous_hunk.new_lines == new_lines
-                    });
-                    match same_hunk {
-                        Some(hunk) => match line {
-                            LineOrHexHash::Line(line) => {
-                                hunk.diff_lines.push_str(line.as_ref());
-                            }
+                if change_type == ChangeType::Modified
+                    && delta.old_file().is_binary() != delta.new_file().is_binary()
+                {
+                    binary_transitions.insert(
+                        file_path.to_path_buf(),
+                        (delta.old_file().id(), delta.new_file().id()),
+                    );
+                }
+                let existing = diff_files.insert(
+                    file_path.to_path_buf(),
+                    FileDiff {
+                        path: file_path.to_path_buf(),
+                        hunks: Vec::new(),
+                        old_path: (delta.status() == git2::Delta::Renamed)
+                            .then(|| delta.old_file().path().map(ToOwned::to_owned))
+                            .flatten(),
+                        skipped: false,
+                        binary: delta.new_file().is_binary(),
+                        old_size_bytes: delta.old_file().size(),
+                        new_size_bytes: delta.new_file().size(),
+                    },
+                );
+                if existing.is_some() {
+                    err = Some(format!(
+                        "Encountered an invalid internal state related to the diff: {existing:?}"
+                    ));
+                    return false;
+                }
+            }
+            Some(line) => {
+                let hunks = &mut diff_files
+                    .get_mut(file_path)
+                    .expect("File header inserts the hunk-list")
+                    .hunks;
+                let same_hunk = hunks.last_mut().filter(|previous_hunk| {
+                    previous_hunk.old_start == old_start
+                        && previous_hunk.old_lines == old_lines
+                        && previous_hunk.new_start == new_start
+                        && previous_hunk.new_lines == new_lines
+                });
+                match same_hunk {
+                    Some(hunk) => match line {
+                        LineOrHexHash::Line(line) => {
+                            hunk.diff_lines.push_str(line.as_ref());
+                        }
+                        LineOrHexHash::HexHashOfBinaryBlob(id) => {
+                            let marker = GitHunk::binary_marker(id, hunk.change_type);
+                            *hunk = marker;
+                        }
+                    },
+                    None => {
+                        let new_hunk = match line {
+                            LineOrHexHash::Line(line) => GitHunk {
+                                old_start,
+                                old_lines,
+                                new_start,
+                                new_lines,
+                                diff_lines: line.into_owned().into(),
+                                binary: false,
+                                old_blob_id: None,
+                                renamed_from: None,
+                                change_type,
+                                moved_lines: Vec::new(),
+                                lines: Vec::new(),
+                            },
                             LineOrHexHash::HexHashOfBinaryBlob(id) => {
-                                let marker =  GitHunk::binary_marker(id, hunk.change_type) ;
-                                *hunk = marker;
+                                GitHunk::binary_marker(id, change_type)
                             }
-                        },
-                        None => {
-                            let new_hunk = match line {
-                                LineOrHexHash::Line(line) => {
-                                    GitHunk {
-                                        old_start,
-                                        old_lines,
-                                        new_start,
-                                        new_lines,
-                                        diff_lines: line.into_owned().into(),
-                                        binary: false,
-                                        change_type,
-                                    }
-                                }
-                                LineOrHexHash::HexHashOfBinaryBlob(id) => {
-                                    GitHunk::binary_marker(id, change_type)
-                                }
-                            };
-                            hunks.push(new_hunk);
-                        }
+                        };
+                        hunks.push(new_hunk);
                     }
                 }
             }
-            true
-        },
-    )
-    .with_context(|| format!("failed to print diff: {err:?}"))?;
+        }
+        true
+    };
+    print(&mut on_line).with_context(|| format!("failed to print diff: {err:?}"))?;
+
+    for (path, (old_id, new_id)) in binary_transitions {
+        let Some(file) = diff_files.get_mut(&path) else {
+            continue;
+        };
+        // The content changed between text and binary, which is shown as binary change to avoid
+        // rendering the binary side as (possibly huge) text.
+        if !file.hunks.iter().any(|hunk| hunk.binary) {
+            if let Some((full_path, repo)) = repo
+                .and_then(|repo| repo.workdir())
+                .map(|workdir| workdir.join(&path))
+                .zip(repo)
+                .filter(|(full_path, _)| full_path.exists())
+            {
+                // Keep the blob in the object database, as done for binary hunks above.
+                repo.blob_path(&full_path)?;
+            }
+            file.hunks = vec![GitHunk::binary_marker(
+                new_id.to_string(),
+                ChangeType::Modified,
+            )];
+        }
+        file.binary = true;
+        for hunk in file.hunks.iter_mut().filter(|hunk| hunk.binary) {
+            hunk.old_blob_id = Some(old_id.to_string());
+        }
+    }
 
     for file in diff_files.values_mut() {
         if let Some(binary_hunk) = file
@@ -320,7 +809,19 @@ pub fn hunks_by_filepath(
                 file.hunks = vec![binary_hunk];
             }
         } else if file.hunks.is_empty() {
-            file.hunks = vec![GitHunk::generic_new_file()];
+            file.hunks = if mode_changes.contains(&file.path) || file.old_path.is_some() {
+                vec![GitHunk::generic_mode_change()]
+            } else {
+                vec![GitHunk::generic_new_file()]
+            };
+        }
+    }
+
+    for file in diff_files.values_mut() {
+        if let Some(old_path) = &file.old_path {
+            for hunk in &mut file.hunks {
+                hunk.renamed_from = Some(old_path.clone());
+            }
         }
     }
 
@@ -402,7 +903,11 @@ pub fn reverse_hunk(hunk: &GitHunk) -> Option<GitHunk> {
             new_lines: hunk.old_lines,
             diff_lines: diff.into(),
             binary: hunk.binary,
+            old_blob_id: None,
+            renamed_from: None,
             change_type: new_change_type,
+            moved_lines: Vec::new(),
+            lines: Vec::new(),
         })
     }
 }
@@ -0,0 +1,146 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bstr::ByteSlice;
+
+use crate::DiffByPathMap;
+
+/// The `clean` command of the filter driver that applies to a file with `filter=<driver>` in
+/// `.gitattributes`, which libgit2 doesn't run by itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CleanFilter {
+    /// The name of the driver, as set with `filter=<driver>`.
+    pub driver: String,
+    /// The command of `filter.<driver>.clean`.
+    pub command: String,
+    /// If `filter.<driver>.required` is set, failing to run the filter is an error.
+    pub required: bool,
+}
+
+impl CleanFilter {
+    /// Find the clean filter for the file at `path` in the worktree of `repo`, if it has one.
+    /// LFS is handled separately, and isn't returned.
+    ///
+    /// Fails if a `required` driver has no clean command.
+    pub fn for_path(repo: &git2::Repository, path: &Path) -> Result<Option<Self>> {
+        let git2::AttrValue::String(driver) = git2::AttrValue::from_string(repo.get_attr(
+            path,
+            "filter",
+            git2::AttrCheckFlags::FILE_THEN_INDEX,
+        )?) else {
+            return Ok(None);
+        };
+        if driver == "lfs" {
+            return Ok(None);
+        }
+
+        let config = repo.config()?;
+        let required = config
+            .get_bool(&format!("filter.{driver}.required"))
+            .unwrap_or(false);
+        let Ok(command) = config.get_string(&format!("filter.{driver}.clean")) else {
+            if required {
+                bail!(
+                    "filter {driver} for {} is required, but has no clean command",
+                    path.display()
+                );
+            }
+            return Ok(None);
+        };
+        Ok(Some(CleanFilter {
+            driver: driver.to_owned(),
+            command,
+            required,
+        }))
+    }
+
+    /// Run the filter on `content` of the file at `path`.
+    pub fn run(&self, repo: &git2::Repository, path: &Path, content: &[u8]) -> Result<Vec<u8>> {
+        run_filter_command(repo, &self.command, path, content)
+    }
+}
+
+/// Remove the files from `files` whose worktree content is the same as their blob in `old_tree`
+/// once it passed through their clean filter, as that is what committing them would store.
+pub(crate) fn drop_unchanged_after_clean(
+    repo: &git2::Repository,
+    old_tree: &git2::Tree,
+    files: &mut DiffByPathMap,
+) {
+    let Some(workdir) = repo.workdir() else {
+        return;
+    };
+    files.retain(|path, _| {
+        let Ok(entry) = old_tree.get_path(path) else {
+            return true;
+        };
+        let Ok(Some(filter)) = CleanFilter::for_path(repo, path) else {
+            return true;
+        };
+        let (Ok(content), Ok(blob)) = (
+            std::fs::read(workdir.join(path)),
+            repo.find_blob(entry.id()),
+        ) else {
+            return true;
+        };
+        match filter.run(repo, path, &content) {
+            Ok(cleaned) => cleaned != blob.content(),
+            Err(err) => {
+                tracing::warn!(
+                    "clean filter {} failed for {}: {err:#}",
+                    filter.driver,
+                    path.display()
+                );
+                true
+            }
+        }
+    });
+}
+
+/// Run the filter `command` through the shell in the worktree, with `%f` replaced by `path`,
+/// feeding it `content` and returning what it writes to stdout.
+fn run_filter_command(
+    repo: &git2::Repository,
+    command: &str,
+    path: &Path,
+    content: &[u8],
+) -> Result<Vec<u8>> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let quoted_path = format!("'{}'", path.to_string_lossy().replace('\'', "'\\''"));
+    let mut cmd = std::process::Command::new("sh");
+    cmd.arg("-c")
+        .arg(command.replace("%f", &quoted_path))
+        .current_dir(repo.workdir().context("the repository has no worktree")?)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let mut child = cmd.spawn()?;
+    let mut stdin = child.stdin.take().context("failed to open stdin")?;
+    // Write on another thread, as the filter may not read all input before writing its output,
+    // or may not read it at all.
+    let content = content.to_owned();
+    let writer = std::thread::spawn(move || match stdin.write_all(&content) {
+        Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        res => res,
+    });
+    let output = child.wait_with_output()?;
+    writer
+        .join()
+        .map_err(|_| anyhow!("failed to write to filter"))??;
+    if !output.status.success() {
+        bail!(
+            "`{command}` exited with {}: {}",
+            output.status,
+            output.stderr.to_str_lossy().trim()
+        );
+    }
+    Ok(output.stdout)
+}
@@ -12,6 +12,18 @@ pub struct Hunk {
     pub hash: Option<HunkHash>,
     pub start: u32,
     pub end: u32,
+    /// If set, only the lines of the given kind are claimed, for committing only part of a hunk.
+    /// It is not taken into account when comparing hunks.
+    pub filter: Option<LineFilter>,
+}
+
+/// The kind of changed lines of a hunk an ownership claim is restricted to.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LineFilter {
+    /// Only the added lines, written as `+` after the range.
+    Additions,
+    /// Only the removed lines, written as `-` after the range.
+    Deletions,
 }
 
 impl From<&diff::GitHunk> for Hunk {
@@ -20,6 +32,7 @@ impl From<&diff::GitHunk> for Hunk {
             start: hunk.new_start,
             end: hunk.new_start + hunk.new_lines,
             hash: Some(Hunk::hash_diff(&hunk.diff_lines)),
+            filter: None,
         }
     }
 }
@@ -40,6 +53,7 @@ impl From<RangeInclusive<u32>> for Hunk {
             start: *range.start(),
             end: *range.end(),
             hash: None,
+            filter: None,
         }
     }
 }
@@ -48,6 +62,13 @@ impl FromStr for Hunk {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (s, filter) = if let Some(s) = s.strip_suffix('+') {
+            (s, Some(LineFilter::Additions))
+        } else if let Some(s) = s.strip_suffix('-') {
+            (s, Some(LineFilter::Deletions))
+        } else {
+            (s, None)
+        };
         let mut range = s.split('-');
         let start = if let Some(raw_start) = range.next() {
             raw_start
@@ -77,15 +98,19 @@ impl FromStr for Hunk {
             None
         };
 
-        Hunk::new(start, end, hash)
+        Ok(Hunk::new(start, end, hash)?.with_filter(filter))
     }
 }
 
 impl Display for Hunk {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}-{}", self.start, self.end)?;
-        match &self.hash {
-            Some(hash) => write!(f, "-{:x}", hash),
+        if let Some(hash) = &self.hash {
+            write!(f, "-{:x}", hash)?;
+        }
+        match self.filter {
+            Some(LineFilter::Additions) => write!(f, "+"),
+            Some(LineFilter::Deletions) => write!(f, "-"),
             None => Ok(()),
         }
     }
@@ -96,7 +121,12 @@ impl Hunk {
         if start > end {
             Err(anyhow!("invalid range: {}-{}", start, end))
         } else {
-            Ok(Hunk { hash, start, end })
+            Ok(Hunk {
+                hash,
+                start,
+                end,
+                filter: None,
+            })
         }
     }
 
@@ -105,10 +135,30 @@ impl Hunk {
         self
     }
 
+    pub fn with_filter(mut self, filter: Option<LineFilter>) -> Self {
+        self.filter = filter;
+        self
+    }
+
     pub(crate) fn contains(&self, line: u32) -> bool {
         self.start <= line && self.end >= line
     }
 
+    /// Returns `true` if this is the `0-0` hunk, which refers to the file as a whole.
+    /// It's used for files that have no lines to point to, like deleted files, or
+    /// untracked files which should be claimed in full.
+    pub fn is_whole_file(&self) -> bool {
+        self.start == 0 && self.end == 0
+    }
+
+    /// Returns `true` if this hunk, as stored in an ownership claim, refers to `another`.
+    /// This is the case if both intersect, or if this is a [whole-file](Self::is_whole_file())
+    /// claim and `another` adds a new file.
+    pub fn claims(&self, another: &diff::GitHunk) -> bool {
+        self.intersects(another)
+            || (self.is_whole_file() && another.change_type == diff::ChangeType::Added)
+    }
+
     pub fn intersects(&self, another: &diff::GitHunk) -> bool {
         self.contains(another.new_start)
             || self.contains(another.new_start + another.new_lines)
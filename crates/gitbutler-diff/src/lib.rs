@@ -1,8 +1,16 @@
+mod cache;
+pub use cache::DiffCache;
 mod diff;
+pub mod filter;
 mod hunk;
+mod lines;
+mod moved;
 pub mod write;
 pub use diff::{
-    diff_files_into_hunks, hunks_by_filepath, reverse_hunk, trees, workdir, ChangeType,
-    DiffByPathMap, FileDiff, GitHunk,
+    diff_files_into_hunks, hunks_by_filepath, reverse_hunk, trees, trees_with_options, workdir,
+    workdir_cached, workdir_stats, workdir_with_options, ChangeType, DiffByPathMap, DiffOptions,
+    FileDiff, GitHunk, RENAME_DETECTION_THRESHOLD,
 };
-pub use hunk::{Hunk, HunkHash};
+pub use hunk::{Hunk, HunkHash, LineFilter};
+pub use lines::{annotate_lines, DiffLine, LineKind};
+pub use moved::{annotate_moved_lines, MoveKind, MovedLine};
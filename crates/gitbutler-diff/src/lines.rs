@@ -0,0 +1,70 @@
+use bstr::ByteSlice;
+use gitbutler_serde::BStringForFrontend;
+use serde::Serialize;
+
+use crate::{DiffByPathMap, GitHunk};
+
+/// What happened to a line of a hunk.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineKind {
+    /// The line is unchanged and only shown for context.
+    Context,
+    /// The line only exists in the new version of the file.
+    Added,
+    /// The line only exists in the old version of the file.
+    Removed,
+}
+
+/// A single line of a hunk, so clients don't have to parse the unified diff.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    pub kind: LineKind,
+    /// The 1-based line number in the old version of the file, or `None` for added lines.
+    pub old_line: Option<u32>,
+    /// The 1-based line number in the new version of the file, or `None` for removed lines.
+    pub new_line: Option<u32>,
+    /// The content of the line, without its `+`, `-` or ` ` prefix and without line separator.
+    pub content: BStringForFrontend,
+}
+
+/// Fill in [`GitHunk::lines`] for all hunks of all non-binary `files`.
+pub fn annotate_lines(files: &mut DiffByPathMap) {
+    for file in files.values_mut().filter(|file| !file.binary) {
+        for hunk in &mut file.hunks {
+            hunk.lines = hunk.structured_lines();
+        }
+    }
+}
+
+impl GitHunk {
+    /// Parse the unified diff of this hunk into a list of lines along with their line numbers.
+    /// Markers like `\ No newline at end of file` are skipped.
+    pub fn structured_lines(&self) -> Vec<DiffLine> {
+        if self.binary {
+            return Vec::new();
+        }
+        let (mut old_line, mut new_line) = (self.old_start, self.new_start);
+        let mut lines = Vec::new();
+        for line in self.diff_lines.lines() {
+            let content = line.get(1..).unwrap_or_default();
+            let (kind, old, new) = match line.first() {
+                Some(b' ') => (LineKind::Context, Some(old_line), Some(new_line)),
+                Some(b'-') => (LineKind::Removed, Some(old_line), None),
+                Some(b'+') => (LineKind::Added, None, Some(new_line)),
+                // hunk headers and 'no newline at end of file' markers.
+                _ => continue,
+            };
+            old_line += u32::from(old.is_some());
+            new_line += u32::from(new.is_some());
+            lines.push(DiffLine {
+                kind,
+                old_line: old,
+                new_line: new,
+                content: content.as_bstr().into(),
+            });
+        }
+        lines
+    }
+}
@@ -0,0 +1,152 @@
+use bstr::{BStr, ByteSlice};
+use serde::Serialize;
+
+use crate::{DiffByPathMap, GitHunk};
+
+/// Blocks with fewer alphanumeric characters than this aren't considered moved, which avoids
+/// lines like `}` to be matched all over the place. It's the same threshold that `git diff --color-moved` uses.
+const MIN_ALNUM_PER_BLOCK: usize = 20;
+
+/// Which side of a move a line in a hunk is on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MoveKind {
+    /// A removed line whose content was added elsewhere in the same file.
+    To,
+    /// An added line whose content was removed elsewhere in the same file.
+    From,
+}
+
+/// A line of a hunk that is part of a block of lines that was moved within the same file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MovedLine {
+    pub kind: MoveKind,
+    /// The 1-based line number of the removed line in the old version of the file.
+    pub old_line: u32,
+    /// The 1-based line number of the added line in the new version of the file.
+    pub new_line: u32,
+}
+
+/// Annotate all hunks in `files` with the lines that were moved within the same file, so
+/// relocated blocks can be told apart from actual deletions and additions.
+pub fn annotate_moved_lines(files: &mut DiffByPathMap) {
+    for file in files.values_mut().filter(|file| !file.binary) {
+        annotate_hunks(&mut file.hunks);
+    }
+}
+
+struct Line<'a> {
+    hunk: usize,
+    number: u32,
+    content: &'a BStr,
+}
+
+fn annotate_hunks(hunks: &mut [GitHunk]) {
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    for (hunk_idx, hunk) in hunks.iter().enumerate() {
+        let (mut old_line, mut new_line) = (hunk.old_start, hunk.new_start);
+        for line in hunk.diff_lines.lines() {
+            let content = line.get(1..).unwrap_or_default().as_bstr();
+            match line.first() {
+                Some(b'-') => {
+                    removed.push(Line {
+                        hunk: hunk_idx,
+                        number: old_line,
+                        content,
+                    });
+                    old_line += 1;
+                }
+                Some(b'+') => {
+                    added.push(Line {
+                        hunk: hunk_idx,
+                        number: new_line,
+                        content,
+                    });
+                    new_line += 1;
+                }
+                Some(b' ') => {
+                    old_line += 1;
+                    new_line += 1;
+                }
+                // hunk headers and 'no newline at end of file' markers.
+                _ => {}
+            }
+        }
+    }
+
+    let mut moves = Vec::new();
+    let mut added_used = vec![false; added.len()];
+    let mut removed_idx = 0;
+    while removed_idx < removed.len() {
+        let mut best = (0, 0);
+        for added_idx in 0..added.len() {
+            let len = block_len(&removed[removed_idx..], &added[added_idx..], |offset| {
+                added_used[added_idx + offset]
+            });
+            if len > best.0 {
+                best = (len, added_idx);
+            }
+        }
+
+        let (len, added_idx) = best;
+        let alnum_count: usize = removed[removed_idx..removed_idx + len]
+            .iter()
+            .map(|line| {
+                line.content
+                    .iter()
+                    .filter(|b| b.is_ascii_alphanumeric())
+                    .count()
+            })
+            .sum();
+        if len == 0 || alnum_count < MIN_ALNUM_PER_BLOCK {
+            removed_idx += 1;
+            continue;
+        }
+
+        for offset in 0..len {
+            let (removed_line, added_line) =
+                (&removed[removed_idx + offset], &added[added_idx + offset]);
+            added_used[added_idx + offset] = true;
+            moves.push((
+                removed_line.hunk,
+                MoveKind::To,
+                removed_line.number,
+                added_line.number,
+            ));
+            moves.push((
+                added_line.hunk,
+                MoveKind::From,
+                removed_line.number,
+                added_line.number,
+            ));
+        }
+        removed_idx += len;
+    }
+
+    for (hunk_idx, kind, old_line, new_line) in moves {
+        hunks[hunk_idx].moved_lines.push(MovedLine {
+            kind,
+            old_line,
+            new_line,
+        });
+    }
+}
+
+/// Return the amount of consecutive lines at the beginning of `removed` and `added` that have the same content.
+/// `is_used(offset)` returns `true` if the added line at `offset` is already part of another move.
+fn block_len(removed: &[Line<'_>], added: &[Line<'_>], is_used: impl Fn(usize) -> bool) -> usize {
+    let mut len = 0;
+    while len < removed.len()
+        && len < added.len()
+        && !is_used(len)
+        && removed[len].content == added[len].content
+        && (len == 0
+            || (removed[len].number == removed[len - 1].number + 1
+                && added[len].number == added[len - 1].number + 1))
+    {
+        len += 1;
+    }
+    len
+}
@@ -52,6 +52,13 @@ where
     T: Into<GitHunk> + Clone,
 {
     let git_repository = ctx.repository();
+    #[cfg(target_family = "unix")]
+    let trust_filemode = git_repository
+        .config()?
+        .get_bool("core.fileMode")
+        .unwrap_or(true);
+    // entries are sorted canonically and empty trees are dropped by the builder,
+    // so the same content always yields the same tree id
     let mut builder = git2::build::TreeUpdateBuilder::new();
     // now update the index with content in the working directory for each file
     for (rel_path, hunks) in files {
@@ -59,12 +66,19 @@ where
         let hunks: Vec<GitHunk> = hunks.borrow().iter().map(|h| h.clone().into()).collect();
         let full_path = ctx.project().worktree_path().join(rel_path);
 
+        // the file is written at its new path below
+        if let Some(renamed_from) = hunks.iter().find_map(|hunk| hunk.renamed_from.as_ref()) {
+            if base_tree.get_path(renamed_from).is_ok() {
+                builder.remove(renamed_from);
+            }
+        }
+
         let is_submodule = full_path.is_dir()
             && hunks.len() == 1
             && hunks[0].diff_lines.contains_str(b"Subproject commit");
 
-        // if file exists
-        let full_path_exists = full_path.exists();
+        // if file exists, which includes symlinks pointing to a path that doesn't exist
+        let full_path_exists = full_path.symlink_metadata().is_ok();
         let discard_hunk = (hunks.len() == 1).then(|| &hunks[0]);
         if full_path_exists || allow_new_file {
             if discard_hunk.map_or(false, |hunk| hunk.change_type == crate::ChangeType::Deleted) {
@@ -81,13 +95,33 @@ where
             }
             // if file is executable, use 755, otherwise 644
             let mut filemode = git2::FileMode::Blob;
+            // Keep the executable bit if it was present in the tree already,
+            // for when the filesystem can't be trusted to tell us.
+            let base_filemode = || {
+                base_tree
+                    .get_path(rel_path)
+                    .ok()
+                    .and_then(|entry| {
+                        (entry.filemode() & 0o100000 == 0o100000 && entry.filemode() & 0o111 != 0)
+                            .then_some(git2::FileMode::BlobExecutable)
+                    })
+                    .unwrap_or(git2::FileMode::Blob)
+            };
             // check if full_path file is executable
             if let Ok(metadata) = std::fs::symlink_metadata(&full_path) {
                 #[cfg(target_family = "unix")]
                 {
-                    if metadata.permissions().mode() & 0o111 != 0 {
-                        filemode = git2::FileMode::BlobExecutable;
-                    }
+                    // Like git, only the user executable bit counts, and only
+                    // if `core.fileMode` doesn't tell us to ignore it.
+                    filemode = if trust_filemode {
+                        if metadata.permissions().mode() & 0o100 != 0 {
+                            git2::FileMode::BlobExecutable
+                        } else {
+                            git2::FileMode::Blob
+                        }
+                    } else {
+                        base_filemode()
+                    };
                 }
 
                 #[cfg(target_os = "windows")]
@@ -95,15 +129,7 @@ where
                     // NOTE: *Keep* the existing executable bit if it was present
                     //       in the tree already, don't try to derive something from
                     //       the FS that doesn't exist.
-                    filemode = base_tree
-                        .get_path(rel_path)
-                        .ok()
-                        .and_then(|entry| {
-                            (entry.filemode() & 0o100000 == 0o100000
-                                && entry.filemode() & 0o111 != 0)
-                                .then_some(git2::FileMode::BlobExecutable)
-                        })
-                        .unwrap_or(filemode);
+                    filemode = base_filemode();
                 }
 
                 if metadata.file_type().is_symlink() {
@@ -140,6 +166,9 @@ where
                         .parse()
                         .context("failed to diff as oid")?;
                     builder.upsert(rel_path, new_blob_oid, filemode);
+                } else if hunks.iter().all(|hunk| hunk.diff_lines.is_empty()) {
+                    // only the mode changed, keep the blob as is
+                    builder.upsert(rel_path, tree_entry.id(), filemode);
                 } else {
                     // blob from tree_entry
                     let blob = tree_entry
@@ -1,4 +1,4 @@
-use gitbutler_diff::Hunk;
+use gitbutler_diff::{Hunk, LineFilter};
 
 #[test]
 fn to_from_string() {
@@ -123,3 +123,16 @@ fn eq() {
         assert_eq!(a == b, expected, "comparing {} and {}", a, b);
     }
 }
+
+#[test]
+fn parse_with_line_filter() {
+    let hunk = "1-2+".parse::<Hunk>().unwrap();
+    assert_eq!(hunk.filter, Some(LineFilter::Additions));
+    assert_eq!("1-2+", hunk.to_string());
+
+    let hash = Hunk::hash("hash");
+    let hunk = format!("2-3-{hash:x}-").parse::<Hunk>().unwrap();
+    assert_eq!(hunk, Hunk::new(2, 3, Some(hash)).unwrap());
+    assert_eq!(hunk.filter, Some(LineFilter::Deletions));
+    assert_eq!(format!("2-3-{hash:x}-"), hunk.to_string());
+}
@@ -0,0 +1,96 @@
+use gitbutler_diff::{annotate_lines, hunks_by_filepath, DiffLine, LineKind};
+
+fn annotated_lines(patch: &str) -> Vec<Vec<DiffLine>> {
+    let diff = git2::Diff::from_buffer(patch.as_bytes()).unwrap();
+    let mut files = hunks_by_filepath(None, &diff).unwrap();
+    annotate_lines(&mut files);
+    files
+        .remove(std::path::Path::new("file.txt"))
+        .unwrap()
+        .hunks
+        .into_iter()
+        .map(|hunk| hunk.lines)
+        .collect()
+}
+
+fn line(kind: LineKind, old_line: Option<u32>, new_line: Option<u32>, content: &str) -> DiffLine {
+    DiffLine {
+        kind,
+        old_line,
+        new_line,
+        content: content.into(),
+    }
+}
+
+#[test]
+fn lines_with_numbers() {
+    let hunks = annotated_lines(
+        r#"diff --git a/file.txt b/file.txt
+index 1111111..2222222 100644
+--- a/file.txt
++++ b/file.txt
+@@ -4,3 +4,3 @@
+ before
+-old
++new
+ after
+@@ -20,2 +20,3 @@
+ context
++added
+ end
+"#,
+    );
+
+    use LineKind::*;
+    assert_eq!(
+        hunks,
+        [
+            vec![
+                line(Context, Some(4), Some(4), "before"),
+                line(Removed, Some(5), None, "old"),
+                line(Added, None, Some(5), "new"),
+                line(Context, Some(6), Some(6), "after"),
+            ],
+            vec![
+                line(Context, Some(20), Some(20), "context"),
+                line(Added, None, Some(21), "added"),
+                line(Context, Some(21), Some(22), "end"),
+            ]
+        ]
+    );
+}
+
+#[test]
+fn no_newline_marker_is_skipped() {
+    let hunks = annotated_lines(
+        r#"diff --git a/file.txt b/file.txt
+index 1111111..2222222 100644
+--- a/file.txt
++++ b/file.txt
+@@ -1 +1 @@
+-old
+\ No newline at end of file
++new
+"#,
+    );
+
+    assert_eq!(
+        hunks,
+        [vec![
+            line(LineKind::Removed, Some(1), None, "old"),
+            line(LineKind::Added, None, Some(1), "new"),
+        ]]
+    );
+}
+
+#[test]
+fn lines_are_off_by_default() {
+    let diff = git2::Diff::from_buffer(
+        b"diff --git a/file.txt b/file.txt\n--- a/file.txt\n+++ b/file.txt\n@@ -1 +1 @@\n-a\n+b\n",
+    )
+    .unwrap();
+    let files = hunks_by_filepath(None, &diff).unwrap();
+    assert!(files[std::path::Path::new("file.txt")].hunks[0]
+        .lines
+        .is_empty());
+}
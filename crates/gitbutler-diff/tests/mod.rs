@@ -1 +1,3 @@
 pub mod hunk;
+pub mod lines;
+pub mod moved;
@@ -0,0 +1,87 @@
+use gitbutler_diff::{annotate_moved_lines, hunks_by_filepath, MoveKind, MovedLine};
+
+fn annotated_hunks(patch: &str) -> Vec<Vec<MovedLine>> {
+    let diff = git2::Diff::from_buffer(patch.as_bytes()).unwrap();
+    let mut files = hunks_by_filepath(None, &diff).unwrap();
+    annotate_moved_lines(&mut files);
+    files
+        .remove(std::path::Path::new("file.rs"))
+        .unwrap()
+        .hunks
+        .into_iter()
+        .map(|hunk| hunk.moved_lines)
+        .collect()
+}
+
+#[test]
+fn block_moved_within_file() {
+    let hunks = annotated_hunks(
+        r#"diff --git a/file.rs b/file.rs
+index 1111111..2222222 100644
+--- a/file.rs
++++ b/file.rs
+@@ -1,4 +1,2 @@
+-fn moved_function() {
+-    println!("relocated");
+-}
+ fn stays() {}
++fn new_code() {}
+@@ -10,2 +8,5 @@
+ fn other() {}
++fn moved_function() {
++    println!("relocated");
++}
+ fn end() {}
+"#,
+    );
+
+    let moved = |kind| {
+        (1..=3)
+            .map(|offset| MovedLine {
+                kind,
+                old_line: offset,
+                new_line: 8 + offset,
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(hunks, [moved(MoveKind::To), moved(MoveKind::From)]);
+}
+
+#[test]
+fn short_blocks_are_not_considered_moved() {
+    let hunks = annotated_hunks(
+        r#"diff --git a/file.rs b/file.rs
+index 1111111..2222222 100644
+--- a/file.rs
++++ b/file.rs
+@@ -1,3 +1,4 @@
+ fn one() {
+-}
++    1
+ fn two() {
++}
+"#,
+    );
+    assert_eq!(hunks, [vec![]]);
+}
+
+#[test]
+fn nothing_is_annotated_by_default() {
+    let diff = git2::Diff::from_buffer(
+        br#"diff --git a/file.rs b/file.rs
+index 1111111..2222222 100644
+--- a/file.rs
++++ b/file.rs
+@@ -1,2 +1,2 @@
+-fn moved_function_with_a_long_name() {}
+ fn stays() {}
++fn moved_function_with_a_long_name() {}
+"#,
+    )
+    .unwrap();
+    let files = hunks_by_filepath(None, &diff).unwrap();
+    assert!(files
+        .values()
+        .flat_map(|file| &file.hunks)
+        .all(|hunk| hunk.moved_lines.is_empty()));
+}
@@ -133,8 +133,13 @@ pub enum Code {
     CommitSigningFailed,
     CommitHookFailed,
     CommitMergeConflictFailure,
+    CommitUnresolvedConflictMarkers,
+    CommitFileTooLarge,
+    CommitMissingFinalNewline,
+    CommitBranchBehindUpstream,
     ProjectMissing,
     AuthorMissing,
+    GitOperationCancelled,
 }
 
 impl std::fmt::Display for Code {
@@ -147,8 +152,13 @@ impl std::fmt::Display for Code {
             Code::CommitSigningFailed => "errors.commit.signing_failed",
             Code::CommitHookFailed => "errors.commit.hook_failed",
             Code::CommitMergeConflictFailure => "errors.commit.merge_conflict_failure",
+            Code::CommitUnresolvedConflictMarkers => "errors.commit.unresolved_conflict_markers",
+            Code::CommitFileTooLarge => "errors.commit.file_too_large",
+            Code::CommitMissingFinalNewline => "errors.commit.missing_final_newline",
+            Code::CommitBranchBehindUpstream => "errors.commit.branch_behind_upstream",
             Code::AuthorMissing => "errors.git.author_missing",
             Code::ProjectMissing => "errors.projects.missing",
+            Code::GitOperationCancelled => "errors.git.cancelled",
         };
         f.write_str(code)
     }
@@ -27,4 +27,13 @@ pub enum Error<BE: std::error::Error + core::fmt::Debug + Send + Sync + 'static>
     /// the remote already existed.
     #[error("remote already exists: {0}")]
     RemoteExists(String, #[source] BE),
+    /// Push options were given, but the remote doesn't advertise
+    /// the `push-options` capability, or the protocol used to talk
+    /// to it doesn't support them.
+    #[error("the remote does not support push options")]
+    PushOptionsNotSupported(#[source] BE),
+    /// A push was made with a lease on the remote ref, but the remote
+    /// ref no longer points to the expected commit.
+    #[error("the remote ref changed since it was last seen")]
+    StaleLease(#[source] BE),
 }
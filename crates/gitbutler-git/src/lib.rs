@@ -26,5 +26,5 @@ pub use self::executor::tokio;
 pub use self::{
     error::Error,
     refspec::{Error as RefSpecError, RefSpec},
-    repository::{fetch, push, sign_commit},
+    repository::{fetch, push, sign_commit, FetchDepth},
 };
@@ -13,6 +13,9 @@ pub enum Error {
 pub struct RefSpec {
     /// If `true`, will update the ref upon a fetch or push even if it is not a fast-forward.
     pub update_non_fastforward: bool,
+    /// If `true`, this is a negative refspec like `^refs/heads/wip/*`, which excludes the refs matching
+    /// its `source` from the other refspecs. It has no destination.
+    pub negative: bool,
     /// The source refspec.
     pub source: Option<String>,
     /// The destination refspec.
@@ -48,6 +51,15 @@ impl RefSpec {
 
         let mut offset = 0;
 
+        if let Some(stripped) = s.strip_prefix('^') {
+            if let Some(colon) = stripped.find(':') {
+                return Err(Error::UnexpectedChar(':', colon + 1));
+            }
+            refspec.negative = true;
+            refspec.source = Some(stripped.trim().to_owned());
+            return Ok(refspec);
+        }
+
         let s = if let Some(stripped) = s.strip_prefix('+') {
             refspec.update_non_fastforward = true;
             offset += 1;
@@ -91,6 +103,10 @@ impl RefSpec {
 
 impl fmt::Display for RefSpec {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negative {
+            f.write_str("^")?;
+            return f.write_str(self.source.as_deref().unwrap_or_default());
+        }
         if self.update_non_fastforward {
             f.write_str("+")?;
         }
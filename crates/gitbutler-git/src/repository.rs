@@ -274,8 +274,30 @@ where
     }
 }
 
-/// Fetches the given refspec from the given remote in the repository
-/// at the given path. Any prompts for the user are passed to the asynchronous
+/// How much history [`fetch()`] retrieves.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FetchDepth {
+    /// Fetch the complete history.
+    #[default]
+    Full,
+    /// Limit the history of each fetched branch to the given amount of commits, like `git fetch --depth`.
+    Shallow(u32),
+    /// Fetch the given amount of commits beyond the current shallow boundary, like `git fetch --deepen`.
+    Deepen(u32),
+}
+
+impl FetchDepth {
+    fn to_arg(self) -> Option<String> {
+        match self {
+            FetchDepth::Full => None,
+            FetchDepth::Shallow(depth) => Some(format!("--depth={depth}")),
+            FetchDepth::Deepen(depth) => Some(format!("--deepen={depth}")),
+        }
+    }
+}
+
+/// Fetches the given refspecs from the given remote in the repository
+/// at the given path, retrieving as much history as `depth` allows. Any prompts for the user are passed to the asynchronous
 /// callback `on_prompt` which should return the user's response or `None` if the
 /// operation should be aborted, in which case an `Err` value is returned from this
 /// function.
@@ -283,7 +305,8 @@ pub async fn fetch<P, F, Fut, E, Extra>(
     repo_path: P,
     executor: E,
     remote: &str,
-    refspec: RefSpec,
+    refspecs: &[RefSpec],
+    depth: FetchDepth,
     on_prompt: F,
     extra: Extra,
 ) -> Result<(), crate::Error<Error<E>>>
@@ -296,10 +319,15 @@ where
 {
     let mut args = vec!["fetch", "--quiet", "--prune"];
 
-    let refspec = refspec.to_string();
+    let depth_arg = depth.to_arg();
+    if let Some(depth_arg) = &depth_arg {
+        args.push(depth_arg);
+    }
+
+    let refspecs: Vec<_> = refspecs.iter().map(ToString::to_string).collect();
 
     args.push(remote);
-    args.push(&refspec);
+    args.extend(refspecs.iter().map(String::as_str));
 
     let (status, stdout, stderr) =
         execute_with_auth_harness(repo_path, &executor, &args, None, on_prompt, extra).await?;
@@ -336,12 +364,22 @@ where
 /// Any prompts for the user are passed to the asynchronous callback `on_prompt`,
 /// which should return the user's response or `None` if the operation should be
 /// aborted, in which case an `Err` value is returned from this function.
+///
+/// Each of `push_options` is sent to the remote as server-side push option,
+/// which fails if the remote doesn't support them.
+///
+/// If `force_with_lease` is set, the destination of `refspec` is only overwritten
+/// if it still points to the given commit on the remote, with an empty string
+/// meaning that it must not exist yet. Otherwise [`crate::Error::StaleLease`] is returned.
+#[allow(clippy::too_many_arguments)]
 pub async fn push<P, F, Fut, E, Extra>(
     repo_path: P,
     executor: E,
     remote: &str,
     refspec: RefSpec,
     force: bool,
+    force_with_lease: Option<&str>,
+    push_options: &[String],
     on_prompt: F,
     extra: Extra,
 ) -> Result<(), crate::Error<Error<E>>>
@@ -354,15 +392,30 @@ where
 {
     let mut args = vec!["push", "--quiet", "--no-verify"];
 
+    let lease = force_with_lease.map(|expected| {
+        format!(
+            "--force-with-lease={}:{expected}",
+            refspec.destination.as_deref().unwrap_or_default()
+        )
+    });
+
     let refspec = refspec.to_string();
 
     args.push(remote);
     args.push(&refspec);
 
-    if force {
+    if let Some(lease) = &lease {
+        args.push(lease);
+    } else if force {
         args.push("--force");
     }
 
+    let push_options = push_options
+        .iter()
+        .map(|option| format!("--push-option={option}"))
+        .collect::<Vec<_>>();
+    args.extend(push_options.iter().map(String::as_str));
+
     let (status, stdout, stderr) =
         execute_with_auth_harness(repo_path, &executor, &args, None, on_prompt, extra).await?;
 
@@ -376,6 +429,23 @@ where
             .map(|line| line.split_whitespace().last().unwrap_or_default())
         {
             Err(crate::Error::RefNotFound(refname.to_owned()))?
+        } else if stderr
+            .to_lowercase()
+            .contains("does not support push options")
+        {
+            Err(crate::Error::PushOptionsNotSupported(Error::<E>::Failed {
+                status,
+                args: args.into_iter().map(Into::into).collect(),
+                stdout,
+                stderr,
+            }))?
+        } else if lease.is_some() && stderr.to_lowercase().contains("stale info") {
+            Err(crate::Error::StaleLease(Error::<E>::Failed {
+                status,
+                args: args.into_iter().map(Into::into).collect(),
+                stdout,
+                stderr,
+            }))?
         } else if stderr.to_lowercase().contains("permission denied") {
             Err(crate::Error::AuthorizationFailed(Error::<E>::Failed {
                 status,
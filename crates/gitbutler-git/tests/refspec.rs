@@ -6,6 +6,7 @@ fn parse_source_dest() {
         RefSpec::parse("refs/heads/*:refs/remotes/origin/*").unwrap(),
         RefSpec {
             update_non_fastforward: false,
+            negative: false,
             source: Some("refs/heads/*".to_owned()),
             destination: Some("refs/remotes/origin/*".to_owned()),
         }
@@ -18,6 +19,7 @@ fn parse_source_dest_force() {
         RefSpec::parse("+refs/heads/*:refs/remotes/origin/*").unwrap(),
         RefSpec {
             update_non_fastforward: true,
+            negative: false,
             source: Some("refs/heads/*".to_owned()),
             destination: Some("refs/remotes/origin/*".to_owned()),
         }
@@ -30,6 +32,7 @@ fn parse_single_colon() {
         RefSpec::parse(":").unwrap(),
         RefSpec {
             update_non_fastforward: false,
+            negative: false,
             source: None,
             destination: None,
         }
@@ -42,6 +45,7 @@ fn parse_single_colon_force() {
         RefSpec::parse("+:").unwrap(),
         RefSpec {
             update_non_fastforward: true,
+            negative: false,
             source: None,
             destination: None,
         }
@@ -54,6 +58,7 @@ fn parse_empty() {
         RefSpec::parse("").unwrap(),
         RefSpec {
             update_non_fastforward: false,
+            negative: false,
             source: None,
             destination: None,
         }
@@ -66,6 +71,7 @@ fn parse_empty_force() {
         RefSpec::parse("+").unwrap(),
         RefSpec {
             update_non_fastforward: true,
+            negative: false,
             source: None,
             destination: None,
         }
@@ -78,6 +84,7 @@ fn parse_single() {
         RefSpec::parse("refs/heads/*").unwrap(),
         RefSpec {
             update_non_fastforward: false,
+            negative: false,
             source: Some("refs/heads/*".to_owned()),
             destination: Some("refs/heads/*".to_owned()),
         }
@@ -90,6 +97,7 @@ fn parse_delete() {
         RefSpec::parse(":refs/heads/experimental").unwrap(),
         RefSpec {
             update_non_fastforward: false,
+            negative: false,
             source: None,
             destination: Some("refs/heads/experimental".to_owned()),
         }
@@ -102,6 +110,7 @@ fn parse_single_force() {
         RefSpec::parse("+refs/heads/*").unwrap(),
         RefSpec {
             update_non_fastforward: true,
+            negative: false,
             source: Some("refs/heads/*".to_owned()),
             destination: Some("refs/heads/*".to_owned()),
         }
@@ -114,6 +123,7 @@ fn parse_delete_force() {
         RefSpec::parse("+:refs/heads/experimental").unwrap(),
         RefSpec {
             update_non_fastforward: true,
+            negative: false,
             source: None,
             destination: Some("refs/heads/experimental".to_owned()),
         }
@@ -126,6 +136,7 @@ fn parse_name() {
         RefSpec::parse("master").unwrap(),
         RefSpec {
             update_non_fastforward: false,
+            negative: false,
             source: Some("master".to_owned()),
             destination: Some("master".to_owned()),
         }
@@ -138,6 +149,7 @@ fn parse_name_force() {
         RefSpec::parse("+master").unwrap(),
         RefSpec {
             update_non_fastforward: true,
+            negative: false,
             source: Some("master".to_owned()),
             destination: Some("master".to_owned()),
         }
@@ -150,6 +162,7 @@ fn parse_source_only() {
         RefSpec::parse("refs/heads/*:").unwrap(),
         RefSpec {
             update_non_fastforward: false,
+            negative: false,
             source: Some("refs/heads/*".to_owned()),
             destination: None,
         }
@@ -161,6 +174,7 @@ fn format_empty() {
     assert_eq!(
         RefSpec {
             update_non_fastforward: false,
+            negative: false,
             source: None,
             destination: None,
         }
@@ -174,6 +188,7 @@ fn format_empty_force() {
     assert_eq!(
         RefSpec {
             update_non_fastforward: true,
+            negative: false,
             source: None,
             destination: None,
         }
@@ -187,6 +202,7 @@ fn format_source_only() {
     assert_eq!(
         RefSpec {
             update_non_fastforward: false,
+            negative: false,
             source: Some("refs/heads/*".to_owned()),
             destination: None,
         }
@@ -200,6 +216,7 @@ fn format_source_only_force() {
     assert_eq!(
         RefSpec {
             update_non_fastforward: true,
+            negative: false,
             source: Some("refs/heads/*".to_owned()),
             destination: None,
         }
@@ -213,6 +230,7 @@ fn format_source_dest() {
     assert_eq!(
         RefSpec {
             update_non_fastforward: false,
+            negative: false,
             source: Some("refs/heads/*".to_owned()),
             destination: Some("refs/remotes/origin/*".to_owned()),
         }
@@ -226,6 +244,7 @@ fn format_source_dest_force() {
     assert_eq!(
         RefSpec {
             update_non_fastforward: true,
+            negative: false,
             source: Some("refs/heads/*".to_owned()),
             destination: Some("refs/remotes/origin/*".to_owned()),
         }
@@ -239,6 +258,7 @@ fn format_dest_only() {
     assert_eq!(
         RefSpec {
             update_non_fastforward: false,
+            negative: false,
             source: None,
             destination: Some("refs/heads/*".to_owned()),
         }
@@ -252,6 +272,7 @@ fn format_dest_only_force() {
     assert_eq!(
         RefSpec {
             update_non_fastforward: true,
+            negative: false,
             source: None,
             destination: Some("refs/heads/*".to_owned()),
         }
@@ -266,8 +287,29 @@ fn tuple() {
         RefSpec::from(("refs/heads/*", "refs/remotes/origin/*")),
         RefSpec {
             update_non_fastforward: false,
+            negative: false,
             source: Some("refs/heads/*".to_owned()),
             destination: Some("refs/remotes/origin/*".to_owned()),
         }
     );
 }
+
+#[test]
+fn parse_negative() {
+    let refspec = RefSpec::parse("^refs/heads/wip/*").unwrap();
+    assert_eq!(
+        refspec,
+        RefSpec {
+            update_non_fastforward: false,
+            negative: true,
+            source: Some("refs/heads/wip/*".to_owned()),
+            destination: None,
+        }
+    );
+    assert_eq!(refspec.to_string(), "^refs/heads/wip/*");
+}
+
+#[test]
+fn parse_negative_with_destination() {
+    assert!(RefSpec::parse("^refs/heads/wip/*:refs/remotes/origin/wip/*").is_err());
+}
@@ -143,11 +143,14 @@ pub enum OperationKind {
     ApplyBranch,
     DiscardHunk,
     DiscardFile,
+    DiscardAllChanges,
+    CheckoutFileFromCommit,
     AmendCommit,
     UndoCommit,
     UnapplyBranch,
     CherryPick,
     SquashCommit,
+    UnsquashCommit,
     UpdateCommitMessage,
     MoveCommit,
     RestoreFromSnapshot,
@@ -157,11 +160,13 @@ pub enum OperationKind {
     FileChanges,
     EnterEditMode,
     SyncWorkspace,
+    ResetWorkspace,
     CreateDependentBranch,
     RemoveDependentBranch,
     UpdateDependentBranchName,
     UpdateDependentBranchDescription,
     UpdateDependentBranchPrNumber,
+    DedupeIntegratedCommits,
     #[default]
     Unknown,
 }
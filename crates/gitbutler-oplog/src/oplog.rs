@@ -113,6 +113,14 @@ pub trait OplogExt {
     /// Returns the sha of the created revert snapshot commit or None if snapshots are disabled.
     fn restore_snapshot(&self, snapshot_commit_id: git2::Oid) -> Result<git2::Oid>;
 
+    /// Like [`restore_snapshot()`](Self::restore_snapshot()), but for callers that already hold
+    /// exclusive access to the worktree with `perm`.
+    fn restore_snapshot_with_permission(
+        &self,
+        snapshot_commit_id: git2::Oid,
+        perm: &mut WorktreeWritePermission,
+    ) -> Result<git2::Oid>;
+
     /// Determines if a new snapshot should be created due to file changes being created since the last snapshot.
     /// The needs for the automatic snapshotting are:
     ///  - It needs to facilitate backup of work in progress code
@@ -130,6 +138,7 @@ pub trait OplogExt {
     fn should_auto_snapshot(&self, check_if_last_snapshot_older_than: Duration) -> Result<bool>;
 
     /// Returns the diff of the snapshot and it's parent. It only includes the workdir changes.
+    /// The very first snapshot has no parent and is diffed against an empty tree.
     ///
     /// This is useful to show what has changed in this particular snapshot
     fn snapshot_diff(&self, sha: git2::Oid) -> Result<HashMap<PathBuf, FileDiff>>;
@@ -284,6 +293,14 @@ impl OplogExt for Project {
         restore_snapshot(self, snapshot_commit_id, guard.write_permission())
     }
 
+    fn restore_snapshot_with_permission(
+        &self,
+        snapshot_commit_id: git2::Oid,
+        perm: &mut WorktreeWritePermission,
+    ) -> Result<git2::Oid> {
+        restore_snapshot(self, snapshot_commit_id, perm)
+    }
+
     #[instrument(level = tracing::Level::DEBUG, skip(self), err(Debug))]
     fn should_auto_snapshot(&self, check_if_last_snapshot_older_than: Duration) -> Result<bool> {
         let last_snapshot_time = OplogHandle::new(&self.gb_dir()).modified_at()?;
@@ -307,7 +324,10 @@ impl OplogExt for Project {
 
         let wd_tree_id = tree_from_applied_vbranches(&gix_repo, commit.id())?;
         let wd_tree = repo.find_tree(wd_tree_id)?;
-        let old_wd_tree_id = tree_from_applied_vbranches(&gix_repo, commit.parent(0)?.id())?;
+        let old_wd_tree_id = match commit.parent_ids().next() {
+            Some(parent_id) => tree_from_applied_vbranches(&gix_repo, parent_id)?,
+            None => repo.treebuilder(None)?.write()?,
+        };
         let old_wd_tree = repo.find_tree(old_wd_tree_id)?;
 
         repo.ignore_large_files_in_diffs(SNAPSHOT_FILE_LIMIT_BYTES)?;
@@ -371,8 +391,7 @@ fn prepare_snapshot(ctx: &Project, _shared_access: &WorktreeReadPermission) -> R
     let conflicts_tree_id = write_conflicts_tree(worktree_dir, &repo)?;
 
     // write out the index as a tree to store
-    let mut index = repo.index()?;
-    let index_tree_oid = index.write_tree()?;
+    let index_tree_oid = repo.index_tree_on_disk()?;
 
     // start building our snapshot tree
     let mut tree_builder = repo.treebuilder(None)?;
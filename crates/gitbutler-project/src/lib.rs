@@ -5,7 +5,10 @@ mod project;
 mod storage;
 
 pub use controller::Controller;
-pub use project::{ApiProject, AuthKey, CodePushState, FetchResult, Project, ProjectId};
+pub use project::{
+    ApiProject, AuthKey, CodePushState, CommitIssueKey, DefaultTrailer, DefaultTrailerValue,
+    FetchResult, Project, ProjectId,
+};
 pub use storage::UpdateRequest;
 
 /// A utility to be used from applications to optimize `git2` configuration.
@@ -21,6 +21,41 @@ pub enum AuthKey {
     SystemExecutable,
 }
 
+/// A trailer that is added to the message of every commit created in a project.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DefaultTrailer {
+    /// The key of the trailer, like `Signed-off-by`.
+    pub key: String,
+    /// How the value of the trailer is obtained.
+    pub value: DefaultTrailerValue,
+}
+
+/// How the value of a [`DefaultTrailer`] is obtained when committing.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", content = "subject", rename_all = "camelCase")]
+pub enum DefaultTrailerValue {
+    /// Always the same value.
+    Fixed(String),
+    /// The author of the commit as `Name <email>`.
+    Author,
+    /// The committer of the commit as `Name <email>`, as used by `git commit --signoff`.
+    Committer,
+    /// A newly generated change-id, unless the message already has a `Change-Id` trailer.
+    ChangeId,
+}
+
+/// How the issue key in the name of a virtual branch is added to the messages of its commits.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitIssueKey {
+    /// A regular expression matched against the branch name. Its first capture group is the issue key,
+    /// or the whole match if it has none, like `[A-Z]+-[0-9]+` for `feature/PROJ-123-thing`.
+    pub pattern: String,
+    /// The prefix added to the message, in which `{key}` is replaced by the issue key, like `[{key}] `.
+    pub format: String,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ApiProject {
     pub name: String,
@@ -97,6 +132,15 @@ pub struct Project {
     // Experimental flag for new hunk dependency algorithm
     #[serde(default = "default_true")]
     pub use_experimental_locking: bool,
+    /// Files larger than this amount of bytes are refused when committing.
+    #[serde(default)]
+    pub max_commit_file_size: Option<u64>,
+    /// Trailers added to the message of every commit, in order.
+    #[serde(default)]
+    pub default_commit_trailers: Vec<DefaultTrailer>,
+    /// If set, the issue key in the branch name is prefixed to the message of commits that don't mention it yet.
+    #[serde(default)]
+    pub commit_issue_key: Option<CommitIssueKey>,
 }
 
 // TODO: Remove after `use_experimental` has been removed.
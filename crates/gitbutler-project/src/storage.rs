@@ -1,9 +1,13 @@
 use std::path::PathBuf;
 
 use anyhow::{Context, Result};
+use gitbutler_error::error::Code;
 use serde::{Deserialize, Serialize};
 
-use crate::{ApiProject, AuthKey, CodePushState, FetchResult, Project, ProjectId};
+use crate::{
+    ApiProject, AuthKey, CodePushState, CommitIssueKey, DefaultTrailer, FetchResult, Project,
+    ProjectId,
+};
 
 const PROJECTS_FILE: &str = "projects.json";
 
@@ -28,6 +32,12 @@ pub struct UpdateRequest {
     pub use_diff_context: Option<bool>,
     pub snapshot_lines_threshold: Option<usize>,
     pub use_experimental_locking: Option<bool>,
+    /// The maximum size of committed files in bytes, with `0` removing the limit.
+    pub max_commit_file_size: Option<u64>,
+    /// The trailers to add to every commit message, replacing the current ones.
+    pub default_commit_trailers: Option<Vec<DefaultTrailer>>,
+    /// How to prefix commit messages with the issue key of their branch, with an empty `pattern` turning it off.
+    pub commit_issue_key: Option<CommitIssueKey>,
 }
 
 impl Storage {
@@ -72,6 +82,16 @@ impl Storage {
     }
 
     pub fn update(&self, update_request: &UpdateRequest) -> Result<Project> {
+        if let Some(commit_issue_key) = &update_request.commit_issue_key {
+            if !commit_issue_key.pattern.is_empty() {
+                regex::Regex::new(&commit_issue_key.pattern)
+                    .with_context(|| {
+                        format!("invalid issue key pattern {:?}", commit_issue_key.pattern)
+                    })
+                    .context(Code::Validation)?;
+            }
+        }
+
         let mut projects = self.list()?;
         let project = projects
             .iter_mut()
@@ -128,6 +148,22 @@ impl Storage {
             project.use_experimental_locking = *use_experimental_locking;
         }
 
+        if let Some(max_commit_file_size) = update_request.max_commit_file_size {
+            project.max_commit_file_size =
+                (max_commit_file_size != 0).then_some(max_commit_file_size);
+        }
+
+        if let Some(default_commit_trailers) = &update_request.default_commit_trailers {
+            project
+                .default_commit_trailers
+                .clone_from(default_commit_trailers);
+        }
+
+        if let Some(commit_issue_key) = &update_request.commit_issue_key {
+            project.commit_issue_key =
+                (!commit_issue_key.pattern.is_empty()).then(|| commit_issue_key.clone());
+        }
+
         self.inner
             .write(PROJECTS_FILE, &serde_json::to_string_pretty(&projects)?)?;
 
@@ -162,3 +162,32 @@ mod delete {
         assert!(!project.path.join(".gitbutler.json").exists());
     }
 }
+
+mod update {
+    use gitbutler_project::{CommitIssueKey, UpdateRequest};
+
+    use super::*;
+
+    #[test]
+    fn reject_invalid_issue_key_pattern() {
+        let (controller, _tmp) = new();
+        let repository = gitbutler_testsupport::TestProject::default();
+        let project = controller.add(repository.path()).unwrap();
+
+        let err = controller
+            .update(&UpdateRequest {
+                id: project.id,
+                commit_issue_key: Some(CommitIssueKey {
+                    pattern: "(unclosed".into(),
+                    format: "[{key}] ".into(),
+                }),
+                ..Default::default()
+            })
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<gitbutler_error::error::Code>(),
+            Some(&gitbutler_error::error::Code::Validation)
+        );
+        assert_eq!(controller.get(project.id).unwrap().commit_issue_key, None);
+    }
+}
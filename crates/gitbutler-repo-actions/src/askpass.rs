@@ -4,6 +4,7 @@ use gitbutler_id::id::Id;
 use gitbutler_stack::StackId;
 use serde::Serialize;
 use tokio::sync::{oneshot, Mutex};
+use tokio_util::sync::CancellationToken;
 
 static mut GLOBAL_ASKPASS_BROKER: Option<AskpassBroker> = None;
 
@@ -34,6 +35,8 @@ pub fn get_broker() -> &'static AskpassBroker {
 
 pub struct AskpassRequest {
     sender: oneshot::Sender<Option<String>>,
+    /// Cancelled when the user dismisses the prompt, to abort the operation waiting for it.
+    cancellation: Option<CancellationToken>,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -67,9 +70,23 @@ impl AskpassBroker {
     }
 
     pub async fn submit_prompt(&self, prompt: String, context: Context) -> Option<String> {
+        self.submit_cancellable_prompt(prompt, context, None).await
+    }
+
+    /// Like [`Self::submit_prompt()`], but `cancellation` is cancelled if the user dismisses the prompt
+    /// instead of answering it.
+    pub async fn submit_cancellable_prompt(
+        &self,
+        prompt: String,
+        context: Context,
+        cancellation: Option<CancellationToken>,
+    ) -> Option<String> {
         let (sender, receiver) = oneshot::channel();
         let id = Id::generate();
-        let request = AskpassRequest { sender };
+        let request = AskpassRequest {
+            sender,
+            cancellation,
+        };
         self.pending_requests.lock().await.insert(id, request);
         (self.submit_prompt_event)(PromptEvent {
             id,
@@ -82,6 +99,11 @@ impl AskpassBroker {
     pub async fn handle_response(&self, id: Id<AskpassRequest>, response: Option<String>) {
         let mut pending_requests = self.pending_requests.lock().await;
         if let Some(request) = pending_requests.remove(&id) {
+            if response.is_none() {
+                if let Some(cancellation) = request.cancellation {
+                    cancellation.cancel();
+                }
+            }
             let _ = request.sender.send(response);
         } else {
             log::warn!("received response for unknown askpass request: {}", id);
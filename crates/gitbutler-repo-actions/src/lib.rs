@@ -1,4 +1,4 @@
 pub mod askpass;
 
 mod repository;
-pub use repository::RepoActionsExt;
+pub use repository::{RepoActionsExt, StaleLeaseError};
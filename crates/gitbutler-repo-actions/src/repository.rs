@@ -1,4 +1,8 @@
 use std::str::FromStr;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 
 use anyhow::{anyhow, Context, Result};
 use gitbutler_command_context::CommandContext;
@@ -7,11 +11,30 @@ use gitbutler_error::error::Code;
 use gitbutler_project::AuthKey;
 use gitbutler_reference::{Refname, RemoteRefname};
 use gitbutler_stack::{Stack, StackId};
+use tokio_util::sync::CancellationToken;
 
 use crate::askpass;
-use gitbutler_repo::{credentials, LogUntil, RepositoryExt};
+use gitbutler_repo::{
+    credentials::{self, Interactivity},
+    LogUntil, RepositoryExt,
+};
 pub trait RepoActionsExt {
     fn fetch(&self, remote_name: &str, askpass: Option<String>) -> Result<()>;
+    /// Like [`fetch()`](Self::fetch()), but if `depth` is set, only fetch that many commits of each branch.
+    /// If the repository is already shallow, its history is deepened by `depth` commits instead.
+    ///
+    /// With [`Interactivity::NonInteractive`], it fails instead of prompting for credentials.
+    ///
+    /// Once `cancellation` is cancelled, or a credentials prompt is dismissed, the fetch is aborted and
+    /// fails with [`Code::GitOperationCancelled`].
+    fn fetch_with_depth(
+        &self,
+        remote_name: &str,
+        askpass: Option<String>,
+        depth: Option<u32>,
+        interactivity: Interactivity,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<()>;
     fn push(
         &self,
         head: git2::Oid,
@@ -20,6 +43,42 @@ pub trait RepoActionsExt {
         refspec: Option<String>,
         askpass_broker: Option<Option<StackId>>,
     ) -> Result<()>;
+    /// Like [`push()`](Self::push()), but also sends each of `push_options` to the remote as
+    /// server-side push option, like `git push --push-option`.
+    ///
+    /// Fails if the remote doesn't advertise support for push options.
+    /// With [`Interactivity::NonInteractive`], it also fails instead of prompting for credentials.
+    ///
+    /// Once `cancellation` is cancelled, or a credentials prompt is dismissed, the push is aborted and
+    /// fails with [`Code::GitOperationCancelled`].
+    #[allow(clippy::too_many_arguments)]
+    fn push_with_options(
+        &self,
+        head: git2::Oid,
+        branch: &RemoteRefname,
+        with_force: bool,
+        refspec: Option<String>,
+        askpass_broker: Option<Option<StackId>>,
+        push_options: &[String],
+        interactivity: Interactivity,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<()>;
+    /// Like [`push_with_options()`](Self::push_with_options()), but force-pushes only if `branch` still
+    /// points to `expected` on the remote, or doesn't exist there if `expected` is zero,
+    /// like `git push --force-with-lease=<branch>:<expected>`.
+    ///
+    /// Fails with [`StaleLeaseError`] if it doesn't.
+    #[allow(clippy::too_many_arguments)]
+    fn push_with_lease(
+        &self,
+        head: git2::Oid,
+        branch: &RemoteRefname,
+        expected: git2::Oid,
+        askpass_broker: Option<Option<StackId>>,
+        push_options: &[String],
+        interactivity: Interactivity,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<()>;
     fn commit(
         &self,
         message: &str,
@@ -161,13 +220,88 @@ impl RepoActionsExt for CommandContext {
         refspec: Option<String>,
         askpass_broker: Option<Option<StackId>>,
     ) -> Result<()> {
-        let refspec = refspec.unwrap_or_else(|| {
-            if with_force {
-                format!("+{}:refs/heads/{}", head, branch.branch())
-            } else {
-                format!("{}:refs/heads/{}", head, branch.branch())
+        self.push_with_options(
+            head,
+            branch,
+            with_force,
+            refspec,
+            askpass_broker,
+            &[],
+            Interactivity::Interactive,
+            None,
+        )
+    }
+
+    fn push_with_options(
+        &self,
+        head: git2::Oid,
+        branch: &RemoteRefname,
+        with_force: bool,
+        refspec: Option<String>,
+        askpass_broker: Option<Option<StackId>>,
+        push_options: &[String],
+        interactivity: Interactivity,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<()> {
+        push(
+            self,
+            head,
+            branch,
+            with_force,
+            None,
+            refspec,
+            askpass_broker,
+            push_options,
+            interactivity,
+            cancellation,
+        )
+    }
+
+    fn push_with_lease(
+        &self,
+        head: git2::Oid,
+        branch: &RemoteRefname,
+        expected: git2::Oid,
+        askpass_broker: Option<Option<StackId>>,
+        push_options: &[String],
+        interactivity: Interactivity,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<()> {
+        push(
+            self,
+            head,
+            branch,
+            true,
+            Some(expected),
+            None,
+            askpass_broker,
+            push_options,
+            interactivity,
+            cancellation,
+        )
+    }
+
+    fn fetch(&self, remote_name: &str, askpass: Option<String>) -> Result<()> {
+        self.fetch_with_depth(remote_name, askpass, None, Interactivity::Interactive, None)
+    }
+
+    fn fetch_with_depth(
+        &self,
+        remote_name: &str,
+        askpass: Option<String>,
+        depth: Option<u32>,
+        interactivity: Interactivity,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<()> {
+        let cancellation = cancellation.cloned().unwrap_or_else(CancellationToken::new);
+        let refspecs = fetch_refspecs(self.repository(), remote_name)?;
+        let depth = match depth {
+            None => gitbutler_git::FetchDepth::Full,
+            Some(depth) if self.repository().is_shallow() => {
+                gitbutler_git::FetchDepth::Deepen(depth)
             }
-        });
+            Some(depth) => gitbutler_git::FetchDepth::Shallow(depth),
+        };
 
         // NOTE(qix-): This is a nasty hack, however the codebase isn't structured
         // NOTE(qix-): in a way that allows us to really incorporate new backends
@@ -175,71 +309,97 @@ impl RepoActionsExt for CommandContext {
         // NOTE(qix-): work around a time-sensitive change that was necessary
         // NOTE(qix-): without having to refactor a large portion of the codebase.
         if self.project().preferred_key == AuthKey::SystemExecutable {
+            let refspecs = refspecs
+                .iter()
+                .map(gitbutler_git::RefSpec::parse)
+                .collect::<Result<Vec<_>, _>>()?;
+            let refused_prompt = non_interactive_prompt_refusal(interactivity);
             let path = self.project().worktree_path();
-            let remote = branch.remote().to_string();
-            return std::thread::spawn(move || {
-                tokio::runtime::Runtime::new()
-                    .unwrap()
-                    .block_on(gitbutler_git::push(
-                        path,
-                        gitbutler_git::tokio::TokioExecutor,
-                        &remote,
-                        gitbutler_git::RefSpec::parse(refspec).unwrap(),
-                        with_force,
-                        handle_git_prompt_push,
-                        askpass_broker,
-                    ))
+            let remote = remote_name.to_string();
+            let result = std::thread::spawn({
+                let cancellation = cancellation.clone();
+                move || {
+                    tokio::runtime::Runtime::new().unwrap().block_on(async {
+                        tokio::select! {
+                            result = gitbutler_git::fetch(
+                                path,
+                                gitbutler_git::tokio::TokioExecutor,
+                                &remote,
+                                &refspecs,
+                                depth,
+                                handle_git_prompt_fetch,
+                                (askpass, cancellation.clone(), refused_prompt.clone()),
+                            ) => result.map_err(Into::into),
+                            () = cancellation.cancelled() => Err(cancelled_error()),
+                        }
+                    })
+                }
             })
             .join()
-            .unwrap()
-            .map_err(Into::into);
+            .unwrap();
+            return result.map_err(|err| {
+                if cancellation.is_cancelled() {
+                    cancelled_error()
+                } else if refused_prompt.is_some_and(|refused| refused.load(Ordering::SeqCst)) {
+                    err.context("credentials were requested interactively, which is disabled")
+                        .context(Code::ProjectGitAuth)
+                } else {
+                    err
+                }
+            });
         }
 
-        let auth_flows = credentials::help(self, branch.remote())?;
+        let depth = match depth {
+            gitbutler_git::FetchDepth::Full => None,
+            gitbutler_git::FetchDepth::Shallow(depth) => {
+                Some(i32::try_from(depth).context("fetch depth is too large")?)
+            }
+            gitbutler_git::FetchDepth::Deepen(depth) => {
+                // libgit2 can't deepen relative to the shallow boundary, so ask for at least as
+                // much history as the remote branches already have, plus `depth` commits.
+                let mut revwalk = self.repository().revwalk()?;
+                revwalk.push_glob(&format!("refs/remotes/{remote_name}/*"))?;
+                let fetched = revwalk.count();
+                Some(
+                    i32::try_from(fetched.saturating_add(depth as usize))
+                        .context("fetch depth is too large")?,
+                )
+            }
+        };
+        let auth_flows = credentials::help(self, remote_name, interactivity)?;
         for (mut remote, callbacks) in auth_flows {
-            let mut update_refs_error: Option<git2::Error> = None;
             for callback in callbacks {
+                let mut fetch_opts = git2::FetchOptions::new();
+                if let Some(depth) = depth {
+                    fetch_opts.depth(depth);
+                }
                 let mut cbs: git2::RemoteCallbacks = callback.into();
                 if self.project().omit_certificate_check.unwrap_or(false) {
                     cbs.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
                 }
-                cbs.push_update_reference(|_reference: &str, status: Option<&str>| {
-                    if let Some(status) = status {
-                        update_refs_error = Some(git2::Error::from_str(status));
-                        return Err(git2::Error::from_str(status));
-                    };
-                    Ok(())
-                });
-
-                let push_result = remote.push(
-                    &[refspec.as_str()],
-                    Some(&mut git2::PushOptions::new().remote_callbacks(cbs)),
-                );
-                match push_result {
+                // Returning `false` makes libgit2 abort the transfer.
+                cbs.transfer_progress(|_| !cancellation.is_cancelled());
+                cbs.sideband_progress(|_| !cancellation.is_cancelled());
+                fetch_opts.remote_callbacks(cbs);
+                fetch_opts.prune(git2::FetchPrune::On);
+
+                match remote.fetch(&refspecs, Some(&mut fetch_opts), None) {
                     Ok(()) => {
-                        tracing::info!(
-                            project_id = %self.project().id,
-                            remote = %branch.remote(),
-                            %head,
-                            branch = branch.branch(),
-                            "pushed git branch"
-                        );
+                        tracing::info!(project_id = %self.project().id, ?refspecs, "git fetched");
                         return Ok(());
                     }
+                    Err(_) if cancellation.is_cancelled() => return Err(cancelled_error()),
                     Err(err) => match err.class() {
                         git2::ErrorClass::Net | git2::ErrorClass::Http => {
-                            tracing::warn!(project_id = %self.project().id, ?err, "push failed due to network");
+                            tracing::warn!(project_id = %self.project().id, ?err, "fetch failed due to network");
                             continue;
                         }
                         _ => match err.code() {
                             git2::ErrorCode::Auth => {
-                                tracing::warn!(project_id = %self.project().id, ?err, "push failed due to auth");
+                                tracing::warn!(project_id = %self.project().id, ?err, "fetch failed due to auth");
                                 continue;
                             }
                             _ => {
-                                if let Some(update_refs_err) = update_refs_error {
-                                    return Err(update_refs_err).context(err);
-                                }
                                 return Err(err.into());
                             }
                         },
@@ -248,84 +408,269 @@ impl RepoActionsExt for CommandContext {
             }
         }
 
-        Err(anyhow!("authentication failed").context(Code::ProjectGitAuth))
+        Err(anyhow!("authentication failed")).context(Code::ProjectGitAuth)
     }
+}
 
-    fn fetch(&self, remote_name: &str, askpass: Option<String>) -> Result<()> {
-        let refspec = format!("+refs/heads/*:refs/remotes/{}/*", remote_name);
+/// The refspecs to fetch from `remote_name`, which are the ones configured in `remote.<name>.fetch`
+/// so single remotes can be narrowed to some of their branches, or all of its branches if none are configured.
+///
+/// Negative refspecs are kept so the refs they exclude stay excluded, but they can only narrow
+/// the default refspec if there is no positive one.
+fn fetch_refspecs(repo: &git2::Repository, remote_name: &str) -> Result<Vec<String>> {
+    let configured = repo.find_remote(remote_name)?.fetch_refspecs()?;
+    let mut refspecs: Vec<_> = configured.iter().flatten().map(ToOwned::to_owned).collect();
+    if refspecs.iter().all(|refspec| refspec.starts_with('^')) {
+        refspecs.insert(0, format!("+refs/heads/*:refs/remotes/{}/*", remote_name));
+    }
+    Ok(refspecs)
+}
 
-        // NOTE(qix-): This is a nasty hack, however the codebase isn't structured
-        // NOTE(qix-): in a way that allows us to really incorporate new backends
-        // NOTE(qix-): without a lot of work. This is a temporary measure to
-        // NOTE(qix-): work around a time-sensitive change that was necessary
-        // NOTE(qix-): without having to refactor a large portion of the codebase.
-        if self.project().preferred_key == AuthKey::SystemExecutable {
-            let path = self.project().worktree_path();
-            let remote = remote_name.to_string();
-            return std::thread::spawn(move || {
-                tokio::runtime::Runtime::new()
-                    .unwrap()
-                    .block_on(gitbutler_git::fetch(
-                        path,
-                        gitbutler_git::tokio::TokioExecutor,
-                        &remote,
-                        gitbutler_git::RefSpec::parse(refspec).unwrap(),
-                        handle_git_prompt_fetch,
-                        askpass,
-                    ))
-            })
-            .join()
-            .unwrap()
-            .map_err(Into::into);
+/// Push `head` to `branch`, see [`RepoActionsExt::push_with_options()`].
+///
+/// If `lease` is set, `branch` is only overwritten if it points to that commit on the remote,
+/// as described in [`RepoActionsExt::push_with_lease()`].
+#[allow(clippy::too_many_arguments)]
+fn push(
+    ctx: &CommandContext,
+    head: git2::Oid,
+    branch: &RemoteRefname,
+    with_force: bool,
+    lease: Option<git2::Oid>,
+    refspec: Option<String>,
+    askpass_broker: Option<Option<StackId>>,
+    push_options: &[String],
+    interactivity: Interactivity,
+    cancellation: Option<&CancellationToken>,
+) -> Result<()> {
+    let cancellation = cancellation.cloned().unwrap_or_else(CancellationToken::new);
+    if let Some(option) = push_options
+        .iter()
+        .find(|option| option.is_empty() || option.contains('\n'))
+    {
+        return Err(anyhow!("invalid push option {option:?}").context(Code::Validation));
+    }
+
+    let refspec = refspec.unwrap_or_else(|| {
+        if with_force {
+            format!("+{}:refs/heads/{}", head, branch.branch())
+        } else {
+            format!("{}:refs/heads/{}", head, branch.branch())
         }
+    });
 
-        let auth_flows = credentials::help(self, remote_name)?;
-        for (mut remote, callbacks) in auth_flows {
-            for callback in callbacks {
-                let mut fetch_opts = git2::FetchOptions::new();
-                let mut cbs: git2::RemoteCallbacks = callback.into();
-                if self.project().omit_certificate_check.unwrap_or(false) {
-                    cbs.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
+    // NOTE(qix-): This is a nasty hack, however the codebase isn't structured
+    // NOTE(qix-): in a way that allows us to really incorporate new backends
+    // NOTE(qix-): without a lot of work. This is a temporary measure to
+    // NOTE(qix-): work around a time-sensitive change that was necessary
+    // NOTE(qix-): without having to refactor a large portion of the codebase.
+    if ctx.project().preferred_key == AuthKey::SystemExecutable {
+        let refused_prompt = non_interactive_prompt_refusal(interactivity);
+        let path = ctx.project().worktree_path();
+        let remote = branch.remote().to_string();
+        let push_options = push_options.to_vec();
+        let lease_expected = lease.map(|expected| {
+            if expected.is_zero() {
+                String::new()
+            } else {
+                expected.to_string()
+            }
+        });
+        // A `+` would force the push no matter what the lease says.
+        let refspec = gitbutler_git::RefSpec::parse(refspec)
+            .unwrap()
+            .with_update_non_fastforward(with_force && lease.is_none());
+        let result = std::thread::spawn({
+            let cancellation = cancellation.clone();
+            move || {
+                tokio::runtime::Runtime::new().unwrap().block_on(async {
+                    tokio::select! {
+                        result = gitbutler_git::push(
+                            path,
+                            gitbutler_git::tokio::TokioExecutor,
+                            &remote,
+                            refspec,
+                            with_force,
+                            lease_expected.as_deref(),
+                            &push_options,
+                            handle_git_prompt_push,
+                            (askpass_broker, cancellation.clone(), refused_prompt.clone()),
+                        ) => result.map_err(|err| match err {
+                            gitbutler_git::Error::PushOptionsNotSupported(_) => {
+                                push_options_not_supported_error(err.into())
+                            }
+                            gitbutler_git::Error::StaleLease(_) => {
+                                stale_lease_error(err.into(), lease.unwrap_or_else(git2::Oid::zero))
+                            }
+                            err => err.into(),
+                        }),
+                        () = cancellation.cancelled() => Err(cancelled_error()),
+                    }
+                })
+            }
+        })
+        .join()
+        .unwrap();
+        return result.map_err(|err| {
+            if cancellation.is_cancelled() {
+                cancelled_error()
+            } else if refused_prompt.is_some_and(|refused| refused.load(Ordering::SeqCst)) {
+                err.context("credentials were requested interactively, which is disabled")
+                    .context(Code::ProjectGitAuth)
+            } else {
+                err
+            }
+        });
+    }
+
+    let auth_flows = credentials::help(ctx, branch.remote(), interactivity)?;
+    for (mut remote, callbacks) in auth_flows {
+        let mut update_refs_error: Option<git2::Error> = None;
+        let mut lease_broken = false;
+        for callback in callbacks {
+            let mut cbs: git2::RemoteCallbacks = callback.into();
+            if ctx.project().omit_certificate_check.unwrap_or(false) {
+                cbs.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
+            }
+            cbs.push_update_reference(|_reference: &str, status: Option<&str>| {
+                if let Some(status) = status {
+                    update_refs_error = Some(git2::Error::from_str(status));
+                    return Err(git2::Error::from_str(status));
+                };
+                Ok(())
+            });
+            cbs.push_negotiation(|updates| {
+                if cancellation.is_cancelled() {
+                    return Err(git2::Error::from_str("the push was cancelled"));
                 }
-                fetch_opts.remote_callbacks(cbs);
-                fetch_opts.prune(git2::FetchPrune::On);
+                if let Some(expected) = lease {
+                    if updates.iter().any(|update| update.src() != expected) {
+                        lease_broken = true;
+                        return Err(git2::Error::from_str(
+                            "the remote ref changed since it was last seen",
+                        ));
+                    }
+                }
+                Ok(())
+            });
+            // Returning `false` makes libgit2 abort the transfer.
+            cbs.sideband_progress(|_| !cancellation.is_cancelled());
 
-                match remote.fetch(&[&refspec], Some(&mut fetch_opts), None) {
-                    Ok(()) => {
-                        tracing::info!(project_id = %self.project().id, %refspec, "git fetched");
-                        return Ok(());
+            let remote_push_options: Vec<_> = push_options.iter().map(String::as_str).collect();
+            let push_result = remote.push(
+                &[refspec.as_str()],
+                Some(
+                    &mut git2::PushOptions::new()
+                        .remote_callbacks(cbs)
+                        .remote_push_options(&remote_push_options),
+                ),
+            );
+            match push_result {
+                Ok(()) => {
+                    tracing::info!(
+                        project_id = %ctx.project().id,
+                        remote = %branch.remote(),
+                        %head,
+                        branch = branch.branch(),
+                        "pushed git branch"
+                    );
+                    return Ok(());
+                }
+                Err(_) if cancellation.is_cancelled() => return Err(cancelled_error()),
+                Err(err) => match err.class() {
+                    git2::ErrorClass::Net | git2::ErrorClass::Http => {
+                        tracing::warn!(project_id = %ctx.project().id, ?err, "push failed due to network");
+                        continue;
                     }
-                    Err(err) => match err.class() {
-                        git2::ErrorClass::Net | git2::ErrorClass::Http => {
-                            tracing::warn!(project_id = %self.project().id, ?err, "fetch failed due to network");
+                    _ => match err.code() {
+                        git2::ErrorCode::Auth => {
+                            tracing::warn!(project_id = %ctx.project().id, ?err, "push failed due to auth");
                             continue;
                         }
-                        _ => match err.code() {
-                            git2::ErrorCode::Auth => {
-                                tracing::warn!(project_id = %self.project().id, ?err, "fetch failed due to auth");
-                                continue;
+                        _ => {
+                            if let Some(expected) = lease.filter(|_| lease_broken) {
+                                return Err(stale_lease_error(err.into(), expected));
                             }
-                            _ => {
-                                return Err(err.into());
+                            if !push_options.is_empty()
+                                && err.message().to_lowercase().contains("push-options")
+                            {
+                                return Err(push_options_not_supported_error(err.into()));
                             }
-                        },
+                            if let Some(update_refs_err) = update_refs_error {
+                                return Err(update_refs_err).context(err);
+                            }
+                            return Err(err.into());
+                        }
                     },
-                }
+                },
             }
         }
-
-        Err(anyhow!("authentication failed")).context(Code::ProjectGitAuth)
     }
+
+    Err(anyhow!("authentication failed").context(Code::ProjectGitAuth))
+}
+
+/// The error returned when a push or fetch was aborted because it was cancelled, or the user dismissed its
+/// credentials prompt.
+fn cancelled_error() -> anyhow::Error {
+    anyhow!("git operation was cancelled").context(Code::GitOperationCancelled)
+}
+
+/// The error returned when push options were given, but the remote doesn't support them.
+fn push_options_not_supported_error(err: anyhow::Error) -> anyhow::Error {
+    err.context("the remote doesn't support push options")
+        .context(Code::Validation)
+}
+
+/// The error returned by [`RepoActionsExt::push_with_lease()`] if the remote branch
+/// doesn't point to the expected commit anymore.
+#[derive(Debug, thiserror::Error)]
+#[error("the remote branch doesn't point to {expected} anymore")]
+pub struct StaleLeaseError {
+    pub expected: git2::Oid,
+}
+
+/// The error returned when a push with a lease was rejected as the remote branch moved.
+fn stale_lease_error(err: anyhow::Error, expected: git2::Oid) -> anyhow::Error {
+    err.context(StaleLeaseError { expected })
+        .context(Code::Validation)
+}
+
+/// Returns `Some` flag to record a refused prompt in if credentials may only be obtained without prompting,
+/// or `None` if prompts are passed on to the askpass broker.
+fn non_interactive_prompt_refusal(interactivity: Interactivity) -> Option<Arc<AtomicBool>> {
+    (interactivity == Interactivity::NonInteractive).then(Default::default)
+}
+
+/// Refuse `prompt` if `refused_prompt` is set, which makes the git operation fail right away.
+fn refuse_non_interactive_prompt(prompt: &str, refused_prompt: Option<&Arc<AtomicBool>>) -> bool {
+    let Some(refused_prompt) = refused_prompt else {
+        return false;
+    };
+    tracing::warn!("refusing askpass prompt in non-interactive mode: {prompt:?}");
+    refused_prompt.store(true, Ordering::SeqCst);
+    true
 }
 
 async fn handle_git_prompt_push(
     prompt: String,
-    askpass: Option<Option<StackId>>,
+    (askpass, cancellation, refused_prompt): (
+        Option<Option<StackId>>,
+        CancellationToken,
+        Option<Arc<AtomicBool>>,
+    ),
 ) -> Option<String> {
+    if refuse_non_interactive_prompt(&prompt, refused_prompt.as_ref()) {
+        return None;
+    }
     if let Some(branch_id) = askpass {
         tracing::info!("received prompt for branch push {branch_id:?}: {prompt:?}");
         askpass::get_broker()
-            .submit_prompt(prompt, askpass::Context::Push { branch_id })
+            .submit_cancellable_prompt(
+                prompt,
+                askpass::Context::Push { branch_id },
+                Some(cancellation),
+            )
             .await
     } else {
         tracing::warn!("received askpass push prompt but no broker was supplied; returning None");
@@ -333,14 +678,56 @@ async fn handle_git_prompt_push(
     }
 }
 
-async fn handle_git_prompt_fetch(prompt: String, askpass: Option<String>) -> Option<String> {
+async fn handle_git_prompt_fetch(
+    prompt: String,
+    (askpass, cancellation, refused_prompt): (
+        Option<String>,
+        CancellationToken,
+        Option<Arc<AtomicBool>>,
+    ),
+) -> Option<String> {
+    if refuse_non_interactive_prompt(&prompt, refused_prompt.as_ref()) {
+        return None;
+    }
     if let Some(action) = askpass {
         tracing::info!("received prompt for fetch with action {action:?}: {prompt:?}");
         askpass::get_broker()
-            .submit_prompt(prompt, askpass::Context::Fetch { action })
+            .submit_cancellable_prompt(
+                prompt,
+                askpass::Context::Fetch { action },
+                Some(cancellation),
+            )
             .await
     } else {
         tracing::warn!("received askpass fetch prompt but no broker was supplied; returning None");
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prompts_are_refused_if_non_interactive() {
+        let refused_prompt = non_interactive_prompt_refusal(Interactivity::NonInteractive);
+        // The askpass broker isn't initialized, so this would panic if the prompt was passed on.
+        let answer = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(handle_git_prompt_fetch(
+                "Password for 'https://example.com': ".into(),
+                (
+                    Some("modal".into()),
+                    CancellationToken::new(),
+                    refused_prompt.clone(),
+                ),
+            ));
+        assert_eq!(answer, None);
+        assert!(refused_prompt.is_some_and(|refused| refused.load(Ordering::SeqCst)));
+    }
+
+    #[test]
+    fn prompts_are_not_refused_if_interactive() {
+        assert!(non_interactive_prompt_refusal(Interactivity::Interactive).is_none());
+    }
+}
@@ -1,6 +1,7 @@
 use std::{path::PathBuf, str::FromStr, vec};
 
 use anyhow::Context;
+use bstr::ByteSlice;
 use gitbutler_command_context::CommandContext;
 use gitbutler_project::AuthKey;
 use gitbutler_url::{ConvertError, Scheme, Url};
@@ -75,9 +76,21 @@ pub enum HelpError {
     Other(#[from] anyhow::Error),
 }
 
+/// Whether the user may be prompted for credentials when fetching from or pushing to a remote.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Interactivity {
+    /// Prompt the user if no credentials are available otherwise.
+    #[default]
+    Interactive,
+    /// Only use credentials that are available without prompting, like those of credential helpers,
+    /// and fail instead of prompting. This is for headless runs that can't answer a prompt.
+    NonInteractive,
+}
+
 pub fn help<'a>(
     ctx: &'a CommandContext,
     remote_name: &str,
+    interactivity: Interactivity,
 ) -> Result<Vec<(git2::Remote<'a>, Vec<Credential>)>, HelpError> {
     let remote = ctx.repository().find_remote(remote_name)?;
     let remote_url = Url::from_str(remote.url().ok_or(HelpError::NoUrlSet)?)
@@ -112,7 +125,7 @@ pub fn help<'a>(
                 let url = remote_url.as_https()?;
                 ctx.repository().remote_anonymous(&url.to_string())
             }?;
-            let flow = https_flow(ctx, &remote_url)?
+            let flow = https_flow(ctx, &remote_url, interactivity)?
                 .into_iter()
                 .map(Credential::Https)
                 .collect::<Vec<_>>();
@@ -127,9 +140,20 @@ pub fn help<'a>(
     }
 }
 
-fn https_flow(ctx: &CommandContext, remote_url: &Url) -> Result<Vec<HttpsCredential>, HelpError> {
+fn https_flow(
+    ctx: &CommandContext,
+    remote_url: &Url,
+    interactivity: Interactivity,
+) -> Result<Vec<HttpsCredential>, HelpError> {
     let mut flow = vec![];
 
+    if interactivity == Interactivity::NonInteractive {
+        if let Some((username, password)) = non_interactive_credentials(ctx, remote_url)? {
+            flow.push(HttpsCredential::CredentialHelper { username, password });
+        }
+        return Ok(flow);
+    }
+
     let mut helper = git2::CredentialHelper::new(&remote_url.to_string());
     let config = ctx.repository().config()?;
     helper.config(&config);
@@ -139,3 +163,54 @@ fn https_flow(ctx: &CommandContext, remote_url: &Url) -> Result<Vec<HttpsCredent
 
     Ok(flow)
 }
+
+/// Ask the configured credential helpers for credentials with `git credential fill`, with all ways
+/// of prompting the user disabled. Returns `None` if no helper has credentials for `remote_url`.
+fn non_interactive_credentials(
+    ctx: &CommandContext,
+    remote_url: &Url,
+) -> Result<Option<(String, String)>, HelpError> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let mut cmd = Command::new("git");
+    cmd.args(["-c", "core.askPass=", "credential", "fill"])
+        .current_dir(ctx.project().worktree_path())
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("GCM_INTERACTIVE", "never")
+        .env_remove("GIT_ASKPASS")
+        .env_remove("SSH_ASKPASS")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let mut child = cmd.spawn().context("failed to run git credential")?;
+    child
+        .stdin
+        .take()
+        .context("failed to open stdin")?
+        .write_all(format!("url={remote_url}\n\n").as_bytes())
+        .context("failed to write to git credential")?;
+    let output = child
+        .wait_with_output()
+        .context("failed to run git credential")?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    let mut username = None;
+    let mut password = None;
+    for line in output.stdout.lines() {
+        match line.split_once_str("=") {
+            Some((b"username", value)) => username = Some(value.to_str_lossy().into_owned()),
+            Some((b"password", value)) => password = Some(value.to_str_lossy().into_owned()),
+            _ => {}
+        }
+    }
+    Ok(username.zip(password))
+}
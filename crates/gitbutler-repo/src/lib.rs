@@ -37,6 +37,44 @@ pub fn signature(purpose: SignaturePurpose) -> anyhow::Result<git2::Signature<'s
     gix_to_git2_signature(signature)
 }
 
+/// Apply `GIT_AUTHOR_NAME`, `GIT_AUTHOR_EMAIL` and `GIT_AUTHOR_DATE`, or their `GIT_COMMITTER_*`
+/// counterparts depending on `purpose`, to `signature` the way Git does: each variable that is set
+/// takes precedence over the respective field of `signature`.
+/// Returns `None` if there is no `signature` and the environment doesn't provide both name and email.
+pub fn signature_with_env_overrides(
+    purpose: SignaturePurpose,
+    signature: Option<git2::Signature<'_>>,
+) -> anyhow::Result<Option<git2::Signature<'static>>> {
+    let prefix = match purpose {
+        SignaturePurpose::Author => "GIT_AUTHOR",
+        SignaturePurpose::Committer => "GIT_COMMITTER",
+    };
+    let var = |field: &str| std::env::var(format!("{prefix}_{field}")).ok();
+    let Some(name) = var("NAME").or_else(|| {
+        signature
+            .as_ref()
+            .and_then(|s| s.name().map(ToOwned::to_owned))
+    }) else {
+        return Ok(None);
+    };
+    let Some(email) = var("EMAIL").or_else(|| {
+        signature
+            .as_ref()
+            .and_then(|s| s.email().map(ToOwned::to_owned))
+    }) else {
+        return Ok(None);
+    };
+    let time = var("DATE")
+        .and_then(|time| gix::date::parse(&time, Some(std::time::SystemTime::now())).ok())
+        .map(|time| git2::Time::new(time.seconds, time.offset / 60))
+        .or_else(|| signature.as_ref().map(|s| s.when()))
+        .unwrap_or_else(|| {
+            let now = gix::date::Time::now_local_or_utc();
+            git2::Time::new(now.seconds, now.offset / 60)
+        });
+    Ok(Some(git2::Signature::new(&name, &email, &time)?))
+}
+
 /// Return the time of a commit as `now` unless the `overriding_variable_name` contains a parseable date,
 /// which is used instead.
 fn commit_time(overriding_variable_name: &str) -> gix::date::Time {
@@ -46,10 +46,38 @@ pub fn cherry_rebase(
 /// rebase empty commits (two commits with identical trees)
 ///
 /// the commit id's to rebase should be ordered such that the child most commit is first
+///
+/// Commits that become empty when rebased are dropped, like `git rebase` does.
 pub fn cherry_rebase_group(
     repository: &git2::Repository,
     target_commit_oid: git2::Oid,
     ids_to_rebase: &[git2::Oid],
+) -> Result<git2::Oid> {
+    cherry_rebase_group_with_options(
+        repository,
+        target_commit_oid,
+        ids_to_rebase,
+        RebaseOptions::default(),
+    )
+}
+
+/// Options for [`cherry_rebase_group_with_options()`] and [`gitbutler_merge_commits_with_options()`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RebaseOptions {
+    /// Keep commits that become empty when rebased instead of dropping them.
+    pub keep_empty: bool,
+    /// Resolve conflicts in files whose `merge` attribute in `.gitattributes` selects a driver that keeps
+    /// the current version, like `merge=ours`, by taking the side of the branch that is integrated into.
+    /// This is meant for integrating upstream changes into a branch.
+    pub keep_current_by_merge_attribute: bool,
+}
+
+/// Like [`cherry_rebase_group()`], but configured by `options`.
+pub fn cherry_rebase_group_with_options(
+    repository: &git2::Repository,
+    target_commit_oid: git2::Oid,
+    ids_to_rebase: &[git2::Oid],
+    options: RebaseOptions,
 ) -> Result<git2::Oid> {
     // now, rebase unchanged commits onto the new commit
     let commits_to_rebase = ids_to_rebase
@@ -75,6 +103,9 @@ pub fn cherry_rebase_group(
                 let mut cherrypick_index = repository
                     .cherry_pick_gitbutler(&head, &to_rebase, None)
                     .context("failed to cherry pick")?;
+                if options.keep_current_by_merge_attribute {
+                    keep_their_side_by_merge_attribute(repository, &mut cherrypick_index)?;
+                }
 
                 if cherrypick_index.has_conflicts() {
                     commit_conflicted_cherry_result(
@@ -84,7 +115,13 @@ pub fn cherry_rebase_group(
                         &mut cherrypick_index,
                     )
                 } else {
-                    commit_unconflicted_cherry_result(repository, head, to_rebase, cherrypick_index)
+                    commit_unconflicted_cherry_result(
+                        repository,
+                        head,
+                        to_rebase,
+                        cherrypick_index,
+                        options.keep_empty,
+                    )
                 }
             },
         )?
@@ -98,6 +135,7 @@ fn commit_unconflicted_cherry_result<'repository>(
     head: git2::Commit<'repository>,
     to_rebase: git2::Commit,
     mut cherrypick_index: git2::Index,
+    keep_empty: bool,
 ) -> Result<git2::Commit<'repository>> {
     let is_merge_commit = to_rebase.parent_count() > 0;
 
@@ -106,7 +144,7 @@ fn commit_unconflicted_cherry_result<'repository>(
         .context("failed to write merge tree")?;
 
     // Remove empty merge commits
-    if is_merge_commit && merge_tree_oid == head.tree_id() {
+    if is_merge_commit && !keep_empty && merge_tree_oid == head.tree_id() {
         return Ok(head);
     }
 
@@ -230,6 +268,26 @@ pub fn gitbutler_merge_commits<'repository>(
     incoming_commit: git2::Commit<'repository>,
     target_branch_name: &str,
     incoming_branch_name: &str,
+) -> Result<git2::Commit<'repository>> {
+    gitbutler_merge_commits_with_options(
+        repository,
+        target_commit,
+        incoming_commit,
+        target_branch_name,
+        incoming_branch_name,
+        RebaseOptions::default(),
+    )
+}
+
+/// Like [`gitbutler_merge_commits()`], but configured by `options`.
+/// Merging never drops the merge commit, so [`RebaseOptions::keep_empty`] has no effect.
+pub fn gitbutler_merge_commits_with_options<'repository>(
+    repository: &'repository git2::Repository,
+    target_commit: git2::Commit<'repository>,
+    incoming_commit: git2::Commit<'repository>,
+    target_branch_name: &str,
+    incoming_branch_name: &str,
+    options: RebaseOptions,
 ) -> Result<git2::Commit<'repository>> {
     let merge_base = repository.merge_base(target_commit.id(), incoming_commit.id())?;
     let merge_base = repository.find_commit(merge_base)?;
@@ -244,6 +302,9 @@ pub fn gitbutler_merge_commits<'repository>(
     let incoming_merge_tree = repository.find_real_tree(&incoming_commit, Default::default())?;
     let mut merged_index =
         repository.merge_trees(&base_tree, &incoming_merge_tree, &target_merge_tree, None)?;
+    if options.keep_current_by_merge_attribute {
+        keep_their_side_by_merge_attribute(repository, &mut merged_index)?;
+    }
 
     let tree_oid;
     let conflicted_files;
@@ -349,6 +410,68 @@ impl ConflictEntries {
 
         set.len()
     }
+
+    /// All conflicted paths, each listed once and in sorted order.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<_> = self
+            .ancestor_entries
+            .iter()
+            .chain(self.our_entries.iter())
+            .chain(self.their_entries.iter())
+            .cloned()
+            .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+}
+
+/// Resolve the conflicts in `index` of files with a `merge` attribute in `.gitattributes` selecting a driver
+/// that keeps the current version, by taking the version of the branch that is being integrated into.
+/// That's `merge=ours`, or any driver configured as `merge.<driver>.driver = true` like Git suggests for it.
+///
+/// `index` is expected to have the side of that branch as *theirs*. For [`gitbutler_merge_commits_with_options()`]
+/// that's the target commit, and when rebasing with [`cherry_rebase_group_with_options()`] it's the commit that
+/// is cherry-picked. Both only do this if [`RebaseOptions::keep_current_by_merge_attribute`] is set.
+fn keep_their_side_by_merge_attribute(
+    repository: &git2::Repository,
+    index: &mut git2::Index,
+) -> Result<()> {
+    let config = repository.config()?;
+    let conflicts = index.conflicts()?.collect::<Result<Vec<_>, _>>()?;
+    for conflict in conflicts {
+        let Some(entry) = conflict
+            .their
+            .as_ref()
+            .or(conflict.our.as_ref())
+            .or(conflict.ancestor.as_ref())
+        else {
+            continue;
+        };
+        let path = entry.path.to_path()?;
+        let git2::AttrValue::String(driver) = git2::AttrValue::from_string(repository.get_attr(
+            path,
+            "merge",
+            git2::AttrCheckFlags::FILE_THEN_INDEX,
+        )?) else {
+            continue;
+        };
+        let keeps_current = driver == "ours"
+            || config
+                .get_string(&format!("merge.{driver}.driver"))
+                .is_ok_and(|command| command.trim() == "true");
+        if !keeps_current {
+            continue;
+        }
+
+        index.remove_path(path)?;
+        if let Some(mut their) = conflict.their {
+            let blob = repository.find_blob(their.id)?;
+            their.flags = 0;
+            index.add_frombuffer(&their, blob.content())?;
+        }
+    }
+    Ok(())
 }
 
 /// Automatically resolves an index with a preferences for the "our" side
@@ -692,6 +815,35 @@ mod test {
             );
         }
 
+        #[test]
+        fn conflict_in_file_marked_merge_ours_keeps_target_side() {
+            let test_repository = TestingRepository::open();
+            std::fs::write(
+                test_repository.tempdir.path().join(".gitattributes"),
+                "CHANGELOG.md merge=ours\n",
+            )
+            .unwrap();
+
+            // Make some commits
+            let a = test_repository.commit_tree(None, &[("CHANGELOG.md", "a"), ("foo.txt", "a")]);
+            let b =
+                test_repository.commit_tree(Some(&a), &[("CHANGELOG.md", "b"), ("foo.txt", "a")]);
+            let c =
+                test_repository.commit_tree(Some(&a), &[("CHANGELOG.md", "c"), ("foo.txt", "c")]);
+
+            let result =
+                gitbutler_merge_commits(&test_repository.repository, b, c, "master", "feature")
+                    .unwrap();
+
+            assert!(!result.is_conflicted());
+
+            assert_commit_tree_matches(
+                &test_repository.repository,
+                &result,
+                &[("CHANGELOG.md", b"b"), ("foo.txt", b"c")],
+            );
+        }
+
         #[test]
         fn merging_conflicted_commit_with_unconflicted_incoming() {
             let test_repository = TestingRepository::open();
@@ -2,14 +2,17 @@
 use std::os::unix::fs::PermissionsExt;
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
-use std::{io::Write, path::Path, process::Stdio, str};
+use std::{borrow::Cow, io::Write, path::Path, process::Stdio, str};
 
 use crate::Config;
 use crate::SignaturePurpose;
 use anyhow::{anyhow, bail, Context, Result};
-use bstr::BString;
+use bstr::{BStr, BString};
 use git2::{BlameOptions, StatusOptions, Tree};
-use gitbutler_commit::commit_headers::CommitHeadersV2;
+use gitbutler_commit::{
+    change_id_trailer::{change_id_from_message, with_change_id_trailer},
+    commit_headers::CommitHeadersV2,
+};
 use gitbutler_config::git::{GbConfig, GitConfig};
 use gitbutler_error::error::Code;
 use gitbutler_oxidize::{
@@ -71,6 +74,16 @@ pub trait RepositoryExt {
     fn checkout_index_builder<'a>(&'a self, index: &'a mut git2::Index)
         -> CheckoutIndexBuilder<'a>;
     fn checkout_index_path_builder<P: AsRef<Path>>(&self, path: P) -> Result<()>;
+    /// Replace the index of the repository with one that represents `tree`.
+    ///
+    /// The new index is built in a temporary index file, like one passed with `GIT_INDEX_FILE`,
+    /// and only moved into place through `index.lock` once it's complete. That way, operations
+    /// reading the index concurrently never see it half-written, and it fails instead of
+    /// overwriting the index while another process holds its lock.
+    fn write_index_from_tree(&self, tree: &git2::Tree<'_>) -> Result<()>;
+    /// Write the tree of the index as it's currently stored on disk, without going through the
+    /// index the repository has cached, which other operations may be changing concurrently.
+    fn index_tree_on_disk(&self) -> Result<git2::Oid>;
     fn checkout_tree_builder<'a>(&'a self, tree: &'a git2::Tree<'a>) -> CheckoutTreeBuidler<'a>;
     fn maybe_find_branch_by_refname(&self, name: &Refname) -> Result<Option<git2::Branch>>;
     /// Based on the index, add all data similar to `git add .` and create a tree from it, which is returned.
@@ -137,6 +150,48 @@ impl RepositoryExt for git2::Repository {
         }
     }
 
+    fn write_index_from_tree(&self, tree: &git2::Tree<'_>) -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("gitbutler-index-")
+            .tempdir_in(self.path())
+            .context("failed to create a directory for the temporary index")?;
+        let temp_index_path = temp_dir.path().join("index");
+        let mut index = git2::Index::open(&temp_index_path)?;
+        index.read_tree(tree)?;
+        index
+            .write()
+            .context("failed to write the temporary index")?;
+
+        let index_path = self.path().join("index");
+        let lock_path = self.path().join("index.lock");
+        if let Err(err) = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+        {
+            let context = if err.kind() == std::io::ErrorKind::AlreadyExists {
+                "failed to write the index as it's locked by another process"
+            } else {
+                "failed to write the index"
+            };
+            return Err(anyhow::Error::from(err).context(context));
+        }
+        std::fs::rename(&temp_index_path, &lock_path)
+            .and_then(|()| std::fs::rename(&lock_path, &index_path))
+            .map_err(|err| {
+                std::fs::remove_file(&lock_path).ok();
+                anyhow::Error::from(err).context("failed to write the index")
+            })?;
+        // The repository caches its index, which now has to be updated from disk.
+        self.index()?.read(true)?;
+        Ok(())
+    }
+
+    fn index_tree_on_disk(&self) -> Result<git2::Oid> {
+        let mut index = git2::Index::open(&self.path().join("index"))?;
+        Ok(index.write_tree_to(self)?)
+    }
+
     fn checkout_index_path_builder<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let mut builder = git2::build::CheckoutBuilder::new();
         builder.path(path.as_ref());
@@ -289,8 +344,22 @@ impl RepositoryExt for git2::Repository {
         commit_headers: Option<CommitHeadersV2>,
     ) -> Result<git2::Oid> {
         let repo = gix::open(self.path())?;
+        let gb_config = self.gb_config()?;
+
+        let mut message = Cow::Borrowed(message);
+        // Keep the trailer and the headers in agreement, preferring the id we already carry.
+        let commit_headers = commit_headers.unwrap_or_else(|| CommitHeadersV2 {
+            change_id: change_id_from_message(BStr::new(&*message))
+                .unwrap_or_else(|| CommitHeadersV2::default().change_id),
+            conflicted: None,
+            integrated_upstream: None,
+        });
+        if gb_config.change_id_trailer.unwrap_or(false) {
+            message = Cow::Owned(with_change_id_trailer(&message, &commit_headers.change_id));
+        }
+
         let mut commit = gix::objs::Commit {
-            message: message.into(),
+            message: (&*message).into(),
             tree: git2_to_gix_object_id(tree.id()),
             author: git2_signature_to_gix_signature(author),
             committer: git2_signature_to_gix_signature(committer),
@@ -299,10 +368,10 @@ impl RepositoryExt for git2::Repository {
                 .iter()
                 .map(|commit| git2_to_gix_object_id(commit.id()))
                 .collect(),
-            extra_headers: commit_headers.unwrap_or_default().into(),
+            extra_headers: commit_headers.into(),
         };
 
-        if self.gb_config()?.sign_commits.unwrap_or(false) {
+        if gb_config.sign_commits.unwrap_or(false) {
             let mut buf = Vec::new();
             commit.write_to(&mut buf)?;
             let signature = self.sign_buffer(&buf);
@@ -327,7 +396,7 @@ impl RepositoryExt for git2::Repository {
 
         // update reference
         if let Some(refname) = update_ref {
-            self.reference(&refname.to_string(), oid, true, message)?;
+            self.reference(&refname.to_string(), oid, true, &message)?;
         }
         Ok(oid)
     }
@@ -408,7 +477,12 @@ impl RepositoryExt for git2::Repository {
                     let child = cmd.spawn()?;
                     output = child.wait_with_output()?;
                 } else {
+                    let agent_held = is_agent_held_ssh_key(Path::new(&signing_key));
                     cmd.arg(signing_key);
+                    // the key file only holds the public key, the private key is provided by `ssh-agent`.
+                    if agent_held {
+                        cmd.arg("-U");
+                    }
                     cmd.arg(&buffer_file_to_sign_path);
                     cmd.stderr(Stdio::piped());
                     cmd.stdout(Stdio::piped());
@@ -599,7 +673,8 @@ impl RepositoryExt for git2::Repository {
             .author()
             .transpose()?
             .map(gix_to_git2_signature)
-            .transpose()?
+            .transpose()?;
+        let author = crate::signature_with_env_overrides(SignaturePurpose::Author, author)?
             .context("No author is configured in Git")
             .context(Code::AuthorMissing)?;
 
@@ -612,6 +687,9 @@ impl RepositoryExt for git2::Repository {
         } else {
             crate::signature(SignaturePurpose::Committer)
         }?;
+        let committer =
+            crate::signature_with_env_overrides(SignaturePurpose::Committer, Some(committer))?
+                .context("BUG: a committer is always present")?;
 
         Ok((author, committer))
     }
@@ -643,6 +721,15 @@ pub fn is_literal_ssh_key(string: &str) -> (bool, &str) {
     (false, string)
 }
 
+/// Return `true` if the SSH key file at `path` contains only a public key, which means that
+/// the private key is held by `ssh-agent`, as is the case for keys on hardware tokens.
+pub fn is_agent_held_ssh_key(path: &Path) -> bool {
+    std::fs::read_to_string(path).is_ok_and(|content| {
+        let content = content.trim_start();
+        !content.is_empty() && !content.starts_with("-----BEGIN")
+    })
+}
+
 pub struct CheckoutTreeBuidler<'a> {
     repo: &'a git2::Repository,
     tree: &'a git2::Tree<'a>,
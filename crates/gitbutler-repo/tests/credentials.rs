@@ -2,7 +2,7 @@ use std::{path::PathBuf, str};
 
 use gitbutler_command_context::CommandContext;
 use gitbutler_project as projects;
-use gitbutler_repo::credentials::{help, Credential, SshCredential};
+use gitbutler_repo::credentials::{help, Credential, Interactivity, SshCredential};
 use gitbutler_testsupport::{temp_dir, test_repository};
 use gitbutler_user as users;
 
@@ -36,7 +36,7 @@ impl TestCase<'_> {
         };
         let ctx = CommandContext::open(&project).unwrap();
 
-        let flow = help(&ctx, "origin").unwrap();
+        let flow = help(&ctx, "origin", Interactivity::Interactive).unwrap();
         flow.into_iter()
             .map(|(remote, credentials)| (remote.url().as_ref().unwrap().to_string(), credentials))
             .collect::<Vec<_>>()
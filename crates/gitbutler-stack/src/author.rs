@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// An identity to author commits with, instead of the one configured in the repository.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct CommitAuthor {
+    pub name: String,
+    pub email: String,
+}
+
+impl CommitAuthor {
+    /// Return a signature of this identity with the current time.
+    pub fn signature(&self) -> Result<git2::Signature<'static>, git2::Error> {
+        git2::Signature::now(&self.name, &self.email)
+    }
+}
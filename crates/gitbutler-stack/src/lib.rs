@@ -16,5 +16,11 @@ mod series;
 pub use series::Series;
 pub use stack::{commit_by_oid_or_change_id, CommitsForId, PatchReferenceUpdate, TargetUpdate};
 
+mod squash;
+pub use squash::SquashedCommits;
+
+mod author;
+pub use author::CommitAuthor;
+
 mod patch_reference;
 pub use patch_reference::{Branch, CommitOrChangeId};
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+/// The commits that were squashed into a single commit, recorded at squash time so the squash can
+/// be undone later.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SquashedCommits {
+    /// The change-id of the commit the commits were squashed into, which identifies it across rebases.
+    pub change_id: String,
+    /// The ids of the squashed commits, oldest first. They are kept reachable by references below
+    /// `refs/gitbutler/squashed/<change_id>/` so they aren't garbage-collected.
+    #[serde(with = "gitbutler_serde::oid_vec")]
+    pub commits: Vec<git2::Oid>,
+}
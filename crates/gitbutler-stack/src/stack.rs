@@ -25,6 +25,7 @@ use crate::Branch;
 use crate::CommitOrChangeId;
 use crate::Series;
 use crate::{ownership::BranchOwnershipClaims, VirtualBranchesHandle};
+use crate::{CommitAuthor, SquashedCommits};
 
 pub type StackId = Id<Stack>;
 
@@ -47,6 +48,9 @@ pub struct Stack {
     // upstream_head is the last commit on we've pushed to the upstream branch
     #[serde(with = "gitbutler_serde::oid_opt", default)]
     pub upstream_head: Option<git2::Oid>,
+    /// If set, the remote branch this branch is based on instead of the project's default target.
+    #[serde(default)]
+    pub target: Option<RemoteRefname>,
     #[serde(
         serialize_with = "serialize_u128",
         deserialize_with = "deserialize_u128"
@@ -63,6 +67,14 @@ pub struct Stack {
     /// head is id of the last "virtual" commit in this branch
     #[serde(with = "gitbutler_serde::oid")]
     head: git2::Oid,
+    /// The commit this branch is stacked on, i.e. the merge base of its head with the target.
+    /// It's set when the branch is created and kept up to date whenever its head changes.
+    #[serde(with = "gitbutler_serde::oid_opt", default)]
+    pub base_oid: Option<git2::Oid>,
+    /// `true` once the [base](Self::base_oid) was computed, even if none could be found, so that branches
+    /// stored before it was tracked are only backfilled once.
+    #[serde(default)]
+    pub base_oid_computed: bool,
     pub ownership: BranchOwnershipClaims,
     // order is the number by which UI should sort branches
     pub order: usize,
@@ -77,6 +89,31 @@ pub struct Stack {
     pub in_workspace: bool,
     #[serde(default)]
     pub not_in_workspace_wip_change_id: Option<String>,
+    /// If `true`, the branch holds changes that are local to this machine and must never leave it.
+    /// It can't be committed to or pushed, and its changes are carried over as they are
+    /// when the workspace is updated to a new base.
+    #[serde(default)]
+    pub local_only: bool,
+    /// If `true`, the branch keeps the hunks it already owns but isn't assigned any new ones,
+    /// which then go to other branches instead. Unlike unapplying, its changes stay in the worktree.
+    #[serde(default)]
+    pub frozen: bool,
+    /// The names of the git hooks, like `pre-commit`, that don't run when committing to this branch,
+    /// even if hooks are enabled.
+    #[serde(default)]
+    pub skipped_hooks: Vec<String>,
+    /// The commits that were squashed into a commit of this branch, one entry per resulting commit,
+    /// so that the squash can be undone later.
+    #[serde(default)]
+    pub squashed_commits: Vec<SquashedCommits>,
+    /// The commits that must keep their position in the branch, so they can't be reordered or squashed.
+    /// Each is identified by its change-id, so it stays pinned when it's rewritten.
+    #[serde(default)]
+    pub pinned_commits: Vec<String>,
+    /// If set, the identity that authors the commits created in this branch instead of the one
+    /// configured in the repository. The committer is never affected.
+    #[serde(default)]
+    pub commit_author: Option<CommitAuthor>,
     /// Represents the Stack state of pseudo-references ("heads").
     /// Do **NOT** edit this directly, instead use the `Stack` trait in gitbutler_stack.
     #[serde(default)]
@@ -143,16 +180,25 @@ impl Stack {
             source_refname,
             upstream,
             upstream_head,
+            target: None,
             created_timestamp_ms: now,
             updated_timestamp_ms: now,
             tree,
             head,
+            base_oid: None,
+            base_oid_computed: false,
             ownership: BranchOwnershipClaims::default(),
             order,
             selected_for_changes,
             allow_rebasing,
             in_workspace: true,
             not_in_workspace_wip_change_id: None,
+            local_only: false,
+            frozen: false,
+            skipped_hooks: Vec::new(),
+            squashed_commits: Vec::new(),
+            pinned_commits: Vec::new(),
+            commit_author: None,
             heads: Default::default(),
         }
     }
@@ -199,6 +245,7 @@ impl Stack {
             selected_for_changes,
             allow_rebasing,
         );
+        branch.refresh_base_oid(ctx);
         if let Err(e) = branch.initialize(ctx, allow_duplicate_refs) {
             // TODO: When this is stable, make it error out
             tracing::warn!("failed to initialize stack: {:?}", e);
@@ -480,6 +527,10 @@ impl Stack {
         if let Some(tree) = tree {
             self.tree = tree;
         }
+        if let Some(base_oid) = base_of(ctx, commit_id) {
+            self.base_oid = Some(base_oid);
+            self.base_oid_computed = true;
+        }
         let commit = ctx.repository().find_commit(commit_id)?;
         // let patch: CommitOrChangeId = commit.into();
 
@@ -494,6 +545,14 @@ impl Stack {
         state.set_branch(self.clone())
     }
 
+    /// Recomputes the [base](Self::base_oid) of this branch, for when the default target moved.
+    ///
+    /// This doesn't write to `virtual_branches.toml`, so branches can be updated together.
+    pub fn refresh_base_oid(&mut self, ctx: &CommandContext) {
+        self.base_oid = base_of(ctx, self.head());
+        self.base_oid_computed = true;
+    }
+
     /// Removes any heads that are refering to commits that are no longer between the stack head and the merge base
     pub fn archive_integrated_heads(&mut self, ctx: &CommandContext) -> Result<()> {
         self.initialized()?;
@@ -939,6 +998,12 @@ fn branch_state(ctx: &CommandContext) -> VirtualBranchesHandle {
     VirtualBranchesHandle::new(ctx.project().gb_dir())
 }
 
+/// The merge base of `head` with the default target, or `None` if there is no target yet.
+fn base_of(ctx: &CommandContext, head: git2::Oid) -> Option<git2::Oid> {
+    let target = branch_state(ctx).get_default_target().ok()?;
+    ctx.repository().merge_base(head, target.sha).ok()
+}
+
 // NB: There can be multiple commits with the same change id on the same branch id.
 // This is an error condition but we must handle it.
 // If there are multiple commits, they are ordered newest to oldest.
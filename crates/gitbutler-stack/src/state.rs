@@ -105,6 +105,18 @@ impl VirtualBranchesHandle {
         Ok(())
     }
 
+    /// Sets the state of all `branches` at once, writing the file only once.
+    ///
+    /// Errors if the file cannot be read or written.
+    pub fn set_branches(&self, branches: impl IntoIterator<Item = Stack>) -> Result<()> {
+        let mut virtual_branches = self.read_file()?;
+        virtual_branches
+            .branches
+            .extend(branches.into_iter().map(|branch| (branch.id, branch)));
+        self.write_file(&virtual_branches)?;
+        Ok(())
+    }
+
     /// Marks a particular branch as not in the workspace
     ///
     /// Errors if the file cannot be read or written.
@@ -21,6 +21,9 @@ pub struct Target {
     pub sha: git2::Oid,
     /// The name of the remote to push to.
     pub push_remote_name: Option<String>,
+    /// The name of a local branch that mirrors `branch`, like `main` for `origin/main`, or `None` if
+    /// no local branch is kept in sync with it. It may be named differently than `branch`.
+    pub local_branch_name: Option<String>,
 }
 
 impl Target {
@@ -38,7 +41,7 @@ impl Serialize for Target {
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("Target", 5)?;
+        let mut state = serializer.serialize_struct("Target", 6)?;
         state.serialize_field("branchName", &self.branch.branch())?;
         state.serialize_field("remoteName", &self.branch.remote())?;
         state.serialize_field("remoteUrl", &self.remote_url)?;
@@ -46,6 +49,9 @@ impl Serialize for Target {
         if let Some(push_remote_name) = &self.push_remote_name {
             state.serialize_field("pushRemoteName", push_remote_name)?;
         }
+        if let Some(local_branch_name) = &self.local_branch_name {
+            state.serialize_field("localBranchName", local_branch_name)?;
+        }
         state.end()
     }
 }
@@ -59,6 +65,7 @@ impl<'de> serde::Deserialize<'de> for Target {
             remote_name: String,
             remote_url: String,
             push_remote_name: Option<String>,
+            local_branch_name: Option<String>,
             sha: String,
         }
         let target_data: TargetData = serde::Deserialize::deserialize(d)?;
@@ -70,6 +77,7 @@ impl<'de> serde::Deserialize<'de> for Target {
             remote_url: target_data.remote_url,
             sha,
             push_remote_name: target_data.push_remote_name,
+            local_branch_name: target_data.local_branch_name,
         };
         Ok(target)
     }
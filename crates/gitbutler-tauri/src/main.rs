@@ -162,12 +162,15 @@ fn main() {
                     repo::commands::get_uncommited_files,
                     repo::commands::get_blob_info,
                     virtual_branches::commands::list_virtual_branches,
+                    virtual_branches::commands::list_virtual_branches_read_only,
+                    virtual_branches::commands::get_status_since,
                     virtual_branches::commands::create_virtual_branch,
                     virtual_branches::commands::delete_local_branch,
                     virtual_branches::commands::commit_virtual_branch,
                     virtual_branches::commands::get_base_branch_data,
                     virtual_branches::commands::set_base_branch,
                     virtual_branches::commands::push_base_branch,
+                    virtual_branches::commands::force_reset_integration,
                     virtual_branches::commands::integrate_upstream_commits,
                     virtual_branches::commands::update_virtual_branch,
                     virtual_branches::commands::update_branch_order,
@@ -185,12 +188,14 @@ fn main() {
                     virtual_branches::commands::undo_commit,
                     virtual_branches::commands::insert_blank_commit,
                     virtual_branches::commands::reorder_stack,
+                    virtual_branches::commands::reorder_commit,
                     virtual_branches::commands::update_commit_message,
                     virtual_branches::commands::list_local_branches,
                     virtual_branches::commands::list_branches,
                     virtual_branches::commands::get_branch_listing_details,
                     virtual_branches::commands::get_remote_branch_data,
                     virtual_branches::commands::squash_branch_commit,
+                    virtual_branches::commands::squash_branch_commit_range,
                     virtual_branches::commands::fetch_from_remotes,
                     virtual_branches::commands::move_commit,
                     virtual_branches::commands::normalize_branch_name,
@@ -198,6 +203,7 @@ fn main() {
                     virtual_branches::commands::integrate_upstream,
                     virtual_branches::commands::resolve_upstream_integration,
                     virtual_branches::commands::find_commit,
+                    virtual_branches::commands::incoming_commits,
                     stack::create_series,
                     stack::remove_series,
                     stack::update_series_name,
@@ -209,6 +215,7 @@ fn main() {
                     undo::list_snapshots,
                     undo::restore_snapshot,
                     undo::snapshot_diff,
+                    undo::undo_last,
                     undo::take_synced_snapshot,
                     config::get_gb_config,
                     config::set_gb_config,
@@ -2,7 +2,10 @@ use std::{collections::HashMap, path::PathBuf};
 
 use anyhow::Context;
 use gitbutler_diff::FileDiff;
-use gitbutler_oplog::{entry::Snapshot, OplogExt};
+use gitbutler_oplog::{
+    entry::{OperationKind, Snapshot},
+    OplogExt,
+};
 use gitbutler_project as projects;
 use gitbutler_project::ProjectId;
 use gitbutler_user::User;
@@ -40,6 +43,16 @@ pub fn restore_snapshot(
     Ok(())
 }
 
+#[tauri::command(async)]
+#[instrument(skip(projects), err(Debug))]
+pub fn undo_last(
+    projects: State<'_, projects::Controller>,
+    project_id: ProjectId,
+) -> Result<OperationKind, Error> {
+    let project = projects.get(project_id).context("failed to get project")?;
+    Ok(gitbutler_branch_actions::undo_last(&project)?)
+}
+
 #[tauri::command(async)]
 #[instrument(skip(projects), err(Debug))]
 pub fn snapshot_diff(
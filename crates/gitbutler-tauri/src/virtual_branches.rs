@@ -1,18 +1,22 @@
 pub mod commands {
     use anyhow::{anyhow, Context};
     use gitbutler_branch::{BranchCreateRequest, BranchUpdateRequest};
-    use gitbutler_branch_actions::internal::PushResult;
+    use gitbutler_branch_actions::internal::{
+        branch_upstream_integration::UpstreamIntegrationOutcome, PushResult,
+    };
     use gitbutler_branch_actions::upstream_integration::{
         BaseBranchResolution, BaseBranchResolutionApproach, BranchStatuses, Resolution,
     };
     use gitbutler_branch_actions::{
-        BaseBranch, BranchListing, BranchListingDetails, BranchListingFilter, RemoteBranch,
-        RemoteBranchData, RemoteBranchFile, RemoteCommit, StackOrder, VirtualBranches,
+        BaseBranch, BranchListing, BranchListingDetails, BranchListingFilter, ForceMode,
+        ForceResetOutcome, RemoteBranch, RemoteBranchData, RemoteBranchFile, RemoteCommit,
+        StackOrder, StatusDelta, VirtualBranches,
     };
     use gitbutler_command_context::CommandContext;
     use gitbutler_project as projects;
     use gitbutler_project::{FetchResult, ProjectId};
     use gitbutler_reference::{normalize_branch_name as normalize_name, Refname, RemoteRefname};
+    use gitbutler_repo::credentials::Interactivity;
     use gitbutler_stack::{BranchOwnershipClaims, StackId};
     use std::path::PathBuf;
     use tauri::State;
@@ -54,9 +58,34 @@ pub mod commands {
     pub fn list_virtual_branches(
         projects: State<'_, projects::Controller>,
         project_id: ProjectId,
+        files_against_base: Option<bool>,
+        detect_moved_lines: Option<bool>,
+        structured_lines: Option<bool>,
     ) -> Result<VirtualBranches, Error> {
         let project = projects.get(project_id)?;
-        gitbutler_branch_actions::list_virtual_branches(&project)
+        gitbutler_branch_actions::list_virtual_branches_with_options(
+            &project,
+            files_against_base.unwrap_or_default(),
+            gitbutler_diff::DiffOptions {
+                detect_moved_lines: detect_moved_lines.unwrap_or_default(),
+                structured_lines: structured_lines.unwrap_or_default(),
+            },
+        )
+        .map_err(Into::into)
+        .map(|(branches, skipped_files)| VirtualBranches {
+            branches,
+            skipped_files,
+        })
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(projects), err(Debug))]
+    pub fn list_virtual_branches_read_only(
+        projects: State<'_, projects::Controller>,
+        project_id: ProjectId,
+    ) -> Result<VirtualBranches, Error> {
+        let project = projects.get(project_id)?;
+        gitbutler_branch_actions::list_virtual_branches_read_only(&project)
             .map_err(Into::into)
             .map(|(branches, skipped_files)| VirtualBranches {
                 branches,
@@ -64,6 +93,17 @@ pub mod commands {
             })
     }
 
+    #[tauri::command(async)]
+    #[instrument(skip(projects, token), err(Debug))]
+    pub fn get_status_since(
+        projects: State<'_, projects::Controller>,
+        project_id: ProjectId,
+        token: Option<String>,
+    ) -> Result<StatusDelta, Error> {
+        let project = projects.get(project_id)?;
+        gitbutler_branch_actions::get_status_since(&project, token.as_deref()).map_err(Into::into)
+    }
+
     #[tauri::command(async)]
     #[instrument(skip(projects, windows), err(Debug))]
     pub fn create_virtual_branch(
@@ -119,11 +159,12 @@ pub mod commands {
         project_id: ProjectId,
         branch: StackId,
         series_name: Option<String>,
-    ) -> Result<(), Error> {
+    ) -> Result<UpstreamIntegrationOutcome, Error> {
         let project = projects.get(project_id)?;
-        gitbutler_branch_actions::integrate_upstream_commits(&project, branch, series_name)?;
+        let outcome =
+            gitbutler_branch_actions::integrate_upstream_commits(&project, branch, series_name)?;
         emit_vbranches(&windows, project_id);
-        Ok(())
+        Ok(outcome)
     }
 
     #[tauri::command(async)]
@@ -148,12 +189,17 @@ pub mod commands {
         project_id: ProjectId,
         branch: &str,
         push_remote: Option<&str>, // optional different name of a remote to push to (defaults to same as the branch)
+        local_branch: Option<&str>, // optional name of a local branch to keep in sync with the branch
     ) -> Result<BaseBranch, Error> {
         let project = projects.get(project_id)?;
         let branch_name = format!("refs/remotes/{}", branch)
             .parse()
             .context("Invalid branch name")?;
-        let base_branch = gitbutler_branch_actions::set_base_branch(&project, &branch_name)?;
+        let base_branch = gitbutler_branch_actions::set_base_branch_with_local_branch(
+            &project,
+            &branch_name,
+            local_branch,
+        )?;
 
         // if they also sent a different push remote, set that too
         if let Some(push_remote) = push_remote {
@@ -163,6 +209,22 @@ pub mod commands {
         Ok(base_branch)
     }
 
+    #[tauri::command(async)]
+    #[instrument(skip(projects, windows), err(Debug))]
+    pub fn force_reset_integration(
+        windows: State<'_, WindowState>,
+        projects: State<'_, projects::Controller>,
+        project_id: ProjectId,
+        confirmed: bool,
+    ) -> Result<ForceResetOutcome, Error> {
+        let project = projects.get(project_id)?;
+        let outcome = gitbutler_branch_actions::force_reset_integration(&project, confirmed)?;
+        if outcome.reset {
+            emit_vbranches(&windows, project_id);
+        }
+        Ok(outcome)
+    }
+
     #[tauri::command(async)]
     #[instrument(skip(projects, windows), err(Debug))]
     pub fn push_base_branch(
@@ -271,13 +333,17 @@ pub mod commands {
         project_id: ProjectId,
         branch_id: StackId,
         with_force: bool,
+        force: Option<ForceMode>,
+        push_options: Option<Vec<String>>,
     ) -> Result<PushResult, Error> {
         let project = projects.get(project_id)?;
-        let upstream_refname = gitbutler_branch_actions::push_virtual_branch(
+        let upstream_refname = gitbutler_branch_actions::push_virtual_branch_with_options(
             &project,
             branch_id,
-            with_force,
+            force.unwrap_or(with_force.into()),
             Some(Some(branch_id)),
+            &push_options.unwrap_or_default(),
+            Interactivity::Interactive,
         )?;
         emit_vbranches(&windows, project_id);
         Ok(upstream_refname)
@@ -333,10 +399,17 @@ pub mod commands {
         branch_id: StackId,
         commit_oid: String,
         ownership: BranchOwnershipClaims,
+        reset_author: Option<bool>,
     ) -> Result<String, Error> {
         let project = projects.get(project_id)?;
         let commit_oid = git2::Oid::from_str(&commit_oid).map_err(|e| anyhow!(e))?;
-        let oid = gitbutler_branch_actions::amend(&project, branch_id, commit_oid, &ownership)?;
+        let oid = gitbutler_branch_actions::amend_with_options(
+            &project,
+            branch_id,
+            commit_oid,
+            &ownership,
+            reset_author.unwrap_or_default(),
+        )?;
         emit_vbranches(&windows, project_id);
         Ok(oid.to_string())
     }
@@ -407,9 +480,32 @@ pub mod commands {
         project_id: ProjectId,
         branch_id: StackId,
         stack_order: StackOrder,
+        keep_empty: Option<bool>,
     ) -> Result<(), Error> {
         let project = projects.get(project_id)?;
-        gitbutler_branch_actions::reorder_stack(&project, branch_id, stack_order)?;
+        gitbutler_branch_actions::reorder_stack_with_options(
+            &project,
+            branch_id,
+            stack_order,
+            keep_empty.unwrap_or_default(),
+        )?;
+        emit_vbranches(&windows, project_id);
+        Ok(())
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(projects, windows), err(Debug))]
+    pub fn reorder_commit(
+        windows: State<'_, WindowState>,
+        projects: State<'_, projects::Controller>,
+        project_id: ProjectId,
+        branch_id: StackId,
+        commit_oid: String,
+        offset: i32,
+    ) -> Result<(), Error> {
+        let project = projects.get(project_id)?;
+        let commit_oid = git2::Oid::from_str(&commit_oid).map_err(|e| anyhow!(e))?;
+        gitbutler_branch_actions::reorder_commit(&project, branch_id, commit_oid, offset)?;
         emit_vbranches(&windows, project_id);
         Ok(())
     }
@@ -477,6 +573,29 @@ pub mod commands {
         Ok(())
     }
 
+    #[tauri::command(async)]
+    #[instrument(skip(projects, windows), err(Debug))]
+    pub fn squash_branch_commit_range(
+        windows: State<'_, WindowState>,
+        projects: State<'_, projects::Controller>,
+        project_id: ProjectId,
+        branch_id: StackId,
+        from_commit_oid: String,
+        to_commit_oid: String,
+    ) -> Result<(), Error> {
+        let project = projects.get(project_id)?;
+        let from_commit_oid = git2::Oid::from_str(&from_commit_oid).map_err(|e| anyhow!(e))?;
+        let to_commit_oid = git2::Oid::from_str(&to_commit_oid).map_err(|e| anyhow!(e))?;
+        gitbutler_branch_actions::squash_range(
+            &project,
+            branch_id,
+            from_commit_oid,
+            to_commit_oid,
+        )?;
+        emit_vbranches(&windows, project_id);
+        Ok(())
+    }
+
     #[tauri::command(async)]
     #[instrument(skip(projects, windows), err(Debug))]
     pub fn fetch_from_remotes(
@@ -484,12 +603,14 @@ pub mod commands {
         projects: State<'_, projects::Controller>,
         project_id: ProjectId,
         action: Option<String>,
+        depth: Option<u32>,
     ) -> Result<BaseBranch, Error> {
         let project = projects.get(project_id)?;
 
         let project_data_last_fetched = gitbutler_branch_actions::fetch_from_remotes(
             &project,
             Some(action.unwrap_or_else(|| "unknown".to_string())),
+            depth,
         )?;
 
         // Updates the project controller with the last fetched timestamp
@@ -563,6 +684,17 @@ pub mod commands {
         gitbutler_branch_actions::find_commit(&project, commit_oid).map_err(Into::into)
     }
 
+    #[tauri::command(async)]
+    #[instrument(skip(projects), err(Debug))]
+    pub fn incoming_commits(
+        projects: State<'_, projects::Controller>,
+        project_id: ProjectId,
+        branch_id: StackId,
+    ) -> Result<Vec<RemoteCommit>, Error> {
+        let project = projects.get(project_id)?;
+        gitbutler_branch_actions::incoming_commits(&project, branch_id).map_err(Into::into)
+    }
+
     #[tauri::command(async)]
     #[instrument(skip(projects), err(Debug))]
     pub fn upstream_integration_statuses(
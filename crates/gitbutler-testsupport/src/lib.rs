@@ -45,6 +45,7 @@ pub mod virtual_branches {
                 remote_url: remote_repo.path().to_str().unwrap().parse().unwrap(),
                 sha: remote_repo.head().unwrap().target().unwrap(),
                 push_remote_name: None,
+                local_branch_name: None,
             })
             .expect("failed to write target");
 